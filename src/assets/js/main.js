@@ -0,0 +1,21 @@
+// Copies the text of the element that a button's data-copy-target attribute names. The listener is
+// on the document, so that it also covers buttons that htmx swaps in later.
+document.addEventListener("click", (event) => {
+  const button = event.target.closest("[data-copy-target]");
+  if (!button) {
+    return;
+  }
+  const target = document.getElementById(button.dataset.copyTarget);
+  if (target) {
+    navigator.clipboard.writeText(target.textContent);
+  }
+});
+
+// Swaps in the CSRF token that the server hands over when a response rotates the session, e.g. a
+// password change, since the one the page was rendered with is bound to the old session
+document.addEventListener("csrfTokenChanged", (event) => {
+  document.body.setAttribute(
+    "hx-headers",
+    JSON.stringify({ "X-CSRF-Token": event.detail.value }),
+  );
+});
@@ -1,26 +1,169 @@
+use argon2::Params;
+use axum_extra::extract::cookie::SameSite;
+use jiff::{Span, ToSpan};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use secrecy::SecretString;
 use std::env;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 const DATABASE_PATH_ENV_VAR: &str = "GLUESTICK_DB_PATH";
 const PORT_ENV_VAR: &str = "GLUESTICK_PORT";
+const SESSION_IDLE_TTL_ENV_VAR: &str = "GLUESTICK_SESSION_IDLE_TTL_SECONDS";
+const SESSION_ABSOLUTE_TTL_ENV_VAR: &str = "GLUESTICK_SESSION_ABSOLUTE_TTL_SECONDS";
+const SESSION_REMEMBER_ME_TTL_ENV_VAR: &str = "GLUESTICK_SESSION_REMEMBER_ME_TTL_SECONDS";
+const SESSION_COOKIE_NAME_ENV_VAR: &str = "GLUESTICK_SESSION_COOKIE_NAME";
+const SESSION_COOKIE_DOMAIN_ENV_VAR: &str = "GLUESTICK_SESSION_COOKIE_DOMAIN";
+const SESSION_COOKIE_SECURE_ENV_VAR: &str = "GLUESTICK_SESSION_COOKIE_SECURE";
+const SESSION_COOKIE_SAME_SITE_ENV_VAR: &str = "GLUESTICK_SESSION_COOKIE_SAME_SITE";
+const SESSION_CSRF_SECRET_ENV_VAR: &str = "GLUESTICK_SESSION_CSRF_SECRET";
+const INVITE_ALLOWANCE_ENV_VAR: &str = "GLUESTICK_INVITE_ALLOWANCE";
+const ADMIN_USERNAMES_ENV_VAR: &str = "GLUESTICK_ADMIN_USERNAMES";
+const ACCOUNT_DELETION_GRACE_PERIOD_ENV_VAR: &str =
+    "GLUESTICK_ACCOUNT_DELETION_GRACE_PERIOD_SECONDS";
+const REGISTRATION_MODE_ENV_VAR: &str = "GLUESTICK_REGISTRATION_MODE";
+const REGISTRATION_ALLOWED_EMAIL_DOMAINS_ENV_VAR: &str =
+    "GLUESTICK_REGISTRATION_ALLOWED_EMAIL_DOMAINS";
+const PASSWORD_HASHING_MEMORY_COST_ENV_VAR: &str = "GLUESTICK_PASSWORD_HASHING_MEMORY_COST_KIB";
+const PASSWORD_HASHING_TIME_COST_ENV_VAR: &str = "GLUESTICK_PASSWORD_HASHING_TIME_COST";
+const PASSWORD_HASHING_PARALLELISM_ENV_VAR: &str = "GLUESTICK_PASSWORD_HASHING_PARALLELISM";
+const PASSWORD_HASHING_MAX_CONCURRENT_ENV_VAR: &str = "GLUESTICK_PASSWORD_HASHING_MAX_CONCURRENT";
+const PASSWORD_HASHING_MAX_QUEUED_ENV_VAR: &str = "GLUESTICK_PASSWORD_HASHING_MAX_QUEUED";
+const BREACHED_PASSWORDS_PATH_ENV_VAR: &str = "GLUESTICK_BREACHED_PASSWORDS_PATH";
+const HSTS_MAX_AGE_ENV_VAR: &str = "GLUESTICK_HSTS_MAX_AGE_SECONDS";
+const CSP_REPORT_ONLY_ENV_VAR: &str = "GLUESTICK_CSP_REPORT_ONLY";
+const RATE_LIMIT_IP_BURST_ENV_VAR: &str = "GLUESTICK_RATE_LIMIT_IP_BURST";
+const RATE_LIMIT_IP_PER_MINUTE_ENV_VAR: &str = "GLUESTICK_RATE_LIMIT_IP_PER_MINUTE";
+const RATE_LIMIT_API_KEY_BURST_ENV_VAR: &str = "GLUESTICK_RATE_LIMIT_API_KEY_BURST";
+const RATE_LIMIT_API_KEY_PER_MINUTE_ENV_VAR: &str = "GLUESTICK_RATE_LIMIT_API_KEY_PER_MINUTE";
+const TRUSTED_PROXIES_ENV_VAR: &str = "GLUESTICK_TRUSTED_PROXIES";
+const WEBHOOKS_ALLOW_PRIVATE_ADDRESSES_ENV_VAR: &str = "GLUESTICK_WEBHOOKS_ALLOW_PRIVATE_ADDRESSES";
+const THROTTLE_LOGIN_ACCOUNT_FREE_ATTEMPTS_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_LOGIN_ACCOUNT_FREE_ATTEMPTS";
+const THROTTLE_LOGIN_ACCOUNT_LOCKOUT_THRESHOLD_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_LOGIN_ACCOUNT_LOCKOUT_THRESHOLD";
+const THROTTLE_LOGIN_ACCOUNT_MAX_DELAY_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_LOGIN_ACCOUNT_MAX_DELAY_SECONDS";
+const THROTTLE_LOGIN_ACCOUNT_LOCKOUT_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_LOGIN_ACCOUNT_LOCKOUT_SECONDS";
+const THROTTLE_LOGIN_IP_FREE_ATTEMPTS_ENV_VAR: &str = "GLUESTICK_THROTTLE_LOGIN_IP_FREE_ATTEMPTS";
+const THROTTLE_LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_LOGIN_IP_LOCKOUT_THRESHOLD";
+const THROTTLE_LOGIN_IP_MAX_DELAY_ENV_VAR: &str = "GLUESTICK_THROTTLE_LOGIN_IP_MAX_DELAY_SECONDS";
+const THROTTLE_LOGIN_IP_LOCKOUT_ENV_VAR: &str = "GLUESTICK_THROTTLE_LOGIN_IP_LOCKOUT_SECONDS";
+const THROTTLE_SIGNUP_VALIDATION_IP_FREE_ATTEMPTS_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_SIGNUP_VALIDATION_IP_FREE_ATTEMPTS";
+const THROTTLE_SIGNUP_VALIDATION_IP_LOCKOUT_THRESHOLD_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_SIGNUP_VALIDATION_IP_LOCKOUT_THRESHOLD";
+const THROTTLE_SIGNUP_VALIDATION_IP_MAX_DELAY_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_SIGNUP_VALIDATION_IP_MAX_DELAY_SECONDS";
+const THROTTLE_SIGNUP_VALIDATION_IP_LOCKOUT_ENV_VAR: &str =
+    "GLUESTICK_THROTTLE_SIGNUP_VALIDATION_IP_LOCKOUT_SECONDS";
 
 const DATABASE_PATH_DEFAULT: &str = "gluestick.db";
 const PORT_DEFAULT: u16 = 3000;
+const SESSION_IDLE_TTL_DEFAULT: i64 = 28_800; // 8 hours
+const SESSION_ABSOLUTE_TTL_DEFAULT: i64 = 1_209_600; // 14 days
+const SESSION_REMEMBER_ME_TTL_DEFAULT: i64 = 2_592_000; // 30 days
+const SESSION_COOKIE_NAME_DEFAULT: &str = "session_token";
+const SESSION_COOKIE_SECURE_DEFAULT: bool = true;
+const SESSION_COOKIE_SAME_SITE_DEFAULT: SameSite = SameSite::Lax;
+const SESSION_CSRF_SECRET_MIN_LEN: usize = 32;
+const INVITE_ALLOWANCE_DEFAULT: i64 = 5;
+const ACCOUNT_DELETION_GRACE_PERIOD_DEFAULT: i64 = 0; // immediate
+const PASSWORD_HASHING_MEMORY_COST_DEFAULT: u32 = Params::DEFAULT_M_COST; // 19 MiB
+const PASSWORD_HASHING_TIME_COST_DEFAULT: u32 = Params::DEFAULT_T_COST;
+const PASSWORD_HASHING_PARALLELISM_DEFAULT: u32 = Params::DEFAULT_P_COST;
+const PASSWORD_HASHING_MAX_CONCURRENT_DEFAULT: usize = 4;
+const PASSWORD_HASHING_MAX_QUEUED_DEFAULT: usize = 32;
+const HSTS_MAX_AGE_DEFAULT: u64 = 31_536_000; // 1 year
+const CSP_REPORT_ONLY_DEFAULT: bool = false;
+const WEBHOOKS_ALLOW_PRIVATE_ADDRESSES_DEFAULT: bool = false;
+// The server only listens on loopback, so it's always reached through a reverse proxy on the same
+// machine
+const TRUSTED_PROXIES_DEFAULT: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::LOCALHOST),
+    IpAddr::V6(Ipv6Addr::LOCALHOST),
+];
+const RATE_LIMIT_IP_BURST_DEFAULT: u32 = 300;
+const RATE_LIMIT_IP_PER_MINUTE_DEFAULT: u32 = 300;
+const RATE_LIMIT_API_KEY_BURST_DEFAULT: u32 = 60;
+const RATE_LIMIT_API_KEY_PER_MINUTE_DEFAULT: u32 = 60;
+const THROTTLE_LOGIN_ACCOUNT_DEFAULT: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 5,
+    lockout_threshold: 10,
+    max_delay_seconds: 30,
+    lockout_seconds: 900, // 15 minutes
+};
+const THROTTLE_LOGIN_IP_DEFAULT: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 20,
+    lockout_threshold: 100,
+    max_delay_seconds: 30,
+    lockout_seconds: 900, // 15 minutes
+};
+const THROTTLE_SIGNUP_VALIDATION_IP_DEFAULT: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 60,
+    lockout_threshold: 300,
+    max_delay_seconds: 10,
+    lockout_seconds: 900, // 15 minutes
+};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Config {
     database_path: PathBuf,
     port: u16,
+    session: SessionConfig,
+    rate_limit: RateLimitConfig,
+    throttle: ThrottleConfig,
+    trusted_proxies: Vec<IpAddr>,
+    webhooks_allow_private_addresses: bool,
+    security_headers: SecurityHeadersConfig,
+    password_hashing: PasswordHashingConfig,
+    breached_passwords_path: Option<PathBuf>,
+    invite_allowance: i64,
+    registration_mode: RegistrationMode,
+    admin_usernames: Vec<String>,
+    account_deletion_grace_period_seconds: i64,
 }
 
 impl Config {
     pub fn parse() -> Result<Config, Error> {
         let database_path = Self::parse_database_path()?;
         let port = Self::parse_port()?;
+        let session = SessionConfig::parse()?;
+        let rate_limit = RateLimitConfig::parse()?;
+        let throttle = ThrottleConfig::parse()?;
+        let trusted_proxies = Self::parse_trusted_proxies()?;
+        let webhooks_allow_private_addresses = parse_env_var(
+            WEBHOOKS_ALLOW_PRIVATE_ADDRESSES_ENV_VAR,
+            WEBHOOKS_ALLOW_PRIVATE_ADDRESSES_DEFAULT,
+        )?;
+        let security_headers = SecurityHeadersConfig::parse()?;
+        let password_hashing = PasswordHashingConfig::parse()?;
+        let breached_passwords_path = Self::parse_breached_passwords_path()?;
+        let invite_allowance = Self::parse_invite_allowance()?;
+        let registration_mode = RegistrationMode::parse()?;
+        let admin_usernames = Self::parse_admin_usernames()?;
+        let account_deletion_grace_period_seconds =
+            Self::parse_account_deletion_grace_period_seconds()?;
 
         Ok(Config {
             database_path,
             port,
+            session,
+            rate_limit,
+            throttle,
+            trusted_proxies,
+            webhooks_allow_private_addresses,
+            security_headers,
+            password_hashing,
+            breached_passwords_path,
+            invite_allowance,
+            registration_mode,
+            admin_usernames,
+            account_deletion_grace_period_seconds,
         })
     }
 
@@ -40,6 +183,87 @@ impl Config {
         }
     }
 
+    // Unset by default, since the corpus is a large download that has to be fetched separately.
+    // A path that doesn't point to a file fails at startup rather than on the first signup.
+    fn parse_breached_passwords_path() -> Result<Option<PathBuf>, EnvVarError> {
+        match env::var(BREACHED_PASSWORDS_PATH_ENV_VAR) {
+            Ok(s) if s.trim().is_empty() => Ok(None),
+            Ok(s) => {
+                let path = PathBuf::from(s);
+                if path.is_file() {
+                    Ok(Some(path))
+                } else {
+                    Err(EnvVarError::InvalidValue {
+                        name: BREACHED_PASSWORDS_PATH_ENV_VAR,
+                        reason: format!("{} is not a file", path.display()),
+                    })
+                }
+            }
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
+    fn parse_invite_allowance() -> Result<i64, EnvVarError> {
+        let invite_allowance = parse_env_var(INVITE_ALLOWANCE_ENV_VAR, INVITE_ALLOWANCE_DEFAULT)?;
+        if invite_allowance >= 0 {
+            Ok(invite_allowance)
+        } else {
+            Err(EnvVarError::InvalidValue {
+                name: INVITE_ALLOWANCE_ENV_VAR,
+                reason: "may not be negative".into(),
+            })
+        }
+    }
+
+    fn parse_account_deletion_grace_period_seconds() -> Result<i64, EnvVarError> {
+        let grace_period = parse_env_var(
+            ACCOUNT_DELETION_GRACE_PERIOD_ENV_VAR,
+            ACCOUNT_DELETION_GRACE_PERIOD_DEFAULT,
+        )?;
+        if grace_period >= 0 {
+            Ok(grace_period)
+        } else {
+            Err(EnvVarError::InvalidValue {
+                name: ACCOUNT_DELETION_GRACE_PERIOD_ENV_VAR,
+                reason: "may not be negative".into(),
+            })
+        }
+    }
+
+    // A comma separated list, e.g. "alice, bob"
+    fn parse_admin_usernames() -> Result<Vec<String>, EnvVarError> {
+        match env::var(ADMIN_USERNAMES_ENV_VAR) {
+            Ok(s) => Ok(s
+                .split(',')
+                .map(|username| username.trim().to_lowercase())
+                .filter(|username| !username.is_empty())
+                .collect()),
+            Err(env::VarError::NotPresent) => Ok(Vec::new()),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
+    // A comma separated list of IP addresses, e.g. "127.0.0.1, ::1". An empty list trusts no
+    // proxies at all.
+    fn parse_trusted_proxies() -> Result<Vec<IpAddr>, EnvVarError> {
+        match env::var(TRUSTED_PROXIES_ENV_VAR) {
+            Ok(s) => s
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| {
+                    proxy.parse().map_err(|_| EnvVarError::InvalidValue {
+                        name: TRUSTED_PROXIES_ENV_VAR,
+                        reason: format!("'{proxy}' is not an IP address"),
+                    })
+                })
+                .collect(),
+            Err(env::VarError::NotPresent) => Ok(TRUSTED_PROXIES_DEFAULT.to_vec()),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
     pub fn database_path(&self) -> &Path {
         &self.database_path
     }
@@ -47,6 +271,774 @@ impl Config {
     pub fn port(&self) -> u16 {
         self.port
     }
+
+    pub fn session(&self) -> &SessionConfig {
+        &self.session
+    }
+
+    pub fn rate_limit(&self) -> &RateLimitConfig {
+        &self.rate_limit
+    }
+
+    pub fn throttle(&self) -> &ThrottleConfig {
+        &self.throttle
+    }
+
+    // Peers whose X-Forwarded-For header is believed when working out a request's client address
+    pub fn trusted_proxies(&self) -> &[IpAddr] {
+        &self.trusted_proxies
+    }
+
+    // Whether webhooks may point at loopback, private and link-local addresses, or at IP addresses
+    // at all. Off by default, so that webhooks can't be used to reach the server's own network.
+    pub fn webhooks_allow_private_addresses(&self) -> bool {
+        self.webhooks_allow_private_addresses
+    }
+
+    pub fn security_headers(&self) -> &SecurityHeadersConfig {
+        &self.security_headers
+    }
+
+    pub fn password_hashing(&self) -> &PasswordHashingConfig {
+        &self.password_hashing
+    }
+
+    // None when new passwords aren't checked against a breached password corpus
+    pub fn breached_passwords_path(&self) -> Option<&Path> {
+        self.breached_passwords_path.as_deref()
+    }
+
+    pub fn invite_allowance(&self) -> i64 {
+        self.invite_allowance
+    }
+
+    pub fn registration_mode(&self) -> &RegistrationMode {
+        &self.registration_mode
+    }
+
+    pub fn admin_usernames(&self) -> &[String] {
+        &self.admin_usernames
+    }
+
+    // None when accounts are deleted immediately upon request
+    pub fn account_deletion_grace_period(&self) -> Option<Span> {
+        (self.account_deletion_grace_period_seconds > 0)
+            .then(|| self.account_deletion_grace_period_seconds.seconds())
+    }
+
+    #[must_use]
+    pub fn with_invite_allowance(mut self, invite_allowance: i64) -> Self {
+        self.invite_allowance = invite_allowance;
+        self
+    }
+
+    #[must_use]
+    pub fn with_account_deletion_grace_period_seconds(mut self, seconds: i64) -> Self {
+        self.account_deletion_grace_period_seconds = seconds;
+        self
+    }
+
+    #[must_use]
+    pub fn with_session(mut self, session: SessionConfig) -> Self {
+        self.session = session;
+        self
+    }
+
+    #[must_use]
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    #[must_use]
+    pub fn with_throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    #[must_use]
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    #[must_use]
+    pub fn with_webhooks_allow_private_addresses(mut self, allow: bool) -> Self {
+        self.webhooks_allow_private_addresses = allow;
+        self
+    }
+
+    #[must_use]
+    pub fn with_security_headers(mut self, security_headers: SecurityHeadersConfig) -> Self {
+        self.security_headers = security_headers;
+        self
+    }
+
+    #[must_use]
+    pub fn with_password_hashing(mut self, password_hashing: PasswordHashingConfig) -> Self {
+        self.password_hashing = password_hashing;
+        self
+    }
+
+    #[must_use]
+    pub fn with_breached_passwords_path(mut self, path: Option<PathBuf>) -> Self {
+        self.breached_passwords_path = path;
+        self
+    }
+
+    #[must_use]
+    pub fn with_registration_mode(mut self, registration_mode: RegistrationMode) -> Self {
+        self.registration_mode = registration_mode;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database_path: PathBuf::from(DATABASE_PATH_DEFAULT),
+            port: PORT_DEFAULT,
+            session: SessionConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            throttle: ThrottleConfig::default(),
+            trusted_proxies: TRUSTED_PROXIES_DEFAULT.to_vec(),
+            webhooks_allow_private_addresses: WEBHOOKS_ALLOW_PRIVATE_ADDRESSES_DEFAULT,
+            security_headers: SecurityHeadersConfig::default(),
+            password_hashing: PasswordHashingConfig::default(),
+            breached_passwords_path: None,
+            invite_allowance: INVITE_ALLOWANCE_DEFAULT,
+            registration_mode: RegistrationMode::default(),
+            admin_usernames: Vec::new(),
+            account_deletion_grace_period_seconds: ACCOUNT_DELETION_GRACE_PERIOD_DEFAULT,
+        }
+    }
+}
+
+// Who may create an account through the signup page
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RegistrationMode {
+    // Anyone may sign up
+    Open,
+    // Signing up requires a valid invite code
+    #[default]
+    InviteOnly,
+    // Nobody may sign up
+    Closed,
+    // Anyone with an email address at one of these domains may sign up
+    AllowedEmailDomains(Vec<String>),
+}
+
+impl RegistrationMode {
+    fn parse() -> Result<Self, EnvVarError> {
+        let mode = match env::var(REGISTRATION_MODE_ENV_VAR) {
+            Ok(s) => s.trim().to_lowercase(),
+            Err(env::VarError::NotPresent) => return Ok(Self::default()),
+            Err(err @ env::VarError::NotUnicode(_)) => return Err(err.into()),
+        };
+
+        match mode.as_str() {
+            "open" => Ok(Self::Open),
+            "invite_only" => Ok(Self::InviteOnly),
+            "closed" => Ok(Self::Closed),
+            "allowed_email_domains" => {
+                let domains = Self::parse_allowed_email_domains()?;
+                if domains.is_empty() {
+                    Err(EnvVarError::InvalidValue {
+                        name: REGISTRATION_ALLOWED_EMAIL_DOMAINS_ENV_VAR,
+                        reason: "must list at least one domain when registration is restricted to allowed email domains".into(),
+                    })
+                } else {
+                    Ok(Self::AllowedEmailDomains(domains))
+                }
+            }
+            _ => Err(EnvVarError::InvalidValue {
+                name: REGISTRATION_MODE_ENV_VAR,
+                reason:
+                    "valid values are 'open', 'invite_only', 'closed', or 'allowed_email_domains'"
+                        .into(),
+            }),
+        }
+    }
+
+    // A comma separated list, e.g. "example.com, example.org"
+    fn parse_allowed_email_domains() -> Result<Vec<String>, EnvVarError> {
+        match env::var(REGISTRATION_ALLOWED_EMAIL_DOMAINS_ENV_VAR) {
+            Ok(s) => Ok(s
+                .split(',')
+                .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect()),
+            Err(env::VarError::NotPresent) => Ok(Vec::new()),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self == Self::Closed
+    }
+
+    pub fn requires_invite_code(&self) -> bool {
+        *self == Self::InviteOnly
+    }
+
+    pub fn allows_email_domain(&self, domain: &str) -> bool {
+        match self {
+            Self::AllowedEmailDomains(domains) => domains.iter().any(|d| d == domain),
+            _ => true,
+        }
+    }
+
+    pub fn allowed_email_domains(&self) -> &[String] {
+        match self {
+            Self::AllowedEmailDomains(domains) => domains,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    idle_ttl_seconds: i64,
+    absolute_ttl_seconds: i64,
+    remember_me_ttl_seconds: i64,
+    cookie_name: String,
+    cookie_domain: Option<String>,
+    cookie_secure: bool,
+    cookie_same_site: SameSite,
+    csrf_secret: SecretString,
+}
+
+impl SessionConfig {
+    fn parse() -> Result<Self, EnvVarError> {
+        let idle_ttl_seconds = Self::parse_ttl(SESSION_IDLE_TTL_ENV_VAR, SESSION_IDLE_TTL_DEFAULT)?;
+        let absolute_ttl_seconds =
+            Self::parse_ttl(SESSION_ABSOLUTE_TTL_ENV_VAR, SESSION_ABSOLUTE_TTL_DEFAULT)?;
+        let remember_me_ttl_seconds = Self::parse_ttl(
+            SESSION_REMEMBER_ME_TTL_ENV_VAR,
+            SESSION_REMEMBER_ME_TTL_DEFAULT,
+        )?;
+        let cookie_name = Self::parse_cookie_name()?;
+        let cookie_domain = Self::parse_cookie_domain()?;
+        let cookie_secure = Self::parse_cookie_secure()?;
+        let cookie_same_site = Self::parse_cookie_same_site()?;
+        let csrf_secret = Self::parse_csrf_secret()?;
+
+        // Browsers reject `SameSite=None` cookies that aren't also marked `Secure`
+        if cookie_same_site == SameSite::None && !cookie_secure {
+            return Err(EnvVarError::InvalidValue {
+                name: SESSION_COOKIE_SAME_SITE_ENV_VAR,
+                reason: "'none' requires the session cookie to be secure".into(),
+            });
+        }
+
+        Ok(Self {
+            idle_ttl_seconds,
+            absolute_ttl_seconds,
+            remember_me_ttl_seconds,
+            cookie_name,
+            cookie_domain,
+            cookie_secure,
+            cookie_same_site,
+            csrf_secret,
+        })
+    }
+
+    fn parse_ttl(name: &'static str, default: i64) -> Result<i64, EnvVarError> {
+        let ttl = parse_env_var(name, default)?;
+        if ttl > 0 {
+            Ok(ttl)
+        } else {
+            Err(EnvVarError::InvalidValue {
+                name,
+                reason: "must be a positive number of seconds".into(),
+            })
+        }
+    }
+
+    fn parse_cookie_name() -> Result<String, EnvVarError> {
+        let cookie_name: String = parse_env_var(
+            SESSION_COOKIE_NAME_ENV_VAR,
+            SESSION_COOKIE_NAME_DEFAULT.into(),
+        )?;
+        if !cookie_name.is_empty()
+            && cookie_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            Ok(cookie_name)
+        } else {
+            Err(EnvVarError::InvalidValue {
+                name: SESSION_COOKIE_NAME_ENV_VAR,
+                reason: "may only contain alphanumeric characters, underscores, or hyphens".into(),
+            })
+        }
+    }
+
+    fn parse_cookie_domain() -> Result<Option<String>, EnvVarError> {
+        match env::var(SESSION_COOKIE_DOMAIN_ENV_VAR) {
+            Ok(s) if s.trim().is_empty() => Ok(None),
+            Ok(s) => Ok(Some(s.trim().to_string())),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
+    fn parse_cookie_secure() -> Result<bool, EnvVarError> {
+        parse_env_var(SESSION_COOKIE_SECURE_ENV_VAR, SESSION_COOKIE_SECURE_DEFAULT)
+    }
+
+    fn parse_cookie_same_site() -> Result<SameSite, EnvVarError> {
+        match env::var(SESSION_COOKIE_SAME_SITE_ENV_VAR) {
+            Ok(s) => match s.to_lowercase().as_str() {
+                "strict" => Ok(SameSite::Strict),
+                "lax" => Ok(SameSite::Lax),
+                "none" => Ok(SameSite::None),
+                _ => Err(EnvVarError::InvalidValue {
+                    name: SESSION_COOKIE_SAME_SITE_ENV_VAR,
+                    reason: "valid values are 'strict', 'lax', or 'none'".into(),
+                }),
+            },
+            Err(env::VarError::NotPresent) => Ok(SESSION_COOKIE_SAME_SITE_DEFAULT),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
+    // Unset by default, in which case a random secret is generated at startup and CSRF tokens handed
+    // out before a restart stop working after it
+    fn parse_csrf_secret() -> Result<SecretString, EnvVarError> {
+        match env::var(SESSION_CSRF_SECRET_ENV_VAR) {
+            Ok(s) if s.len() >= SESSION_CSRF_SECRET_MIN_LEN => Ok(s.into()),
+            Ok(_) => Err(EnvVarError::InvalidValue {
+                name: SESSION_CSRF_SECRET_ENV_VAR,
+                reason: format!("must be at least {SESSION_CSRF_SECRET_MIN_LEN} characters long"),
+            }),
+            Err(env::VarError::NotPresent) => Ok(generate_secret()),
+            Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+        }
+    }
+
+    pub fn idle_ttl(&self) -> Span {
+        self.idle_ttl_seconds.seconds()
+    }
+
+    pub fn absolute_ttl(&self) -> Span {
+        self.absolute_ttl_seconds.seconds()
+    }
+
+    pub fn remember_me_ttl(&self) -> Span {
+        self.remember_me_ttl_seconds.seconds()
+    }
+
+    pub fn remember_me_ttl_seconds(&self) -> i64 {
+        self.remember_me_ttl_seconds
+    }
+
+    pub fn cookie_name(&self) -> &str {
+        &self.cookie_name
+    }
+
+    pub fn cookie_domain(&self) -> Option<&str> {
+        self.cookie_domain.as_deref()
+    }
+
+    pub fn cookie_secure(&self) -> bool {
+        self.cookie_secure
+    }
+
+    pub fn cookie_same_site(&self) -> SameSite {
+        self.cookie_same_site
+    }
+
+    // The key that CSRF tokens are signed with
+    pub fn csrf_secret(&self) -> &SecretString {
+        &self.csrf_secret
+    }
+
+    #[must_use]
+    pub fn with_csrf_secret(mut self, csrf_secret: SecretString) -> Self {
+        self.csrf_secret = csrf_secret;
+        self
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_ttl_seconds: SESSION_IDLE_TTL_DEFAULT,
+            absolute_ttl_seconds: SESSION_ABSOLUTE_TTL_DEFAULT,
+            remember_me_ttl_seconds: SESSION_REMEMBER_ME_TTL_DEFAULT,
+            cookie_name: SESSION_COOKIE_NAME_DEFAULT.into(),
+            cookie_domain: None,
+            cookie_secure: SESSION_COOKIE_SECURE_DEFAULT,
+            cookie_same_site: SESSION_COOKIE_SAME_SITE_DEFAULT,
+            csrf_secret: generate_secret(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    ip_quota: Quota,
+    api_key_quota: Quota,
+}
+
+// A token bucket quota: up to `burst` requests at once, refilled at `per_minute` requests a minute
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl RateLimitConfig {
+    pub fn new(ip_quota: Quota, api_key_quota: Quota) -> Self {
+        Self {
+            ip_quota,
+            api_key_quota,
+        }
+    }
+
+    fn parse() -> Result<Self, EnvVarError> {
+        let ip_quota = Quota {
+            burst: Self::parse_positive(RATE_LIMIT_IP_BURST_ENV_VAR, RATE_LIMIT_IP_BURST_DEFAULT)?,
+            per_minute: Self::parse_positive(
+                RATE_LIMIT_IP_PER_MINUTE_ENV_VAR,
+                RATE_LIMIT_IP_PER_MINUTE_DEFAULT,
+            )?,
+        };
+        let api_key_quota = Quota {
+            burst: Self::parse_positive(
+                RATE_LIMIT_API_KEY_BURST_ENV_VAR,
+                RATE_LIMIT_API_KEY_BURST_DEFAULT,
+            )?,
+            per_minute: Self::parse_positive(
+                RATE_LIMIT_API_KEY_PER_MINUTE_ENV_VAR,
+                RATE_LIMIT_API_KEY_PER_MINUTE_DEFAULT,
+            )?,
+        };
+
+        Ok(Self {
+            ip_quota,
+            api_key_quota,
+        })
+    }
+
+    fn parse_positive(name: &'static str, default: u32) -> Result<u32, EnvVarError> {
+        let value = parse_env_var(name, default)?;
+        if value > 0 {
+            Ok(value)
+        } else {
+            Err(EnvVarError::InvalidValue {
+                name,
+                reason: "must be a positive number".into(),
+            })
+        }
+    }
+
+    pub fn ip_quota(&self) -> Quota {
+        self.ip_quota
+    }
+
+    pub fn api_key_quota(&self) -> Quota {
+        self.api_key_quota
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            ip_quota: Quota {
+                burst: RATE_LIMIT_IP_BURST_DEFAULT,
+                per_minute: RATE_LIMIT_IP_PER_MINUTE_DEFAULT,
+            },
+            api_key_quota: Quota {
+                burst: RATE_LIMIT_API_KEY_BURST_DEFAULT,
+                per_minute: RATE_LIMIT_API_KEY_PER_MINUTE_DEFAULT,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ThrottleConfig {
+    login_account: ThrottlePolicy,
+    login_ip: ThrottlePolicy,
+    signup_validation_ip: ThrottlePolicy,
+}
+
+// How repeated attempts against a single throttle key are slowed down. The first `free_attempts`
+// go through back-to-back, after which the delay between attempts doubles from one second up to
+// `max_delay_seconds`. Reaching `lockout_threshold` attempts locks the key out entirely for
+// `lockout_seconds`, and a threshold of 0 never locks it out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThrottlePolicy {
+    pub free_attempts: i64,
+    pub lockout_threshold: i64,
+    pub max_delay_seconds: i64,
+    pub lockout_seconds: i64,
+}
+
+impl ThrottleConfig {
+    pub fn new(
+        login_account: ThrottlePolicy,
+        login_ip: ThrottlePolicy,
+        signup_validation_ip: ThrottlePolicy,
+    ) -> Self {
+        Self {
+            login_account,
+            login_ip,
+            signup_validation_ip,
+        }
+    }
+
+    fn parse() -> Result<Self, EnvVarError> {
+        Ok(Self {
+            login_account: ThrottlePolicy::parse(
+                [
+                    THROTTLE_LOGIN_ACCOUNT_FREE_ATTEMPTS_ENV_VAR,
+                    THROTTLE_LOGIN_ACCOUNT_LOCKOUT_THRESHOLD_ENV_VAR,
+                    THROTTLE_LOGIN_ACCOUNT_MAX_DELAY_ENV_VAR,
+                    THROTTLE_LOGIN_ACCOUNT_LOCKOUT_ENV_VAR,
+                ],
+                THROTTLE_LOGIN_ACCOUNT_DEFAULT,
+            )?,
+            login_ip: ThrottlePolicy::parse(
+                [
+                    THROTTLE_LOGIN_IP_FREE_ATTEMPTS_ENV_VAR,
+                    THROTTLE_LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR,
+                    THROTTLE_LOGIN_IP_MAX_DELAY_ENV_VAR,
+                    THROTTLE_LOGIN_IP_LOCKOUT_ENV_VAR,
+                ],
+                THROTTLE_LOGIN_IP_DEFAULT,
+            )?,
+            signup_validation_ip: ThrottlePolicy::parse(
+                [
+                    THROTTLE_SIGNUP_VALIDATION_IP_FREE_ATTEMPTS_ENV_VAR,
+                    THROTTLE_SIGNUP_VALIDATION_IP_LOCKOUT_THRESHOLD_ENV_VAR,
+                    THROTTLE_SIGNUP_VALIDATION_IP_MAX_DELAY_ENV_VAR,
+                    THROTTLE_SIGNUP_VALIDATION_IP_LOCKOUT_ENV_VAR,
+                ],
+                THROTTLE_SIGNUP_VALIDATION_IP_DEFAULT,
+            )?,
+        })
+    }
+
+    // Failed sign ins against a single email address
+    pub fn login_account(&self) -> ThrottlePolicy {
+        self.login_account
+    }
+
+    // Failed sign ins from a single IP address, across all email addresses
+    pub fn login_ip(&self) -> ThrottlePolicy {
+        self.login_ip
+    }
+
+    // Username and email availability checks from a single IP address
+    pub fn signup_validation_ip(&self) -> ThrottlePolicy {
+        self.signup_validation_ip
+    }
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            login_account: THROTTLE_LOGIN_ACCOUNT_DEFAULT,
+            login_ip: THROTTLE_LOGIN_IP_DEFAULT,
+            signup_validation_ip: THROTTLE_SIGNUP_VALIDATION_IP_DEFAULT,
+        }
+    }
+}
+
+impl ThrottlePolicy {
+    // `names` holds the free attempts, lockout threshold, max delay and lockout env vars in order
+    fn parse(names: [&'static str; 4], default: Self) -> Result<Self, EnvVarError> {
+        let [free_attempts, lockout_threshold, max_delay_seconds, lockout_seconds] = names;
+        Ok(Self {
+            free_attempts: Self::parse_non_negative(free_attempts, default.free_attempts)?,
+            lockout_threshold: Self::parse_non_negative(
+                lockout_threshold,
+                default.lockout_threshold,
+            )?,
+            max_delay_seconds: Self::parse_non_negative(
+                max_delay_seconds,
+                default.max_delay_seconds,
+            )?,
+            lockout_seconds: Self::parse_non_negative(lockout_seconds, default.lockout_seconds)?,
+        })
+    }
+
+    fn parse_non_negative(name: &'static str, default: i64) -> Result<i64, EnvVarError> {
+        let value = parse_env_var(name, default)?;
+        if value >= 0 {
+            Ok(value)
+        } else {
+            Err(EnvVarError::InvalidValue {
+                name,
+                reason: "may not be negative".into(),
+            })
+        }
+    }
+}
+
+// Strict-Transport-Security is left off entirely when `hsts_max_age_seconds` is 0. A report only
+// Content-Security-Policy lets a deployment see what the policy would block before enforcing it.
+#[derive(Clone, Debug)]
+pub struct SecurityHeadersConfig {
+    hsts_max_age_seconds: u64,
+    csp_report_only: bool,
+}
+
+impl SecurityHeadersConfig {
+    pub fn new(hsts_max_age_seconds: u64, csp_report_only: bool) -> Self {
+        Self {
+            hsts_max_age_seconds,
+            csp_report_only,
+        }
+    }
+
+    fn parse() -> Result<Self, EnvVarError> {
+        Ok(Self {
+            hsts_max_age_seconds: parse_env_var(HSTS_MAX_AGE_ENV_VAR, HSTS_MAX_AGE_DEFAULT)?,
+            csp_report_only: parse_env_var(CSP_REPORT_ONLY_ENV_VAR, CSP_REPORT_ONLY_DEFAULT)?,
+        })
+    }
+
+    // None when HSTS is disabled
+    pub fn hsts_max_age_seconds(&self) -> Option<u64> {
+        (self.hsts_max_age_seconds > 0).then_some(self.hsts_max_age_seconds)
+    }
+
+    pub fn csp_report_only(&self) -> bool {
+        self.csp_report_only
+    }
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            hsts_max_age_seconds: HSTS_MAX_AGE_DEFAULT,
+            csp_report_only: CSP_REPORT_ONLY_DEFAULT,
+        }
+    }
+}
+
+// The Argon2id cost parameters that new password hashes are created with. Raising them later is
+// safe, since existing hashes record their own parameters and are upgraded on sign in. Hashing
+// runs on the blocking thread pool, with at most `max_concurrent` hashes at once and at most
+// `max_queued` more waiting for their turn.
+#[derive(Clone, Debug)]
+pub struct PasswordHashingConfig {
+    params: Params,
+    max_concurrent: usize,
+    max_queued: usize,
+}
+
+impl PasswordHashingConfig {
+    pub fn new(
+        memory_cost_kib: u32,
+        time_cost: u32,
+        parallelism: u32,
+    ) -> Result<Self, argon2::Error> {
+        Ok(Self {
+            params: Params::new(memory_cost_kib, time_cost, parallelism, None)?,
+            ..Default::default()
+        })
+    }
+
+    fn parse() -> Result<Self, EnvVarError> {
+        let memory_cost_kib = parse_env_var(
+            PASSWORD_HASHING_MEMORY_COST_ENV_VAR,
+            PASSWORD_HASHING_MEMORY_COST_DEFAULT,
+        )?;
+        let time_cost = parse_env_var(
+            PASSWORD_HASHING_TIME_COST_ENV_VAR,
+            PASSWORD_HASHING_TIME_COST_DEFAULT,
+        )?;
+        let parallelism = parse_env_var(
+            PASSWORD_HASHING_PARALLELISM_ENV_VAR,
+            PASSWORD_HASHING_PARALLELISM_DEFAULT,
+        )?;
+        let max_concurrent = parse_env_var(
+            PASSWORD_HASHING_MAX_CONCURRENT_ENV_VAR,
+            PASSWORD_HASHING_MAX_CONCURRENT_DEFAULT,
+        )?;
+        if max_concurrent == 0 {
+            return Err(EnvVarError::InvalidValue {
+                name: PASSWORD_HASHING_MAX_CONCURRENT_ENV_VAR,
+                reason: "must be a positive number".into(),
+            });
+        }
+        let max_queued = parse_env_var(
+            PASSWORD_HASHING_MAX_QUEUED_ENV_VAR,
+            PASSWORD_HASHING_MAX_QUEUED_DEFAULT,
+        )?;
+
+        let config = Self::new(memory_cost_kib, time_cost, parallelism).map_err(|e| {
+            let name = match e {
+                argon2::Error::TimeTooSmall => PASSWORD_HASHING_TIME_COST_ENV_VAR,
+                argon2::Error::ThreadsTooFew | argon2::Error::ThreadsTooMany => {
+                    PASSWORD_HASHING_PARALLELISM_ENV_VAR
+                }
+                _ => PASSWORD_HASHING_MEMORY_COST_ENV_VAR,
+            };
+            EnvVarError::InvalidValue {
+                name,
+                reason: e.to_string(),
+            }
+        })?;
+        Ok(config
+            .with_max_concurrent(max_concurrent)
+            .with_max_queued(max_queued))
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    pub fn max_queued(&self) -> usize {
+        self.max_queued
+    }
+
+    #[must_use]
+    pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+}
+
+impl Default for PasswordHashingConfig {
+    fn default() -> Self {
+        Self {
+            params: Params::default(),
+            max_concurrent: PASSWORD_HASHING_MAX_CONCURRENT_DEFAULT,
+            max_queued: PASSWORD_HASHING_MAX_QUEUED_DEFAULT,
+        }
+    }
+}
+
+fn generate_secret() -> SecretString {
+    let mut rng = ChaCha20Rng::from_entropy();
+    format!("{:032x}{:032x}", rng.gen::<u128>(), rng.gen::<u128>()).into()
+}
+
+fn parse_env_var<T: FromStr>(name: &'static str, default: T) -> Result<T, EnvVarError> {
+    match env::var(name) {
+        Ok(s) => s.trim().parse().map_err(|_| EnvVarError::InvalidValue {
+            name,
+            reason: format!("could not parse '{s}'"),
+        }),
+        Err(env::VarError::NotPresent) => Ok(default),
+        Err(err @ env::VarError::NotUnicode(_)) => Err(err.into()),
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -54,6 +1046,7 @@ impl Config {
 pub enum Error {
     DatabasePathError(#[from] DatabasePathError),
     PortError(#[from] PortError),
+    EnvVarError(#[from] EnvVarError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -68,3 +1061,12 @@ pub enum PortError {
     InvalidValue(#[from] std::num::ParseIntError),
     InvalidUnicode(#[from] env::VarError),
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvVarError {
+    #[error("invalid value for {name}: {reason}")]
+    InvalidValue { name: &'static str, reason: String },
+
+    #[error(transparent)]
+    InvalidUnicode(#[from] env::VarError),
+}
@@ -0,0 +1,173 @@
+use crate::config::Config;
+use crate::controllers::prelude::*;
+use crate::db::Database;
+use crate::helpers::conditional_request_helper::IfMatch;
+use crate::helpers::pagination_helper::{CursorPaginationParams, CursorPaginationResponse};
+use crate::models::instance_stats::InstanceStats;
+use crate::models::paste::Paste;
+use crate::models::session::{AdminSession, Session};
+use crate::models::user::User;
+use crate::views::admin::index::IndexPage;
+use crate::views::admin::pastes::{PasteRowPartial, PastesPage};
+use crate::views::admin::users::{UserRowPartial, UsersPage};
+use axum::extract::{Path, Query, State};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub async fn index(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    State(config): State<Config>,
+) -> Result<impl IntoResponse> {
+    let stats = InstanceStats::load(&db, config.password_hashing().params()).await?;
+    Ok(IndexPage {
+        session: Some(session),
+        stats,
+    })
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SearchParams {
+    pub q: Option<String>,
+}
+
+pub async fn users(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Query(search_params): Query<SearchParams>,
+    Query(pagination_params): Query<CursorPaginationParams>,
+) -> Result<impl IntoResponse> {
+    let query = search_params
+        .q
+        .map(|q| q.trim().to_string())
+        .filter(|q| !q.is_empty());
+    let mut users = User::cursor_paginated_search(
+        &db,
+        query.clone(),
+        pagination_params.limit_with_lookahead(),
+        pagination_params.direction(),
+        pagination_params.cursor(),
+    )
+    .await?;
+    let pagination = CursorPaginationResponse::new_with_lookahead(&pagination_params, &mut users);
+
+    Ok(UsersPage {
+        session: Some(session),
+        query,
+        users,
+        pagination,
+    })
+}
+
+pub async fn suspend_user(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let user = find_user(&db, &session, &user_id).await?;
+    // Admins can't be suspended, so that the admin area can't be used to lock every admin out
+    if user.is_admin {
+        return Err(Error::Forbidden(Some(session)));
+    }
+    user.suspend(&db).await?;
+
+    let user = find_user(&db, &session, &user_id).await?;
+    Ok(UserRowPartial { user })
+}
+
+pub async fn unsuspend_user(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let user = find_user(&db, &session, &user_id).await?;
+    user.unsuspend(&db).await?;
+
+    let user = find_user(&db, &session, &user_id).await?;
+    Ok(UserRowPartial { user })
+}
+
+pub async fn pastes(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Query(pagination_params): Query<CursorPaginationParams>,
+) -> Result<impl IntoResponse> {
+    let mut paste_username_pairs = Paste::cursor_paginated_all_with_username(
+        &db,
+        pagination_params.limit_with_lookahead(),
+        pagination_params.direction(),
+        pagination_params.cursor(),
+    )
+    .await?;
+    let pagination =
+        CursorPaginationResponse::new_with_lookahead(&pagination_params, &mut paste_username_pairs);
+
+    Ok(PastesPage {
+        session: Some(session),
+        paste_username_pairs,
+        pagination,
+    })
+}
+
+pub async fn hide_paste(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Path(paste_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let (paste, _) = find_paste_with_owner(&db, &session, &paste_id).await?;
+    paste.hide(&db).await?;
+
+    let (paste, owner) = find_paste_with_owner(&db, &session, &paste_id).await?;
+    Ok(PasteRowPartial {
+        paste,
+        username: owner.username,
+    })
+}
+
+pub async fn unhide_paste(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Path(paste_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let (paste, _) = find_paste_with_owner(&db, &session, &paste_id).await?;
+    paste.unhide(&db).await?;
+
+    let (paste, owner) = find_paste_with_owner(&db, &session, &paste_id).await?;
+    Ok(PasteRowPartial {
+        paste,
+        username: owner.username,
+    })
+}
+
+pub async fn destroy_paste(
+    AdminSession(session): AdminSession,
+    State(db): State<Database>,
+    Path(paste_id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let (paste, _) = find_paste_with_owner(&db, &session, &paste_id).await?;
+    paste.delete(&db, &IfMatch::Any).await?;
+    Ok(())
+}
+
+async fn find_user(db: &Database, session: &Session, user_id: &str) -> Result<User> {
+    let user_id = Uuid::try_parse(user_id).map_err(|_| Error::NotFound(Some(session.clone())))?;
+    User::find(db, user_id)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))
+}
+
+async fn find_paste_with_owner(
+    db: &Database,
+    session: &Session,
+    paste_id: &str,
+) -> Result<(Paste, User)> {
+    let paste_id = Uuid::try_parse(paste_id).map_err(|_| Error::NotFound(Some(session.clone())))?;
+    let paste = Paste::find(db, paste_id)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))?;
+    let owner = User::find(db, paste.user_id)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))?;
+    Ok((paste, owner))
+}
@@ -1,3 +1,5 @@
 pub mod application_controller;
+pub mod openapi_controller;
 pub mod pastes_controller;
 pub mod prelude;
+pub mod users_controller;
@@ -0,0 +1,90 @@
+use crate::controllers::api::pastes_controller::{
+    BatchDeleteParams, BatchOperationParams, BatchParams, BatchResponse, BatchResult,
+    BatchUpdateParams, CreatePasteParams, IndexResponse, UpdatePasteParams,
+};
+use crate::controllers::api::prelude::{ErrorBody, ErrorReport, FieldError, ProblemDetails};
+use crate::controllers::api::users_controller::{PastesResponse, ShowMeResponse};
+use crate::models::api_session::API_KEY_HEADER_NAME;
+use crate::models::paste::{Paste, Visibility};
+use crate::models::paste_batch::BatchMode;
+use axum::response::IntoResponse;
+use axum::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+// Every route in `json_api_routes` has to be listed here, which the API tests check
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Gluestick",
+        description = "The JSON API for gluestick, a pastebin. See `docs/json-api.md` for a guide."
+    ),
+    servers((url = "/api/v1")),
+    paths(
+        crate::controllers::api::pastes_controller::index,
+        crate::controllers::api::pastes_controller::create,
+        crate::controllers::api::pastes_controller::show,
+        crate::controllers::api::pastes_controller::show_raw,
+        crate::controllers::api::pastes_controller::update,
+        crate::controllers::api::pastes_controller::destroy,
+        crate::controllers::api::pastes_controller::batch,
+        crate::controllers::api::users_controller::show_me,
+        crate::controllers::api::users_controller::index_my_pastes,
+        crate::controllers::api::users_controller::index_pastes,
+        show,
+    ),
+    components(schemas(
+        CreatePasteParams,
+        UpdatePasteParams,
+        BatchParams,
+        BatchMode,
+        BatchOperationParams,
+        BatchUpdateParams,
+        BatchDeleteParams,
+        BatchResponse,
+        BatchResult,
+        IndexResponse,
+        PastesResponse,
+        ShowMeResponse,
+        Paste,
+        Visibility,
+        ErrorReport,
+        ErrorBody,
+        ProblemDetails,
+        FieldError,
+    )),
+    modifiers(&ApiKeySecurity),
+    tags(
+        (name = "pastes", description = "Creating, reading, updating and deleting pastes"),
+        (name = "users", description = "Users and the pastes they own"),
+        (name = "meta", description = "Describing the API itself"),
+    )
+)]
+pub struct ApiDoc;
+
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                API_KEY_HEADER_NAME,
+                "An API key, created from the API keys page of your account settings",
+            ))),
+        );
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "meta",
+    summary = "This OpenAPI document",
+    description = "Doesn't need an API key.",
+    responses((status = 200, content_type = "application/json", description = "An OpenAPI 3.1 document"))
+)]
+pub async fn show() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
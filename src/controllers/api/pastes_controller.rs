@@ -1,90 +1,214 @@
+use crate::config::Config;
 use crate::controllers::api::prelude::*;
 use crate::db::Database;
+use crate::extractors::pagination::ApiPagination;
+use crate::helpers::conditional_request_helper::{is_not_modified, ETag, IfMatch};
 use crate::helpers::pagination_helper::{CursorPaginationParams, CursorPaginationResponse};
-use crate::models::api_session::ApiSession;
+use crate::middleware::rate_limit::RateLimiter;
+use crate::middleware::security_headers::SANDBOX_CSP;
+use crate::models::api_session::{ApiSession, Scope};
 use crate::models::paste::{Body, Description, Filename, Paste, Visibility};
-use axum::extract::{Path, State};
-use axum::response::IntoResponse;
+use crate::models::paste_batch::{
+    self, BatchMode, PasteOperation, PasteOperationOutcome, MAX_BATCH_OPERATIONS,
+};
+use crate::models::paste_query::{PasteCursor, PasteFilterParams, PasteQuery};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Serialize)]
-struct IndexResponse {
+#[derive(Serialize, ToSchema)]
+pub struct IndexResponse {
     pastes: Vec<Paste>,
-    pagination: CursorPaginationResponse,
+    pagination: CursorPaginationResponse<PasteCursor>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/pastes",
+    tag = "pastes",
+    summary = "List public pastes",
+    description = "Requires the `pastes:read` scope.",
+    params(PasteFilterParams, CursorPaginationParams<PasteCursor>),
+    responses(
+        (status = 200, body = IndexResponse, headers(("Link" = String, description = "Links to the previous and next pages"))),
+        (status = 422, body = ErrorBody, description = "Invalid filters or cursor"),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn index(
-    _session: ApiSession,
+    session: ApiSession,
     State(db): State<Database>,
-    pagination_params: Option<Json<CursorPaginationParams>>,
+    Query(filter_params): Query<PasteFilterParams>,
+    api_pagination: ApiPagination<PasteCursor>,
 ) -> Result<impl IntoResponse> {
-    let pagination_params = pagination_params.unwrap_or_default();
+    require_scope(&session, Scope::PastesRead)?;
+    let pagination_params = &api_pagination.params;
 
-    let mut pastes = Paste::cursor_paginated(
-        &db,
-        pagination_params.limit_with_lookahead(),
-        pagination_params.direction(),
-        pagination_params.cursor(),
-    )
-    .await?;
-    let pagination = CursorPaginationResponse::new_with_lookahead(&pagination_params, &mut pastes);
-    Ok(Json(IndexResponse { pastes, pagination }))
+    let query = PasteQuery::public()
+        .filtered(&filter_params)
+        .map_err(to_validation_error)?;
+    let sort = query.sort();
+    let mut pastes = query
+        .cursor_paginated(
+            &db,
+            pagination_params.limit_with_lookahead(),
+            pagination_params.direction(),
+            pagination_params.cursor(),
+        )
+        .await
+        .map_err(to_validation_error)?;
+    let pagination =
+        CursorPaginationResponse::new_with_lookahead_by(pagination_params, &mut pastes, |paste| {
+            sort.cursor_for(paste)
+        });
+    Ok((
+        api_pagination.link_headers(&pagination),
+        Json(IndexResponse { pastes, pagination }),
+    ))
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, ToSchema)]
 pub struct CreatePasteParams {
+    #[schema(example = "example-paste.txt")]
     pub filename: String,
     pub description: String,
     pub body: String,
+    #[schema(example = "secret")]
     pub visibility: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/pastes",
+    tag = "pastes",
+    summary = "Create a paste",
+    description = "Requires the `pastes:write` scope.",
+    request_body = CreatePasteParams,
+    responses(
+        (status = 200, body = Uuid, description = "The new paste's id"),
+        (status = 422, description = "Invalid fields, or a malformed request", content(
+            (ProblemDetails = "application/problem+json"),
+            (ErrorBody = "application/json"),
+        )),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn create(
     session: ApiSession,
     State(db): State<Database>,
     Json(params): Json<CreatePasteParams>,
 ) -> Result<impl IntoResponse> {
-    let filename =
-        Filename::try_from(&params.filename).map_err(|e| Error::Unprocessable(Box::new(e)))?;
-    let description = Description::try_from(&params.description)
-        .map_err(|e| Error::Unprocessable(Box::new(e)))?;
-    let body = Body::try_from(&params.body).map_err(|e| Error::Unprocessable(Box::new(e)))?;
-    let visibility =
-        Visibility::try_from(&params.visibility).map_err(|e| Error::Unprocessable(Box::new(e)))?;
-
-    let paste = Paste::new(session.user.id, filename, description, body, visibility)?;
+    require_scope(&session, Scope::PastesWrite)?;
+    let filename = params.filename.parse::<Filename>();
+    let description = params.description.parse::<Description>();
+    let body = params.body.parse::<Body>();
+    let visibility = params.visibility.parse::<Visibility>();
+
+    let mut errors = FieldErrors::default();
+    errors.check("filename", &filename);
+    errors.check("description", &description);
+    errors.check("body", &body);
+    errors.check("visibility", &visibility);
+    errors.into_result()?;
+
+    let paste = Paste::new(session.user.id, filename?, description?, body?, visibility?)?;
     let id = paste.id;
     paste.insert(&db).await?;
     Ok(Json(id))
 }
 
+#[utoipa::path(
+    get,
+    path = "/pastes/{id}",
+    tag = "pastes",
+    summary = "Show a paste",
+    description = "Requires the `pastes:read` scope.",
+    params(
+        ("id" = Uuid, Path),
+        ("If-None-Match" = Option<String>, Header, description = "ETags of copies the client already has, to get a 304 if one is current"),
+    ),
+    responses(
+        (status = 200, body = Paste, headers(("ETag" = String))),
+        (status = 304, description = "The paste matches the ETag in If-None-Match"),
+        (status = 404, body = ErrorBody),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn show(
-    _session: ApiSession,
+    session: ApiSession,
     Path(id): Path<String>,
     State(db): State<Database>,
-) -> Result<impl IntoResponse> {
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    require_scope(&session, Scope::PastesRead)?;
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound)?;
     match Paste::find(&db, id).await? {
-        Some(paste) => Ok(Json(paste)),
-        None => Err(Error::NotFound),
+        Some(paste) if paste.is_visible_to(Some(&session.user)) => {
+            let etag = paste.etag();
+            if is_not_modified(&request_headers, &etag) {
+                return Ok((StatusCode::NOT_MODIFIED, etag_header(&etag)).into_response());
+            }
+            Ok((etag_header(&etag), Json(paste)).into_response())
+        }
+        Some(_) | None => Err(Error::NotFound),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/pastes/{id}/raw",
+    tag = "pastes",
+    summary = "Show a paste's body",
+    description = "Requires the `pastes:read` scope.",
+    params(
+        ("id" = Uuid, Path),
+        ("If-None-Match" = Option<String>, Header, description = "ETags of copies the client already has, to get a 304 if one is current"),
+    ),
+    responses(
+        (status = 200, body = String, content_type = "text/plain", headers(("ETag" = String))),
+        (status = 304, description = "The paste matches the ETag in If-None-Match"),
+        (status = 404, body = ErrorBody),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn show_raw(
-    _session: ApiSession,
+    session: ApiSession,
     Path(id): Path<String>,
     State(db): State<Database>,
-) -> Result<impl IntoResponse> {
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    require_scope(&session, Scope::PastesRead)?;
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound)?;
     match Paste::find(&db, id).await? {
-        Some(paste) => Ok(paste.body.to_string()),
-        None => Err(Error::NotFound),
+        Some(paste) if paste.is_visible_to(Some(&session.user)) => {
+            let etag = paste.etag();
+            let headers = [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+                (header::CONTENT_SECURITY_POLICY, SANDBOX_CSP),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            ];
+            if is_not_modified(&request_headers, &etag) {
+                return Ok((StatusCode::NOT_MODIFIED, headers, etag_header(&etag)).into_response());
+            }
+            Ok((headers, etag_header(&etag), paste.body.to_string()).into_response())
+        }
+        Some(_) | None => Err(Error::NotFound),
+    }
+}
+
+fn etag_header(etag: &ETag) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = etag.to_header_value() {
+        headers.insert(header::ETAG, value);
     }
+    headers
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, ToSchema)]
 pub struct UpdatePasteParams {
     pub filename: Option<String>,
     pub description: Option<String>,
@@ -92,74 +216,372 @@ pub struct UpdatePasteParams {
     pub visibility: Option<String>,
 }
 
+#[utoipa::path(
+    patch,
+    path = "/pastes/{id}",
+    tag = "pastes",
+    summary = "Update a paste",
+    description = "Requires the `pastes:write` scope. Fields that are left out are unchanged.",
+    params(
+        ("id" = Uuid, Path),
+        ("If-Match" = Option<String>, Header, description = "Only apply the change if the paste still has this ETag, or `*` for any version"),
+    ),
+    request_body = UpdatePasteParams,
+    responses(
+        (status = 200, headers(("ETag" = String, description = "The updated paste's ETag"))),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 412, body = ErrorBody, description = "The paste no longer matches If-Match"),
+        (status = 422, description = "Invalid fields, or a malformed request", content(
+            (ProblemDetails = "application/problem+json"),
+            (ErrorBody = "application/json"),
+        )),
+    ),
+    security(("api_key" = []))
+)]
+// Honors If-Match, so that clients can make sure they aren't overwriting changes they haven't seen
 pub async fn update(
     session: ApiSession,
     Path(id): Path<String>,
     State(db): State<Database>,
+    request_headers: HeaderMap,
     Json(params): Json<UpdatePasteParams>,
 ) -> Result<impl IntoResponse> {
+    require_scope(&session, Scope::PastesWrite)?;
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound)?;
-    let filename = match params.filename {
-        Some(filename) => {
-            Some(Filename::try_from(&filename).map_err(|e| Error::Unprocessable(Box::new(e)))?)
-        }
-        None => None,
-    };
-    let description = match params.description {
-        Some(description) => Some(
-            Description::try_from(&description).map_err(|e| Error::Unprocessable(Box::new(e)))?,
-        ),
-        None => None,
-    };
-    let body = match params.body {
-        Some(body) => Some(Body::try_from(&body).map_err(|e| Error::Unprocessable(Box::new(e)))?),
-        None => None,
-    };
-    let visibility = match params.visibility {
-        Some(visibility) => {
-            Some(Visibility::try_from(&visibility).map_err(|e| Error::Unprocessable(Box::new(e)))?)
-        }
-        None => None,
-    };
+    let filename = params.filename.map(|s| s.parse::<Filename>()).transpose();
+    let description = params
+        .description
+        .map(|s| s.parse::<Description>())
+        .transpose();
+    let body = params.body.map(|s| s.parse::<Body>()).transpose();
+    let visibility = params
+        .visibility
+        .map(|s| s.parse::<Visibility>())
+        .transpose();
 
-    let optional_paste = Paste::find(&db, id).await?;
+    let mut errors = FieldErrors::default();
+    errors.check("filename", &filename);
+    errors.check("description", &description);
+    errors.check("body", &body);
+    errors.check("visibility", &visibility);
+    errors.into_result()?;
+    let (filename, description, body, visibility) = (filename?, description?, body?, visibility?);
 
-    match optional_paste {
-        Some(paste) if paste.user_id == session.user.id => {
-            // Once a paste is public, we don't let people update it back to secret because the
-            // paste could have been indexed (or otherwise seen/recorded by someone) and we don't
-            // want to give the impression that we can somehow undo the paste's public disclosure
-            if paste.visibility.is_public()
-                && visibility.as_ref().map_or(false, Visibility::is_secret)
-            {
-                let e = ValidationError("Cannot change from public to secret visibility".into());
-                Err(Error::Unprocessable(Box::new(e)))
-            } else {
-                paste
-                    .update(&db, filename, description, body, visibility)
-                    .await?;
-                Ok(())
-            }
-        }
-        Some(_) => Err(Error::Forbidden),
-        None => Err(Error::NotFound),
+    let paste = match Paste::find(&db, id).await? {
+        Some(paste) if paste.user_id == session.user.id => paste,
+        Some(_) => return Err(Error::Forbidden),
+        None => return Err(Error::NotFound),
+    };
+    if let Some(visibility) = visibility {
+        let mut errors = FieldErrors::default();
+        errors.check("visibility", &paste.visibility.check_change_to(visibility));
+        errors.into_result()?;
     }
+
+    let if_match = IfMatch::from_headers(&request_headers);
+    let paste = paste
+        .update(&db, &if_match, filename, description, body, visibility)
+        .await?;
+    Ok(etag_header(&paste.etag()))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/pastes/{id}",
+    tag = "pastes",
+    summary = "Delete a paste",
+    description = "Requires the `pastes:delete` scope.",
+    params(
+        ("id" = Uuid, Path),
+        ("If-Match" = Option<String>, Header, description = "Only apply the change if the paste still has this ETag, or `*` for any version"),
+    ),
+    responses(
+        (status = 200),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 412, body = ErrorBody, description = "The paste no longer matches If-Match"),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn destroy(
     session: ApiSession,
     Path(id): Path<String>,
     State(db): State<Database>,
+    request_headers: HeaderMap,
 ) -> Result<impl IntoResponse> {
+    require_scope(&session, Scope::PastesDelete)?;
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound)?;
     let optional_paste = Paste::find(&db, id).await?;
 
     match optional_paste {
         Some(paste) if paste.user_id == session.user.id => {
-            paste.delete(&db).await?;
+            paste
+                .delete(&db, &IfMatch::from_headers(&request_headers))
+                .await?;
             Ok(())
         }
         Some(_) => Err(Error::Forbidden),
         None => Err(Error::NotFound),
     }
 }
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchParams {
+    #[serde(default)]
+    pub mode: BatchMode,
+    pub operations: Vec<BatchOperationParams>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOperationParams {
+    Create(CreatePasteParams),
+    Update(BatchUpdateParams),
+    Delete(BatchDeleteParams),
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchUpdateParams {
+    pub id: Uuid,
+    // Plays the part of the If-Match header, with the same values
+    pub if_match: Option<String>,
+    pub filename: Option<String>,
+    pub description: Option<String>,
+    pub body: Option<String>,
+    pub visibility: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchDeleteParams {
+    pub id: Uuid,
+    pub if_match: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchResponse {
+    // Whether any of the operations were applied
+    committed: bool,
+    results: Vec<BatchResult>,
+}
+
+// The outcome of one operation, in the same position as it was in the request
+#[derive(Serialize, ToSchema)]
+pub struct BatchResult {
+    #[schema(example = 201)]
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorReport>,
+}
+
+impl BatchResult {
+    fn success(status: StatusCode, id: Uuid, etag: Option<ETag>) -> Self {
+        Self {
+            status: status.as_u16(),
+            id: Some(id),
+            etag: etag.map(|etag| etag.to_string()),
+            error: None,
+        }
+    }
+
+    fn failure(error: Error) -> Self {
+        let report = error.into_report();
+        Self {
+            status: report.status(),
+            id: None,
+            etag: None,
+            error: Some(report),
+        }
+    }
+}
+
+impl From<PasteOperationOutcome> for BatchResult {
+    fn from(outcome: PasteOperationOutcome) -> Self {
+        match outcome {
+            PasteOperationOutcome::Created(paste) => {
+                Self::success(StatusCode::CREATED, paste.id, Some(paste.etag()))
+            }
+            PasteOperationOutcome::Updated(paste) => {
+                Self::success(StatusCode::OK, paste.id, Some(paste.etag()))
+            }
+            PasteOperationOutcome::Deleted(id) => Self::success(StatusCode::OK, id, None),
+            PasteOperationOutcome::NotFound => Self::failure(Error::NotFound),
+            PasteOperationOutcome::Forbidden => Self::failure(Error::Forbidden),
+            PasteOperationOutcome::PreconditionFailed => Self::failure(Error::PreconditionFailed),
+            PasteOperationOutcome::Invalid(err) => {
+                let mut errors = FieldErrors::default();
+                errors.add("visibility", err);
+                Self::failure(Error::InvalidFields(errors))
+            }
+            PasteOperationOutcome::RolledBack => Self::failure(Error::FailedDependency),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/pastes/batch",
+    tag = "pastes",
+    summary = "Create, update and delete pastes in one request",
+    description = "Requires the `pastes:write` scope for creates and updates, and `pastes:delete` for deletes. \
+        Each operation counts as a request against the API key's rate limit, up to a full bucket. \
+        Operations are applied in order in a single transaction. In `all_or_nothing` mode, the default, \
+        either all of them are applied or none are. In `per_item` mode, each is applied if it can be. \
+        Each operation has a result in the same position, with the status it would have had as a request \
+        of its own, or 424 if it was undone because another operation failed.",
+    request_body = BatchParams,
+    responses(
+        (status = 200, body = BatchResponse),
+        (status = 403, body = ErrorBody, description = "The API key is missing a scope that an operation needs"),
+        (status = 422, body = ErrorBody, description = "More than 100 operations"),
+        (status = 429, body = ErrorBody, description = "The API key's rate limit can't cover every operation"),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn batch(
+    session: ApiSession,
+    State(db): State<Database>,
+    State(config): State<Config>,
+    State(rate_limiter): State<RateLimiter>,
+    Json(params): Json<BatchParams>,
+) -> Result<Response> {
+    for operation in &params.operations {
+        match operation {
+            BatchOperationParams::Create(_) | BatchOperationParams::Update(_) => {
+                require_scope(&session, Scope::PastesWrite)?;
+            }
+            BatchOperationParams::Delete(_) => require_scope(&session, Scope::PastesDelete)?,
+        }
+    }
+    if params.operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(Error::Unprocessable(
+            format!("A batch may not have more than {MAX_BATCH_OPERATIONS} operations").into(),
+        ));
+    }
+    // The rate limiting middleware has already counted the first operation
+    let extra_operations =
+        u32::try_from(params.operations.len().saturating_sub(1)).unwrap_or(u32::MAX);
+    let rate_limit = rate_limiter.spend_api_key_tokens(
+        session.api_key.id,
+        config.rate_limit().api_key_quota(),
+        extra_operations,
+    );
+    if !rate_limit.allowed() {
+        return Ok((rate_limit, Error::TooManyRequests).into_response());
+    }
+
+    // Invalid operations never reach the database, so their results are kept aside to be slotted
+    // back in between the outcomes of the others. Errors aren't Send, so they're turned into
+    // results before anything is awaited.
+    let mut operations = Vec::with_capacity(params.operations.len());
+    let mut failures = Vec::with_capacity(params.operations.len());
+    for operation in params.operations {
+        match parse_operation(session.user.id, operation) {
+            Ok(operation) => {
+                operations.push(operation);
+                failures.push(None);
+            }
+            Err(err) => failures.push(Some(BatchResult::failure(err))),
+        }
+    }
+    if params.mode == BatchMode::AllOrNothing && operations.len() < failures.len() {
+        let results = failures
+            .into_iter()
+            .map(|failure| failure.unwrap_or_else(|| BatchResult::failure(Error::FailedDependency)))
+            .collect();
+        return Ok((
+            rate_limit,
+            Json(BatchResponse {
+                committed: false,
+                results,
+            }),
+        )
+            .into_response());
+    }
+
+    let mut outcomes = paste_batch::run(&db, session.user.id, params.mode, operations)
+        .await?
+        .into_iter();
+    let results: Vec<BatchResult> = failures
+        .into_iter()
+        .map(|failure| match failure {
+            Some(failure) => failure,
+            None => outcomes
+                .next()
+                .map_or_else(|| BatchResult::failure(Error::FailedDependency), Into::into),
+        })
+        .collect();
+    Ok((
+        rate_limit,
+        Json(BatchResponse {
+            committed: results.iter().any(|result| result.error.is_none()),
+            results,
+        }),
+    )
+        .into_response())
+}
+
+fn parse_operation(user_id: Uuid, operation: BatchOperationParams) -> Result<PasteOperation> {
+    match operation {
+        BatchOperationParams::Create(params) => {
+            let filename = params.filename.parse::<Filename>();
+            let description = params.description.parse::<Description>();
+            let body = params.body.parse::<Body>();
+            let visibility = params.visibility.parse::<Visibility>();
+
+            let mut errors = FieldErrors::default();
+            errors.check("filename", &filename);
+            errors.check("description", &description);
+            errors.check("body", &body);
+            errors.check("visibility", &visibility);
+            errors.into_result()?;
+
+            let paste = Paste::new(user_id, filename?, description?, body?, visibility?)?;
+            Ok(PasteOperation::Create(paste))
+        }
+        BatchOperationParams::Update(params) => {
+            let filename = params.filename.map(|s| s.parse::<Filename>()).transpose();
+            let description = params
+                .description
+                .map(|s| s.parse::<Description>())
+                .transpose();
+            let body = params.body.map(|s| s.parse::<Body>()).transpose();
+            let visibility = params
+                .visibility
+                .map(|s| s.parse::<Visibility>())
+                .transpose();
+
+            let mut errors = FieldErrors::default();
+            errors.check("filename", &filename);
+            errors.check("description", &description);
+            errors.check("body", &body);
+            errors.check("visibility", &visibility);
+            errors.into_result()?;
+
+            Ok(PasteOperation::Update {
+                id: params.id,
+                if_match: batch_if_match(params.if_match.as_deref()),
+                filename: filename?,
+                description: description?,
+                body: body?,
+                visibility: visibility?,
+            })
+        }
+        BatchOperationParams::Delete(params) => Ok(PasteOperation::Delete {
+            id: params.id,
+            if_match: batch_if_match(params.if_match.as_deref()),
+        }),
+    }
+}
+
+fn batch_if_match(value: Option<&str>) -> IfMatch {
+    match value.map(str::trim) {
+        None | Some("*") => IfMatch::Any,
+        // A tag that doesn't parse can't match anything, like in the If-Match header
+        Some(value) => IfMatch::Tags(value.parse().into_iter().collect()),
+    }
+}
@@ -1,41 +1,74 @@
+use crate::models::api_session::{ApiSession, Scope};
+use crate::models::paste::PasteFieldError;
 use crate::models::prelude::Error as ModelsError;
-use axum::http::StatusCode;
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-#[derive(Debug, thiserror::Error)]
-#[error("{0}")]
-pub struct ValidationError(pub String);
-
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("malformed request")]
     Unprocessable(Box<dyn std::error::Error>),
 
+    #[error("invalid fields")]
+    InvalidFields(FieldErrors),
+
     #[error("invalid authentication credentials")]
     Unauthorized,
 
     #[error("insufficient privileges")]
     Forbidden,
 
+    #[error("api key is missing the {0} scope")]
+    MissingScope(Scope),
+
     #[error("resource not found")]
     NotFound,
 
+    #[error("precondition failed")]
+    PreconditionFailed,
+
+    // An operation in an all-or-nothing batch that wasn't applied because another one failed
+    #[error("failed dependency")]
+    FailedDependency,
+
+    #[error("too many requests")]
+    TooManyRequests,
+
     #[error("internal server error: {0}")]
     InternalServerError(Box<dyn std::error::Error>),
 }
 
+impl From<PasteFieldError> for Error {
+    fn from(error: PasteFieldError) -> Self {
+        Self::Unprocessable(Box::new(error))
+    }
+}
+
 impl From<ModelsError> for Error {
     fn from(error: ModelsError) -> Self {
-        Self::InternalServerError(Box::new(error))
+        match error {
+            ModelsError::PreconditionFailed => Self::PreconditionFailed,
+            error => Self::InternalServerError(Box::new(error)),
+        }
     }
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> Response {
+// Parse errors come from what the client sent, anything else is on our end
+pub fn to_validation_error(err: ModelsError) -> Error {
+    match err {
+        ModelsError::Parse(_) => Error::Unprocessable(Box::new(err)),
+        err => err.into(),
+    }
+}
+
+impl Error {
+    pub fn into_report(self) -> ErrorReport {
         let (status, message) = match self {
             Error::Unauthorized => (
                 StatusCode::UNAUTHORIZED,
@@ -44,10 +77,41 @@ impl IntoResponse for Error {
 
             Error::Forbidden => (StatusCode::FORBIDDEN, "Insufficient privileges".into()),
 
+            Error::MissingScope(scope) => (
+                StatusCode::FORBIDDEN,
+                format!("This API key is missing the '{scope}' scope."),
+            ),
+
             Error::NotFound => (StatusCode::NOT_FOUND, "Resource not found.".into()),
 
+            Error::PreconditionFailed => (
+                StatusCode::PRECONDITION_FAILED,
+                "The resource has changed since the version in If-Match. Fetch it again for its current ETag.".into(),
+            ),
+
+            Error::FailedDependency => (
+                StatusCode::FAILED_DEPENDENCY,
+                "Not applied, because another operation in the batch failed.".into(),
+            ),
+
+            Error::TooManyRequests => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Rate limit exceeded, please retry later.".into(),
+            ),
+
             Error::Unprocessable(err) => (StatusCode::UNPROCESSABLE_ENTITY, format!("{err}")),
 
+            Error::InvalidFields(field_errors) => {
+                let status = StatusCode::UNPROCESSABLE_ENTITY;
+                return ErrorReport::Problem(ProblemDetails {
+                    problem_type: "about:blank",
+                    title: status.canonical_reason().unwrap_or_default(),
+                    status: status.as_u16(),
+                    detail: "One or more fields are invalid.".into(),
+                    errors: field_errors.0,
+                });
+            }
+
             Error::InternalServerError(err) => {
                 tracing::error!(%err, "internal server error");
                 (
@@ -57,27 +121,135 @@ impl IntoResponse for Error {
             }
         };
 
-        let body = ErrorBody::new(status, &message);
-        (status, Json(body)).into_response()
+        ErrorReport::Error(ErrorBody::new(status, message))
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let report = self.into_report();
+        let status =
+            StatusCode::from_u16(report.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(&report)).into_response();
+        if let ErrorReport::Problem(_) = report {
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            );
+        }
+        response
+    }
+}
+
+// Handlers check scopes before anything else, so that a key without the scope can't learn whether
+// a paste exists from the difference between a 403 and a 404
+pub fn require_scope(session: &ApiSession, scope: Scope) -> Result<()> {
+    if session.scopes().contains(&scope) {
+        Ok(())
+    } else {
+        Err(Error::MissingScope(scope))
+    }
+}
+
+// Invalid fields are reported as RFC 9457 problem details, so that each problem can carry a code.
+// Every other error keeps the plain body that clients already parse.
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum ErrorReport {
+    Problem(ProblemDetails),
+    Error(ErrorBody),
+}
+
+impl ErrorReport {
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Problem(problem) => problem.status,
+            Self::Error(body) => body.status,
+        }
     }
 }
 
-#[derive(Serialize)]
-struct ErrorBody {
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    #[schema(example = 404)]
     status: u16,
+    #[schema(example = "Not Found")]
     error: String,
+    #[schema(example = "Resource not found.")]
     message: String,
 }
 
 impl ErrorBody {
-    fn new(status_code: StatusCode, message: &str) -> Self {
+    fn new(status_code: StatusCode, message: String) -> Self {
         Self {
             status: status_code.as_u16(),
             error: status_code
                 .canonical_reason()
                 .unwrap_or_default()
                 .to_string(),
-            message: message.to_string(),
+            message,
+        }
+    }
+}
+
+// There are no problem types beyond the status code, so `type` is always `about:blank` and `title`
+// is the status's reason phrase. Every problem found is listed under the field's name.
+#[derive(Serialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    problem_type: &'static str,
+    #[schema(example = "Unprocessable Entity")]
+    title: &'static str,
+    #[schema(example = 422)]
+    status: u16,
+    #[schema(example = "One or more fields are invalid.")]
+    detail: String,
+    errors: BTreeMap<&'static str, Vec<FieldError>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldError {
+    #[schema(example = "too_long")]
+    code: &'static str,
+    #[schema(example = "Filename may not be longer than 256 characters")]
+    message: String,
+}
+
+impl From<PasteFieldError> for FieldError {
+    fn from(error: PasteFieldError) -> Self {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+// Collects the problems with each field of a request, so that they can all be reported at once
+// rather than only the first
+#[derive(Debug, Default)]
+pub struct FieldErrors(BTreeMap<&'static str, Vec<FieldError>>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &'static str, error: impl Into<FieldError>) {
+        self.0.entry(field).or_default().push(error.into());
+    }
+
+    pub fn check<T, E: Copy + Into<FieldError>>(
+        &mut self,
+        field: &'static str,
+        result: &Result<T, E>,
+    ) {
+        if let Err(e) = result {
+            self.add(field, *e);
+        }
+    }
+
+    pub fn into_result(self) -> Result<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidFields(self))
         }
     }
 }
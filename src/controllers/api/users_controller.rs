@@ -0,0 +1,144 @@
+use crate::controllers::api::prelude::*;
+use crate::db::Database;
+use crate::extractors::pagination::ApiPagination;
+use crate::helpers::pagination_helper::{CursorPaginationParams, CursorPaginationResponse};
+use crate::models::api_session::{ApiSession, Scope};
+use crate::models::paste::Paste;
+use crate::models::paste_query::{PasteCursor, PasteFilterParams, PasteQuery};
+use crate::models::user::{User, Username};
+use axum::extract::{Path, Query, State};
+use axum::response::IntoResponse;
+use axum::Json;
+use jiff::Timestamp;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Serialize, ToSchema)]
+pub struct ShowMeResponse {
+    id: Uuid,
+    username: String,
+    email: String,
+    display_name: String,
+    bio: String,
+    website: String,
+    #[schema(value_type = String, format = DateTime)]
+    created_at: Timestamp,
+}
+
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "users",
+    summary = "Show the current user",
+    description = "The account that the API key belongs to. Requires the `account:read` scope.",
+    responses((status = 200, body = ShowMeResponse)),
+    security(("api_key" = []))
+)]
+pub async fn show_me(session: ApiSession) -> Result<impl IntoResponse> {
+    require_scope(&session, Scope::AccountRead)?;
+    let user = session.user;
+    Ok(Json(ShowMeResponse {
+        id: user.id,
+        username: user.username.to_string(),
+        email: user.email.to_string(),
+        display_name: user.display_name.to_string(),
+        bio: user.bio.to_string(),
+        website: user.website.to_string(),
+        created_at: user.created_at,
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PastesResponse {
+    pastes: Vec<Paste>,
+    pagination: CursorPaginationResponse<PasteCursor>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/pastes",
+    tag = "users",
+    summary = "List the current user's pastes",
+    description = "Includes secret and hidden pastes. Requires the `pastes:read` scope.",
+    params(PasteFilterParams, CursorPaginationParams<PasteCursor>),
+    responses(
+        (status = 200, body = PastesResponse, headers(("Link" = String, description = "Links to the previous and next pages"))),
+        (status = 422, body = ErrorBody, description = "Invalid filters or cursor"),
+    ),
+    security(("api_key" = []))
+)]
+// The caller's own pastes, including secret and hidden ones, which no other endpoint lists
+pub async fn index_my_pastes(
+    session: ApiSession,
+    State(db): State<Database>,
+    Query(filter_params): Query<PasteFilterParams>,
+    api_pagination: ApiPagination<PasteCursor>,
+) -> Result<impl IntoResponse> {
+    require_scope(&session, Scope::PastesRead)?;
+    let query = PasteQuery::all_for_user_id(session.user.id)
+        .filtered(&filter_params)
+        .map_err(to_validation_error)?;
+    index_pastes_for_query(&db, query, &api_pagination).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/{username}/pastes",
+    tag = "users",
+    summary = "List a user's public pastes",
+    description = "Requires the `pastes:read` scope.",
+    params(("username" = String, Path), PasteFilterParams, CursorPaginationParams<PasteCursor>),
+    responses(
+        (status = 200, body = PastesResponse, headers(("Link" = String, description = "Links to the previous and next pages"))),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody, description = "Invalid filters or cursor"),
+    ),
+    security(("api_key" = []))
+)]
+// Only ever public pastes, even when the username is the caller's own, so that the response
+// doesn't depend on who is asking
+pub async fn index_pastes(
+    session: ApiSession,
+    Path(username): Path<String>,
+    State(db): State<Database>,
+    Query(filter_params): Query<PasteFilterParams>,
+    api_pagination: ApiPagination<PasteCursor>,
+) -> Result<impl IntoResponse> {
+    require_scope(&session, Scope::PastesRead)?;
+    let username = Username::try_from(&username).map_err(|_| Error::NotFound)?;
+    let user = User::find_by_username(&db, username)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let query = PasteQuery::public_for_user_id(user.id)
+        .filtered(&filter_params)
+        .map_err(to_validation_error)?;
+    index_pastes_for_query(&db, query, &api_pagination).await
+}
+
+async fn index_pastes_for_query(
+    db: &Database,
+    query: PasteQuery,
+    api_pagination: &ApiPagination<PasteCursor>,
+) -> Result<impl IntoResponse> {
+    let pagination_params = &api_pagination.params;
+    let sort = query.sort();
+    let mut pastes = query
+        .cursor_paginated(
+            db,
+            pagination_params.limit_with_lookahead(),
+            pagination_params.direction(),
+            pagination_params.cursor(),
+        )
+        .await
+        .map_err(to_validation_error)?;
+    let pagination =
+        CursorPaginationResponse::new_with_lookahead_by(pagination_params, &mut pastes, |paste| {
+            sort.cursor_for(paste)
+        });
+    Ok((
+        api_pagination.link_headers(&pagination),
+        Json(PastesResponse { pastes, pagination }),
+    ))
+}
@@ -0,0 +1,10 @@
+use crate::controllers::api::openapi_controller::ApiDoc;
+use crate::controllers::prelude::*;
+use crate::models::session::Session;
+use crate::views::api_docs::show::ShowPage;
+use axum::response::IntoResponse;
+use utoipa::OpenApi;
+
+pub async fn show(session: Option<Session>) -> Result<impl IntoResponse> {
+    Ok(ShowPage::from((session, ApiDoc::openapi())))
+}
@@ -1,23 +1,78 @@
+use crate::controllers::invite_codes_controller::Expiry;
 use crate::controllers::prelude::*;
 use crate::db::Database;
-use crate::models::api_session::ApiKey;
+use crate::models::api_session::{ApiKey, Scope, Scopes};
 use crate::models::session::Session;
-use crate::views::api_sessions::create::CreatePage;
+use crate::views::api_sessions::create::{CreatePage, NewApiKeyFormPartial};
 use axum::{
-    extract::{Path, State},
+    extract::{Form, Path, State},
     response::IntoResponse,
 };
+use serde::Deserialize;
 use uuid::Uuid;
 
-pub async fn create(session: Session, State(db): State<Database>) -> Result<impl IntoResponse> {
-    let (unhashed_key, api_key) = ApiKey::new(session.user.id);
-    let api_key_id = api_key.id;
+// Each scope is its own checkbox, since url encoded forms can't be deserialized into a list
+#[derive(Clone, Debug, Deserialize)]
+#[expect(clippy::struct_excessive_bools)]
+pub struct CreateParams {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "pastes:read")]
+    pub pastes_read: bool,
+    #[serde(default, rename = "pastes:write")]
+    pub pastes_write: bool,
+    #[serde(default, rename = "pastes:delete")]
+    pub pastes_delete: bool,
+    #[serde(default, rename = "account:read")]
+    pub account_read: bool,
+    #[serde(default = "never_expires")]
+    pub expiry: Expiry,
+}
+
+fn never_expires() -> Expiry {
+    Expiry::Never
+}
+
+impl CreateParams {
+    fn scopes(&self) -> Scopes {
+        [
+            (Scope::PastesRead, self.pastes_read),
+            (Scope::PastesWrite, self.pastes_write),
+            (Scope::PastesDelete, self.pastes_delete),
+            (Scope::AccountRead, self.account_read),
+        ]
+        .into_iter()
+        .filter_map(|(scope, checked)| checked.then_some(scope))
+        .collect()
+    }
+}
+
+pub async fn create(
+    session: Session,
+    State(db): State<Database>,
+    Form(params): Form<CreateParams>,
+) -> Result<impl IntoResponse> {
+    let scopes = params.scopes();
+    let error_template = |msg: &str| NewApiKeyFormPartial {
+        name: params.name.clone(),
+        scopes: scopes.clone(),
+        expiry: params.expiry,
+        error_message: Some(msg.into()),
+    };
 
-    api_key.insert(&db).await?;
+    let (unhashed_key, api_key) = ApiKey::new(
+        session.user.id,
+        &params.name,
+        scopes.clone(),
+        params.expiry.span(),
+    )
+    .map_err(|e| to_validation_error(Some(session.clone()), e, error_template))?;
+    api_key.clone().insert(&db).await?;
 
     Ok(CreatePage {
         unhashed_key,
-        api_key_id,
+        api_key,
+        new_api_key_form: NewApiKeyFormPartial::default(),
     })
 }
 
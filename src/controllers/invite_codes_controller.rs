@@ -0,0 +1,87 @@
+use crate::config::Config;
+use crate::controllers::prelude::*;
+use crate::db::Database;
+use crate::models::invite_code::{InviteCode, InviteCodeRedemption};
+use crate::models::session::Session;
+use crate::views::invite_codes::index::{IndexPage, NewInviteCodeFormPartial};
+use axum::extract::{Form, State};
+use axum::http::{header::HeaderMap, HeaderValue};
+use axum::response::IntoResponse;
+use jiff::{Span, ToSpan};
+use serde::Deserialize;
+
+pub async fn index(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+) -> Result<impl IntoResponse> {
+    let invite_codes = InviteCode::all_for_user_id(&db, session.user.id).await?;
+    let redemptions = InviteCodeRedemption::all_for_creator_id(&db, session.user.id).await?;
+    let spent = InviteCode::invitations_spent_by_user_id(&db, session.user.id).await?;
+
+    Ok(IndexPage {
+        session: Some(session),
+        registration_mode: config.registration_mode().clone(),
+        invite_codes,
+        redemptions,
+        new_invite_code_form: NewInviteCodeFormPartial {
+            remaining_invitations: config.invite_allowance().saturating_sub(spent).max(0),
+            ..Default::default()
+        },
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiry {
+    OneDay,
+    #[default]
+    OneWeek,
+    OneMonth,
+    Never,
+}
+
+impl Expiry {
+    pub fn span(self) -> Option<Span> {
+        match self {
+            Self::OneDay => Some(24.hours()),
+            Self::OneWeek => Some((7 * 24).hours()),
+            Self::OneMonth => Some((30 * 24).hours()),
+            Self::Never => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CreateParams {
+    pub max_uses: i64,
+    #[serde(default)]
+    pub expiry: Expiry,
+}
+
+pub async fn create(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+    Form(params): Form<CreateParams>,
+) -> Result<impl IntoResponse> {
+    let spent = InviteCode::invitations_spent_by_user_id(&db, session.user.id).await?;
+    let remaining_invitations = config.invite_allowance().saturating_sub(spent).max(0);
+    let error_template = |msg: &str| NewInviteCodeFormPartial {
+        remaining_invitations,
+        max_uses: params.max_uses,
+        expiry: params.expiry,
+        error_message: Some(msg.into()),
+    };
+
+    let invite_code = InviteCode::new(session.user.id, params.max_uses, params.expiry.span())
+        .map_err(|e| to_validation_error(Some(session.clone()), e, error_template))?;
+    invite_code
+        .insert_within_allowance(&db, config.invite_allowance())
+        .await
+        .map_err(|e| to_validation_error(Some(session.clone()), e, error_template))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Redirect", HeaderValue::from_static("/settings/invites"));
+    Ok(headers)
+}
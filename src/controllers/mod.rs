@@ -1,7 +1,11 @@
+pub mod admin_controller;
 pub mod api;
+pub mod api_docs_controller;
 pub mod api_sessions_controller;
 pub mod application_controller;
+pub mod invite_codes_controller;
 pub mod pastes_controller;
 pub mod prelude;
 pub mod sessions_controller;
 pub mod users_controller;
+pub mod webhooks_controller;
@@ -1,34 +1,66 @@
 use crate::controllers::prelude::*;
 use crate::db::Database;
+use crate::helpers::conditional_request_helper::{is_not_modified, IfMatch};
+use crate::helpers::diff_helper::line_diff;
 use crate::helpers::pagination_helper::{CursorPaginationParams, CursorPaginationResponse};
-use crate::models::paste::{Body, Description, Filename, Paste, Visibility};
+use crate::middleware::security_headers::SANDBOX_CSP;
+use crate::models::paste::{Body, Description, Filename, Paste, Visibility, MAX_PINNED_PASTES};
+use crate::models::paste_batch::{self, BatchMode, PasteOperation, MAX_BATCH_OPERATIONS};
+use crate::models::paste_query::{PasteCursor, PasteFilterParams, PasteQuery};
 use crate::models::prelude::Error as ModelsError;
 use crate::models::session::Session;
 use crate::models::user::{User, Username};
+use crate::views::pastes::conflict::{ConflictPage, ConflictingField};
 use crate::views::pastes::edit::{EditFormPartial, EditPage};
 use crate::views::pastes::index::IndexPage;
 use crate::views::pastes::new::{NewFormPartial, NewPage};
-use crate::views::pastes::show::ShowPage;
+use crate::views::pastes::show::{PinButtonPartial, ShowPage};
+use crate::views::users::show::BatchActionsPartial;
 use axum::extract::{Form, Path, Query, State};
-use axum::http::{header::HeaderMap, HeaderValue, StatusCode};
-use axum::response::IntoResponse;
+use axum::http::{header, header::HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
 use serde::Deserialize;
+use std::str::FromStr;
 use uuid::Uuid;
 
 pub async fn index(
     session: Option<Session>,
-    Query(pagination_params): Query<CursorPaginationParams>,
+    Query(pagination_params): Query<CursorPaginationParams<PasteCursor>>,
+    Query(filter_params): Query<PasteFilterParams>,
     State(db): State<Database>,
 ) -> Result<impl IntoResponse> {
-    let mut pairs = Paste::cursor_paginated_with_username(
-        &db,
-        pagination_params.limit_with_lookahead(),
-        pagination_params.direction(),
-        pagination_params.cursor(),
-    )
-    .await?;
-    let pagination_response =
-        CursorPaginationResponse::new_with_lookahead(&pagination_params, &mut pairs);
+    let to_filter_error = |e| {
+        to_validation_error(session.clone(), e, |msg| IndexPage {
+            session: session.clone(),
+            paste_username_html_triples: Vec::new(),
+            pagination: CursorPaginationResponse {
+                prev_page: None,
+                next_page: None,
+            },
+            filters: filter_params.clone(),
+            filter_error: Some(msg.into()),
+            show_owner_filter: true,
+            show_visibility_filter: false,
+        })
+    };
+    let query = PasteQuery::public()
+        .filtered(&filter_params)
+        .map_err(to_filter_error)?;
+    let sort = query.sort();
+    let mut pairs = query
+        .cursor_paginated_with_username(
+            &db,
+            pagination_params.limit_with_lookahead(),
+            pagination_params.direction(),
+            pagination_params.cursor(),
+        )
+        .await
+        .map_err(to_filter_error)?;
+    let pagination_response = CursorPaginationResponse::new_with_lookahead_by(
+        &pagination_params,
+        &mut pairs,
+        |(paste, _)| sort.cursor_for(paste),
+    );
     let mut triples = Vec::new();
     for (paste, username) in pairs {
         let optional_html = paste
@@ -40,6 +72,10 @@ pub async fn index(
         session,
         paste_username_html_triples: triples,
         pagination: pagination_response,
+        filters: filter_params,
+        filter_error: None,
+        show_owner_filter: true,
+        show_visibility_filter: false,
     })
 }
 
@@ -117,17 +153,30 @@ pub async fn show(
     session: Option<Session>,
     State(db): State<Database>,
     Path((username, id)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
+    uri: Uri,
+) -> Result<Response> {
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound(session.clone()))?;
-    let username = Username::try_from(&username).map_err(|_| Error::NotFound(session.clone()))?;
-
-    let user = User::find_by_username(&db, username)
-        .await?
-        .ok_or(Error::NotFound(session.clone()))?;
+    let user = match find_user_by_url_username(&db, session.as_ref(), &username, &uri).await? {
+        UserLookup::Found(user) => *user,
+        UserLookup::Renamed(redirect) => return Ok(redirect.into_response()),
+    };
     let paste = Paste::find_scoped_by_user_id(&db, id, user.id)
         .await?
+        .filter(|paste| paste.is_visible_to(session.as_ref().map(|s| &s.user)))
         .ok_or(Error::NotFound(session.clone()))?;
     let syntax_highlighted_html = paste.syntax_highlight(&db).await?;
+    // Only public pastes can be pinned, since pinning is about showing pastes off on a profile
+    let is_owner = session.as_ref().is_some_and(|s| s.user.id == paste.user_id);
+    let pin_button = if is_owner && paste.visibility.is_public() {
+        Some(PinButtonPartial {
+            username: user.username.to_string(),
+            paste_id: paste.id.as_simple().to_string(),
+            is_pinned: paste.is_pinned(&db).await?,
+            ..Default::default()
+        })
+    } else {
+        None
+    };
 
     let mut headers = HeaderMap::new();
     if paste.visibility.is_secret() {
@@ -142,49 +191,54 @@ pub async fn show(
             paste,
             username: user.username,
             syntax_highlighted_html,
+            pin_button,
         },
-    ))
+    )
+        .into_response())
 }
 
 pub async fn show_raw(
     session: Option<Session>,
     State(db): State<Database>,
     Path((username, id)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
+    uri: Uri,
+    request_headers: HeaderMap,
+) -> Result<Response> {
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound(session.clone()))?;
-    let username = Username::try_from(&username).map_err(|_| Error::NotFound(session.clone()))?;
-
-    let user = User::find_by_username(&db, username)
-        .await?
-        .ok_or(Error::NotFound(session.clone()))?;
+    let user = match find_user_by_url_username(&db, session.as_ref(), &username, &uri).await? {
+        UserLookup::Found(user) => *user,
+        UserLookup::Renamed(redirect) => return Ok(redirect.into_response()),
+    };
     let paste = Paste::find_scoped_by_user_id(&db, id, user.id)
         .await?
+        .filter(|paste| paste.is_visible_to(session.as_ref().map(|s| &s.user)))
         .ok_or(Error::NotFound(session))?;
 
-    let mut headers = HeaderMap::new();
-    if paste.visibility.is_secret() {
-        headers.insert("X-Robots-Tag", HeaderValue::from_static("noindex"));
+    let headers = raw_headers(&paste);
+    if is_not_modified(&request_headers, &paste.etag()) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
     }
-
-    Ok((StatusCode::OK, headers, paste.body.to_string()))
+    Ok((StatusCode::OK, headers, paste.body.to_string()).into_response())
 }
 
 pub async fn download(
     session: Option<Session>,
     State(db): State<Database>,
     Path((username, id)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
+    uri: Uri,
+    request_headers: HeaderMap,
+) -> Result<Response> {
     let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound(session.clone()))?;
-    let username = Username::try_from(&username).map_err(|_| Error::NotFound(session.clone()))?;
-
-    let user = User::find_by_username(&db, username)
-        .await?
-        .ok_or(Error::NotFound(session.clone()))?;
+    let user = match find_user_by_url_username(&db, session.as_ref(), &username, &uri).await? {
+        UserLookup::Found(user) => *user,
+        UserLookup::Renamed(redirect) => return Ok(redirect.into_response()),
+    };
     let paste = Paste::find_scoped_by_user_id(&db, id, user.id)
         .await?
+        .filter(|paste| paste.is_visible_to(session.as_ref().map(|s| &s.user)))
         .ok_or(Error::NotFound(session.clone()))?;
 
-    let mut headers = HeaderMap::new();
+    let mut headers = raw_headers(&paste);
     headers.insert(
         "Content-Disposition",
         HeaderValue::from_str(&format!("attachment; filename=\"{}\"", paste.filename)).map_err(
@@ -194,11 +248,36 @@ pub async fn download(
             },
         )?,
     );
+
+    if is_not_modified(&request_headers, &paste.etag()) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    Ok((StatusCode::OK, headers, paste.body.to_string()).into_response())
+}
+
+// Headers for serving a paste's body verbatim. The body is whatever the user pasted, so it's always
+// served as plain text and sandboxed, in case a browser is talked into rendering it anyway.
+fn raw_headers(paste: &Paste) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(etag) = paste.etag().to_header_value() {
+        headers.insert(header::ETAG, etag);
+    }
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static(SANDBOX_CSP),
+    );
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
     if paste.visibility.is_secret() {
         headers.insert("X-Robots-Tag", HeaderValue::from_static("noindex"));
     }
-
-    Ok((StatusCode::OK, headers, paste.body.to_string()))
+    headers
 }
 
 pub async fn edit(
@@ -236,6 +315,19 @@ pub struct UpdateParams {
     pub description: String,
     pub body: String,
     pub visibility: String,
+    // The ETag of the version being edited. Without it, the update applies to whatever is current.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl UpdateParams {
+    fn if_match(&self) -> IfMatch {
+        match self.version.as_deref().map(str::trim) {
+            None | Some("") => IfMatch::Any,
+            // A version that doesn't parse can't match anything, so it's reported as a conflict
+            Some(version) => IfMatch::Tags(version.parse().into_iter().collect()),
+        }
+    }
 }
 
 pub async fn update(
@@ -266,13 +358,13 @@ pub async fn update(
         "HX-Redirect",
         HeaderValue::from_str(&format!("/{username}/{}", &paste.id)).map_err(|e| {
             Error::InternalServerError {
-                session: Some(session),
+                session: Some(session.clone()),
                 source: Box::new(e),
             }
         })?,
     );
 
-    let mut error_template = EditFormPartial::from((username, paste_id, params.clone()));
+    let mut error_template = EditFormPartial::from((username.clone(), paste_id, params.clone()));
 
     let filename_result = Filename::try_from(&params.filename);
     if let Err(ModelsError::Parse(ref msg)) = filename_result {
@@ -306,26 +398,82 @@ pub async fn update(
         visibility_result?,
     );
 
-    // Once a paste is public, we don't let people update it back to secret because the paste could
-    // have been indexed (or otherwise seen/recorded by someone) and we don't want to give the
-    // impression that we can somehow undo the paste's public disclosure
-    if paste.visibility.is_public() && visibility.is_secret() {
-        error_template.visibility_error_message =
-            Some("Cannot change from public to secret visibility".into());
+    if let Err(e) = paste.visibility.check_change_to(visibility) {
+        error_template.visibility_error_message = Some(e.to_string());
         return Err(Error::Unprocessable(Box::new(error_template)));
     }
 
-    paste
+    let result = paste
         .update(
             &db,
+            &params.if_match(),
             Some(filename),
             Some(description),
             Some(body),
             Some(visibility),
         )
-        .await?;
+        .await;
+    match result {
+        Ok(_) => Ok(response),
+        Err(ModelsError::PreconditionFailed) => {
+            let current = Paste::find_scoped_by_user_id(&db, paste_id, session.user.id)
+                .await?
+                .ok_or(Error::NotFound(Some(session.clone())))?;
+            Err(Error::Conflict(Box::new(conflict_page(
+                session, username, &current, params,
+            ))))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
 
-    Ok(response)
+// Shows how the submitted edit differs from the current version of the paste, with the edit form
+// filled in again so that it can be saved over the current version
+fn conflict_page(
+    session: Session,
+    username: Username,
+    current: &Paste,
+    params: UpdateParams,
+) -> ConflictPage {
+    let mut changed_fields = Vec::new();
+    for (label, current, submitted) in [
+        ("Filename", current.filename.to_string(), &params.filename),
+        (
+            "Description",
+            current.description.to_string(),
+            &params.description,
+        ),
+        (
+            "Visibility",
+            current.visibility.to_string(),
+            &params.visibility,
+        ),
+    ] {
+        if &current != submitted {
+            changed_fields.push(ConflictingField {
+                label,
+                current,
+                submitted: submitted.clone(),
+            });
+        }
+    }
+    let current_body = current.body.to_string();
+    let body_diff = if current_body == params.body {
+        Vec::new()
+    } else {
+        line_diff(&current_body, &params.body)
+    };
+    let mut edit_pastes_form = EditFormPartial::from((username, current.id, params));
+    edit_pastes_form.version = current.etag().to_string();
+
+    ConflictPage {
+        session: Some(session),
+        paste_id: current.id,
+        filename: current.filename.to_string(),
+        changed_fields,
+        body_diff,
+        edit_pastes_form,
+    }
 }
 
 pub async fn destroy(
@@ -347,9 +495,156 @@ pub async fn destroy(
     let paste = Paste::find_scoped_by_user_id(&db, id, session.user.id)
         .await?
         .ok_or(Error::NotFound(Some(session)))?;
-    paste.delete(&db).await?;
+    paste.delete(&db, &IfMatch::Any).await?;
 
     let mut response = HeaderMap::new();
     response.insert("HX-Redirect", HeaderValue::from_static("/pastes"));
     Ok(response)
 }
+
+// Acts on the pastes picked out with the checkboxes on the signed in user's profile. Each paste is
+// acted on if it can be. The profile is reloaded to show the result if they all were, otherwise
+// the batch actions say how many weren't.
+pub async fn batch(
+    session: Session,
+    State(db): State<Database>,
+    Form(params): Form<Vec<(String, String)>>,
+) -> Result<Response> {
+    let mut action = None;
+    let mut ids = Vec::new();
+    for (key, value) in params {
+        match key.as_str() {
+            "action" => action = Some(value),
+            "ids" => ids
+                .push(Uuid::try_parse(&value).map_err(|_| Error::NotFound(Some(session.clone())))?),
+            _ => {}
+        }
+    }
+    let unprocessable = |msg: String| {
+        Error::Unprocessable(Box::new(BatchActionsPartial {
+            error_message: Some(msg),
+        }))
+    };
+    if ids.is_empty() {
+        return Err(unprocessable("Select at least one paste".into()));
+    }
+    if ids.len() > MAX_BATCH_OPERATIONS {
+        return Err(unprocessable(format!(
+            "Select at most {MAX_BATCH_OPERATIONS} pastes at a time"
+        )));
+    }
+
+    let (operations, verb) = match action.as_deref() {
+        Some("delete") => (
+            ids.into_iter()
+                .map(|id| PasteOperation::Delete {
+                    id,
+                    if_match: IfMatch::Any,
+                })
+                .collect::<Vec<_>>(),
+            "deleted",
+        ),
+        Some("make_public") => (
+            ids.into_iter()
+                .map(|id| PasteOperation::Update {
+                    id,
+                    if_match: IfMatch::Any,
+                    filename: None,
+                    description: None,
+                    body: None,
+                    visibility: Some(Visibility::Public),
+                })
+                .collect(),
+            "made public",
+        ),
+        _ => {
+            return Err(unprocessable(
+                "Choose what to do with the selected pastes".into(),
+            ))
+        }
+    };
+    let outcomes = paste_batch::run(&db, session.user.id, BatchMode::PerItem, operations).await?;
+
+    let failures = outcomes
+        .iter()
+        .filter(|outcome| !outcome.is_success())
+        .count();
+    if failures == 0 {
+        let mut response = HeaderMap::new();
+        response.insert("HX-Refresh", HeaderValue::from_static("true"));
+        return Ok(response.into_response());
+    }
+    let mut error_message = format!(
+        "{failures} of {} selected pastes could not be {verb}.",
+        outcomes.len()
+    );
+    if failures < outcomes.len() {
+        error_message.push_str(" Reload the page to see the rest.");
+    }
+    Ok(BatchActionsPartial {
+        error_message: Some(error_message),
+    }
+    .into_response())
+}
+
+pub async fn pin(
+    session: Session,
+    State(db): State<Database>,
+    Path((username, id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let paste = find_owned_paste(&db, &session, &username, &id).await?;
+    let mut template = PinButtonPartial {
+        username: session.user.username.to_string(),
+        paste_id: paste.id.as_simple().to_string(),
+        ..Default::default()
+    };
+
+    if paste.visibility.is_secret() {
+        template.error_message = Some("Only public pastes can be pinned".into());
+        return Err(Error::Unprocessable(Box::new(template)));
+    }
+    if !paste.pin(&db).await? {
+        template.error_message = Some(format!("You can pin at most {MAX_PINNED_PASTES} pastes"));
+        return Err(Error::Unprocessable(Box::new(template)));
+    }
+
+    template.is_pinned = true;
+    Ok(template)
+}
+
+pub async fn unpin(
+    session: Session,
+    State(db): State<Database>,
+    Path((username, id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let paste = find_owned_paste(&db, &session, &username, &id).await?;
+    paste.unpin(&db).await?;
+
+    Ok(PinButtonPartial {
+        username: session.user.username.to_string(),
+        paste_id: paste.id.as_simple().to_string(),
+        ..Default::default()
+    })
+}
+
+async fn find_owned_paste(
+    db: &Database,
+    session: &Session,
+    username: &str,
+    id: &str,
+) -> Result<Paste> {
+    let id = Uuid::try_parse(id).map_err(|_| Error::NotFound(Some(session.clone())))?;
+    let username =
+        Username::from_str(username).map_err(|_| Error::NotFound(Some(session.clone())))?;
+
+    let user = User::find_by_username(db, username)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))?;
+    if session.user != user {
+        return Err(Error::Forbidden(Some(session.clone())));
+    }
+
+    Paste::find_scoped_by_user_id(db, id, session.user.id)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))
+}
@@ -1,16 +1,25 @@
+use crate::db::Database;
+use crate::helpers::password_hashing_helper::Error as PasswordHashingError;
 use crate::models::prelude::Error as ModelsError;
 use crate::models::session::Session;
+use crate::models::user::{User, Username};
 use crate::views::errors::forbidden::ForbiddenPage;
 use crate::views::errors::internal_server_error::InternalServerErrorPage;
 use crate::views::errors::not_found::NotFoundPage;
+use crate::views::errors::service_unavailable::ServiceUnavailablePage;
 use crate::views::errors::unauthorized::UnauthorizedPage;
 use askama::Template;
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
+use axum::http::{header, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use jiff::SignedDuration;
 use std::fmt::Debug;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+// Password hashes only take a fraction of a second each, so a saturated hashing pool usually
+// drains quickly
+const SERVICE_UNAVAILABLE_RETRY_AFTER_SECS: u64 = 1;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("invalid authentication credentials")]
@@ -28,6 +37,19 @@ pub enum Error {
     #[error("unprocessable content")]
     Unprocessable(Box<dyn ErrorTemplate>),
 
+    // The change was based on a version of the resource that is no longer current
+    #[error("conflict")]
+    Conflict(Box<dyn ErrorTemplate>),
+
+    #[error("too many requests")]
+    TooManyRequests {
+        retry_after: SignedDuration,
+        template: Box<dyn ErrorTemplate>,
+    },
+
+    #[error("service unavailable")]
+    ServiceUnavailable(Option<Session>),
+
     #[error("internal server error: {source}")]
     InternalServerError {
         session: Option<Session>,
@@ -37,9 +59,14 @@ pub enum Error {
 
 impl From<ModelsError> for Error {
     fn from(error: ModelsError) -> Self {
-        Self::InternalServerError {
-            session: None,
-            source: Box::new(error),
+        match error {
+            ModelsError::PasswordHashing(PasswordHashingError::Saturated) => {
+                Self::ServiceUnavailable(None)
+            }
+            error => Self::InternalServerError {
+                session: None,
+                source: Box::new(error),
+            },
         }
     }
 }
@@ -91,6 +118,55 @@ impl IntoResponse for Error {
                 }
             },
 
+            Error::Conflict(template) => match template.render_template() {
+                Ok(html) => (StatusCode::CONFLICT, html).into_response(),
+                Err(err) => {
+                    tracing::error!(%err, "template rendering error");
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        InternalServerErrorPage { session: None },
+                    )
+                        .into_response()
+                }
+            },
+
+            Error::TooManyRequests {
+                retry_after,
+                template,
+            } => match template.render_template() {
+                Ok(html) => {
+                    // Retry-After is a whole number of seconds, so round up to avoid retrying early
+                    let retry_after_secs =
+                        retry_after.as_secs() + i64::from(retry_after.subsec_nanos() > 0);
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                        html,
+                    )
+                        .into_response()
+                }
+                Err(err) => {
+                    tracing::error!(%err, "template rendering error");
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        InternalServerErrorPage { session: None },
+                    )
+                        .into_response()
+                }
+            },
+
+            Error::ServiceUnavailable(maybe_session) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(
+                    header::RETRY_AFTER,
+                    SERVICE_UNAVAILABLE_RETRY_AFTER_SECS.to_string(),
+                )],
+                ServiceUnavailablePage {
+                    session: maybe_session,
+                },
+            )
+                .into_response(),
+
             Error::InternalServerError {
                 session: maybe_session,
                 source,
@@ -125,6 +201,9 @@ where
 {
     match err {
         ModelsError::Parse(msg) => Error::Unprocessable(Box::new(f(&msg))),
+        ModelsError::PasswordHashing(PasswordHashingError::Saturated) => {
+            Error::ServiceUnavailable(session)
+        }
         e => Error::InternalServerError {
             session,
             source: Box::new(e),
@@ -140,9 +219,50 @@ where
     match err {
         ModelsError::Parse(msg) => Error::UnauthorizedInline(Box::new(f(&msg))),
         ModelsError::Argon2(e) => Error::UnauthorizedInline(Box::new(f(&e.to_string()))),
+        ModelsError::PasswordHashing(PasswordHashingError::Saturated) => {
+            Error::ServiceUnavailable(session)
+        }
         e => Error::InternalServerError {
             session,
             source: Box::new(e),
         },
     }
 }
+
+// The outcome of looking up the user that the leading `/:username` segment of a URL refers to
+pub enum UserLookup {
+    Found(Box<User>),
+    // The username has since been changed, so the URL permanently redirects to the same URL under
+    // the user's current username
+    Renamed(Redirect),
+}
+
+pub async fn find_user_by_url_username(
+    db: &Database,
+    session: Option<&Session>,
+    username: &str,
+    uri: &Uri,
+) -> Result<UserLookup> {
+    let not_found = || Error::NotFound(session.cloned());
+    let username = Username::try_from(&username.to_string()).map_err(|_| not_found())?;
+
+    if let Some(user) = User::find_by_username(db, username.clone()).await? {
+        return Ok(UserLookup::Found(Box::new(user)));
+    }
+
+    let user = User::find_by_previous_username(db, username)
+        .await?
+        .ok_or_else(not_found)?;
+    let rest_of_path = uri
+        .path()
+        .trim_start_matches('/')
+        .split_once('/')
+        .map_or(String::new(), |(_, rest)| format!("/{rest}"));
+    let query = uri
+        .query()
+        .map_or(String::new(), |query| format!("?{query}"));
+    Ok(UserLookup::Renamed(Redirect::permanent(&format!(
+        "/{}{rest_of_path}{query}",
+        user.username
+    ))))
+}
@@ -1,14 +1,22 @@
+use crate::config::Config;
 use crate::controllers::prelude::*;
 use crate::db::Database;
-use crate::models::session::{Session, SessionToken, SESSION_COOKIE_NAME};
-use crate::models::user::{EmailAddress, UnhashedPassword, User};
+use crate::extractors::client_info::ClientInfo;
+use crate::helpers::password_hashing_helper::PasswordHashingPool;
+use crate::helpers::session_cookie_helper;
+use crate::models::prelude::Error as ModelsError;
+use crate::models::security_event::{SecurityEvent, SecurityEventKind};
+use crate::models::session::{Session, SessionToken};
+use crate::models::throttle::{Throttle, ThrottleKey, ThrottleScope, ThrottledAttempt};
+use crate::models::user::{EmailAddress, HashedPassword, UnhashedPassword, User};
 use crate::views::sessions::new::NewPage;
-use axum::body::Body;
-use axum::extract::{Form, State};
-use axum::http::{header::HeaderMap, HeaderValue, StatusCode};
-use axum::response::{IntoResponse, Response};
-use secrecy::{ExposeSecret, SecretString};
+use axum::extract::{Form, Path, State};
+use axum::http::{header::HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Redirect};
+use axum_extra::extract::CookieJar;
+use secrecy::SecretString;
 use serde::Deserialize;
+use uuid::Uuid;
 
 pub async fn new() -> NewPage {
     NewPage::default()
@@ -18,60 +26,185 @@ pub async fn new() -> NewPage {
 pub struct CreateParams {
     pub email: String,
     pub password: SecretString,
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 pub async fn create(
     State(db): State<Database>,
+    State(config): State<Config>,
+    State(hashing_pool): State<PasswordHashingPool>,
+    client_info: ClientInfo,
     Form(params): Form<CreateParams>,
 ) -> Result<impl IntoResponse> {
-    let email = EmailAddress::try_from(&params.email).map_err(|e| {
-        to_unauthorized_error(None, e, |_| NewPage {
-            error_message: Some("Incorrect email or password".into()),
-            ..params.clone().into()
-        })
-    })?;
-
-    let password = UnhashedPassword::try_from(params.password.clone()).map_err(|e| {
-        to_unauthorized_error(None, e, |_| NewPage {
-            error_message: Some("Incorrect email or password".into()),
-            ..params.clone().into()
-        })
-    })?;
-
-    let user = match User::find_by_email(&db, email).await? {
-        Some(user) if user.verify_password(&password).is_ok() => user,
-        _ => Err(Error::UnauthorizedInline(Box::new(NewPage {
-            error_message: Some("Incorrect email or password".into()),
-            ..params.into()
-        })))?,
+    // The account throttle is keyed on the submitted email rather than on a user, so that it
+    // behaves identically whether or not an account exists for that email
+    let account_throttle_key = ThrottleKey::new(ThrottleScope::LoginAccount, &params.email);
+    let throttle_keys: Vec<_> = account_throttle_key
+        .iter()
+        .cloned()
+        .chain(
+            client_info
+                .ip_address
+                .and_then(|ip| ThrottleKey::new(ThrottleScope::LoginIp, &ip.to_string())),
+        )
+        .collect();
+
+    // The attempt is counted before the password is verified, and handed back if it turns out to
+    // be right
+    let locked_keys = match Throttle::attempt(&db, config.throttle(), throttle_keys.clone()).await?
+    {
+        ThrottledAttempt::RetryAfter(retry_after) => {
+            return Err(Error::TooManyRequests {
+                retry_after,
+                template: Box::new(NewPage {
+                    error_message: Some("Too many sign in attempts, please try again later".into()),
+                    ..params.clone().into()
+                }),
+            });
+        }
+        ThrottledAttempt::Counted { locked_keys } => locked_keys,
     };
 
-    let (unhashed_token, hashed_token) = SessionToken::new(user.id);
-    let response = Response::builder()
-        .status(StatusCode::SEE_OTHER)
-        .header("Location", "/new")
-        .header(
-            "Set-Cookie",
-            format!(
-                "{}={}; Max-Age=999999; Secure; HttpOnly; SameSite=Lax",
-                SESSION_COOKIE_NAME,
-                &unhashed_token.expose_secret()
-            ),
-        )
-        .body(Body::empty())
-        .map_err(|e| Error::InternalServerError {
-            session: None,
-            source: Box::new(e),
-        })?;
+    let maybe_user = match EmailAddress::try_from(&params.email) {
+        Ok(email) => User::find_by_email(&db, email).await?,
+        Err(_) => None,
+    };
+    let password = UnhashedPassword::try_from(params.password.clone());
+
+    // Without an account the password is checked against a dummy hash, so that the response takes
+    // as long as it would have if the account existed. A mismatched password is an ordinary failed
+    // sign in, but anything else that goes wrong while verifying, such as the hashing pool being
+    // saturated, has to be surfaced as is, and isn't held against the client.
+    let verification = match (&maybe_user, &password) {
+        (Some(user), Ok(password)) => match user.verify_password(password, &hashing_pool).await {
+            Ok(()) => Ok(true),
+            Err(ModelsError::Argon2(_)) => Ok(false),
+            Err(e) => Err(e),
+        },
+        (None, Ok(password)) => {
+            let dummy = HashedPassword::dummy(hashing_pool.params());
+            match dummy.verify(password, &hashing_pool).await {
+                Ok(()) | Err(ModelsError::Argon2(_)) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+        (_, Err(_)) => Ok(false),
+    };
+    let verified = match verification {
+        Ok(verified) => verified,
+        Err(e) => {
+            Throttle::refund(&db, throttle_keys, locked_keys).await?;
+            return Err(e.into());
+        }
+    };
+
+    let (user, password) = match (maybe_user, password) {
+        (Some(user), Ok(password)) if verified => (user, password),
+        (maybe_user, _) => {
+            // Verifying can take a while, so the delay before the next attempt starts from here
+            Throttle::restart_delays(&db, throttle_keys).await?;
+            if let Some(user) = maybe_user {
+                if locked_keys
+                    .iter()
+                    .any(|key| key.scope == ThrottleScope::LoginAccount)
+                {
+                    SecurityEvent::new(
+                        user.id,
+                        SecurityEventKind::AccountLocked,
+                        client_info.user_agent.clone(),
+                        client_info.ip_address.map(|ip| ip.to_string()),
+                    )
+                    .insert(&db)
+                    .await?;
+                }
+            }
+
+            return Err(Error::UnauthorizedInline(Box::new(NewPage {
+                error_message: Some("Incorrect email or password".into()),
+                ..params.into()
+            })));
+        }
+    };
+
+    Throttle::refund(&db, throttle_keys, locked_keys).await?;
+    if let Some(key) = account_throttle_key {
+        Throttle::clear(&db, key).await?;
+    }
+
+    if user.is_suspended() {
+        return Err(Error::UnauthorizedInline(Box::new(NewPage {
+            error_message: Some("This account has been suspended".into()),
+            ..params.into()
+        })));
+    }
+
+    // Sign in is the only time that the plaintext password is available, so it's when hashes
+    // created with weaker parameters than currently configured get upgraded. The upgrade can wait
+    // for the next sign in, so failing to make it, e.g. because the hashing pool is saturated,
+    // doesn't fail this one.
+    if user.password.is_weaker_than(hashing_pool.params()) {
+        if let Err(e) = user.rehash_password(&db, password, &hashing_pool).await {
+            tracing::error!("failed to rehash password for user {user}: {e}");
+        }
+    }
+
+    let remember_me = params.remember_me;
+    let (unhashed_token, hashed_token) = SessionToken::new(
+        user.id,
+        client_info.user_agent,
+        client_info.ip_address.map(|ip| ip.to_string()),
+        remember_me,
+    );
     hashed_token.insert(&db).await?;
 
-    Ok(response)
+    let cookie = session_cookie_helper::build(config.session(), &unhashed_token, remember_me);
+    Ok((CookieJar::new().add(cookie), Redirect::to("/new")))
 }
 
-pub async fn delete(session: Session, State(db): State<Database>) -> Result<impl IntoResponse> {
-    session.user.delete_sessions(&db).await?;
+pub async fn delete(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+) -> Result<impl IntoResponse> {
+    session.session_token.delete(&db).await?;
 
     let mut headers = HeaderMap::new();
     headers.insert("HX-Redirect", HeaderValue::from_static("/"));
+    let cookie = session_cookie_helper::build_removal(config.session());
+    Ok((CookieJar::new().add(cookie), headers))
+}
+
+pub async fn destroy(
+    session: Session,
+    State(db): State<Database>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound(Some(session.clone())))?;
+
+    let session_token = SessionToken::find_scoped_by_user_id(&db, id, session.user.id)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))?;
+    let is_current_session = session_token.id == session.session_token.id;
+    session_token.delete(&db).await?;
+
+    let mut headers = HeaderMap::new();
+    if is_current_session {
+        headers.insert("HX-Redirect", HeaderValue::from_static("/"));
+    }
+    Ok(headers)
+}
+
+pub async fn destroy_others(
+    session: Session,
+    State(db): State<Database>,
+) -> Result<impl IntoResponse> {
+    session
+        .session_token
+        .delete_all_others_for_user_id(&db)
+        .await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Redirect", HeaderValue::from_static("/settings"));
     Ok(headers)
 }
@@ -1,27 +1,50 @@
+use crate::config::{Config, RegistrationMode};
 use crate::controllers::prelude::*;
 use crate::db::Database;
+use crate::extractors::client_info::ClientInfo;
+use crate::helpers::breached_password_helper;
 use crate::helpers::pagination_helper::{CursorPaginationParams, CursorPaginationResponse};
+use crate::helpers::password_hashing_helper::PasswordHashingPool;
+use crate::helpers::session_cookie_helper;
+use crate::models::account_deletion::AccountDeletion;
 use crate::models::api_session::ApiKey;
-use crate::models::invite_code::InviteCode;
+use crate::models::avatar::Avatar;
 use crate::models::paste::Paste;
+use crate::models::paste_query::{PasteCursor, PasteFilterParams, PasteQuery};
 use crate::models::prelude::Error as ModelsError;
-use crate::models::session::{Session, SessionToken, SESSION_COOKIE_NAME};
-use crate::models::user::{EmailAddress, UnhashedPassword, User, Username};
+use crate::models::security_event::{SecurityEvent, SecurityEventKind};
+use crate::models::session::{Session, SessionToken};
+use crate::models::throttle::{Throttle, ThrottleKey, ThrottleScope, ThrottledAttempt};
+use crate::models::user::{
+    Bio, DisplayName, EmailAddress, UnhashedPassword, User, Username, Website,
+};
 use crate::views::users::new::{
     EmailInputPartial, NewPage, PasswordInputPartial, UsernameInputPartial,
 };
-use crate::views::users::settings::{ChangePasswordFormPartial, SettingsPage};
-use crate::views::users::show::ShowPage;
-use axum::body::Body;
-use axum::extract::{Form, State};
+use crate::views::users::settings::{
+    AccountFormPartial, AvatarFormPartial, ChangePasswordFormPartial, DeleteAccountFormPartial,
+    ProfileFormPartial, SettingsPage,
+};
+use crate::views::users::show::{BatchActionsPartial, ShowPage};
+use axum::extract::multipart::MultipartError;
+use axum::extract::{Form, Multipart, State};
 use axum::extract::{Path, Query};
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Response};
+use axum::http::{header, header::HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::extract::CookieJar;
+use jiff::SignedDuration;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
 
-pub async fn new() -> NewPage {
-    NewPage::default()
+const TOO_MANY_VALIDATIONS_MESSAGE: &str = "Too many requests, please try again later";
+const BREACHED_PASSWORD_MESSAGE: &str =
+    "Password has appeared in a data breach, please choose a different one";
+
+pub async fn new(State(config): State<Config>) -> NewPage {
+    NewPage {
+        registration_mode: config.registration_mode().clone(),
+        ..Default::default()
+    }
 }
 
 #[derive(Clone, Deserialize)]
@@ -29,14 +52,27 @@ pub struct CreateParams {
     pub username: String,
     pub email: String,
     pub password: SecretString,
+    // Only submitted when the registration mode requires an invite code
+    #[serde(default)]
     pub invite_code: String,
 }
 
 pub async fn create(
     State(db): State<Database>,
+    State(config): State<Config>,
+    State(hashing_pool): State<PasswordHashingPool>,
+    client_info: ClientInfo,
     Form(params): Form<CreateParams>,
 ) -> Result<impl IntoResponse> {
-    let mut error_template: NewPage = params.clone().into();
+    let registration_mode = config.registration_mode();
+    if registration_mode.is_closed() {
+        return Err(Error::Forbidden(None));
+    }
+
+    let mut error_template = NewPage {
+        registration_mode: registration_mode.clone(),
+        ..params.clone().into()
+    };
 
     let username_result = Username::try_from(&params.username);
     if let Err(ModelsError::Parse(ref msg)) = username_result {
@@ -50,6 +86,11 @@ pub async fn create(
     if let Err(ModelsError::Parse(ref msg)) = password_result {
         error_template.password_error_message = Some(msg.into());
     }
+    if let Ok(ref password) = password_result {
+        if let Some(msg) = breached_password_error_message(&config, None, password).await? {
+            error_template.password_error_message = Some(msg);
+        }
+    }
 
     if let Ok(ref username) = username_result {
         if User::find_by_username(&db, username.clone())
@@ -60,7 +101,9 @@ pub async fn create(
         }
     }
     if let Ok(ref email) = email_result {
-        if User::find_by_email(&db, email.clone()).await?.is_some() {
+        if let Some(msg) = email_domain_error_message(registration_mode, email) {
+            error_template.email_error_message = Some(msg);
+        } else if User::find_by_email(&db, email.clone()).await?.is_some() {
             error_template.email_error_message = Some("Email is already taken".into());
         }
     }
@@ -72,71 +115,99 @@ pub async fn create(
         return Err(Error::Unprocessable(Box::new(error_template)));
     }
 
-    let Some(invite_code) = InviteCode::find(&db, &params.invite_code).await? else {
-        error_template.invite_code_error_message = Some("Invalid invite code".into());
-        return Err(Error::Unprocessable(Box::new(error_template)));
-    };
-
     let (username, email, password) = (username_result?, email_result?, password_result?);
-    let user: User = User::new(username, email, password)?;
+    let user: User = User::new(username, email, password, &hashing_pool).await?;
     let user_id = user.id;
-    user.insert(&db).await?;
-
-    let (unhashed_token, hashed_token) = SessionToken::new(user_id);
-    let response = Response::builder()
-        .status(StatusCode::SEE_OTHER)
-        .header("Location", "/new")
-        .header(
-            "Set-Cookie",
-            format!(
-                "{}={}; Max-Age=999999; Secure; HttpOnly",
-                SESSION_COOKIE_NAME,
-                &unhashed_token.expose_secret()
-            ),
-        )
-        .body(Body::empty())
-        .map_err(|e| Error::InternalServerError {
-            session: None,
-            source: Box::new(e),
-        })?;
-    hashed_token.insert(&db).await?;
+    if registration_mode.requires_invite_code() {
+        if !user
+            .insert_with_invite_code(&db, params.invite_code.trim().to_string())
+            .await?
+        {
+            error_template.invite_code_error_message = Some("Invalid invite code".into());
+            return Err(Error::Unprocessable(Box::new(error_template)));
+        }
+    } else {
+        user.insert(&db).await?;
+    }
 
-    invite_code.delete(&db).await?;
+    let (unhashed_token, hashed_token) = SessionToken::new(
+        user_id,
+        client_info.user_agent,
+        client_info.ip_address.map(|ip| ip.to_string()),
+        false,
+    );
+    hashed_token.insert(&db).await?;
 
-    Ok(response)
+    let cookie = session_cookie_helper::build(config.session(), &unhashed_token, false);
+    Ok((CookieJar::new().add(cookie), Redirect::to("/new")))
 }
 
 pub async fn show(
     session: Option<Session>,
     State(db): State<Database>,
     Path(username): Path<String>,
-    Query(pagination_params): Query<CursorPaginationParams>,
-) -> Result<impl IntoResponse> {
-    let username = Username::try_from(&username).map_err(|_| Error::NotFound(session.clone()))?;
-
-    match User::find_by_username(&db, username).await? {
-        Some(user) => {
-            let mut pastes = if Some(&user) == session.as_ref().map(|s| &s.user) {
-                Paste::cursor_paginated_for_user_id_with_secrets(
-                    &db,
-                    user.id,
-                    pagination_params.limit_with_lookahead(),
-                    pagination_params.direction(),
-                    pagination_params.cursor(),
-                )
-                .await?
+    Query(pagination_params): Query<CursorPaginationParams<PasteCursor>>,
+    Query(filter_params): Query<PasteFilterParams>,
+    uri: Uri,
+) -> Result<Response> {
+    let user_lookup = find_user_by_url_username(&db, session.as_ref(), &username, &uri).await?;
+    match user_lookup {
+        UserLookup::Found(user) => {
+            let user = *user;
+            let is_owner = Some(&user) == session.as_ref().map(|s| &s.user);
+            let avatar_created_at = Avatar::created_at_for_user_id(&db, user.id).await?;
+            let to_filter_error = |e| {
+                to_validation_error(session.clone(), e, |msg| ShowPage {
+                    session: session.clone(),
+                    user: user.clone(),
+                    avatar_created_at,
+                    pinned_pastes: Vec::new(),
+                    paste_html_pairs: Vec::new(),
+                    pagination: CursorPaginationResponse {
+                        prev_page: None,
+                        next_page: None,
+                    },
+                    filters: filter_params.clone(),
+                    filter_error: Some(msg.into()),
+                    show_owner_filter: false,
+                    show_visibility_filter: is_owner,
+                    batch_actions: None,
+                })
+            };
+            let query = if is_owner {
+                PasteQuery::all_for_user_id(user.id)
             } else {
-                Paste::cursor_paginated_for_user_id(
+                PasteQuery::public_for_user_id(user.id)
+            }
+            .filtered(&filter_params)
+            .map_err(to_filter_error)?;
+            let sort = query.sort();
+            let mut pastes = query
+                .cursor_paginated(
                     &db,
-                    user.id,
                     pagination_params.limit_with_lookahead(),
                     pagination_params.direction(),
                     pagination_params.cursor(),
                 )
-                .await?
-            };
-            let pagination_response =
-                CursorPaginationResponse::new_with_lookahead(&pagination_params, &mut pastes);
+                .await
+                .map_err(to_filter_error)?;
+            let pagination_response = CursorPaginationResponse::new_with_lookahead_by(
+                &pagination_params,
+                &mut pastes,
+                |paste| sort.cursor_for(paste),
+            );
+            // Pinned pastes are only shown on the first page, above the rest of the pastes, and
+            // not at all while filtering, since they might not match the filters
+            let pinned_pastes =
+                if pagination_params.cursor().is_none() && !filter_params.is_active() {
+                    Paste::pinned_for_user_id(&db, user.id, true)
+                        .await?
+                        .into_iter()
+                        .filter(|paste| paste.is_visible_to(session.as_ref().map(|s| &s.user)))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
             let mut pairs = Vec::new();
             for paste in pastes {
                 let optional_html = paste
@@ -147,21 +218,323 @@ pub async fn show(
             Ok(ShowPage {
                 session,
                 user,
+                avatar_created_at,
+                pinned_pastes,
                 paste_html_pairs: pairs,
                 pagination: pagination_response,
-            })
+                filters: filter_params,
+                filter_error: None,
+                show_owner_filter: false,
+                show_visibility_filter: is_owner,
+                batch_actions: is_owner.then(BatchActionsPartial::default),
+            }
+            .into_response())
         }
-        None => Err(Error::NotFound(session)),
+        UserLookup::Renamed(redirect) => Ok(redirect.into_response()),
     }
 }
 
-pub async fn settings(session: Session, State(db): State<Database>) -> Result<impl IntoResponse> {
+#[derive(Deserialize)]
+pub struct ShowAvatarParams {
+    pub v: Option<i64>,
+}
+
+pub async fn show_avatar(
+    session: Option<Session>,
+    State(db): State<Database>,
+    Path(username): Path<String>,
+    Query(params): Query<ShowAvatarParams>,
+    uri: Uri,
+) -> Result<Response> {
+    let user_lookup = find_user_by_url_username(&db, session.as_ref(), &username, &uri).await?;
+    let user = match user_lookup {
+        UserLookup::Found(user) => *user,
+        UserLookup::Renamed(redirect) => return Ok(redirect.into_response()),
+    };
+    let avatar = Avatar::find_for_user_id(&db, user.id)
+        .await?
+        .ok_or(Error::NotFound(session))?;
+
+    // Avatar URLs carry the avatar's creation time, so a URL that matches the current avatar will
+    // never change and can be cached indefinitely
+    let cache_control = if params.v == Some(avatar.created_at.as_millisecond()) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        avatar.image,
+    )
+        .into_response())
+}
+
+pub async fn settings(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+) -> Result<impl IntoResponse> {
     let api_keys = ApiKey::all_for_user_id(&db, session.user.id).await?;
+    let session_tokens = SessionToken::all_for_user_id(&db, session.user.id).await?;
+    let security_events = SecurityEvent::recent_for_user_id(&db, session.user.id).await?;
+    let account_deletion = AccountDeletion::find_for_user_id(&db, session.user.id).await?;
+    let account_form = AccountFormPartial {
+        username: session.user.username.to_string(),
+        email: session.user.email.to_string(),
+        ..Default::default()
+    };
+    let profile_form = ProfileFormPartial {
+        display_name: session.user.display_name.to_string(),
+        bio: session.user.bio.to_string(),
+        website: session.user.website.to_string(),
+        ..Default::default()
+    };
+    let avatar_form = AvatarFormPartial {
+        username: session.user.username.to_string(),
+        avatar_created_at: Avatar::created_at_for_user_id(&db, session.user.id).await?,
+        ..Default::default()
+    };
     let session = Some(session);
 
     Ok(SettingsPage {
         session,
+        profile_form,
+        avatar_form,
         api_keys,
+        session_tokens,
+        security_events,
+        account_form,
+        account_deletion,
+        delete_account_form: DeleteAccountFormPartial {
+            has_grace_period: config.account_deletion_grace_period().is_some(),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+}
+
+#[derive(Clone, Deserialize)]
+pub struct UpdateAccountParams {
+    pub username: String,
+    pub email: String,
+    pub password: SecretString,
+}
+
+pub async fn update_account(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+    State(hashing_pool): State<PasswordHashingPool>,
+    client_info: ClientInfo,
+    Form(params): Form<UpdateAccountParams>,
+) -> Result<impl IntoResponse> {
+    let username = Username::try_from(&params.username).map_err(|e| {
+        to_validation_error(Some(session.clone()), e, |msg| AccountFormPartial {
+            username_error_message: Some(msg.into()),
+            ..params.clone().into()
+        })
+    })?;
+    let email = EmailAddress::try_from(&params.email).map_err(|e| {
+        to_validation_error(Some(session.clone()), e, |msg| AccountFormPartial {
+            email_error_message: Some(msg.into()),
+            ..params.clone().into()
+        })
+    })?;
+
+    if username != session.user.username
+        && User::find_by_username(&db, username.clone())
+            .await?
+            .is_some()
+    {
+        Err(Error::Unprocessable(Box::new(AccountFormPartial {
+            username_error_message: Some("Username is already taken".into()),
+            ..params.clone().into()
+        })))?;
+    }
+
+    if email != session.user.email {
+        if let Some(msg) = email_domain_error_message(config.registration_mode(), &email) {
+            Err(Error::Unprocessable(Box::new(AccountFormPartial {
+                email_error_message: Some(msg),
+                ..params.clone().into()
+            })))?;
+        }
+
+        if User::find_by_email(&db, email.clone()).await?.is_some() {
+            Err(Error::Unprocessable(Box::new(AccountFormPartial {
+                email_error_message: Some("Email is already taken".into()),
+                ..params.clone().into()
+            })))?;
+        }
+    }
+
+    let password = UnhashedPassword::try_from(params.password.clone()).map_err(|e| {
+        to_unauthorized_error(Some(session.clone()), e, |_| AccountFormPartial {
+            password_error_message: Some("Incorrect password".into()),
+            ..params.clone().into()
+        })
+    })?;
+
+    session
+        .user
+        .verify_password(&password, &hashing_pool)
+        .await
+        .map_err(|e| {
+            to_unauthorized_error(Some(session.clone()), e, |_| AccountFormPartial {
+                password_error_message: Some("Incorrect password".into()),
+                ..params.clone().into()
+            })
+        })?;
+
+    // The checks above give a friendly error in the common case, but another account can still
+    // claim the username or email in the meantime
+    let result = session
+        .user
+        .update_account(
+            &db,
+            username,
+            email,
+            client_info.user_agent,
+            client_info.ip_address.map(|ip| ip.to_string()),
+        )
+        .await;
+    match result {
+        Ok(_) => {}
+        Err(ModelsError::UsernameTaken) => {
+            return Err(Error::Unprocessable(Box::new(AccountFormPartial {
+                username_error_message: Some("Username is already taken".into()),
+                ..params.into()
+            })));
+        }
+        Err(ModelsError::EmailTaken) => {
+            return Err(Error::Unprocessable(Box::new(AccountFormPartial {
+                email_error_message: Some("Email is already taken".into()),
+                ..params.into()
+            })));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // The whole page is reloaded, since the username also appears in the navigation
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Redirect", HeaderValue::from_static("/settings"));
+    Ok(headers)
+}
+
+#[derive(Clone, Deserialize)]
+pub struct UpdateProfileParams {
+    pub display_name: String,
+    pub bio: String,
+    pub website: String,
+}
+
+pub async fn update_profile(
+    session: Session,
+    State(db): State<Database>,
+    Form(params): Form<UpdateProfileParams>,
+) -> Result<impl IntoResponse> {
+    let mut error_template: ProfileFormPartial = params.clone().into();
+
+    let display_name_result = DisplayName::try_from(&params.display_name);
+    if let Err(ModelsError::Parse(ref msg)) = display_name_result {
+        error_template.display_name_error_message = Some(msg.into());
+    }
+    let bio_result = Bio::try_from(&params.bio);
+    if let Err(ModelsError::Parse(ref msg)) = bio_result {
+        error_template.bio_error_message = Some(msg.into());
+    }
+    let website_result = Website::try_from(&params.website);
+    if let Err(ModelsError::Parse(ref msg)) = website_result {
+        error_template.website_error_message = Some(msg.into());
+    }
+
+    if error_template.display_name_error_message.is_some()
+        || error_template.bio_error_message.is_some()
+        || error_template.website_error_message.is_some()
+    {
+        return Err(Error::Unprocessable(Box::new(error_template)));
+    }
+
+    let (display_name, bio, website) = (display_name_result?, bio_result?, website_result?);
+    session
+        .user
+        .update_profile(&db, display_name.clone(), bio.clone(), website.clone())
+        .await?;
+
+    Ok(ProfileFormPartial {
+        display_name: display_name.to_string(),
+        bio: bio.to_string(),
+        website: website.to_string(),
+        show_success_message: true,
+        ..Default::default()
+    })
+}
+
+pub async fn update_avatar(
+    session: Session,
+    State(db): State<Database>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let username = session.user.username.to_string();
+    let avatar_created_at = Avatar::created_at_for_user_id(&db, session.user.id).await?;
+    let error_template = |msg: &str| AvatarFormPartial {
+        username: username.clone(),
+        avatar_created_at,
+        error_message: Some(msg.into()),
+    };
+
+    // A malformed multipart body is treated like an unreadable image, and one that's too large is
+    // reported with the same message the model uses for oversized uploads
+    let multipart_error = |e: MultipartError| {
+        let msg = if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            "Avatar is too large (maximum is 1 MB)"
+        } else {
+            "Avatar could not be read"
+        };
+        Error::Unprocessable(Box::new(error_template(msg)))
+    };
+    let mut bytes = None;
+    loop {
+        let next_field = multipart.next_field().await.map_err(multipart_error);
+        let Some(field) = next_field? else { break };
+        if field.name() == Some("avatar") {
+            let field_bytes = field.bytes().await.map_err(multipart_error);
+            bytes = Some(field_bytes?);
+        }
+    }
+    let bytes = bytes.unwrap_or_default();
+
+    let user_id = session.user.id;
+    let avatar = tokio::task::spawn_blocking(move || Avatar::from_upload(user_id, &bytes))
+        .await
+        .map_err(|e| Error::InternalServerError {
+            session: Some(session.clone()),
+            source: Box::new(e),
+        })?
+        .map_err(|e| to_validation_error(Some(session.clone()), e, error_template))?;
+    let avatar_created_at = Some(avatar.created_at);
+    avatar.insert(&db).await?;
+
+    Ok(AvatarFormPartial {
+        username,
+        avatar_created_at,
+        ..Default::default()
+    })
+}
+
+pub async fn destroy_avatar(
+    session: Session,
+    State(db): State<Database>,
+) -> Result<impl IntoResponse> {
+    Avatar::delete_for_user_id(&db, session.user.id).await?;
+
+    Ok(AvatarFormPartial {
+        username: session.user.username.to_string(),
         ..Default::default()
     })
 }
@@ -171,11 +544,16 @@ pub struct ChangePasswordParams {
     pub old_password: SecretString,
     pub new_password: SecretString,
     pub new_password_confirm: SecretString,
+    #[serde(default)]
+    pub revoke_api_keys: bool,
 }
 
 pub async fn change_password(
     session: Session,
     State(db): State<Database>,
+    State(config): State<Config>,
+    State(hashing_pool): State<PasswordHashingPool>,
+    client_info: ClientInfo,
     Form(params): Form<ChangePasswordParams>,
 ) -> Result<impl IntoResponse> {
     let new_password = UnhashedPassword::try_from(params.new_password.clone()).map_err(|e| {
@@ -185,6 +563,15 @@ pub async fn change_password(
         })
     })?;
 
+    if let Some(msg) =
+        breached_password_error_message(&config, Some(&session), &new_password).await?
+    {
+        Err(Error::Unprocessable(Box::new(ChangePasswordFormPartial {
+            new_password_error_message: Some(msg),
+            ..params.clone().into()
+        })))?;
+    }
+
     if params.new_password.expose_secret() != params.new_password_confirm.expose_secret() {
         Err(Error::Unprocessable(Box::new(ChangePasswordFormPartial {
             new_password_error_message: Some(
@@ -201,34 +588,154 @@ pub async fn change_password(
         })
     })?;
 
-    session.user.verify_password(&old_password).map_err(|e| {
-        to_unauthorized_error(Some(session.clone()), e, |_| ChangePasswordFormPartial {
-            old_password_error_message: Some("Incorrect password".into()),
-            ..params.into()
+    session
+        .user
+        .verify_password(&old_password, &hashing_pool)
+        .await
+        .map_err(|e| {
+            to_unauthorized_error(Some(session.clone()), e, |_| ChangePasswordFormPartial {
+                old_password_error_message: Some("Incorrect password".into()),
+                ..params.clone().into()
+            })
+        })?;
+
+    // The rotated session inherits the current session's remember me preference
+    let remember_me = session.session_token.remember_me;
+    let (unhashed_token, hashed_token) = SessionToken::new(
+        session.user.id,
+        client_info.user_agent,
+        client_info.ip_address.map(|ip| ip.to_string()),
+        remember_me,
+    );
+    session
+        .user
+        .update_password(
+            &db,
+            new_password,
+            &hashing_pool,
+            hashed_token,
+            params.revoke_api_keys,
+        )
+        .await?;
+
+    let cookie = session_cookie_helper::build(config.session(), &unhashed_token, remember_me);
+    Ok((
+        CookieJar::new().add(cookie),
+        ChangePasswordFormPartial {
+            show_success_message: true,
+            ..Default::default()
+        },
+    ))
+}
+
+#[derive(Clone, Deserialize)]
+pub struct DeleteAccountParams {
+    pub password: SecretString,
+}
+
+pub async fn delete_account(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+    State(hashing_pool): State<PasswordHashingPool>,
+    client_info: ClientInfo,
+    Form(params): Form<DeleteAccountParams>,
+) -> Result<impl IntoResponse> {
+    let grace_period = config.account_deletion_grace_period();
+    let password = UnhashedPassword::try_from(params.password.clone()).map_err(|e| {
+        to_unauthorized_error(Some(session.clone()), e, |_| DeleteAccountFormPartial {
+            password_error_message: Some("Incorrect password".into()),
+            has_grace_period: grace_period.is_some(),
+            ..params.clone().into()
         })
     })?;
 
-    session.user.update_password(&db, new_password).await?;
+    session
+        .user
+        .verify_password(&password, &hashing_pool)
+        .await
+        .map_err(|e| {
+            to_unauthorized_error(Some(session.clone()), e, |_| DeleteAccountFormPartial {
+                password_error_message: Some("Incorrect password".into()),
+                has_grace_period: grace_period.is_some(),
+                ..params.clone().into()
+            })
+        })?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(grace_period) = grace_period {
+        let event = SecurityEvent::new(
+            session.user.id,
+            SecurityEventKind::AccountDeletionScheduled,
+            client_info.user_agent,
+            client_info.ip_address.map(|ip| ip.to_string()),
+        );
+        AccountDeletion::new(session.user.id, grace_period)?
+            .insert(&db, event)
+            .await?;
 
-    Ok(ChangePasswordFormPartial {
-        show_success_message: true,
-        ..Default::default()
-    })
+        headers.insert("HX-Redirect", HeaderValue::from_static("/settings"));
+        Ok((CookieJar::new(), headers))
+    } else {
+        session.user.delete(&db).await?;
+
+        headers.insert("HX-Redirect", HeaderValue::from_static("/"));
+        let cookie = session_cookie_helper::build_removal(config.session());
+        Ok((CookieJar::new().add(cookie), headers))
+    }
+}
+
+pub async fn cancel_account_deletion(
+    session: Session,
+    State(db): State<Database>,
+    client_info: ClientInfo,
+) -> Result<impl IntoResponse> {
+    let account_deletion = AccountDeletion::find_for_user_id(&db, session.user.id)
+        .await?
+        .ok_or(Error::NotFound(Some(session.clone())))?;
+    let event = SecurityEvent::new(
+        session.user.id,
+        SecurityEventKind::AccountDeletionCancelled,
+        client_info.user_agent,
+        client_info.ip_address.map(|ip| ip.to_string()),
+    );
+    account_deletion.cancel(&db, event).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Redirect", HeaderValue::from_static("/settings"));
+    Ok(headers)
 }
 
 pub async fn validate_username(
     session: Option<Session>,
     State(db): State<Database>,
+    State(config): State<Config>,
+    client_info: ClientInfo,
     Form(params): Form<CreateParams>,
 ) -> Result<impl IntoResponse> {
+    if let Some(retry_after) = throttle_signup_validation(&db, &config, &client_info).await? {
+        Err(Error::TooManyRequests {
+            retry_after,
+            template: Box::new(UsernameInputPartial {
+                username_error_message: Some(TOO_MANY_VALIDATIONS_MESSAGE.into()),
+                ..params.clone().into()
+            }),
+        })?;
+    }
+
     let username = Username::try_from(&params.username).map_err(|e| {
-        to_validation_error(session, e, |msg| UsernameInputPartial {
+        to_validation_error(session.clone(), e, |msg| UsernameInputPartial {
             username_error_message: Some(msg.into()),
             ..params.clone().into()
         })
     })?;
 
-    if User::find_by_username(&db, username).await?.is_some() {
+    // The signed in user's own username isn't taken from their point of view, so that the same
+    // validation works when changing it in settings
+    if User::find_by_username(&db, username)
+        .await?
+        .is_some_and(|user| session.as_ref().is_none_or(|s| s.user.id != user.id))
+    {
         Err(Error::Unprocessable(Box::new(UsernameInputPartial {
             username_error_message: Some("Username is already taken".into()),
             ..params.clone().into()
@@ -242,16 +749,38 @@ pub async fn validate_username(
 pub async fn validate_email(
     session: Option<Session>,
     State(db): State<Database>,
+    State(config): State<Config>,
+    client_info: ClientInfo,
     Form(params): Form<CreateParams>,
 ) -> Result<impl IntoResponse> {
+    if let Some(retry_after) = throttle_signup_validation(&db, &config, &client_info).await? {
+        Err(Error::TooManyRequests {
+            retry_after,
+            template: Box::new(EmailInputPartial {
+                email_error_message: Some(TOO_MANY_VALIDATIONS_MESSAGE.into()),
+                ..params.clone().into()
+            }),
+        })?;
+    }
+
     let email = EmailAddress::try_from(&params.email).map_err(|e| {
-        to_validation_error(session, e, |msg| EmailInputPartial {
+        to_validation_error(session.clone(), e, |msg| EmailInputPartial {
             email_error_message: Some(msg.into()),
             ..params.clone().into()
         })
     })?;
 
-    if User::find_by_email(&db, email).await?.is_some() {
+    if let Some(msg) = email_domain_error_message(config.registration_mode(), &email) {
+        Err(Error::Unprocessable(Box::new(EmailInputPartial {
+            email_error_message: Some(msg),
+            ..params.clone().into()
+        })))?;
+    }
+
+    if User::find_by_email(&db, email)
+        .await?
+        .is_some_and(|user| session.as_ref().is_none_or(|s| s.user.id != user.id))
+    {
         Err(Error::Unprocessable(Box::new(EmailInputPartial {
             email_error_message: Some("Email is already taken".into()),
             ..params.clone().into()
@@ -264,15 +793,88 @@ pub async fn validate_email(
 
 pub async fn validate_password(
     session: Option<Session>,
+    State(db): State<Database>,
+    State(config): State<Config>,
+    client_info: ClientInfo,
     Form(params): Form<CreateParams>,
 ) -> Result<impl IntoResponse> {
-    let _password = UnhashedPassword::try_from(params.password.clone()).map_err(|e| {
-        to_validation_error(session, e, |msg| PasswordInputPartial {
+    if let Some(retry_after) = throttle_signup_validation(&db, &config, &client_info).await? {
+        Err(Error::TooManyRequests {
+            retry_after,
+            template: Box::new(PasswordInputPartial {
+                password_error_message: Some(TOO_MANY_VALIDATIONS_MESSAGE.into()),
+                ..params.clone().into()
+            }),
+        })?;
+    }
+
+    let password = UnhashedPassword::try_from(params.password.clone()).map_err(|e| {
+        to_validation_error(session.clone(), e, |msg| PasswordInputPartial {
             password_error_message: Some(msg.into()),
             ..params.clone().into()
         })
     })?;
 
+    if let Some(msg) = breached_password_error_message(&config, session.as_ref(), &password).await?
+    {
+        Err(Error::Unprocessable(Box::new(PasswordInputPartial {
+            password_error_message: Some(msg),
+            ..params.clone().into()
+        })))?;
+    }
+
     let template: PasswordInputPartial = params.into();
     Ok(template)
 }
+
+// Username and email availability checks reveal whether an account exists, so the validation
+// endpoints are throttled per client to keep them from being used to enumerate accounts.
+async fn throttle_signup_validation(
+    db: &Database,
+    config: &Config,
+    client_info: &ClientInfo,
+) -> Result<Option<SignedDuration>> {
+    let Some(key) = client_info
+        .ip_address
+        .and_then(|ip| ThrottleKey::new(ThrottleScope::SignupValidationIp, &ip.to_string()))
+    else {
+        return Ok(None);
+    };
+
+    match Throttle::attempt(db, config.throttle(), vec![key]).await? {
+        ThrottledAttempt::RetryAfter(retry_after) => Ok(Some(retry_after)),
+        ThrottledAttempt::Counted { .. } => Ok(None),
+    }
+}
+
+// None when no breached password corpus is configured, or the password doesn't appear in it
+async fn breached_password_error_message(
+    config: &Config,
+    session: Option<&Session>,
+    password: &UnhashedPassword,
+) -> Result<Option<String>> {
+    let Some(corpus_path) = config.breached_passwords_path() else {
+        return Ok(None);
+    };
+    let is_breached = breached_password_helper::is_breached(corpus_path, password)
+        .await
+        .map_err(|e| Error::InternalServerError {
+            session: session.cloned(),
+            source: Box::new(e),
+        })?;
+    Ok(is_breached.then(|| BREACHED_PASSWORD_MESSAGE.into()))
+}
+
+fn email_domain_error_message(
+    registration_mode: &RegistrationMode,
+    email: &EmailAddress,
+) -> Option<String> {
+    if registration_mode.allows_email_domain(email.domain()) {
+        None
+    } else {
+        Some(format!(
+            "Sign ups are limited to email addresses at {}",
+            registration_mode.allowed_email_domains().join(", ")
+        ))
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::Config;
+use crate::controllers::prelude::*;
+use crate::db::Database;
+use crate::models::session::Session;
+use crate::models::webhook::{
+    Webhook, WebhookDelivery, WebhookEvent, WebhookEvents, MAX_WEBHOOKS_PER_USER,
+};
+use crate::views::webhooks::create::CreatePage;
+use crate::views::webhooks::index::{IndexPage, NewWebhookFormPartial};
+use crate::views::webhooks::show::ShowPage;
+use axum::{
+    extract::{Form, Path, State},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub async fn index(session: Session, State(db): State<Database>) -> Result<impl IntoResponse> {
+    let webhooks = Webhook::all_for_user_id(&db, session.user.id).await?;
+
+    Ok(IndexPage {
+        session: Some(session),
+        webhooks,
+        new_webhook_form: NewWebhookFormPartial::default(),
+    })
+}
+
+// Each event is its own checkbox, since url encoded forms can't be deserialized into a list
+#[derive(Clone, Debug, Deserialize)]
+pub struct CreateParams {
+    #[serde(default)]
+    pub url: String,
+    #[serde(default, rename = "paste.created")]
+    pub paste_created: bool,
+    #[serde(default, rename = "paste.updated")]
+    pub paste_updated: bool,
+    #[serde(default, rename = "paste.deleted")]
+    pub paste_deleted: bool,
+}
+
+impl CreateParams {
+    fn events(&self) -> WebhookEvents {
+        [
+            (WebhookEvent::PasteCreated, self.paste_created),
+            (WebhookEvent::PasteUpdated, self.paste_updated),
+            (WebhookEvent::PasteDeleted, self.paste_deleted),
+        ]
+        .into_iter()
+        .filter_map(|(event, checked)| checked.then_some(event))
+        .collect()
+    }
+}
+
+pub async fn create(
+    session: Session,
+    State(db): State<Database>,
+    State(config): State<Config>,
+    Form(params): Form<CreateParams>,
+) -> Result<impl IntoResponse> {
+    let events = params.events();
+    let error_template = |msg: &str| NewWebhookFormPartial {
+        url: params.url.clone(),
+        events: events.clone(),
+        error_message: Some(msg.into()),
+    };
+
+    if Webhook::count_for_user_id(&db, session.user.id).await? >= MAX_WEBHOOKS_PER_USER {
+        Err(Error::Unprocessable(Box::new(error_template(&format!(
+            "You can't have more than {MAX_WEBHOOKS_PER_USER} webhooks"
+        )))))?;
+    }
+
+    let webhook = Webhook::new(
+        session.user.id,
+        &params.url,
+        events.clone(),
+        config.webhooks_allow_private_addresses(),
+    )
+    .map_err(|e| to_validation_error(Some(session.clone()), e, error_template))?;
+    webhook.clone().insert(&db).await?;
+
+    Ok(CreatePage {
+        webhook,
+        new_webhook_form: NewWebhookFormPartial::default(),
+    })
+}
+
+pub async fn show(
+    session: Session,
+    State(db): State<Database>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound(Some(session.clone())))?;
+
+    let webhook = Webhook::find_scoped_by_user_id(&db, id, session.user.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(Some(session.clone())))?;
+    let deliveries = WebhookDelivery::recent_for_webhook_id(&db, webhook.id).await?;
+
+    Ok(ShowPage {
+        session: Some(session),
+        webhook,
+        deliveries,
+    })
+}
+
+pub async fn destroy(
+    session: Session,
+    State(db): State<Database>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    let id = Uuid::try_parse(&id).map_err(|_| Error::NotFound(Some(session.clone())))?;
+
+    let webhook = Webhook::find_scoped_by_user_id(&db, id, session.user.id)
+        .await?
+        .ok_or(Error::NotFound(Some(session)))?;
+    webhook.delete(&db).await?;
+
+    Ok(())
+}
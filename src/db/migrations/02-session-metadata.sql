@@ -0,0 +1,4 @@
+-- user_agent is the User-Agent header sent by the client when the session was created
+ALTER TABLE session_tokens ADD COLUMN user_agent TEXT CHECK(length(user_agent) <= 512);
+-- ip_address is the client's IP address (v4 or v6) when the session was created
+ALTER TABLE session_tokens ADD COLUMN ip_address TEXT CHECK(length(ip_address) <= 45);
@@ -0,0 +1,14 @@
+CREATE TABLE security_events (
+  -- id is a UUIDv7
+  id BLOB PRIMARY KEY CHECK(length(id) = 16),
+  -- user_id is a UUIDv7
+  user_id BLOB NOT NULL CHECK(length(user_id) = 16),
+  -- kind is a snake_case identifier for the event, e.g. 'password_changed'
+  kind TEXT NOT NULL CHECK(length(kind) BETWEEN 1 AND 64),
+  -- user_agent and ip_address describe the client that triggered the event, when known
+  user_agent TEXT CHECK(length(user_agent) <= 512),
+  ip_address TEXT CHECK(length(ip_address) <= 45),
+  -- created_at is a unix timestamp, with millisecond precision
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
@@ -0,0 +1,3 @@
+-- remember_me sessions are issued a persistent cookie and expire after the (longer) remember me
+-- lifetime rather than the default absolute session lifetime
+ALTER TABLE session_tokens ADD COLUMN remember_me INTEGER NOT NULL DEFAULT 0 CHECK(remember_me IN (0, 1));
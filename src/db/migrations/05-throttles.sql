@@ -0,0 +1,15 @@
+CREATE TABLE throttles (
+  -- id is a UUIDv7
+  id BLOB PRIMARY KEY CHECK(length(id) = 16),
+  -- scope is a snake_case identifier for what is being throttled, e.g. 'login_account'
+  scope TEXT NOT NULL CHECK(length(scope) BETWEEN 1 AND 64),
+  -- key identifies who is being throttled within the scope, e.g. an email address or IP address
+  key TEXT NOT NULL CHECK(length(key) BETWEEN 1 AND 256),
+  attempt_count INTEGER NOT NULL CHECK(attempt_count > 0),
+  -- created_at, last_attempt_at, and locked_until are all unix timestamps, with millisecond precision
+  created_at INTEGER NOT NULL,
+  last_attempt_at INTEGER NOT NULL,
+  locked_until INTEGER,
+  CHECK(created_at <= last_attempt_at),
+  UNIQUE(scope, key)
+) STRICT;
@@ -0,0 +1,33 @@
+ALTER TABLE invite_codes RENAME TO legacy_invite_codes;
+
+CREATE TABLE invite_codes (
+  code TEXT PRIMARY KEY CHECK(length(code) BETWEEN 1 AND 64),
+  -- created_by is a UUIDv7, or null for codes that were not minted by a user (e.g. inserted via SQL)
+  created_by BLOB CHECK(length(created_by) = 16),
+  max_uses INTEGER NOT NULL CHECK(max_uses > 0),
+  used_count INTEGER NOT NULL DEFAULT 0 CHECK(used_count BETWEEN 0 AND max_uses),
+  -- expires_at and created_at are both unix timestamps, with millisecond precision
+  -- a null expires_at means that the code never expires
+  expires_at INTEGER,
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(created_by) REFERENCES users(id) ON DELETE SET NULL
+) STRICT;
+
+-- Codes that predate this migration were single use and never expired
+INSERT INTO invite_codes (code, created_by, max_uses, used_count, expires_at, created_at)
+SELECT code, NULL, 1, 0, NULL, CAST(strftime('%s', 'now') AS INTEGER) * 1000
+FROM legacy_invite_codes;
+
+DROP TABLE legacy_invite_codes;
+
+CREATE TABLE invite_code_redemptions (
+  code TEXT NOT NULL,
+  -- user_id is a UUIDv7, and a user can only ever have signed up with a single code
+  user_id BLOB PRIMARY KEY CHECK(length(user_id) = 16),
+  -- created_at is a unix timestamp, with millisecond precision
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(code) REFERENCES invite_codes(code) ON DELETE CASCADE,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX invite_code_redemptions_code_idx ON invite_code_redemptions(code);
@@ -0,0 +1,7 @@
+ALTER TABLE users ADD COLUMN is_admin INTEGER NOT NULL DEFAULT 0 CHECK(is_admin IN (0, 1));
+-- suspended_at is a unix timestamp, with millisecond precision, or NULL for accounts in good standing
+ALTER TABLE users ADD COLUMN suspended_at INTEGER;
+
+-- hidden_at is a unix timestamp, with millisecond precision, or NULL for pastes that an admin hasn't
+-- hidden
+ALTER TABLE pastes ADD COLUMN hidden_at INTEGER;
@@ -0,0 +1,58 @@
+-- api_keys and pastes were created without ON DELETE CASCADE, which SQLite can only add by
+-- rebuilding the table. Dropping the old pastes table also clears syntax_highlight_cache through
+-- its own cascade, which is fine since the cache is repopulated on demand.
+CREATE TABLE new_api_keys (
+  -- id is a UUIDv7
+  id BLOB PRIMARY KEY CHECK(length(id) = 16),
+  name TEXT NOT NULL CHECK (length(name) BETWEEN 1 AND 256),
+  -- key is a randomly generated u128, formatted as hex, hashed via SHA-256
+  key BLOB NOT NULL CHECK(length(key) = 32),
+  -- user_id is a UUIDv7
+  user_id BLOB NOT NULL CHECK(length(user_id) = 16),
+  -- created_at and last_used_at are both unix timestamps, with millisecond precision
+  created_at INTEGER NOT NULL,
+  last_used_at INTEGER NOT NULL,
+  CHECK(created_at <= last_used_at),
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+INSERT INTO new_api_keys (id, name, key, user_id, created_at, last_used_at)
+SELECT id, name, key, user_id, created_at, last_used_at FROM api_keys;
+
+DROP TABLE api_keys;
+ALTER TABLE new_api_keys RENAME TO api_keys;
+
+CREATE TABLE new_pastes (
+  -- id is a UUIDv7
+  id BLOB PRIMARY KEY CHECK(length(id) = 16),
+  -- user_id is a UUIDv7
+  user_id BLOB NOT NULL CHECK(length(user_id) = 16),
+  filename TEXT NOT NULL CHECK(length(filename) BETWEEN 1 AND 256),
+  description TEXT NOT NULL CHECK(length(description) <= 256),
+  body TEXT NOT NULL CHECK(length(body) > 0),
+  visibility TEXT NOT NULL CHECK(visibility IN ('public', 'secret')),
+  -- created_at and updated_at are both unix timestamps, with millisecond precision
+  created_at INTEGER NOT NULL,
+  updated_at INTEGER NOT NULL,
+  -- hidden_at is a unix timestamp, with millisecond precision, or NULL for pastes that an admin
+  -- hasn't hidden
+  hidden_at INTEGER,
+  CHECK(created_at <= updated_at),
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+INSERT INTO new_pastes (id, user_id, filename, description, body, visibility, created_at, updated_at, hidden_at)
+SELECT id, user_id, filename, description, body, visibility, created_at, updated_at, hidden_at FROM pastes;
+
+DROP TABLE pastes;
+ALTER TABLE new_pastes RENAME TO pastes;
+
+CREATE TABLE account_deletions (
+  -- user_id is a UUIDv7, and an account can only have a single pending deletion
+  user_id BLOB PRIMARY KEY CHECK(length(user_id) = 16),
+  -- requested_at and scheduled_for are both unix timestamps, with millisecond precision
+  requested_at INTEGER NOT NULL,
+  scheduled_for INTEGER NOT NULL,
+  CHECK(requested_at <= scheduled_for),
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
@@ -0,0 +1,12 @@
+CREATE TABLE username_history (
+  -- username is a name that a user has since changed away from. Old paste and profile URLs that
+  -- use it redirect to the user's current username, until someone else claims it.
+  username TEXT PRIMARY KEY CHECK(username = lower(username) AND length(username) BETWEEN 1 AND 32),
+  -- user_id is a UUIDv7
+  user_id BLOB NOT NULL CHECK(length(user_id) = 16),
+  -- created_at is a unix timestamp, with millisecond precision, of when the username was changed
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX username_history_user_id_idx ON username_history(user_id);
@@ -0,0 +1,27 @@
+-- display_name, bio, and website are optional profile fields, with an empty string meaning unset
+ALTER TABLE users ADD COLUMN display_name TEXT NOT NULL DEFAULT '' CHECK(length(display_name) <= 64);
+ALTER TABLE users ADD COLUMN bio TEXT NOT NULL DEFAULT '' CHECK(length(bio) <= 256);
+ALTER TABLE users ADD COLUMN website TEXT NOT NULL DEFAULT '' CHECK(length(website) <= 256);
+
+CREATE TABLE avatars (
+  -- user_id is a UUIDv7, and a user can only have a single avatar
+  user_id BLOB PRIMARY KEY CHECK(length(user_id) = 16),
+  -- image is the uploaded image, cropped to a square and resized, encoded as a PNG
+  image BLOB NOT NULL CHECK(length(image) > 0),
+  -- created_at is a unix timestamp, with millisecond precision
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE TABLE pinned_pastes (
+  -- paste_id is a UUIDv7
+  paste_id BLOB PRIMARY KEY CHECK(length(paste_id) = 16),
+  -- user_id is a UUIDv7, and is always the owner of the paste
+  user_id BLOB NOT NULL CHECK(length(user_id) = 16),
+  -- created_at is a unix timestamp, with millisecond precision
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(paste_id) REFERENCES pastes(id) ON DELETE CASCADE,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX pinned_pastes_user_id_idx ON pinned_pastes(user_id);
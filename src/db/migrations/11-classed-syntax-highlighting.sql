@@ -0,0 +1,3 @@
+-- Highlighted html used to be styled inline, which a strict Content-Security-Policy blocks. Cached
+-- html is regenerated with classes the next time each paste is viewed.
+DELETE FROM syntax_highlight_cache;
@@ -0,0 +1,7 @@
+-- scopes is a space separated list of what the key may be used for. Keys created before scopes
+-- existed could do anything their owner could, so they keep every scope.
+ALTER TABLE api_keys ADD COLUMN scopes TEXT NOT NULL DEFAULT 'pastes:read pastes:write pastes:delete account:read';
+-- expires_at is a unix timestamp, with millisecond precision, or NULL for keys that never expire
+ALTER TABLE api_keys ADD COLUMN expires_at INTEGER;
+
+CREATE INDEX api_keys_expires_at_idx ON api_keys(expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Paste listings can be sorted by when pastes were created or last updated, and are paginated with
+-- the sort key and id of the last paste on a page, so both pairs are indexed
+CREATE INDEX pastes_created_at_id_idx ON pastes(created_at, id);
+CREATE INDEX pastes_updated_at_id_idx ON pastes(updated_at, id);
+CREATE INDEX pastes_user_id_idx ON pastes(user_id);
@@ -0,0 +1,44 @@
+CREATE TABLE webhooks (
+  -- id is a UUIDv7
+  id BLOB PRIMARY KEY CHECK(length(id) = 16),
+  -- user_id is a UUIDv7
+  user_id BLOB NOT NULL CHECK(length(user_id) = 16),
+  url TEXT NOT NULL CHECK(length(url) BETWEEN 1 AND 2048),
+  -- events is a space separated list of the paste events that are delivered to the webhook
+  events TEXT NOT NULL,
+  -- secret is a randomly generated 256 bit key, formatted as hex. It's kept as is rather than
+  -- hashed, since every delivery has to be signed with it.
+  secret TEXT NOT NULL CHECK(length(secret) = 64),
+  -- created_at is a unix timestamp, with millisecond precision
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX webhooks_user_id_idx ON webhooks(user_id);
+
+CREATE TABLE webhook_deliveries (
+  -- id is a UUIDv7
+  id BLOB PRIMARY KEY CHECK(length(id) = 16),
+  -- webhook_id is a UUIDv7
+  webhook_id BLOB NOT NULL CHECK(length(webhook_id) = 16),
+  event TEXT NOT NULL CHECK(event IN ('paste.created', 'paste.updated', 'paste.deleted')),
+  -- payload is the JSON body of the delivery, fixed when the event happens so that every attempt
+  -- sends the same thing
+  payload TEXT NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  -- response_status is the HTTP status of the latest attempt, and error describes why the latest
+  -- attempt got no response at all
+  response_status INTEGER,
+  error TEXT,
+  -- all timestamps are unix timestamps, with millisecond precision. next_attempt_at is NULL once a
+  -- delivery has succeeded or has been given up on.
+  created_at INTEGER NOT NULL,
+  last_attempt_at INTEGER,
+  next_attempt_at INTEGER,
+  delivered_at INTEGER,
+  FOREIGN KEY(webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX webhook_deliveries_webhook_id_idx ON webhook_deliveries(webhook_id, id);
+CREATE INDEX webhook_deliveries_next_attempt_at_idx ON webhook_deliveries(next_attempt_at)
+  WHERE next_attempt_at IS NOT NULL;
@@ -0,0 +1,7 @@
+-- "admin" became a reserved username along with the admin area at /admin, which shadows the profile
+-- of anyone who had already taken it. Such a user is renamed to "admin-" followed by a random
+-- suffix, and can pick a new name from their account settings.
+UPDATE users SET
+  username = 'admin-' || lower(hex(randomblob(4))),
+  updated_at = max(updated_at, CAST(unixepoch('subsec') * 1000 AS INTEGER))
+WHERE username = 'admin';
@@ -0,0 +1,14 @@
+CREATE TABLE configured_admins (
+  -- username is a name listed in the admin usernames config. It's bound to the user who held it
+  -- the first time it was listed, so that the role follows that user through renames rather than
+  -- passing to whoever claims the name next.
+  username TEXT PRIMARY KEY CHECK(username = lower(username) AND length(username) BETWEEN 1 AND 32),
+  -- user_id is a UUIDv7, or NULL once the bound user is deleted. A name whose user is gone stays
+  -- bound to nobody until it's taken off the list.
+  user_id BLOB CHECK(user_id IS NULL OR length(user_id) = 16),
+  -- created_at is a unix timestamp, with millisecond precision, of when the name was bound
+  created_at INTEGER NOT NULL,
+  FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE SET NULL
+) STRICT;
+
+CREATE INDEX configured_admins_user_id_idx ON configured_admins(user_id);
@@ -18,7 +18,26 @@ impl Database {
 }
 
 pub fn migrations() -> AsyncMigrations {
-    AsyncMigrations::new(vec![M::up(include_str!("migrations/01-init.sql"))])
+    AsyncMigrations::new(vec![
+        M::up(include_str!("migrations/01-init.sql")),
+        M::up(include_str!("migrations/02-session-metadata.sql")),
+        M::up(include_str!("migrations/03-security-events.sql")),
+        M::up(include_str!("migrations/04-session-remember-me.sql")),
+        M::up(include_str!("migrations/05-throttles.sql")),
+        M::up(include_str!("migrations/06-invite-code-management.sql")),
+        M::up(include_str!("migrations/07-admin-moderation.sql")),
+        M::up(include_str!("migrations/08-account-deletion.sql")),
+        M::up(include_str!("migrations/09-username-history.sql")),
+        M::up(include_str!("migrations/10-user-profiles.sql")),
+        M::up(include_str!(
+            "migrations/11-classed-syntax-highlighting.sql"
+        )),
+        M::up(include_str!("migrations/12-api-key-scopes.sql")),
+        M::up(include_str!("migrations/13-paste-sort-indexes.sql")),
+        M::up(include_str!("migrations/14-webhooks.sql")),
+        M::up(include_str!("migrations/15-reserved-admin-username.sql")),
+        M::up(include_str!("migrations/16-configured-admins.sql")),
+    ])
 }
 
 #[async_trait]
@@ -1,8 +1,9 @@
+use crate::config::Config;
 use crate::controllers::api::prelude::Error as ApiControllerError;
 use crate::controllers::prelude::Error as ControllerError;
 use crate::db::Database;
 use crate::models::api_session::{ApiSession, UnhashedKey, API_KEY_HEADER_NAME};
-use crate::models::session::{Session, UnhashedToken, SESSION_COOKIE_NAME};
+use crate::models::session::{AdminSession, Session, UnhashedToken};
 use axum::extract::{FromRef, FromRequestParts};
 use axum::http::request::Parts;
 use axum::{async_trait, RequestPartsExt};
@@ -11,6 +12,7 @@ use axum_extra::extract::CookieJar;
 #[async_trait]
 impl<S> FromRequestParts<S> for Session
 where
+    Config: FromRef<S>,
     Database: FromRef<S>,
     S: Send + Sync,
 {
@@ -25,10 +27,12 @@ where
                 source: Box::new(e),
             })?;
 
+        let config = Config::from_ref(state);
+
         let cookie = CookieJar::from_request_parts(parts, state)
             .await
             .map_err(|_| ControllerError::Unauthorized)?
-            .get(SESSION_COOKIE_NAME)
+            .get(config.session().cookie_name())
             .ok_or(ControllerError::Unauthorized)?
             .to_owned();
 
@@ -47,6 +51,25 @@ where
     }
 }
 
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminSession
+where
+    Config: FromRef<S>,
+    Database: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ControllerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state).await?;
+        if session.user.is_admin {
+            Ok(Self(session))
+        } else {
+            Err(ControllerError::Forbidden(Some(session)))
+        }
+    }
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for ApiSession
 where
@@ -0,0 +1,42 @@
+use crate::config::Config;
+use crate::helpers::client_ip_helper;
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::header::USER_AGENT;
+use axum::http::request::Parts;
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+const MAX_USER_AGENT_CHARS: usize = 512;
+
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<IpAddr>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    Config: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|s| s.chars().take(MAX_USER_AGENT_CHARS).collect());
+
+        let config = Config::from_ref(state);
+        let ip_address =
+            client_ip_helper::resolve(&parts.extensions, &parts.headers, config.trusted_proxies());
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use crate::controllers::api::prelude::Error as ApiControllerError;
+use crate::helpers::pagination_helper::{CursorPaginationParams, CursorPaginationResponse};
+use axum::extract::{FromRequest, OriginalUri, Query, Request};
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::{async_trait, Json};
+use serde::de::DeserializeOwned;
+use std::fmt::Display;
+use uuid::Uuid;
+
+const PAGINATION_PARAMS: [&str; 3] = ["per_page", "prev_page", "next_page"];
+
+// Pagination for the JSON API, read from the query string, or from a JSON body for clients
+// written before query parameters were supported. Pagination params in the query string take
+// precedence, since some proxies drop the body of a GET request.
+#[derive(Clone, Debug)]
+pub struct ApiPagination<C = Uuid> {
+    pub params: CursorPaginationParams<C>,
+    path: String,
+    // The rest of the query string, such as filters, which links to other pages have to keep
+    other_params: Vec<(String, String)>,
+}
+
+impl<C: Display> ApiPagination<C> {
+    // RFC 8288 Link headers pointing at the neighbouring pages, so that generic HTTP tooling can
+    // page through results without knowing about the `pagination` object
+    pub fn link_headers(&self, pagination: &CursorPaginationResponse<C>) -> HeaderMap {
+        let per_page: usize = self.params.per_page.into();
+        let other_params = serde_urlencoded::to_string(&self.other_params)
+            .ok()
+            .filter(|query| !query.is_empty())
+            .map_or_else(String::new, |query| query + "&");
+        let link = |relation: &str, param: &str, cursor: &C| {
+            format!(
+                r#"<{}?{other_params}per_page={per_page}&{param}={cursor}>; rel="{relation}""#,
+                self.path
+            )
+        };
+        let links: Vec<_> = [
+            pagination
+                .next_page
+                .as_ref()
+                .map(|cursor| link("next", "next_page", cursor)),
+            pagination
+                .prev_page
+                .as_ref()
+                .map(|cursor| link("prev", "prev_page", cursor)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let mut headers = HeaderMap::new();
+        if links.is_empty() {
+            return headers;
+        }
+        if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+            headers.insert(header::LINK, value);
+        }
+        headers
+    }
+}
+
+#[async_trait]
+impl<S, C> FromRequest<S> for ApiPagination<C>
+where
+    S: Send + Sync,
+    C: DeserializeOwned + Send,
+{
+    type Rejection = ApiControllerError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        // Handlers are nested under /api/v1, so the request's own URI is missing that prefix
+        let path = request
+            .extensions()
+            .get::<OriginalUri>()
+            .map_or_else(|| request.uri().path(), |OriginalUri(uri)| uri.path())
+            .to_string();
+
+        let query_params: Vec<(String, String)> =
+            serde_urlencoded::from_str(request.uri().query().unwrap_or_default())
+                .map_err(|e| ApiControllerError::Unprocessable(Box::new(e)))?;
+        let (pagination_params, other_params): (Vec<_>, Vec<_>) = query_params
+            .into_iter()
+            .partition(|(name, _)| PAGINATION_PARAMS.contains(&name.as_str()));
+
+        let params = if pagination_params.is_empty() {
+            Option::<Json<CursorPaginationParams<C>>>::from_request(request, state)
+                .await
+                .unwrap_or_default()
+                .map(|Json(params)| params)
+                .unwrap_or_default()
+        } else {
+            let Query(params) = Query::<CursorPaginationParams<C>>::try_from_uri(request.uri())
+                .map_err(|e| ApiControllerError::Unprocessable(Box::new(e)))?;
+            params
+        };
+
+        Ok(Self {
+            params,
+            path,
+            other_params,
+        })
+    }
+}
@@ -0,0 +1,83 @@
+use crate::models::user::UnhashedPassword;
+use secrecy::ExposeSecret;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+// Once the search has narrowed down to this many bytes, it's cheaper to read through them than to
+// keep seeking around
+const LINEAR_SCAN_BYTES: u64 = 4096;
+
+// Checks whether a password appears in a breached password corpus, without needing network access.
+// The corpus is a single file of SHA-1 hashes sorted in ascending order, one `HASH:COUNT` line per
+// hash, which is how the Pwned Passwords downloader writes its output. Since the corpus runs to
+// tens of gigabytes, it's binary searched on disk instead of being loaded into memory.
+pub async fn is_breached(corpus_path: &Path, password: &UnhashedPassword) -> io::Result<bool> {
+    let corpus_path = corpus_path.to_path_buf();
+    let hash = format!("{:X}", Sha1::digest(password.expose_secret().as_bytes()));
+    tokio::task::spawn_blocking(move || corpus_contains(&corpus_path, &hash))
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn corpus_contains(corpus_path: &Path, hash: &str) -> io::Result<bool> {
+    let mut reader = BufReader::new(File::open(corpus_path)?);
+    let mut line = String::new();
+
+    // Every line that starts at or before `low` sorts before the hash, so once the range is small
+    // enough, the scan can pick up from the first line that starts after it
+    let (mut low, mut high) = (0, reader.get_ref().metadata()?.len());
+    while high - low > LINEAR_SCAN_BYTES {
+        let middle = low + (high - low) / 2;
+        let ordering = if read_line_after(&mut reader, middle, &mut line)? {
+            compare_line(&line, hash)
+        } else {
+            None
+        };
+        match ordering {
+            Some(Ordering::Equal) => return Ok(true),
+            Some(Ordering::Less) => low = middle,
+            _ => high = middle,
+        }
+    }
+
+    if !read_line_after(&mut reader, low, &mut line)? {
+        return Ok(false);
+    }
+    loop {
+        match compare_line(&line, hash) {
+            Some(Ordering::Less) | None => {}
+            Some(ordering) => return Ok(ordering == Ordering::Equal),
+        }
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+    }
+}
+
+// Reads the first complete line that starts after `position` into `line`, or the very first line
+// when `position` is the start of the file. False when there's no such line.
+fn read_line_after(
+    reader: &mut BufReader<File>,
+    position: u64,
+    line: &mut String,
+) -> io::Result<bool> {
+    reader.seek(SeekFrom::Start(position))?;
+    if position > 0 {
+        reader.read_line(&mut String::new())?;
+    }
+    line.clear();
+    Ok(reader.read_line(line)? > 0)
+}
+
+// Compares the hash on a corpus line with the hash being searched for. None for blank lines.
+fn compare_line(line: &str, hash: &str) -> Option<Ordering> {
+    let line_hash = line.split(':').next()?.trim();
+    if line_hash.is_empty() {
+        return None;
+    }
+    Some(line_hash.to_ascii_uppercase().as_str().cmp(hash))
+}
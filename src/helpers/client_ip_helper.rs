@@ -0,0 +1,50 @@
+use axum::extract::ConnectInfo;
+use axum::http::{Extensions, HeaderMap, HeaderName};
+use std::net::{IpAddr, SocketAddr};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+// The address of the client that made a request. Behind a reverse proxy the peer is the proxy, so
+// when the peer is one of `trusted_proxies` the client is read from X-Forwarded-For instead. Each
+// proxy appends the address it received the request from, so the header is walked from the right,
+// past any further trusted proxies, to the first address that no trusted proxy vouches for.
+// Anything left of that could have been made up by the client, and is ignored.
+pub fn resolve(
+    extensions: &Extensions,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    // Connection info is only present when the app is served via
+    // `into_make_service_with_connect_info`, so we treat it as optional rather than rejecting.
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded_for: Vec<&str> = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    let mut client = peer;
+    for entry in forwarded_for.into_iter().rev() {
+        // Some proxies include the port, e.g. "203.0.113.7:51234" or "[2001:db8::1]:51234"
+        let Some(ip) = entry
+            .parse::<IpAddr>()
+            .ok()
+            .or_else(|| entry.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        else {
+            break;
+        };
+        client = ip;
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+    }
+    Some(client)
+}
@@ -0,0 +1,103 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+// A strong entity tag (RFC 9110 section 8.8.3). It's kept without the surrounding quotes, which
+// are added back whenever it's displayed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ETag(String);
+
+impl ETag {
+    // Tags are derived from every part of a resource that affects how it's served, so that the tag
+    // changes whenever any of them does. Each part is length prefixed so that moving bytes from
+    // one part to the next can't produce the same tag.
+    pub fn from_parts(parts: &[&[u8]]) -> Self {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_be_bytes());
+            hasher.update(part);
+        }
+        let digest = format!("{:x}", hasher.finalize());
+        Self(digest[..32].to_string())
+    }
+
+    pub fn to_header_value(&self) -> Option<HeaderValue> {
+        HeaderValue::from_str(&self.to_string()).ok()
+    }
+}
+
+impl FromStr for ETag {
+    type Err = ();
+
+    // Only strong tags parse, since weak tags never match under the strong comparison that
+    // If-Match uses
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let opaque = s
+            .trim()
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .ok_or(())?;
+        if opaque.contains('"') {
+            return Err(());
+        }
+        Ok(Self(opaque.to_string()))
+    }
+}
+
+impl std::fmt::Display for ETag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
+// The versions that a change may be applied to
+#[derive(Clone, Debug, PartialEq)]
+pub enum IfMatch {
+    // `If-Match: *`, or no If-Match header at all
+    Any,
+    Tags(Vec<ETag>),
+}
+
+impl IfMatch {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let values: Vec<_> = list_header_values(headers, &header::IF_MATCH).collect();
+        if values.is_empty() || values.contains(&"*") {
+            return Self::Any;
+        }
+        // Tags that don't parse are dropped, but still count as a condition that must be met
+        Self::Tags(values.into_iter().filter_map(|v| v.parse().ok()).collect())
+    }
+
+    pub fn matches(&self, current: &ETag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.contains(current),
+        }
+    }
+}
+
+// Whether the client's cached copy is still current, per If-None-Match, in which case a read can
+// be answered with 304 Not Modified. If-None-Match uses the weak comparison, so a `W/` prefix is
+// ignored.
+pub fn is_not_modified(headers: &HeaderMap, current: &ETag) -> bool {
+    list_header_values(headers, &header::IF_NONE_MATCH).any(|value| {
+        value == "*"
+            || value
+                .trim_start_matches("W/")
+                .parse::<ETag>()
+                .is_ok_and(|tag| &tag == current)
+    })
+}
+
+fn list_header_values<'a>(
+    headers: &'a HeaderMap,
+    name: &header::HeaderName,
+) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
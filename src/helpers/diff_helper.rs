@@ -0,0 +1,47 @@
+use similar::{ChangeTag, TextDiff};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffLineKind {
+    Unchanged,
+    Removed,
+    Added,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub text: String,
+}
+
+impl DiffLine {
+    pub fn marker(&self) -> &str {
+        match self.kind {
+            DiffLineKind::Unchanged => " ",
+            DiffLineKind::Removed => "-",
+            DiffLineKind::Added => "+",
+        }
+    }
+
+    pub fn class(&self) -> &str {
+        match self.kind {
+            DiffLineKind::Unchanged => "unchanged",
+            DiffLineKind::Removed => "removed",
+            DiffLineKind::Added => "added",
+        }
+    }
+}
+
+// A line by line diff, from `old` to `new`
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            kind: match change.tag() {
+                ChangeTag::Equal => DiffLineKind::Unchanged,
+                ChangeTag::Delete => DiffLineKind::Removed,
+                ChangeTag::Insert => DiffLineKind::Added,
+            },
+            text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+        })
+        .collect()
+}
@@ -1,3 +1,10 @@
+pub mod breached_password_helper;
+pub mod client_ip_helper;
+pub mod conditional_request_helper;
+pub mod diff_helper;
 pub mod pagination_helper;
+pub mod password_hashing_helper;
+pub mod session_cookie_helper;
 pub mod syntax_highlight_helper;
 pub mod view_helper;
+pub mod webhook_helper;
@@ -1,16 +1,33 @@
 use derive_more::Into;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Default, Deserialize)]
-pub struct CursorPaginationParams {
+// Most listings are ordered by their UUIDv7 ids, so an id is all a cursor needs to be. Listings
+// that can be sorted by something else use a cursor type that also carries the sort key.
+#[derive(Clone, Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CursorPaginationParams<C = Uuid> {
     #[serde(default)]
+    #[param(value_type = Option<u32>, minimum = 1, maximum = 100, default = 10)]
     pub per_page: PerPage,
-    pub prev_page: Option<Uuid>,
-    pub next_page: Option<Uuid>,
+    #[param(value_type = Option<String>)]
+    pub prev_page: Option<C>,
+    #[param(value_type = Option<String>)]
+    pub next_page: Option<C>,
 }
 
-impl CursorPaginationParams {
+impl<C> Default for CursorPaginationParams<C> {
+    fn default() -> Self {
+        Self {
+            per_page: PerPage::default(),
+            prev_page: None,
+            next_page: None,
+        }
+    }
+}
+
+impl<C: Clone> CursorPaginationParams<C> {
     pub fn limit(&self) -> usize {
         self.per_page.into()
     }
@@ -21,13 +38,13 @@ impl CursorPaginationParams {
         self.limit() + 1
     }
 
-    pub fn cursor(&self) -> Option<Uuid> {
+    pub fn cursor(&self) -> Option<C> {
         // if both next_page and prev_page are (incorrectly) in the params, then this is next_page
-        self.next_page.or(self.prev_page)
+        self.next_page.clone().or_else(|| self.prev_page.clone())
     }
 
     pub fn direction(&self) -> Direction {
-        match (self.prev_page, self.next_page) {
+        match (&self.prev_page, &self.next_page) {
             (Some(_), None) => Direction::Ascending,
             // if both next_page and prev_page are (incorrectly) in the params, then this is Descending
             _ => Direction::Descending,
@@ -35,10 +52,10 @@ impl CursorPaginationParams {
     }
 }
 
-#[derive(Serialize)]
-pub struct CursorPaginationResponse {
-    pub prev_page: Option<Uuid>,
-    pub next_page: Option<Uuid>,
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CursorPaginationResponse<C = Uuid> {
+    pub prev_page: Option<C>,
+    pub next_page: Option<C>,
 }
 
 impl CursorPaginationResponse {
@@ -63,15 +80,26 @@ impl CursorPaginationResponse {
     pub fn new_with_lookahead(
         params: &CursorPaginationParams,
         results: &mut Vec<impl HasOrderedId>,
+    ) -> Self {
+        Self::new_with_lookahead_by(params, results, HasOrderedId::ordered_id)
+    }
+}
+
+impl<C: Clone> CursorPaginationResponse<C> {
+    // Like new_with_lookahead, for results whose cursor isn't simply their id
+    pub fn new_with_lookahead_by<T>(
+        params: &CursorPaginationParams<C>,
+        results: &mut Vec<T>,
+        cursor: impl Fn(&T) -> C,
     ) -> Self {
         let (mut prev_page, mut next_page) = match params.cursor() {
-            Some(_) => (results.first().map(HasOrderedId::ordered_id), None),
+            Some(_) => (results.first().map(&cursor), None),
             None => (None, None),
         };
 
         if results.len() > params.per_page.into() {
             results.pop();
-            next_page = results.last().map(HasOrderedId::ordered_id);
+            next_page = results.last().map(&cursor);
         }
 
         if params.direction() == Direction::Ascending {
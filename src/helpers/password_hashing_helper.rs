@@ -0,0 +1,69 @@
+use crate::config::PasswordHashingConfig;
+use argon2::Params;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinError;
+
+// Runs password hashing and verification on the blocking thread pool, so that a burst of sign ins
+// can't tie up the async worker threads that every other request needs. At most `max_concurrent`
+// hashes run at once, and at most `max_queued` more wait for their turn. Anything beyond that is
+// turned away immediately, since making it wait would only delay its eventual failure.
+#[derive(Clone, Debug)]
+pub struct PasswordHashingPool {
+    params: Params,
+    admitted: Arc<Semaphore>,
+    running: Arc<Semaphore>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("too many password hashes are already running or queued")]
+    Saturated,
+
+    #[error(transparent)]
+    Join(#[from] JoinError),
+}
+
+impl PasswordHashingPool {
+    pub fn new(config: &PasswordHashingConfig) -> Self {
+        Self {
+            params: config.params().clone(),
+            admitted: Arc::new(Semaphore::new(
+                config.max_concurrent() + config.max_queued(),
+            )),
+            running: Arc::new(Semaphore::new(config.max_concurrent())),
+        }
+    }
+
+    // The parameters that new hashes are created with
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub async fn run<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Params) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let admitted_permit = self
+            .admitted
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| Error::Saturated)?;
+        let running_permit = self
+            .running
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::Saturated)?;
+        let params = self.params.clone();
+
+        // The permits move into the blocking task, so that a hash keeps counting against the
+        // limits until it finishes, even if the request that started it has gone away
+        Ok(tokio::task::spawn_blocking(move || {
+            let _permits = (admitted_permit, running_permit);
+            f(&params)
+        })
+        .await?)
+    }
+}
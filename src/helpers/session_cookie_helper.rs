@@ -0,0 +1,37 @@
+use crate::config::SessionConfig;
+use crate::models::session::UnhashedToken;
+use axum_extra::extract::cookie::Cookie;
+use secrecy::ExposeSecret;
+use time::Duration;
+
+pub fn build(
+    config: &SessionConfig,
+    unhashed_token: &UnhashedToken,
+    remember_me: bool,
+) -> Cookie<'static> {
+    let mut cookie = base(config, unhashed_token.expose_secret().to_string());
+    // Without a Max-Age the cookie only lives as long as the browser session does
+    if remember_me {
+        cookie.set_max_age(Duration::seconds(config.remember_me_ttl_seconds()));
+    }
+    cookie
+}
+
+pub fn build_removal(config: &SessionConfig) -> Cookie<'static> {
+    let mut cookie = base(config, String::new());
+    cookie.make_removal();
+    cookie
+}
+
+fn base(config: &SessionConfig, value: String) -> Cookie<'static> {
+    let mut cookie = Cookie::build((config.cookie_name().to_string(), value))
+        .path("/")
+        .http_only(true)
+        .secure(config.cookie_secure())
+        .same_site(config.cookie_same_site())
+        .build();
+    if let Some(domain) = config.cookie_domain() {
+        cookie.set_domain(domain.to_string());
+    }
+    cookie
+}
@@ -1,17 +1,58 @@
 use crate::{db::Database, models::paste::Paste};
 use rusqlite::{named_params, Transaction, TransactionBehavior};
-use syntect::{highlighting::ThemeSet, html::highlighted_html_for_string, parsing::SyntaxSet};
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
 use uuid::Uuid;
 
+// Highlighting is done with classes rather than inline styles, since a strict Content-Security-Policy
+// doesn't allow style attributes. The prefix keeps the scope names syntect turns into classes from
+// clashing with the site's own.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
 pub fn generate(body: &str, extension: Option<&str>) -> Option<String> {
     let extension = extension?;
 
     let syntax_set = SyntaxSet::load_defaults_newlines();
     let syntax = syntax_set.find_syntax_by_extension(extension)?;
-    let theme = ThemeSet::get_theme("src/helpers/syntax_highlight_themes/CatppuccinFrappe.tmTheme")
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &syntax_set, CLASS_STYLE);
+    for line in LinesWithEndings::from(body) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+    Some(format!(
+        "<pre class=\"hl-code\">\n{}</pre>",
+        generator.finalize()
+    ))
+}
+
+// The stylesheet that colors the classes in generated html. It's served as the static
+// `assets/css/syntax-highlight.css`, which has to be regenerated from this whenever the theme
+// changes. A test fails when the two drift apart.
+pub fn theme_css() -> Option<String> {
+    css_for_theme_with_class_style(&theme()?, CLASS_STYLE)
+        .map_err(|err| tracing::error!("failed to generate syntax highlighting css: {}", err))
+        .ok()
+}
+
+// The file extensions that get highlighted as the named language, so that pastes can be found by
+// language as well as by extension. Names are matched case-insensitively, e.g. "rust" or "Rust".
+pub fn extensions_for_language(name: &str) -> Option<Vec<String>> {
+    let syntax_set = SyntaxSet::load_defaults_newlines();
+    let syntax = syntax_set
+        .syntaxes()
+        .iter()
+        .find(|syntax| syntax.name.eq_ignore_ascii_case(name.trim()))?;
+    Some(syntax.file_extensions.clone())
+}
+
+fn theme() -> Option<Theme> {
+    ThemeSet::get_theme("src/helpers/syntax_highlight_themes/CatppuccinFrappe.tmTheme")
         .map_err(|err| tracing::error!("failed to get syntax highlighting theme: {}", err))
-        .ok()?;
-    highlighted_html_for_string(body, &syntax_set, syntax, &theme).ok()
+        .ok()
 }
 
 pub async fn generate_with_cache_attempt(
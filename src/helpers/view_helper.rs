@@ -98,7 +98,7 @@ pub mod filters {
 
         let size = size as f64;
         let base = size.log10() / UNIT.log10();
-        let result = format!("{:.1}", UNIT.powf(base - base.floor()),)
+        let result = format!("{:.1}", UNIT.powf(base - base.floor()))
             .trim_end_matches(".0")
             .to_owned();
         Ok([&result, SUFFIX[base.floor() as usize]].join(" "))
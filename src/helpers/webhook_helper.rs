@@ -0,0 +1,212 @@
+use hmac::{Hmac, Mac};
+use jiff::Timestamp;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Client, StatusCode, Url};
+use sha2::Sha256;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const EVENT_HEADER_NAME: &str = "X-Gluestick-Event";
+pub const DELIVERY_HEADER_NAME: &str = "X-Gluestick-Delivery";
+pub const TIMESTAMP_HEADER_NAME: &str = "X-Gluestick-Timestamp";
+pub const SIGNATURE_HEADER_NAME: &str = "X-Gluestick-Signature";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("refusing to deliver to an IP address rather than a domain")]
+    IpAddressHost,
+
+    #[error(transparent)]
+    Request(reqwest::Error),
+}
+
+// Sends deliveries. Redirects aren't followed, so that a webhook can only ever be delivered to the
+// URL its owner gave.
+//
+// Unless private addresses are allowed, hosts are resolved by `PublicAddressResolver` as each
+// connection is made, so a domain that's changed to point somewhere private after its webhook was
+// added is still refused. URLs naming an IP address never go through the resolver, so they're
+// refused outright, and proxies are bypassed since they'd resolve hosts themselves.
+#[derive(Clone, Debug)]
+pub struct WebhookClient {
+    client: Client,
+    allow_private_addresses: bool,
+}
+
+impl WebhookClient {
+    pub fn new(allow_private_addresses: bool) -> reqwest::Result<Self> {
+        let mut builder = Client::builder()
+            .user_agent(concat!("Gluestick-Webhooks/", env!("CARGO_PKG_VERSION")))
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(redirect::Policy::none());
+        if !allow_private_addresses {
+            builder = builder
+                .no_proxy()
+                .dns_resolver(Arc::new(PublicAddressResolver));
+        }
+        Ok(Self {
+            client: builder.build()?,
+            allow_private_addresses,
+        })
+    }
+}
+
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(io::Error::other(format!(
+                    "{host} doesn't resolve to any public addresses"
+                ))
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Whether an address is out on the internet, rather than on the server itself or a network it's
+// part of
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, third, _] = ip.octets();
+            // 100.64.0.0/10 is shared by carrier grade NATs and some cloud networks
+            let is_shared = first == 100 && second & 0b1100_0000 == 64;
+            // 198.18.0.0/15 is set aside for benchmarking networks
+            let is_benchmarking = first == 198 && second & 0b1111_1110 == 18;
+            // 0.0.0.0/8 is "this network", 192.0.0.0/24 is for protocol assignments and 240.0.0.0/4
+            // is reserved, with none of them routed on the internet
+            let is_reserved = first == 0 || (first, second, third) == (192, 0, 0) || first >= 240;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || is_shared
+                || is_benchmarking
+                || is_reserved)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            // 64:ff9b::/96 reaches IPv4 hosts through a NAT64 gateway, so it's as public as the
+            // address it embeds
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_public(IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+            }
+            // 64:ff9b:1::/48 is for NAT64 gateways within a network
+            let is_local_nat64 = segments[..3] == [0x64, 0xff9b, 1];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || ip.is_multicast()
+                || is_local_nat64)
+        }
+    }
+}
+
+// Whether a URL names an IP address instead of a domain. IPv6 hosts are written in brackets.
+pub fn has_ip_address_host(url: &Url) -> bool {
+    url.host_str().is_some_and(|host| {
+        host.trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok()
+    })
+}
+
+// An HMAC-SHA256 of the timestamp and the payload, joined by a period, keyed with the webhook's
+// secret. Covering the timestamp lets receivers turn away old deliveries that are replayed to them.
+fn signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{timestamp}.{payload}").as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+// Makes a single attempt at a delivery, which is signed anew each time since the timestamp changes
+pub async fn send(
+    client: &WebhookClient,
+    url: &str,
+    secret: &str,
+    event: &str,
+    delivery_id: Uuid,
+    payload: String,
+) -> Result<StatusCode, SendError> {
+    if !client.allow_private_addresses && Url::parse(url).is_ok_and(|url| has_ip_address_host(&url))
+    {
+        return Err(SendError::IpAddressHost);
+    }
+
+    let timestamp = Timestamp::now().as_second();
+    let response = client
+        .client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER_NAME, event)
+        .header(DELIVERY_HEADER_NAME, delivery_id.to_string())
+        .header(TIMESTAMP_HEADER_NAME, timestamp)
+        .header(
+            SIGNATURE_HEADER_NAME,
+            signature(secret, timestamp, &payload),
+        )
+        .body(payload)
+        .send()
+        .await
+        .map_err(|e| SendError::Request(e.without_url()))?;
+    Ok(response.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_purpose_addresses_are_not_public() {
+        for ip in [
+            "0.1.2.3",
+            "100.64.0.1",
+            "192.0.0.8",
+            "198.18.0.1",
+            "198.19.255.255",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::ffff:10.0.0.1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a00:1",
+            "64:ff9b:1::1",
+            "fd00::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip} should not be public");
+        }
+    }
+
+    #[test]
+    fn internet_addresses_are_public() {
+        for ip in [
+            "1.1.1.1",
+            "198.17.255.255",
+            "198.20.0.1",
+            "64:ff9b::808:808",
+            "2606:4700::1111",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{ip} should be public");
+        }
+    }
+}
@@ -6,11 +6,24 @@
 #![allow(clippy::must_use_candidate)]
 #![allow(clippy::too_many_lines)]
 
+use crate::config::Config;
 use crate::db::Database;
+use crate::helpers::password_hashing_helper::PasswordHashingPool;
+use crate::helpers::webhook_helper::WebhookClient;
+use crate::middleware::csrf;
+use crate::middleware::rate_limit::{self, RateLimiter};
+use crate::middleware::security_headers;
+use crate::models::account_deletion::AccountDeletion;
+use crate::models::api_session::ApiKey;
 use crate::models::session::SessionToken;
+use crate::models::throttle::Throttle;
+use crate::models::webhook::WebhookDelivery;
 use axum::{
-    extract::Request,
-    routing::{delete, get, patch, post, put},
+    extract::{FromRef, Request},
+    handler::Handler,
+    http::Method,
+    middleware::from_fn_with_state,
+    routing::{delete, get, on, post, put, MethodFilter, MethodRouter},
     Router,
 };
 use memory_serve::{load_assets, MemoryServe};
@@ -25,43 +38,91 @@ pub mod controllers;
 pub mod db;
 pub mod extractors;
 pub mod helpers;
+pub mod middleware;
 pub mod models;
 pub mod views;
 
-pub fn router(db: Database) -> Router {
+#[derive(Clone, Debug, FromRef)]
+pub struct AppState {
+    pub db: Database,
+    pub config: Config,
+    pub rate_limiter: RateLimiter,
+    pub password_hashing_pool: PasswordHashingPool,
+}
+
+pub struct JsonApiRoute {
+    pub method: Method,
+    pub path: &'static str,
+    pub handler: MethodRouter<AppState>,
+}
+
+// Every route in the JSON API. The router is built from this table, and the API tests check it
+// against the OpenAPI document, so that no route goes undocumented.
+pub fn json_api_routes() -> Vec<JsonApiRoute> {
+    use controllers::api::{openapi_controller, pastes_controller, users_controller};
+    vec![
+        json_api_route(Method::GET, "/pastes", pastes_controller::index),
+        json_api_route(Method::POST, "/pastes", pastes_controller::create),
+        json_api_route(Method::POST, "/pastes/batch", pastes_controller::batch),
+        json_api_route(Method::GET, "/pastes/:id", pastes_controller::show),
+        json_api_route(Method::GET, "/pastes/:id/raw", pastes_controller::show_raw),
+        json_api_route(Method::PATCH, "/pastes/:id", pastes_controller::update),
+        json_api_route(Method::DELETE, "/pastes/:id", pastes_controller::destroy),
+        json_api_route(Method::GET, "/me", users_controller::show_me),
+        json_api_route(Method::GET, "/me/pastes", users_controller::index_my_pastes),
+        json_api_route(
+            Method::GET,
+            "/users/:username/pastes",
+            users_controller::index_pastes,
+        ),
+        json_api_route(Method::GET, "/openapi.json", openapi_controller::show),
+    ]
+}
+
+fn json_api_route<H, T>(method: Method, path: &'static str, handler: H) -> JsonApiRoute
+where
+    H: Handler<T, AppState>,
+    T: 'static,
+{
+    let filter = MethodFilter::try_from(method.clone())
+        .unwrap_or_else(|_| panic!("{method} can't be routed"));
+    JsonApiRoute {
+        method,
+        path,
+        handler: on(filter, handler),
+    }
+}
+
+pub fn router(db: Database, config: Config) -> Router {
+    let state = AppState {
+        db,
+        password_hashing_pool: PasswordHashingPool::new(config.password_hashing()),
+        config,
+        rate_limiter: RateLimiter::default(),
+    };
+
     let assets_router = MemoryServe::new(load_assets!("src/assets"))
         .index_file(None)
         .into_router();
 
-    let json_api_router = Router::new()
-        .route("/pastes", get(controllers::api::pastes_controller::index))
-        .route("/pastes", post(controllers::api::pastes_controller::create))
-        .route(
-            "/pastes/:id",
-            get(controllers::api::pastes_controller::show),
-        )
-        .route(
-            "/pastes/:id/raw",
-            get(controllers::api::pastes_controller::show_raw),
-        )
-        .route(
-            "/pastes/:id",
-            patch(controllers::api::pastes_controller::update),
-        )
-        .route(
-            "/pastes/:id",
-            delete(controllers::api::pastes_controller::destroy),
-        )
-        .fallback(controllers::api::application_controller::not_found);
+    let json_api_router = json_api_routes()
+        .into_iter()
+        .fold(Router::new(), |router, route| {
+            router.route(route.path, route.handler)
+        })
+        .fallback(controllers::api::application_controller::not_found)
+        .layer(from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_by_api_key,
+        ));
 
     Router::new()
-        .nest("/api/v1", json_api_router)
-        .nest("/assets", assets_router)
         .route("/", get(controllers::application_controller::index))
         .route(
             "/health",
             get(controllers::application_controller::health_check),
         )
+        .route("/api/docs", get(controllers::api_docs_controller::show))
         .route("/signup", get(controllers::users_controller::new))
         .route("/signup", post(controllers::users_controller::create))
         .route(
@@ -80,10 +141,66 @@ pub fn router(db: Database) -> Router {
         .route("/login", post(controllers::sessions_controller::create))
         .route("/logout", delete(controllers::sessions_controller::delete))
         .route("/settings", get(controllers::users_controller::settings))
+        .route(
+            "/settings/account",
+            post(controllers::users_controller::update_account),
+        )
+        .route(
+            "/settings/profile",
+            post(controllers::users_controller::update_profile),
+        )
+        .route(
+            "/settings/avatar",
+            post(controllers::users_controller::update_avatar),
+        )
+        .route(
+            "/settings/avatar",
+            delete(controllers::users_controller::destroy_avatar),
+        )
         .route(
             "/settings/change_password",
             post(controllers::users_controller::change_password),
         )
+        .route(
+            "/settings/delete_account",
+            post(controllers::users_controller::delete_account),
+        )
+        .route(
+            "/settings/delete_account",
+            delete(controllers::users_controller::cancel_account_deletion),
+        )
+        .route(
+            "/settings/invites",
+            get(controllers::invite_codes_controller::index),
+        )
+        .route(
+            "/settings/invites",
+            post(controllers::invite_codes_controller::create),
+        )
+        .route(
+            "/settings/webhooks",
+            get(controllers::webhooks_controller::index),
+        )
+        .route(
+            "/settings/webhooks",
+            post(controllers::webhooks_controller::create),
+        )
+        .route(
+            "/settings/webhooks/:webhook_id",
+            get(controllers::webhooks_controller::show),
+        )
+        .route(
+            "/settings/webhooks/:webhook_id",
+            delete(controllers::webhooks_controller::destroy),
+        )
+        .route(
+            "/settings/sessions",
+            delete(controllers::sessions_controller::destroy_others),
+        )
+        .route(
+            "/settings/sessions/:session_id",
+            delete(controllers::sessions_controller::destroy),
+        )
         .route(
             "/api_sessions",
             post(controllers::api_sessions_controller::create),
@@ -92,10 +209,38 @@ pub fn router(db: Database) -> Router {
             "/api_sessions/:api_key_id",
             delete(controllers::api_sessions_controller::destroy),
         )
+        .route("/admin", get(controllers::admin_controller::index))
+        .route("/admin/users", get(controllers::admin_controller::users))
+        .route(
+            "/admin/users/:user_id/suspension",
+            post(controllers::admin_controller::suspend_user),
+        )
+        .route(
+            "/admin/users/:user_id/suspension",
+            delete(controllers::admin_controller::unsuspend_user),
+        )
+        .route("/admin/pastes", get(controllers::admin_controller::pastes))
+        .route(
+            "/admin/pastes/:paste_id/hidden",
+            post(controllers::admin_controller::hide_paste),
+        )
+        .route(
+            "/admin/pastes/:paste_id/hidden",
+            delete(controllers::admin_controller::unhide_paste),
+        )
+        .route(
+            "/admin/pastes/:paste_id",
+            delete(controllers::admin_controller::destroy_paste),
+        )
         .route("/new", get(controllers::pastes_controller::new))
         .route("/pastes", get(controllers::pastes_controller::index))
         .route("/pastes", post(controllers::pastes_controller::create))
+        .route("/pastes/batch", post(controllers::pastes_controller::batch))
         .route("/:username", get(controllers::users_controller::show))
+        .route(
+            "/:username/avatar",
+            get(controllers::users_controller::show_avatar),
+        )
         .route(
             "/:username/:paste_id",
             get(controllers::pastes_controller::show),
@@ -120,7 +265,20 @@ pub fn router(db: Database) -> Router {
             "/:username/:paste_id",
             delete(controllers::pastes_controller::destroy),
         )
+        .route(
+            "/:username/:paste_id/pin",
+            post(controllers::pastes_controller::pin),
+        )
+        .route(
+            "/:username/:paste_id/pin",
+            delete(controllers::pastes_controller::unpin),
+        )
         .fallback(controllers::application_controller::not_found)
+        .layer(from_fn_with_state(state.clone(), csrf::protect))
+        .layer(from_fn_with_state(state.clone(), rate_limit::limit_by_ip))
+        .nest("/api/v1", json_api_router)
+        .nest("/assets", assets_router)
+        .layer(from_fn_with_state(state.clone(), security_headers::set))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<_>| {
@@ -140,12 +298,25 @@ pub fn router(db: Database) -> Router {
         )
         .layer(TimeoutLayer::new(Duration::from_secs(10)))
         .layer(CompressionLayer::new())
-        .with_state(db)
+        .with_state(state)
 }
 
-pub fn background_tasks(mut shutdown_rx: mpsc::Receiver<()>, db: Database) -> JoinHandle<()> {
+pub fn background_tasks(
+    mut shutdown_rx: mpsc::Receiver<()>,
+    db: Database,
+    config: Config,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        let mut every_minute = interval(Duration::from_secs(60));
+        let mut every_minute = interval(Duration::from_mins(1));
+        let mut every_ten_seconds = interval(Duration::from_secs(10));
+        let webhook_client = WebhookClient::new(config.webhooks_allow_private_addresses())
+            .inspect_err(|e| {
+                tracing::error!(
+                    "unable to build webhook client, so webhooks won't be delivered: {e}"
+                );
+            })
+            .ok();
+        let mut delivery_run: Option<JoinHandle<()>> = None;
 
         loop {
             tokio::select! {
@@ -156,18 +327,52 @@ pub fn background_tasks(mut shutdown_rx: mpsc::Receiver<()>, db: Database) -> Jo
                 _ = every_minute.tick() => {
                     tracing::trace!("starting per minute background tasks");
 
-                    if let Err(e) = SessionToken::expire_idle(&db).await {
+                    if let Err(e) = SessionToken::expire_idle(&db, config.session().idle_ttl()).await {
                        tracing::error!("error in background task SessionToken::expire_idle: {e}");
                     }
-                    if let Err(e) = SessionToken::expire_absolute(&db).await {
+                    if let Err(e) = SessionToken::expire_absolute(
+                        &db,
+                        config.session().absolute_ttl(),
+                        config.session().remember_me_ttl(),
+                    ).await {
                        tracing::error!("error in background task SessionToken::expire_absolute: {e}");
                     }
+                    if let Err(e) = Throttle::expire(&db).await {
+                       tracing::error!("error in background task Throttle::expire: {e}");
+                    }
+                    if let Err(e) = AccountDeletion::purge_due(&db).await {
+                       tracing::error!("error in background task AccountDeletion::purge_due: {e}");
+                    }
+                    if let Err(e) = ApiKey::purge_expired(&db).await {
+                       tracing::error!("error in background task ApiKey::purge_expired: {e}");
+                    }
+                    if let Err(e) = WebhookDelivery::purge_old(&db).await {
+                       tracing::error!("error in background task WebhookDelivery::purge_old: {e}");
+                    }
 
                     tracing::trace!("finishing per minute background tasks");
                 }
+                _ = every_ten_seconds.tick(), if webhook_client.is_some() => {
+                    // Deliveries run in a task of their own, so slow receivers can't hold up the
+                    // tasks above. A run is skipped while the last one is still going.
+                    if delivery_run.as_ref().is_none_or(JoinHandle::is_finished) {
+                        if let Some(client) = webhook_client.clone() {
+                            let db = db.clone();
+                            delivery_run = Some(tokio::spawn(async move {
+                                if let Err(e) = WebhookDelivery::deliver_due(&db, &client).await {
+                                   tracing::error!("error in background task WebhookDelivery::deliver_due: {e}");
+                                }
+                            }));
+                        }
+                    }
+                }
             }
         }
 
+        // Attempts cut short here haven't been recorded, so they're made again after a restart
+        if let Some(delivery_run) = delivery_run {
+            delivery_run.abort();
+        }
         tracing::info!("shutting down background tasks");
     })
 }
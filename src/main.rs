@@ -1,5 +1,7 @@
 use dotenvy::dotenv;
+use gluestick::models::user::User;
 use gluestick::{background_tasks, config, db, router};
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -48,15 +50,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     db::migrations().to_latest(&mut db.conn).await?;
 
+    User::sync_admins_by_usernames(&db, config.admin_usernames().to_vec()).await?;
+
+    let outdated_password_hashes =
+        User::count_with_outdated_password_hashes(&db, config.password_hashing().params()).await?;
+    if outdated_password_hashes > 0 {
+        tracing::info!(
+            "{outdated_password_hashes} accounts have password hashes with outdated parameters, which will be upgraded when they next sign in"
+        );
+    }
+
     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
-    let background_tasks_handle = background_tasks(shutdown_rx, db.clone());
+    let background_tasks_handle = background_tasks(shutdown_rx, db.clone(), config.clone());
 
-    let app = router(db);
-    let listener = TcpListener::bind(("127.0.0.1", config.port())).await?;
+    let port = config.port();
+    let app = router(db, config);
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
     tracing::info!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(shutdown_tx))
-        .await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(shutdown_tx))
+    .await?;
 
     background_tasks_handle.await?;
     Ok(())
@@ -0,0 +1,215 @@
+use crate::config::{Config, SessionConfig};
+use crate::controllers::prelude::Error as ControllerError;
+use axum::body::{to_bytes, Body};
+use axum::extract::{FromRequest, Multipart, Request, State};
+use axum::http::{header, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use hmac::{Hmac, Mac};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use sha2::Sha256;
+
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+pub const CSRF_HEADER_NAME: HeaderName = HeaderName::from_static("x-csrf-token");
+const CSRF_FIELD_NAME: &str = "csrf_token";
+// The htmx event that hands the page a new token, when a response rotates the session
+const TOKEN_CHANGED_EVENT: &str = "csrfTokenChanged";
+const HX_TRIGGER: HeaderName = HeaderName::from_static("hx-trigger");
+const NONCE_LEN: usize = 32;
+// Matches the limit that axum's Form extractor enforces downstream
+const MAX_FORM_BYTES: usize = 2 * 1024 * 1024;
+
+tokio::task_local! {
+    static CSRF_TOKEN: String;
+}
+
+// The CSRF token of the request currently being handled, for embedding in rendered templates.
+// Empty outside of a request that passed through `protect`, e.g. for errors rendered before it.
+pub fn current_token() -> String {
+    CSRF_TOKEN.try_with(Clone::clone).unwrap_or_default()
+}
+
+#[derive(Deserialize)]
+struct CsrfForm {
+    csrf_token: Option<String>,
+}
+
+// Signed double submit CSRF protection. Every client is handed a random nonce in a cookie, and its
+// token is an HMAC of that nonce and the session cookie, under a key only the server knows.
+// Requests that change state must echo the token back, either in the X-CSRF-Token header, which
+// htmx sends on every request, or in a `csrf_token` form field, for forms submitted without
+// JavaScript. A cross site attacker can make the browser send the cookies, but can't read the
+// token to echo it back, and one that can plant cookies from a sibling subdomain still can't sign
+// a token for the victim's session.
+pub async fn protect(State(config): State<Config>, request: Request, next: Next) -> Response {
+    let secret = config.session().csrf_secret();
+    let jar = CookieJar::from_headers(request.headers());
+    let cookie_nonce = jar
+        .get(CSRF_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|nonce| is_well_formed(nonce));
+    let session_cookie = jar
+        .get(config.session().cookie_name())
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default();
+
+    let request = if is_safe(request.method()) {
+        request
+    } else {
+        let Some(nonce) = cookie_nonce.as_deref() else {
+            return forbidden();
+        };
+        let expected_token = sign(secret, nonce, &session_cookie);
+        let (request, submitted_token) = match submitted_token(request).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        };
+        if !submitted_token.is_some_and(|token| tokens_match(&token, &expected_token)) {
+            return forbidden();
+        }
+        request
+    };
+
+    let nonce = cookie_nonce.clone().unwrap_or_else(generate_nonce);
+    let token = sign(secret, &nonce, &session_cookie);
+    let mut response = CSRF_TOKEN.scope(token, next.run(request)).await;
+
+    // A response that signs in, out or rotates the session leaves the page holding a token for the
+    // old one. Full page loads render a fresh token anyway, but htmx has to be handed it.
+    if let Some(new_session_cookie) = issued_session_cookie(&response, config.session()) {
+        if new_session_cookie != session_cookie {
+            let token = sign(secret, &nonce, &new_session_cookie);
+            let trigger = format!(r#"{{"{TOKEN_CHANGED_EVENT}":"{token}"}}"#);
+            if let Ok(trigger) = HeaderValue::from_str(&trigger) {
+                response.headers_mut().entry(HX_TRIGGER).or_insert(trigger);
+            }
+        }
+    }
+    if cookie_nonce.is_none() {
+        if let Ok(cookie) =
+            HeaderValue::from_str(&build_cookie(config.session(), nonce).to_string())
+        {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+// Prefers the header, and only falls back to reading the token out of a url encoded or multipart
+// body when there's no header. Reading the body consumes it, so the request is rebuilt around the
+// buffered bytes for the handler to extract from as usual.
+async fn submitted_token(request: Request) -> Result<(Request, Option<String>), Response> {
+    if let Some(header) = request.headers().get(CSRF_HEADER_NAME) {
+        let token = header.to_str().ok().map(str::to_string);
+        return Ok((request, token));
+    }
+
+    let Some(content_type) = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(str::to_string)
+    else {
+        return Ok((request, None));
+    };
+    let is_form = content_type.starts_with("application/x-www-form-urlencoded");
+    let is_multipart = content_type.starts_with("multipart/form-data");
+    if !is_form && !is_multipart {
+        return Ok((request, None));
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_FORM_BYTES)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE.into_response())?;
+    let token = if is_form {
+        serde_urlencoded::from_bytes::<CsrfForm>(&bytes)
+            .ok()
+            .and_then(|form| form.csrf_token)
+    } else {
+        multipart_token(&content_type, bytes.clone()).await
+    };
+    Ok((Request::from_parts(parts, Body::from(bytes)), token))
+}
+
+// Reads fields until the token turns up. A malformed body has no token, and is turned away.
+async fn multipart_token(content_type: &str, bytes: axum::body::Bytes) -> Option<String> {
+    let request = Request::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(bytes))
+        .ok()?;
+    let mut multipart = Multipart::from_request(request, &()).await.ok()?;
+    while let Some(field) = multipart.next_field().await.ok()? {
+        if field.name() == Some(CSRF_FIELD_NAME) {
+            return field.text().await.ok();
+        }
+    }
+    None
+}
+
+// The value of the session cookie that the response sets, which is empty when it's removed
+fn issued_session_cookie(response: &Response, config: &SessionConfig) -> Option<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| Cookie::parse(value.to_str().ok()?).ok())
+        .filter(|cookie| cookie.name() == config.cookie_name())
+        .map(|cookie| cookie.value().to_string())
+        .next_back()
+}
+
+fn sign(secret: &SecretString, nonce: &str, session_cookie: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(format!("{nonce}.{session_cookie}").as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+fn generate_nonce() -> String {
+    let mut rng = ChaCha20Rng::from_entropy();
+    format!("{:032x}", rng.gen::<u128>())
+}
+
+fn is_well_formed(nonce: &str) -> bool {
+    nonce.len() == NONCE_LEN && nonce.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// Compares every byte regardless of where the first difference is, so that response timing
+// doesn't reveal how much of a guessed token was right
+fn tokens_match(submitted: &str, expected: &str) -> bool {
+    submitted.len() == expected.len()
+        && submitted
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn build_cookie(config: &SessionConfig, nonce: String) -> Cookie<'static> {
+    let mut cookie = Cookie::build((CSRF_COOKIE_NAME, nonce))
+        .path("/")
+        .http_only(true)
+        .secure(config.cookie_secure())
+        .same_site(config.cookie_same_site())
+        .build();
+    if let Some(domain) = config.cookie_domain() {
+        cookie.set_domain(domain.to_string());
+    }
+    cookie
+}
+
+fn forbidden() -> Response {
+    ControllerError::Forbidden(None).into_response()
+}
@@ -0,0 +1,347 @@
+use crate::config::{Config, Quota};
+use crate::controllers::api::prelude::Error as ApiControllerError;
+use crate::controllers::prelude::Error as ControllerError;
+use crate::db::Database;
+use crate::helpers::client_ip_helper;
+use crate::models::api_session::{ApiKey, HashedKey, UnhashedKey, API_KEY_HEADER_NAME};
+use crate::views::errors::too_many_requests::TooManyRequestsPage;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, IntoResponseParts, Response, ResponseParts};
+use jiff::SignedDuration;
+use secrecy::ExposeSecret;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const PRUNE_INTERVAL: Duration = Duration::from_mins(1);
+// Clients beyond this many are turned away until pruning makes room, so that spraying requests
+// from many addresses can't grow the store without bound
+const MAX_BUCKETS: usize = 100_000;
+// How long a looked up API key is remembered for. Only the choice of bucket depends on it, since
+// the key is authenticated again by the endpoint itself.
+const API_KEY_CACHE_TTL: Duration = Duration::from_mins(1);
+const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+// Rate limits requests with in-memory token buckets. Each client gets a bucket holding up to
+// `burst` tokens, which refills continuously at `per_minute` tokens a minute, and every request
+// spends one token. Requests that find their bucket empty are rejected.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter(Arc<Mutex<Store>>);
+
+#[derive(Debug, Default)]
+struct Store {
+    buckets: HashMap<BucketKey, Bucket>,
+    // API key ids by the hash of the key, along with when they were looked up
+    api_key_ids: HashMap<Vec<u8>, (Uuid, Instant)>,
+    last_pruned_at: Option<Instant>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum BucketKey {
+    Ip(IpAddr),
+    ApiKey(Uuid),
+}
+
+impl BucketKey {
+    // IPv6 clients are usually handed a whole /64, so each /64 shares a bucket rather than every
+    // address in it getting one of its own
+    fn ip(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => Self::Ip(IpAddr::V4(ip)),
+                None => Self::Ip(IpAddr::V6(Ipv6Addr::from(
+                    u128::from(ip) & !(u128::MAX >> 64),
+                ))),
+            },
+            IpAddr::V4(_) => Self::Ip(ip),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    quota: Quota,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+// The outcome of spending tokens from a bucket, which also describes the bucket afterwards
+#[derive(Debug)]
+pub struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    // time until the bucket is full again
+    reset_after: Duration,
+    // time until the next request would be allowed
+    retry_after: Duration,
+}
+
+impl RateLimiter {
+    // For API requests that do the work of several, such as batches. The middleware has already
+    // spent one token on the request, so this spends the rest from the API key's bucket. A request
+    // is never charged more than a full bucket, or it could never be made at all.
+    pub fn spend_api_key_tokens(&self, api_key_id: Uuid, quota: Quota, tokens: u32) -> Decision {
+        self.spend(
+            BucketKey::ApiKey(api_key_id),
+            quota,
+            tokens.min(quota.burst.saturating_sub(1)),
+        )
+    }
+
+    fn spend(&self, key: BucketKey, quota: Quota, tokens: u32) -> Decision {
+        let now = Instant::now();
+        let mut store = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        store.prune(now);
+        if store.buckets.len() >= MAX_BUCKETS && !store.buckets.contains_key(&key) {
+            return Decision {
+                allowed: false,
+                limit: quota.burst,
+                remaining: 0,
+                reset_after: PRUNE_INTERVAL,
+                retry_after: PRUNE_INTERVAL,
+            };
+        }
+
+        let bucket = store.buckets.entry(key).or_insert_with(|| Bucket {
+            quota,
+            tokens: f64::from(quota.burst),
+            updated_at: now,
+        });
+        // The quota can only differ from the bucket's if the bucket predates a config change
+        bucket.quota = quota;
+        bucket.refill(now);
+
+        let tokens = f64::from(tokens);
+        let allowed = bucket.tokens >= tokens;
+        if allowed {
+            bucket.tokens -= tokens;
+        }
+
+        Decision {
+            allowed,
+            limit: quota.burst,
+            remaining: bucket.whole_tokens(),
+            reset_after: bucket.time_until(f64::from(quota.burst)),
+            retry_after: bucket.time_until(tokens),
+        }
+    }
+
+    fn cached_api_key_id(&self, hashed_key: &HashedKey) -> Option<Uuid> {
+        let now = Instant::now();
+        let store = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        store
+            .api_key_ids
+            .get(hashed_key.expose_secret())
+            .filter(|(_, cached_at)| now.duration_since(*cached_at) < API_KEY_CACHE_TTL)
+            .map(|(id, _)| *id)
+    }
+
+    fn cache_api_key_id(&self, hashed_key: &HashedKey, id: Uuid) {
+        let mut store = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        store
+            .api_key_ids
+            .insert(hashed_key.expose_secret().clone(), (id, Instant::now()));
+    }
+}
+
+impl Decision {
+    pub fn allowed(&self) -> bool {
+        self.allowed
+    }
+}
+
+impl IntoResponseParts for Decision {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        insert_rate_limit_headers(res.headers_mut(), &self);
+        Ok(res)
+    }
+}
+
+impl Store {
+    // Drops buckets that have refilled completely, since a fresh bucket would be identical, along
+    // with API keys that have been cached for too long
+    fn prune(&mut self, now: Instant) {
+        if self
+            .last_pruned_at
+            .is_some_and(|last_pruned_at| now.duration_since(last_pruned_at) < PRUNE_INTERVAL)
+        {
+            return;
+        }
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            bucket.tokens < f64::from(bucket.quota.burst)
+        });
+        self.api_key_ids
+            .retain(|_, (_, cached_at)| now.duration_since(*cached_at) < API_KEY_CACHE_TTL);
+        self.last_pruned_at = Some(now);
+        if self.buckets.len() >= MAX_BUCKETS {
+            tracing::warn!(
+                "rate limiting {MAX_BUCKETS} clients, so new ones are being turned away"
+            );
+        }
+    }
+}
+
+impl Bucket {
+    fn tokens_per_second(&self) -> f64 {
+        f64::from(self.quota.per_minute) / 60.0
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.tokens_per_second()).min(f64::from(self.quota.burst));
+        self.updated_at = now;
+    }
+
+    fn time_until(&self, tokens: f64) -> Duration {
+        let missing = (tokens - self.tokens).max(0.0);
+        Duration::from_secs_f64(missing / self.tokens_per_second())
+    }
+
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn whole_tokens(&self) -> u32 {
+        // tokens is always within 0..=burst, so this can't truncate or lose a sign
+        self.tokens.floor() as u32
+    }
+}
+
+pub async fn limit_by_ip(
+    State(rate_limiter): State<RateLimiter>,
+    State(config): State<Config>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ip) = client_ip(&request, &config) else {
+        return next.run(request).await;
+    };
+
+    let decision = rate_limiter.spend(BucketKey::ip(ip), config.rate_limit().ip_quota(), 1);
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        ControllerError::TooManyRequests {
+            retry_after: SignedDuration::try_from(decision.retry_after)
+                .unwrap_or(SignedDuration::MAX),
+            template: Box::new(TooManyRequestsPage { session: None }),
+        }
+        .into_response()
+    };
+    insert_rate_limit_headers(response.headers_mut(), &decision);
+    response
+}
+
+// API requests made with a valid API key are limited per key, so that clients sharing an IP don't
+// share a limit. Anything else falls back to being limited per IP.
+pub async fn limit_by_api_key(
+    State(rate_limiter): State<RateLimiter>,
+    State(config): State<Config>,
+    State(db): State<Database>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let maybe_ip = client_ip(&request, &config);
+    let maybe_unhashed_key = request
+        .headers()
+        .get(API_KEY_HEADER_NAME)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| UnhashedKey::try_from(header).ok());
+
+    let mut maybe_ip_decision = None;
+    let maybe_api_key_id = match maybe_unhashed_key {
+        Some(unhashed_key) => {
+            let hashed_key = HashedKey::from(&unhashed_key);
+            if let Some(id) = rate_limiter.cached_api_key_id(&hashed_key) {
+                Some(id)
+            } else {
+                // Looking a key up costs a database query, so keys that aren't cached are
+                // counted against the client's IP first. Otherwise a flood of made up keys
+                // would each reach the database before being rate limited at all.
+                if let Some(ip) = maybe_ip {
+                    let decision =
+                        rate_limiter.spend(BucketKey::ip(ip), config.rate_limit().ip_quota(), 1);
+                    if !decision.allowed {
+                        return run_api_request(&decision, request, next).await;
+                    }
+                    maybe_ip_decision = Some(decision);
+                }
+                match ApiKey::find_id_by_unhashed_key(&db, &unhashed_key).await {
+                    Ok(Some(id)) => {
+                        rate_limiter.cache_api_key_id(&hashed_key, id);
+                        Some(id)
+                    }
+                    Ok(None) => None,
+                    Err(e) => return ApiControllerError::from(e).into_response(),
+                }
+            }
+        }
+        None => None,
+    };
+
+    let decision = match (maybe_api_key_id, maybe_ip_decision, maybe_ip) {
+        (Some(id), _, _) => rate_limiter.spend(
+            BucketKey::ApiKey(id),
+            config.rate_limit().api_key_quota(),
+            1,
+        ),
+        // The IP has already been counted ahead of the lookup
+        (None, Some(ip_decision), _) => ip_decision,
+        (None, None, Some(ip)) => {
+            rate_limiter.spend(BucketKey::ip(ip), config.rate_limit().ip_quota(), 1)
+        }
+        (None, None, None) => return next.run(request).await,
+    };
+    run_api_request(&decision, request, next).await
+}
+
+async fn run_api_request(decision: &Decision, request: Request, next: Next) -> Response {
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        ApiControllerError::TooManyRequests.into_response()
+    };
+    // Endpoints that spend more tokens set the headers themselves, since their decision was made
+    // later and describes the bucket as it is now
+    if !response.headers().contains_key(X_RATELIMIT_LIMIT) {
+        insert_rate_limit_headers(response.headers_mut(), decision);
+    }
+    response
+}
+
+fn client_ip(request: &Request, config: &Config) -> Option<IpAddr> {
+    client_ip_helper::resolve(
+        request.extensions(),
+        request.headers(),
+        config.trusted_proxies(),
+    )
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(
+        X_RATELIMIT_RESET,
+        HeaderValue::from(whole_seconds(decision.reset_after)),
+    );
+    if !decision.allowed {
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(whole_seconds(decision.retry_after)),
+        );
+    }
+}
+
+// Headers carry whole seconds, so round up to avoid telling clients to come back too early
+fn whole_seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
@@ -0,0 +1,83 @@
+use crate::config::Config;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+// For responses that carry user content verbatim, like raw pastes. Sandboxing gives the content a
+// unique origin with scripts disabled, so that even HTML opened directly in the browser can't act
+// on behalf of the signed in user.
+pub const SANDBOX_CSP: &str = "sandbox; default-src 'none'";
+
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+const PERMISSIONS_POLICY_VALUE: &str =
+    "camera=(), geolocation=(), microphone=(), payment=(), usb=(), clipboard-write=(self)";
+
+tokio::task_local! {
+    static CSP_NONCE: String;
+}
+
+// The CSP nonce of the request currently being handled, which every script and style element in
+// a rendered template has to carry. Empty outside of a request that passed through `set`.
+pub fn current_nonce() -> String {
+    CSP_NONCE.try_with(Clone::clone).unwrap_or_default()
+}
+
+// Adds security headers to every response. Handlers can set their own Content-Security-Policy,
+// which is left as is. The default policy only allows scripts and styles that carry the nonce
+// generated for the request, which is what keeps injected markup from running anything.
+pub async fn set(State(config): State<Config>, request: Request, next: Next) -> Response {
+    let nonce = generate_nonce();
+    let mut response = CSP_NONCE.scope(nonce.clone(), next.run(request)).await;
+
+    let security_headers = config.security_headers();
+    let headers = response.headers_mut();
+    let csp_header = if security_headers.csp_report_only() {
+        header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+    } else {
+        header::CONTENT_SECURITY_POLICY
+    };
+    if !headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        insert(headers, csp_header, &content_security_policy(&nonce));
+    }
+    if let Some(max_age) = security_headers.hsts_max_age_seconds() {
+        insert(
+            headers,
+            header::STRICT_TRANSPORT_SECURITY,
+            &format!("max-age={max_age}; includeSubDomains"),
+        );
+    }
+    insert(headers, header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    // Secret pastes are only protected by their URL, which must not leak to other sites
+    insert(headers, header::REFERRER_POLICY, "same-origin");
+    insert(headers, PERMISSIONS_POLICY, PERMISSIONS_POLICY_VALUE);
+    response
+}
+
+fn content_security_policy(nonce: &str) -> String {
+    [
+        "default-src 'none'".to_string(),
+        format!("script-src 'nonce-{nonce}' 'strict-dynamic'"),
+        format!("style-src 'self' 'nonce-{nonce}'"),
+        "img-src 'self'".to_string(),
+        "connect-src 'self'".to_string(),
+        "manifest-src 'self'".to_string(),
+        "form-action 'self'".to_string(),
+        "frame-ancestors 'none'".to_string(),
+        "base-uri 'none'".to_string(),
+    ]
+    .join("; ")
+}
+
+fn generate_nonce() -> String {
+    let mut rng = ChaCha20Rng::from_entropy();
+    format!("{:032x}", rng.gen::<u128>())
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.entry(name).or_insert(value);
+    }
+}
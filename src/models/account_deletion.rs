@@ -0,0 +1,141 @@
+use crate::db::Database;
+use crate::models::prelude::*;
+use crate::models::security_event::SecurityEvent;
+use crate::models::user::User;
+use derive_more::Display;
+use jiff::{Span, Timestamp};
+use rusqlite::types::Type;
+use rusqlite::{named_params, Row, TransactionBehavior};
+use uuid::Uuid;
+
+// A request to delete an account once its grace period has passed, during which the owner can still
+// sign in and cancel it
+#[derive(Clone, Debug, Display)]
+#[display("{{ user_id: {user_id}, scheduled_for: {scheduled_for} }}")]
+pub struct AccountDeletion {
+    pub user_id: Uuid,
+    pub requested_at: Timestamp,
+    pub scheduled_for: Timestamp,
+}
+
+impl AccountDeletion {
+    pub fn new(user_id: Uuid, grace_period: Span) -> Result<Self> {
+        let now = Timestamp::now();
+        Ok(Self {
+            user_id,
+            requested_at: now,
+            scheduled_for: now.checked_add(grace_period)?,
+        })
+    }
+
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            user_id: row.get(0)?,
+            requested_at: Timestamp::from_millisecond(row.get(1)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, Type::Integer, Box::new(e))
+            })?,
+            scheduled_for: Timestamp::from_millisecond(row.get(2)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(2, Type::Integer, Box::new(e))
+            })?,
+        })
+    }
+
+    pub async fn find_for_user_id(db: &Database, user_id: Uuid) -> Result<Option<Self>> {
+        let maybe_account_deletion = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"SELECT user_id, requested_at, scheduled_for
+                    FROM account_deletions WHERE user_id = :user_id;",
+                )?;
+                let mut rows = statement.query(named_params! {":user_id": user_id})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(Self::from_sql_row(row)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_account_deletion)
+    }
+
+    pub async fn insert(self, db: &Database, event: SecurityEvent) -> Result<usize> {
+        tracing::info!("scheduling account deletion {self}");
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let result = {
+                    let mut statement = tx.prepare(
+                        r"INSERT OR REPLACE INTO account_deletions (user_id, requested_at, scheduled_for)
+                        VALUES (:user_id, :requested_at, :scheduled_for);",
+                    )?;
+                    let result = statement.execute(named_params! {
+                        ":user_id": self.user_id,
+                        ":requested_at": self.requested_at.as_millisecond(),
+                        ":scheduled_for": self.scheduled_for.as_millisecond(),
+                    })?;
+                    event.tx_insert(&tx)?;
+                    result
+                };
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn cancel(self, db: &Database, event: SecurityEvent) -> Result<usize> {
+        tracing::info!("cancelling account deletion {self}");
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let result = {
+                    let mut statement =
+                        tx.prepare("DELETE FROM account_deletions WHERE user_id = :user_id;")?;
+                    let result = statement.execute(named_params! {":user_id": self.user_id})?;
+                    event.tx_insert(&tx)?;
+                    result
+                };
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    // Deletes every account whose grace period has passed. Finding and deleting those accounts
+    // happens in a single transaction, so that a deletion cancelled in the meantime is never
+    // carried out.
+    pub async fn purge_due(db: &Database) -> Result<usize> {
+        tracing::info!("deleting accounts that are due for deletion");
+        let now = Timestamp::now();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let user_ids = {
+                    let mut statement = tx.prepare(
+                        "SELECT user_id FROM account_deletions WHERE scheduled_for <= :now;",
+                    )?;
+                    let user_id_iter = statement
+                        .query_map(named_params! {":now": now.as_millisecond()}, |row| {
+                            row.get::<_, Uuid>(0)
+                        })?;
+                    user_id_iter.collect::<Result<Vec<_>, _>>()?
+                };
+                for user_id in &user_ids {
+                    User::tx_delete(&tx, *user_id)?;
+                }
+                tx.commit()?;
+                Ok(user_ids.len())
+            })
+            .await?;
+
+        tracing::info!("done deleting accounts, deleted {result} accounts");
+        Ok(result)
+    }
+}
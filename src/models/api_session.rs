@@ -2,17 +2,20 @@ use crate::db::Database;
 use crate::models::prelude::*;
 use crate::models::user::User;
 use derive_more::{Display, From};
-use jiff::Timestamp;
+use jiff::{Span, Timestamp};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
-use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
 use rusqlite::{Row, Transaction, TransactionBehavior};
 use secrecy::{ExposeSecret, SecretBox, SecretString};
 use sha2::{Digest, Sha256};
+use std::str::FromStr;
 use tokio_rusqlite::named_params;
 use uuid::Uuid;
 
 pub const API_KEY_HEADER_NAME: &str = "X-GLUESTICK-API-KEY";
+const API_KEY_COLUMNS: &str =
+    "api_keys.id, api_keys.name, api_keys.key, api_keys.user_id, api_keys.created_at, api_keys.last_used_at, api_keys.scopes, api_keys.expires_at";
 
 #[derive(Debug, Display)]
 #[display("{{ api_key: {api_key}, user: {user} }}")]
@@ -22,6 +25,10 @@ pub struct ApiSession {
 }
 
 impl ApiSession {
+    pub fn scopes(&self) -> &Scopes {
+        &self.api_key.scopes
+    }
+
     pub async fn find_by_unhashed_key(
         db: &Database,
         unhashed_key: &UnhashedKey,
@@ -43,22 +50,26 @@ impl ApiSession {
         Ok(maybe_api_session)
     }
 
+    // Expired keys are treated as if they didn't exist, even before the background task that purges
+    // them gets around to it
     pub fn tx_find_by_hashed_key(
         tx: &Transaction,
         key: &HashedKey,
     ) -> tokio_rusqlite::Result<Option<Self>> {
-        let mut stmt = tx.prepare(
+        let mut stmt = tx.prepare(&format!(
             r"SELECT
-                users.id, users.username, users.email, users.password, users.created_at, users.updated_at,
-                api_keys.id, api_keys.name, api_keys.key, api_keys.user_id, api_keys.created_at, api_keys.last_used_at
+                users.id, users.username, users.email, users.password, users.created_at, users.updated_at, users.is_admin, users.suspended_at, users.display_name, users.bio, users.website,
+                {API_KEY_COLUMNS}
             FROM users JOIN api_keys ON users.id = api_keys.user_id
-            WHERE api_keys.key = :key;"
-        )?;
-        let mut rows = stmt.query(named_params! {":key": key})?;
+            WHERE api_keys.key = :key AND users.suspended_at IS NULL
+            AND (api_keys.expires_at IS NULL OR api_keys.expires_at > :now);"
+        ))?;
+        let mut rows =
+            stmt.query(named_params! {":key": key, ":now": Timestamp::now().as_millisecond()})?;
         match rows.next()? {
             Some(row) => {
                 let user = User::from_sql_row(row)?;
-                let api_key = ApiKey::from_sql_row(row, 6)?;
+                let api_key = ApiKey::from_sql_row(row, 11)?;
                 Ok(Some(ApiSession { api_key, user }))
             }
             None => Ok(None),
@@ -66,7 +77,7 @@ impl ApiSession {
     }
 }
 
-#[derive(Debug, Display)]
+#[derive(Clone, Debug, Display)]
 #[display("{{ id: {id}, user_id: {user_id} }}")]
 pub struct ApiKey {
     pub id: Uuid,
@@ -75,23 +86,55 @@ pub struct ApiKey {
     pub user_id: Uuid,
     pub created_at: Timestamp,
     pub last_used_at: Timestamp,
+    pub scopes: Scopes,
+    pub expires_at: Option<Timestamp>,
 }
 
 impl ApiKey {
-    pub fn new(user_id: Uuid) -> (UnhashedKey, Self) {
+    // A blank name falls back to one generated from the end of the key, so that keys can still be
+    // told apart in settings
+    pub fn new(
+        user_id: Uuid,
+        name: &str,
+        scopes: Scopes,
+        expires_in: Option<Span>,
+    ) -> Result<(UnhashedKey, Self)> {
+        let name = name.trim();
+        if name.chars().count() > 256 {
+            return Err(Error::Parse(
+                "Name may not be longer than 256 characters".into(),
+            ));
+        }
+        if scopes.is_empty() {
+            return Err(Error::Parse("Choose at least one scope".into()));
+        }
+
         let now = Timestamp::now();
         let unhashed_key = UnhashedKey::generate();
-        let last_four =
-            &unhashed_key.expose_secret()[unhashed_key.expose_secret().len().saturating_sub(4)..];
+        let name = if name.is_empty() {
+            let last_four = &unhashed_key.expose_secret()
+                [unhashed_key.expose_secret().len().saturating_sub(4)..];
+            format!("API Key ending in '{last_four}'")
+        } else {
+            name.to_string()
+        };
+        let expires_at = expires_in.map(|span| now.checked_add(span)).transpose()?;
         let api_key = Self {
             id: Uuid::now_v7(),
-            name: format!("API Key ending in '{last_four}'"),
+            name,
             key: HashedKey::from(&unhashed_key),
             user_id,
             created_at: now,
             last_used_at: now,
+            scopes,
+            expires_at,
         };
-        (unhashed_key, api_key)
+        Ok((unhashed_key, api_key))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Timestamp::now())
     }
 
     pub fn from_sql_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
@@ -106,6 +149,18 @@ impl ApiKey {
             last_used_at: Timestamp::from_millisecond(row.get(5 + offset)?).map_err(|e| {
                 rusqlite::Error::FromSqlConversionFailure(5 + offset, Type::Integer, Box::new(e))
             })?,
+            scopes: row.get(6 + offset)?,
+            expires_at: row
+                .get::<_, Option<i64>>(7 + offset)?
+                .map(Timestamp::from_millisecond)
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        7 + offset,
+                        Type::Integer,
+                        Box::new(e),
+                    )
+                })?,
         })
     }
 
@@ -113,10 +168,10 @@ impl ApiKey {
         let api_keys: Vec<_> = db
             .conn
             .call(move |conn| {
-                let mut statement = conn.prepare(
-                    r"SELECT id, name, key, user_id, created_at, last_used_at FROM api_keys
+                let mut statement = conn.prepare(&format!(
+                    r"SELECT {API_KEY_COLUMNS} FROM api_keys
                     WHERE user_id = :user_id ORDER BY id DESC;",
-                )?;
+                ))?;
                 let api_key_iter = statement
                     .query_map(named_params! {":user_id": user_id}, |k| {
                         Self::from_sql_row(k, 0)
@@ -135,10 +190,10 @@ impl ApiKey {
         let maybe_api_key = db
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare(
-                    r"SELECT id, name, key, user_id, created_at, last_used_at FROM api_keys
+                let mut stmt = conn.prepare(&format!(
+                    r"SELECT {API_KEY_COLUMNS} FROM api_keys
                     WHERE id = :id AND user_id = :user_id;",
-                )?;
+                ))?;
                 let mut rows = stmt.query(named_params! {":id": id, ":user_id": user_id})?;
                 match rows.next()? {
                     Some(row) => Ok(Some(ApiKey::from_sql_row(row, 0)?)),
@@ -150,13 +205,34 @@ impl ApiKey {
         Ok(maybe_api_key)
     }
 
+    pub async fn find_id_by_unhashed_key(
+        db: &Database,
+        unhashed_key: &UnhashedKey,
+    ) -> Result<Option<Uuid>> {
+        let hashed_key = HashedKey::from(unhashed_key);
+        let maybe_id = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("SELECT id FROM api_keys WHERE key = :key;")?;
+                let mut rows = stmt.query(named_params! {":key": hashed_key})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(row.get(0)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_id)
+    }
+
     pub async fn insert(self, db: &Database) -> Result<usize> {
         tracing::info!("inserting api key {self}");
         let result = db
             .conn
             .call(move |conn| {
                 let mut statement = conn.prepare(
-                    "INSERT INTO api_keys VALUES (:id, :name, :key, :user_id, :created_at, :last_used_at);",
+                    r"INSERT INTO api_keys (id, name, key, user_id, created_at, last_used_at, scopes, expires_at)
+                    VALUES (:id, :name, :key, :user_id, :created_at, :last_used_at, :scopes, :expires_at);",
                 )?;
                 let result = statement.execute(named_params! {
                     ":id": self.id,
@@ -165,6 +241,8 @@ impl ApiKey {
                     ":user_id": self.user_id,
                     ":created_at": self.created_at.as_millisecond(),
                     ":last_used_at": self.last_used_at.as_millisecond(),
+                    ":scopes": self.scopes,
+                    ":expires_at": self.expires_at.map(Timestamp::as_millisecond),
                 })?;
                 Ok(result)
             })
@@ -186,6 +264,24 @@ impl ApiKey {
         Ok(result)
     }
 
+    pub async fn purge_expired(db: &Database) -> Result<usize> {
+        tracing::info!("purging expired api keys");
+        let now = Timestamp::now().as_millisecond();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "DELETE FROM api_keys WHERE expires_at IS NOT NULL AND expires_at <= :now;",
+                )?;
+                let result = stmt.execute(named_params! {":now": now})?;
+                Ok(result)
+            })
+            .await?;
+
+        tracing::info!("done purging api keys, purged {result} api keys");
+        Ok(result)
+    }
+
     pub fn tx_touch(&self, tx: &Transaction) -> tokio_rusqlite::Result<()> {
         let mut stmt =
             tx.prepare("UPDATE api_keys SET last_used_at = :last_used_at WHERE key = :key;")?;
@@ -196,6 +292,125 @@ impl ApiKey {
     }
 }
 
+// What an API key may be used for. Keys can only ever act as their owner, so scopes narrow down
+// what the owner can do rather than grant anything on top of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    PastesRead,
+    PastesWrite,
+    PastesDelete,
+    AccountRead,
+}
+
+impl Scope {
+    pub const ALL: [Self; 4] = [
+        Self::PastesRead,
+        Self::PastesWrite,
+        Self::PastesDelete,
+        Self::AccountRead,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PastesRead => "pastes:read",
+            Self::PastesWrite => "pastes:write",
+            Self::PastesDelete => "pastes:delete",
+            Self::AccountRead => "account:read",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::PastesRead => "List and view pastes, including your secret pastes",
+            Self::PastesWrite => "Create and edit your pastes",
+            Self::PastesDelete => "Delete your pastes",
+            Self::AccountRead => "View your account details",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| Error::Parse(format!("Unrecognized scope '{s}'")))
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// A set of scopes, always kept in the order of `Scope::ALL` so that equal sets compare and store
+// the same way
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Scopes(Vec<Scope>);
+
+impl Scopes {
+    pub fn all() -> Self {
+        Self(Scope::ALL.to_vec())
+    }
+
+    pub fn contains(&self, scope: &Scope) -> bool {
+        self.0.contains(scope)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Scope> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl FromIterator<Scope> for Scopes {
+    fn from_iter<I: IntoIterator<Item = Scope>>(iter: I) -> Self {
+        let scopes: Vec<_> = iter.into_iter().collect();
+        Self(
+            Scope::ALL
+                .into_iter()
+                .filter(|scope| scopes.contains(scope))
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for Scopes {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace().map(str::parse).collect()
+    }
+}
+
+impl std::fmt::Display for Scopes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scopes: Vec<_> = self.iter().map(Scope::as_str).collect();
+        f.write_str(&scopes.join(" "))
+    }
+}
+
+impl ToSql for Scopes {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        Ok(self.to_string().into())
+    }
+}
+
+impl FromSql for Scopes {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).and_then(|s| {
+            s.parse()
+                .map_err(|_| FromSqlError::Other("Unrecognized value for scopes".into()))
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct UnhashedKey(SecretString);
 
@@ -239,6 +454,12 @@ impl ExposeSecret<Vec<u8>> for HashedKey {
     }
 }
 
+impl Clone for HashedKey {
+    fn clone(&self) -> Self {
+        Self(SecretBox::new(Box::new(self.expose_secret().clone())))
+    }
+}
+
 impl std::fmt::Debug for HashedKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("[REDACTED HashedKey]")
@@ -0,0 +1,152 @@
+use crate::db::Database;
+use crate::models::prelude::*;
+use derive_more::Display;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use jiff::Timestamp;
+use rusqlite::types::Type;
+use rusqlite::{named_params, Row};
+use std::io::Cursor;
+use uuid::Uuid;
+
+pub const MAX_AVATAR_UPLOAD_BYTES: usize = 1024 * 1024;
+const AVATAR_SIZE: u32 = 256;
+const MAX_AVATAR_UPLOAD_DIMENSION: u32 = 4096;
+
+// A user's avatar, which is always stored as a square PNG, no matter what was uploaded
+#[derive(Clone, Debug, Display)]
+#[display("{{ user_id: {user_id}, created_at: {created_at} }}")]
+pub struct Avatar {
+    pub user_id: Uuid,
+    pub image: Vec<u8>,
+    pub created_at: Timestamp,
+}
+
+impl Avatar {
+    // Decodes an uploaded image, center crops it to a square and resizes it. Decoding is bounded
+    // by dimension and allocation limits, so that a small but maliciously crafted file can't
+    // exhaust memory. This is CPU heavy, so callers should run it off of the async runtime.
+    pub fn from_upload(user_id: Uuid, bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() {
+            return Err(Error::Parse("Avatar may not be blank".into()));
+        }
+        if bytes.len() > MAX_AVATAR_UPLOAD_BYTES {
+            return Err(Error::Parse("Avatar is too large (maximum is 1 MB)".into()));
+        }
+
+        let mut reader = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(|_| Error::Parse("Avatar could not be read".into()))?;
+        if !matches!(
+            reader.format(),
+            Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP)
+        ) {
+            return Err(Error::Parse(
+                "Avatar must be a PNG, JPEG, GIF or WebP image".into(),
+            ));
+        }
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_AVATAR_UPLOAD_DIMENSION);
+        limits.max_image_height = Some(MAX_AVATAR_UPLOAD_DIMENSION);
+        reader.limits(limits);
+        let decoded = reader
+            .decode()
+            .map_err(|_| Error::Parse("Avatar could not be read".into()))?;
+
+        let mut image = Vec::new();
+        decoded
+            .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3)
+            .into_rgba8()
+            .write_to(&mut Cursor::new(&mut image), ImageFormat::Png)
+            .map_err(|_| Error::Parse("Avatar could not be processed".into()))?;
+
+        Ok(Self {
+            user_id,
+            image,
+            created_at: Timestamp::now(),
+        })
+    }
+
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            user_id: row.get(0)?,
+            image: row.get(1)?,
+            created_at: Timestamp::from_millisecond(row.get(2)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(2, Type::Integer, Box::new(e))
+            })?,
+        })
+    }
+
+    pub async fn find_for_user_id(db: &Database, user_id: Uuid) -> Result<Option<Self>> {
+        let maybe_avatar = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    "SELECT user_id, image, created_at FROM avatars WHERE user_id = :user_id;",
+                )?;
+                let mut rows = statement.query(named_params! {":user_id": user_id})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(Self::from_sql_row(row)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_avatar)
+    }
+
+    // Only the timestamp is needed to build a cache busting URL, so there's no point loading the
+    // image itself on every page that shows an avatar
+    pub async fn created_at_for_user_id(db: &Database, user_id: Uuid) -> Result<Option<Timestamp>> {
+        let maybe_created_at = db
+            .conn
+            .call(move |conn| {
+                let mut statement =
+                    conn.prepare("SELECT created_at FROM avatars WHERE user_id = :user_id;")?;
+                let mut rows = statement.query(named_params! {":user_id": user_id})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(row.get::<_, i64>(0)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_created_at
+            .map(Timestamp::from_millisecond)
+            .transpose()?)
+    }
+
+    pub async fn insert(self, db: &Database) -> Result<usize> {
+        tracing::info!("inserting avatar {self}");
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"INSERT OR REPLACE INTO avatars (user_id, image, created_at)
+                    VALUES (:user_id, :image, :created_at);",
+                )?;
+                Ok(statement.execute(named_params! {
+                    ":user_id": self.user_id,
+                    ":image": self.image,
+                    ":created_at": self.created_at.as_millisecond(),
+                })?)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn delete_for_user_id(db: &Database, user_id: Uuid) -> Result<usize> {
+        tracing::info!("deleting avatar for user {user_id}");
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut statement =
+                    conn.prepare("DELETE FROM avatars WHERE user_id = :user_id;")?;
+                Ok(statement.execute(named_params! {":user_id": user_id})?)
+            })
+            .await?;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::db::Database;
+use crate::models::prelude::*;
+use crate::models::user::User;
+use argon2::Params;
+use jiff::{SignedDuration, Timestamp};
+use rusqlite::named_params;
+
+// Instance-wide counts, as shown on the admin dashboard
+#[derive(Clone, Debug, Default)]
+pub struct InstanceStats {
+    pub users: i64,
+    pub admins: i64,
+    pub suspended_users: i64,
+    pub public_pastes: i64,
+    pub secret_pastes: i64,
+    pub hidden_pastes: i64,
+    pub pastes_created_today: i64,
+    pub active_sessions: i64,
+    pub api_keys: i64,
+    pub outdated_password_hashes: usize,
+}
+
+impl InstanceStats {
+    pub async fn load(db: &Database, password_params: &Params) -> Result<Self> {
+        let day_ago = Timestamp::now().checked_sub(SignedDuration::from_hours(24))?;
+        let stats = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"SELECT
+                      (SELECT count(*) FROM users),
+                      (SELECT count(*) FROM users WHERE is_admin = 1),
+                      (SELECT count(*) FROM users WHERE suspended_at IS NOT NULL),
+                      (SELECT count(*) FROM pastes WHERE visibility = 'public'),
+                      (SELECT count(*) FROM pastes WHERE visibility = 'secret'),
+                      (SELECT count(*) FROM pastes WHERE hidden_at IS NOT NULL),
+                      (SELECT count(*) FROM pastes WHERE created_at >= :day_ago),
+                      (SELECT count(*) FROM session_tokens),
+                      (SELECT count(*) FROM api_keys);",
+                )?;
+                let stats = stmt.query_row(
+                    named_params! {":day_ago": day_ago.as_millisecond()},
+                    |row| {
+                        Ok(Self {
+                            users: row.get(0)?,
+                            admins: row.get(1)?,
+                            suspended_users: row.get(2)?,
+                            public_pastes: row.get(3)?,
+                            secret_pastes: row.get(4)?,
+                            hidden_pastes: row.get(5)?,
+                            pastes_created_today: row.get(6)?,
+                            active_sessions: row.get(7)?,
+                            api_keys: row.get(8)?,
+                            outdated_password_hashes: 0,
+                        })
+                    },
+                )?;
+                Ok(stats)
+            })
+            .await?;
+        Ok(Self {
+            outdated_password_hashes: User::count_with_outdated_password_hashes(
+                db,
+                password_params,
+            )
+            .await?,
+            ..stats
+        })
+    }
+}
@@ -1,52 +1,246 @@
 use crate::db::Database;
 use crate::models::prelude::*;
+use crate::models::user::Username;
 use derive_more::Display;
-use rusqlite::named_params;
-use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use jiff::{Span, Timestamp};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rusqlite::types::Type;
+use rusqlite::{named_params, Row, Transaction, TransactionBehavior};
+use uuid::Uuid;
+
+pub const MAX_USES_PER_CODE: i64 = 10;
 
 #[derive(Clone, Debug, Display)]
-pub struct InviteCode(String);
+#[display("{{ code: {code}, created_by: {created_by:?} }}")]
+pub struct InviteCode {
+    pub code: String,
+    pub created_by: Option<Uuid>,
+    pub max_uses: i64,
+    pub used_count: i64,
+    pub expires_at: Option<Timestamp>,
+    pub created_at: Timestamp,
+}
 
 impl InviteCode {
-    pub async fn find(db: &Database, code: impl Into<String>) -> Result<Option<Self>> {
-        let code = code.into();
-        let optional_code = db
+    pub fn new(created_by: Uuid, max_uses: i64, expires_in: Option<Span>) -> Result<Self> {
+        if !(1..=MAX_USES_PER_CODE).contains(&max_uses) {
+            return Err(Error::Parse(format!(
+                "Invite codes must allow between 1 and {MAX_USES_PER_CODE} uses"
+            )));
+        }
+
+        let now = Timestamp::now();
+        let expires_at = expires_in.map(|span| now.checked_add(span)).transpose()?;
+        let mut rng = ChaCha20Rng::from_entropy();
+        Ok(Self {
+            code: format!("{:016x}", rng.gen::<u64>()),
+            created_by: Some(created_by),
+            max_uses,
+            used_count: 0,
+            expires_at,
+            created_at: now,
+        })
+    }
+
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            code: row.get(0)?,
+            created_by: row.get(1)?,
+            max_uses: row.get(2)?,
+            used_count: row.get(3)?,
+            expires_at: row
+                .get::<_, Option<i64>>(4)?
+                .map(Timestamp::from_millisecond)
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(4, Type::Integer, Box::new(e))
+                })?,
+            created_at: Timestamp::from_millisecond(row.get(5)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(5, Type::Integer, Box::new(e))
+            })?,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Timestamp::now())
+    }
+
+    pub fn remaining_uses(&self) -> i64 {
+        if self.is_expired() {
+            0
+        } else {
+            self.max_uses - self.used_count
+        }
+    }
+
+    pub async fn all_for_user_id(db: &Database, user_id: Uuid) -> Result<Vec<Self>> {
+        let invite_codes: Vec<_> = db
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare("SELECT code FROM invite_codes WHERE code = :code;")?;
-                let mut rows = stmt.query(named_params! {":code": code})?;
-                match rows.next()? {
-                    Some(row) => Ok(Some(row.get(0)?)),
-                    None => Ok(None),
-                }
+                let mut statement = conn.prepare(
+                    r"SELECT code, created_by, max_uses, used_count, expires_at, created_at
+                    FROM invite_codes
+                    WHERE created_by = :user_id ORDER BY created_at DESC;",
+                )?;
+                let invite_code_iter =
+                    statement.query_map(named_params! {":user_id": user_id}, Self::from_sql_row)?;
+                Ok(invite_code_iter.collect::<Result<Vec<_>, _>>()?)
             })
             .await?;
+        Ok(invite_codes)
+    }
 
-        Ok(optional_code)
+    // The number of invitations a user has spent out of their allowance. A code spends as many
+    // invitations as it allows uses, except that once it expires, its unused invitations are
+    // returned to the allowance.
+    pub async fn invitations_spent_by_user_id(db: &Database, user_id: Uuid) -> Result<i64> {
+        let now = Timestamp::now();
+        let spent = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let spent = Self::tx_invitations_spent_by_user_id(&tx, user_id, now)?;
+                tx.commit()?;
+                Ok(spent)
+            })
+            .await?;
+        Ok(spent)
     }
 
-    pub async fn delete(self, db: &Database) -> Result<usize> {
-        tracing::info!("deleting invite code: {self}");
+    // Inserts the code only if its uses fit within what's left of its creator's `allowance`. The
+    // allowance is counted and spent in a single transaction, so that concurrent requests can't
+    // both spend the same invitations.
+    pub async fn insert_within_allowance(self, db: &Database, allowance: i64) -> Result<usize> {
+        tracing::info!("inserting invite code {self}");
+        let now = Timestamp::now();
         let result = db
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare("DELETE FROM invite_codes WHERE code = :code;")?;
-                let result = stmt.execute(named_params! {":code": self})?;
-                Ok(result)
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let spent = match self.created_by {
+                    Some(user_id) => Self::tx_invitations_spent_by_user_id(&tx, user_id, now)?,
+                    None => 0,
+                };
+                let remaining = allowance.saturating_sub(spent).max(0);
+                if self.max_uses > remaining {
+                    return Ok(Err(remaining));
+                }
+
+                let result = self.tx_insert(&tx)?;
+                tx.commit()?;
+                Ok(Ok(result))
             })
             .await?;
-        Ok(result)
+
+        result.map_err(|remaining| {
+            Error::Parse(format!("You only have {remaining} invitations remaining"))
+        })
+    }
+
+    fn tx_insert(&self, tx: &Transaction) -> rusqlite::Result<usize> {
+        let mut statement = tx.prepare(
+            r"INSERT INTO invite_codes (code, created_by, max_uses, used_count, expires_at, created_at)
+            VALUES (:code, :created_by, :max_uses, :used_count, :expires_at, :created_at);",
+        )?;
+        statement.execute(named_params! {
+            ":code": self.code,
+            ":created_by": self.created_by,
+            ":max_uses": self.max_uses,
+            ":used_count": self.used_count,
+            ":expires_at": self.expires_at.map(Timestamp::as_millisecond),
+            ":created_at": self.created_at.as_millisecond(),
+        })
     }
-}
 
-impl ToSql for InviteCode {
-    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
-        self.0.to_sql()
+    fn tx_invitations_spent_by_user_id(
+        tx: &Transaction,
+        user_id: Uuid,
+        now: Timestamp,
+    ) -> rusqlite::Result<i64> {
+        let mut stmt = tx.prepare(
+            r"SELECT coalesce(sum(
+                CASE WHEN expires_at IS NOT NULL AND expires_at <= :now THEN used_count
+                ELSE max_uses END
+            ), 0)
+            FROM invite_codes WHERE created_by = :user_id;",
+        )?;
+        stmt.query_row(
+            named_params! {":now": now.as_millisecond(), ":user_id": user_id},
+            |row| row.get(0),
+        )
+    }
+
+    // Spends one use of `code` on behalf of `user_id`, returning false if the code doesn't exist,
+    // has expired, or has no uses left.
+    pub fn tx_redeem(
+        tx: &Transaction,
+        code: &str,
+        user_id: Uuid,
+        now: Timestamp,
+    ) -> rusqlite::Result<bool> {
+        let mut stmt = tx.prepare(
+            r"UPDATE invite_codes SET used_count = used_count + 1
+            WHERE code = :code
+            AND used_count < max_uses
+            AND (expires_at IS NULL OR expires_at > :now);",
+        )?;
+        let updated = stmt.execute(named_params! {":code": code, ":now": now.as_millisecond()})?;
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        let mut stmt = tx.prepare(
+            r"INSERT INTO invite_code_redemptions (code, user_id, created_at)
+            VALUES (:code, :user_id, :created_at);",
+        )?;
+        stmt.execute(named_params! {
+            ":code": code,
+            ":user_id": user_id,
+            ":created_at": now.as_millisecond(),
+        })?;
+        Ok(true)
     }
 }
 
-impl FromSql for InviteCode {
-    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-        String::column_result(value).map(Self)
+#[derive(Clone, Debug, Display)]
+#[display("{{ code: {code}, username: {username} }}")]
+pub struct InviteCodeRedemption {
+    pub code: String,
+    pub username: Username,
+    pub created_at: Timestamp,
+}
+
+impl InviteCodeRedemption {
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            code: row.get(0)?,
+            username: row.get(1)?,
+            created_at: Timestamp::from_millisecond(row.get(2)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(2, Type::Integer, Box::new(e))
+            })?,
+        })
+    }
+
+    // Every redemption of a code minted by `user_id`, i.e. everyone that user has invited
+    pub async fn all_for_creator_id(db: &Database, user_id: Uuid) -> Result<Vec<Self>> {
+        let redemptions: Vec<_> = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"SELECT invite_code_redemptions.code, users.username, invite_code_redemptions.created_at
+                    FROM invite_code_redemptions
+                    JOIN invite_codes ON invite_codes.code = invite_code_redemptions.code
+                    JOIN users ON users.id = invite_code_redemptions.user_id
+                    WHERE invite_codes.created_by = :user_id
+                    ORDER BY invite_code_redemptions.created_at DESC;",
+                )?;
+                let redemption_iter = statement
+                    .query_map(named_params! {":user_id": user_id}, Self::from_sql_row)?;
+                Ok(redemption_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(redemptions)
     }
 }
@@ -1,6 +1,14 @@
+pub mod account_deletion;
 pub mod api_session;
+pub mod avatar;
+pub mod instance_stats;
 pub mod invite_code;
 pub mod paste;
+pub mod paste_batch;
+pub mod paste_query;
 pub mod prelude;
+pub mod security_event;
 pub mod session;
+pub mod throttle;
 pub mod user;
+pub mod webhook;
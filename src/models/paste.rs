@@ -1,27 +1,41 @@
 use crate::db::Database;
+use crate::helpers::conditional_request_helper::{ETag, IfMatch};
 use crate::helpers::pagination_helper::{Direction, HasOrderedId};
 use crate::helpers::syntax_highlight_helper;
 use crate::models::prelude::*;
-use crate::models::user::Username;
+use crate::models::user::{User, Username};
+use crate::models::webhook::{WebhookDelivery, WebhookEvent};
 use derive_more::{AsRef, Display, IsVariant};
 use jiff::Timestamp;
 use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
 use rusqlite::{named_params, Row, Transaction, TransactionBehavior};
 use serde::Serialize;
 use std::str::FromStr;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Display, Serialize)]
+pub const MAX_PINNED_PASTES: usize = 6;
+
+#[derive(Clone, Debug, Display, Serialize, ToSchema)]
 #[display("{{ id: {id}, filename: {filename} }}")]
 pub struct Paste {
     pub id: Uuid,
     pub user_id: Uuid,
+    #[schema(value_type = String, example = "example-paste.txt")]
     pub filename: Filename,
+    #[schema(value_type = String)]
     pub description: Description,
+    #[schema(value_type = String)]
     pub body: Body,
     pub visibility: Visibility,
+    #[schema(value_type = String, format = DateTime)]
     pub created_at: Timestamp,
+    #[schema(value_type = String, format = DateTime)]
     pub updated_at: Timestamp,
+    // Only present on pastes an admin has hidden, which only their owner can still see
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub hidden_at: Option<Timestamp>,
 }
 
 impl Paste {
@@ -42,6 +56,7 @@ impl Paste {
             visibility,
             created_at: now,
             updated_at: now,
+            hidden_at: None,
         })
     }
 
@@ -59,9 +74,49 @@ impl Paste {
             updated_at: Timestamp::from_millisecond(row.get(7)?).map_err(|e| {
                 rusqlite::Error::FromSqlConversionFailure(7, Type::Integer, Box::new(e))
             })?,
+            hidden_at: row
+                .get::<_, Option<i64>>(8)?
+                .map(Timestamp::from_millisecond)
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(8, Type::Integer, Box::new(e))
+                })?,
         })
     }
 
+    pub fn is_hidden(&self) -> bool {
+        self.hidden_at.is_some()
+    }
+
+    // Hidden pastes are only visible to their owner and to admins
+    pub fn is_visible_to(&self, maybe_user: Option<&User>) -> bool {
+        !self.is_hidden() || maybe_user.is_some_and(|user| user.id == self.user_id || user.is_admin)
+    }
+
+    // A strong validator for the paste as it's currently stored. It covers everything that's served
+    // about a paste, including whether an admin has hidden it.
+    pub fn etag(&self) -> ETag {
+        let created_at = self.created_at.as_millisecond().to_be_bytes();
+        let updated_at = self.updated_at.as_millisecond().to_be_bytes();
+        let hidden_at = self
+            .hidden_at
+            .map(|hidden_at| hidden_at.as_millisecond().to_be_bytes());
+        let (filename, description) = (self.filename.to_string(), self.description.to_string());
+        ETag::from_parts(&[
+            self.id.as_bytes(),
+            self.user_id.as_bytes(),
+            filename.as_bytes(),
+            description.as_bytes(),
+            self.body.as_ref().as_bytes(),
+            self.visibility.to_string().as_bytes(),
+            &created_at,
+            &updated_at,
+            hidden_at
+                .as_ref()
+                .map_or(&[][..], |hidden_at| &hidden_at[..]),
+        ])
+    }
+
     pub async fn syntax_highlight(&self, db: &Database) -> Result<Option<String>> {
         Ok(syntax_highlight_helper::generate_with_cache_attempt(
             db,
@@ -72,45 +127,8 @@ impl Paste {
         .await?)
     }
 
-    pub async fn cursor_paginated(
-        db: &Database,
-        limit: usize,
-        direction: Direction,
-        cursor: Option<Uuid>,
-    ) -> Result<Vec<Paste>> {
-        let pastes: Vec<_> = db
-            .conn
-            .call(move |conn| {
-                let direction_sql = direction.to_raw_sql();
-                let cursor_sql = match (cursor, &direction) {
-                    (None, _) => "",
-                    (Some(_), Direction::Ascending) => "AND pastes.id > :cursor",
-                    (Some(_), Direction::Descending) => "AND pastes.id < :cursor",
-                };
-                let raw_sql = format!(
-                    r"SELECT id, user_id, filename, description, body, visibility, created_at, updated_at
-                    FROM pastes
-                    WHERE visibility = 'public' {cursor_sql}
-                    ORDER BY pastes.id {direction_sql}
-                    LIMIT :limit;"
-                );
-                let mut stmt = conn.prepare(&raw_sql)?;
-                match cursor {
-                    None => {
-                        let paste_iter = stmt.query_map(named_params! {":limit": limit}, Paste::from_sql_row)?;
-                        Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
-                    }
-                    Some(cursor) => {
-                        let paste_iter = stmt.query_map(named_params! {":limit": limit, ":cursor": cursor}, Paste::from_sql_row)?;
-                        Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
-                    }
-                }
-            })
-            .await?;
-        Ok(pastes)
-    }
-
-    pub async fn cursor_paginated_with_username(
+    // Every paste, including secret and hidden ones, for moderation by admins
+    pub async fn cursor_paginated_all_with_username(
         db: &Database,
         limit: usize,
         direction: Direction,
@@ -135,9 +153,10 @@ impl Paste {
                       pastes.visibility,
                       pastes.created_at,
                       pastes.updated_at,
+                      pastes.hidden_at,
                       users.username
                     FROM pastes JOIN users ON pastes.user_id = users.id
-                    WHERE pastes.visibility = 'public' {cursor_sql}
+                    WHERE 1 = 1 {cursor_sql}
                     ORDER BY pastes.id {direction_sql}
                     LIMIT :limit;"
                 );
@@ -147,7 +166,7 @@ impl Paste {
                         let paste_iter =
                             stmt.query_map(named_params! {":limit": limit}, |row| {
                                 let paste_result = Paste::from_sql_row(row)?;
-                                let username: Username = row.get(8)?;
+                                let username: Username = row.get(9)?;
                                 Ok((paste_result, username))
                             })?;
                         Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
@@ -157,7 +176,7 @@ impl Paste {
                             named_params! {":cursor": cursor, ":limit": limit},
                             |row| {
                                 let paste_result = Paste::from_sql_row(row)?;
-                                let username: Username = row.get(8)?;
+                                let username: Username = row.get(9)?;
                                 Ok((paste_result, username))
                             },
                         )?;
@@ -170,84 +189,6 @@ impl Paste {
         Ok(pairs)
     }
 
-    pub async fn cursor_paginated_for_user_id(
-        db: &Database,
-        user_id: Uuid,
-        limit: usize,
-        direction: Direction,
-        cursor: Option<Uuid>,
-    ) -> Result<Vec<Paste>> {
-        let pastes: Vec<_> = db
-            .conn
-            .call(move |conn| {
-                let direction_sql = direction.to_raw_sql();
-                let cursor_sql = match (cursor, &direction) {
-                    (None, _) => "",
-                    (Some(_), Direction::Ascending) => "AND pastes.id > :cursor",
-                    (Some(_), Direction::Descending) => "AND pastes.id < :cursor",
-                };
-                let raw_sql = format!(
-                    r"SELECT id, user_id, filename, description, body, visibility, created_at, updated_at
-                    FROM pastes
-                    WHERE user_id = :user_id AND visibility = 'public' {cursor_sql}
-                    ORDER BY pastes.id {direction_sql}
-                    LIMIT :limit;"
-                );
-                let mut stmt = conn.prepare(&raw_sql)?;
-                match cursor {
-                    None => {
-                        let paste_iter = stmt.query_map(named_params! {":user_id": user_id, ":limit": limit}, Paste::from_sql_row)?;
-                        Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
-                    }
-                    Some(cursor) => {
-                        let paste_iter = stmt.query_map(named_params! {":user_id": user_id, ":limit": limit, ":cursor": cursor}, Paste::from_sql_row)?;
-                        Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
-                    }
-                }
-            })
-            .await?;
-        Ok(pastes)
-    }
-
-    pub async fn cursor_paginated_for_user_id_with_secrets(
-        db: &Database,
-        user_id: Uuid,
-        limit: usize,
-        direction: Direction,
-        cursor: Option<Uuid>,
-    ) -> Result<Vec<Paste>> {
-        let pastes: Vec<_> = db
-            .conn
-            .call(move |conn| {
-                let direction_sql = direction.to_raw_sql();
-                let cursor_sql = match (cursor, &direction) {
-                    (None, _) => "",
-                    (Some(_), Direction::Ascending) => "AND pastes.id > :cursor",
-                    (Some(_), Direction::Descending) => "AND pastes.id < :cursor",
-                };
-                let raw_sql = format!(
-                    r"SELECT id, user_id, filename, description, body, visibility, created_at, updated_at
-                    FROM pastes
-                    WHERE user_id = :user_id {cursor_sql}
-                    ORDER BY pastes.id {direction_sql}
-                    LIMIT :limit;"
-                );
-                let mut stmt = conn.prepare(&raw_sql)?;
-                match cursor {
-                    None => {
-                        let paste_iter = stmt.query_map(named_params! {":user_id": user_id, ":limit": limit}, Paste::from_sql_row)?;
-                        Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
-                    }
-                    Some(cursor) => {
-                        let paste_iter = stmt.query_map(named_params! {":user_id": user_id, ":limit": limit, ":cursor": cursor}, Paste::from_sql_row)?;
-                        Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
-                    }
-                }
-            })
-            .await?;
-        Ok(pastes)
-    }
-
     pub async fn insert(self, db: &Database) -> Result<()> {
         tracing::info!("inserting paste {self}");
         let optional_html =
@@ -256,27 +197,7 @@ impl Paste {
         db.conn
             .call(move |conn| {
                 let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
-                {
-                    let mut stmt = tx.prepare(
-                        "INSERT INTO pastes VALUES (:id, :user_id, :filename, :description, :body, :visibility, :created_at, :updated_at);"
-                    )?;
-                    stmt.execute(
-                        named_params! {
-                            ":id": self.id,
-                            ":user_id": self.user_id,
-                            ":filename": self.filename,
-                            ":description": self.description,
-                            ":body": self.body,
-                            ":visibility": self.visibility,
-                            ":created_at": self.created_at.as_millisecond(),
-                            ":updated_at": self.updated_at.as_millisecond(),
-                        }
-                    )?;
-
-                    if let Some(html) = optional_html {
-                        syntax_highlight_helper::tx_cache_set(&tx, &self.id, &html)?;
-                    }
-                }
+                self.tx_insert(&tx, optional_html.as_deref())?;
                 tx.commit()?;
 
                 Ok(())
@@ -291,7 +212,7 @@ impl Paste {
             .conn
             .call(move |conn| {
                 let mut stmt = conn
-                    .prepare("SELECT id, user_id, filename, description, body, visibility, created_at, updated_at FROM pastes WHERE id = :id;")?;
+                    .prepare("SELECT id, user_id, filename, description, body, visibility, created_at, updated_at, hidden_at FROM pastes WHERE id = :id;")?;
                 let mut rows = stmt.query(named_params! {":id": id})?;
                 match rows.next()? {
                     Some(row) => Ok(Some(
@@ -306,7 +227,7 @@ impl Paste {
     }
 
     pub fn tx_find(tx: &Transaction, id: &Uuid) -> tokio_rusqlite::Result<Option<Paste>> {
-        let mut stmt = tx.prepare("SELECT id, user_id, filename, description, body, visibility, created_at, updated_at FROM pastes WHERE id = :id;")?;
+        let mut stmt = tx.prepare("SELECT id, user_id, filename, description, body, visibility, created_at, updated_at, hidden_at FROM pastes WHERE id = :id;")?;
         let mut rows = stmt.query(named_params! {":id": id})?;
         match rows.next()? {
             Some(row) => Ok(Some(Paste::from_sql_row(row)?)),
@@ -314,6 +235,60 @@ impl Paste {
         }
     }
 
+    pub fn tx_insert(&self, tx: &Transaction, html: Option<&str>) -> tokio_rusqlite::Result<()> {
+        let mut stmt = tx.prepare(
+            r"INSERT INTO pastes (id, user_id, filename, description, body, visibility, created_at, updated_at, hidden_at)
+            VALUES (:id, :user_id, :filename, :description, :body, :visibility, :created_at, :updated_at, :hidden_at);"
+        )?;
+        stmt.execute(named_params! {
+            ":id": self.id,
+            ":user_id": self.user_id,
+            ":filename": self.filename,
+            ":description": self.description,
+            ":body": self.body,
+            ":visibility": self.visibility,
+            ":created_at": self.created_at.as_millisecond(),
+            ":updated_at": self.updated_at.as_millisecond(),
+            ":hidden_at": self.hidden_at.map(Timestamp::as_millisecond),
+        })?;
+        if let Some(html) = html {
+            syntax_highlight_helper::tx_cache_set(tx, &self.id, html)?;
+        }
+        WebhookDelivery::tx_enqueue(tx, WebhookEvent::PasteCreated, self)?;
+        Ok(())
+    }
+
+    // Writes the paste's editable fields. Keeping the syntax highlighting cache in step is up to
+    // the caller.
+    pub fn tx_save(&self, tx: &Transaction) -> tokio_rusqlite::Result<()> {
+        let mut stmt = tx.prepare(
+            r"UPDATE pastes
+            SET filename = :filename, description = :desc, body = :body, visibility = :visibility, updated_at = :updated_at
+            WHERE id = :id;",
+        )?;
+        stmt.execute(named_params! {
+            ":filename": self.filename,
+            ":desc": self.description,
+            ":body": self.body,
+            ":visibility": self.visibility,
+            ":updated_at": self.updated_at.as_millisecond(),
+            ":id": self.id,
+        })?;
+        WebhookDelivery::tx_enqueue(tx, WebhookEvent::PasteUpdated, self)?;
+        Ok(())
+    }
+
+    pub fn tx_delete(&self, tx: &Transaction) -> tokio_rusqlite::Result<usize> {
+        let result = tx.execute(
+            "DELETE FROM pastes WHERE id = :id;",
+            named_params! {":id": self.id},
+        )?;
+        if result > 0 {
+            WebhookDelivery::tx_enqueue(tx, WebhookEvent::PasteDeleted, self)?;
+        }
+        Ok(result)
+    }
+
     pub async fn find_scoped_by_user_id(
         db: &Database,
         id: Uuid,
@@ -323,7 +298,7 @@ impl Paste {
             .conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    r"SELECT id, user_id, filename, description, body, visibility, created_at, updated_at
+                    r"SELECT id, user_id, filename, description, body, visibility, created_at, updated_at, hidden_at
                     FROM pastes
                     WHERE id = :id AND user_id = :user_id;",
                 )?;
@@ -340,14 +315,18 @@ impl Paste {
         Ok(optional_paste)
     }
 
+    // Applies the changes, provided that the paste still matches `if_match`, which is checked in
+    // the same transaction as the write so that concurrent edits can't slip in between. Returns
+    // the paste as it is after the update.
     pub async fn update(
         mut self,
         db: &Database,
+        if_match: &IfMatch,
         filename: Option<Filename>,
         description: Option<Description>,
         body: Option<Body>,
         visibility: Option<Visibility>,
-    ) -> Result<()> {
+    ) -> Result<Paste> {
         tracing::info!("updating paste {self}");
         let original_filename = self.filename.clone();
         let original_body = self.body.clone();
@@ -364,6 +343,7 @@ impl Paste {
         if let Some(visibility) = visibility {
             self.visibility = visibility;
         }
+        self.updated_at = Timestamp::now();
 
         let mut maybe_html: Option<String> = None;
         let body_changed = original_body != self.body;
@@ -373,55 +353,207 @@ impl Paste {
                 syntax_highlight_helper::generate(self.body.as_ref(), self.filename.extension());
         }
 
-        db.conn.call(move |conn| {
-            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
-            {
-                let mut pastes_stmt = tx.prepare(
-                    r"UPDATE pastes
-                    SET filename = :filename, description = :desc, body = :body, visibility = :visibility, updated_at = :updated_at
-                    WHERE id = :id;"
-                )?;
-                pastes_stmt.execute(named_params! {
-                    ":filename": self.filename,
-                    ":desc": self.description,
-                    ":body": self.body,
-                    ":visibility": self.visibility,
-                    ":updated_at": Timestamp::now().as_millisecond(),
-                    ":id": self.id,
-                })?;
-
-                if body_changed || extension_changed {
-                    if let Some(html) = maybe_html {
-                        syntax_highlight_helper::tx_cache_set(&tx, &self.id, &html)?;
-                    } else {
-                        syntax_highlight_helper::tx_cache_expire(&tx, &self.id)?;
+        let if_match = if_match.clone();
+        let updated = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                {
+                    if !tx_precondition_met(&tx, &self.id, &if_match)? {
+                        return Ok(None);
+                    }
+
+                    self.tx_save(&tx)?;
+
+                    if body_changed || extension_changed {
+                        if let Some(html) = maybe_html {
+                            syntax_highlight_helper::tx_cache_set(&tx, &self.id, &html)?;
+                        } else {
+                            syntax_highlight_helper::tx_cache_expire(&tx, &self.id)?;
+                        }
                     }
                 }
-            }
-            tx.commit()?;
-            Ok(())
-        }).await?;
-        Ok(())
+                tx.commit()?;
+                Ok(Some(self))
+            })
+            .await?;
+        updated.ok_or(Error::PreconditionFailed)
+    }
+
+    pub async fn hide(&self, db: &Database) -> Result<usize> {
+        tracing::info!("hiding paste {self}");
+        let id = self.id;
+        let now = Timestamp::now();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "UPDATE pastes SET hidden_at = :hidden_at WHERE id = :id AND hidden_at IS NULL;",
+                )?;
+                let result =
+                    stmt.execute(named_params! {":hidden_at": now.as_millisecond(), ":id": id})?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn unhide(&self, db: &Database) -> Result<usize> {
+        tracing::info!("unhiding paste {self}");
+        let id = self.id;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("UPDATE pastes SET hidden_at = NULL WHERE id = :id;")?;
+                let result = stmt.execute(named_params! {":id": id})?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
     }
 
-    pub async fn delete(self, db: &Database) -> Result<usize> {
+    // Deletes the paste, provided that it still matches `if_match`
+    pub async fn delete(self, db: &Database, if_match: &IfMatch) -> Result<usize> {
         tracing::info!("deleting paste {self}");
+        let if_match = if_match.clone();
         let result = db
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare("DELETE FROM pastes WHERE id = :id;")?;
-                let result = stmt.execute(named_params! {":id": self.id})?;
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                if !tx_precondition_met(&tx, &self.id, &if_match)? {
+                    return Ok(None);
+                }
+                let result = self.tx_delete(&tx)?;
+                tx.commit()?;
+                Ok(Some(result))
+            })
+            .await?;
+        result.ok_or(Error::PreconditionFailed)
+    }
+
+    // Pinned pastes are shown at the top of their owner's profile, in the order they were pinned.
+    // Hidden pastes stay pinned, but are only listed when `include_hidden` is set.
+    pub async fn pinned_for_user_id(
+        db: &Database,
+        user_id: Uuid,
+        include_hidden: bool,
+    ) -> Result<Vec<Paste>> {
+        let pastes: Vec<_> = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"SELECT
+                      pastes.id,
+                      pastes.user_id,
+                      pastes.filename,
+                      pastes.description,
+                      pastes.body,
+                      pastes.visibility,
+                      pastes.created_at,
+                      pastes.updated_at,
+                      pastes.hidden_at
+                    FROM pinned_pastes JOIN pastes ON pinned_pastes.paste_id = pastes.id
+                    WHERE pinned_pastes.user_id = :user_id
+                      AND pastes.visibility = 'public'
+                      AND (:include_hidden OR pastes.hidden_at IS NULL)
+                    ORDER BY pinned_pastes.created_at ASC;",
+                )?;
+                let paste_iter = stmt.query_map(
+                    named_params! {":user_id": user_id, ":include_hidden": include_hidden},
+                    Paste::from_sql_row,
+                )?;
+                Ok(paste_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(pastes)
+    }
+
+    pub async fn is_pinned(&self, db: &Database) -> Result<bool> {
+        let id = self.id;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare("SELECT EXISTS(SELECT 1 FROM pinned_pastes WHERE paste_id = :id);")?;
+                let result = stmt.query_row(named_params! {":id": id}, |row| row.get(0))?;
                 Ok(result)
             })
             .await?;
         Ok(result)
     }
-}
 
-impl HasOrderedId for Paste {
-    fn ordered_id(&self) -> Uuid {
-        self.id
+    // Counting the owner's pins and adding this one happen in a single transaction, so that
+    // concurrent requests can't pin more than `MAX_PINNED_PASTES`. Returns false, having pinned
+    // nothing, when the owner is already at the limit. Pinning an already pinned paste is a no-op.
+    pub async fn pin(&self, db: &Database) -> Result<bool> {
+        tracing::info!("pinning paste {self}");
+        let (id, user_id) = (self.id, self.user_id);
+        let now = Timestamp::now();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let result = {
+                    let mut count_stmt = tx.prepare(
+                        r"SELECT
+                          COUNT(*),
+                          EXISTS(SELECT 1 FROM pinned_pastes WHERE paste_id = :id)
+                        FROM pinned_pastes WHERE user_id = :user_id;",
+                    )?;
+                    let (count, already_pinned): (usize, bool) = count_stmt
+                        .query_row(named_params! {":id": id, ":user_id": user_id}, |row| {
+                            Ok((row.get(0)?, row.get(1)?))
+                        })?;
+                    if already_pinned {
+                        true
+                    } else if count >= MAX_PINNED_PASTES {
+                        false
+                    } else {
+                        let mut insert_stmt = tx.prepare(
+                            r"INSERT INTO pinned_pastes (paste_id, user_id, created_at)
+                            VALUES (:paste_id, :user_id, :created_at);",
+                        )?;
+                        insert_stmt.execute(named_params! {
+                            ":paste_id": id,
+                            ":user_id": user_id,
+                            ":created_at": now.as_millisecond(),
+                        })?;
+                        true
+                    }
+                };
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
     }
+
+    pub async fn unpin(&self, db: &Database) -> Result<usize> {
+        tracing::info!("unpinning paste {self}");
+        let id = self.id;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("DELETE FROM pinned_pastes WHERE paste_id = :id;")?;
+                let result = stmt.execute(named_params! {":id": id})?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+}
+
+// A paste that has been deleted in the meantime only matches a change that was unconditional
+fn tx_precondition_met(
+    tx: &Transaction,
+    id: &Uuid,
+    if_match: &IfMatch,
+) -> tokio_rusqlite::Result<bool> {
+    Ok(match Paste::tx_find(tx, id)? {
+        Some(current) => if_match.matches(&current.etag()),
+        None => *if_match == IfMatch::Any,
+    })
 }
 
 #[derive(Clone, Debug, Display, Serialize)]
@@ -444,26 +576,20 @@ impl Filename {
 }
 
 impl FromStr for Filename {
-    type Err = Error;
+    type Err = PasteFieldError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.trim().is_empty() {
-            Err(Error::Parse("Filename may not be blank".into()))
+            Err(PasteFieldError::FilenameBlank)
         } else if s.chars().count() > 256 {
-            Err(Error::Parse(
-                "Filename may not be longer than 256 characters".into(),
-            ))
+            Err(PasteFieldError::FilenameTooLong)
         } else if s
             .chars()
             .any(|c| ['<', '>', ':', '"', '/', '\\', '|', '?', '*'].contains(&c))
         {
-            Err(Error::Parse(
-                "Filename may not contain the following characters: < > : \" / \\ | ? *".into(),
-            ))
+            Err(PasteFieldError::FilenameInvalidCharacters)
         } else if s.ends_with('.') {
-            Err(Error::Parse(
-                "Filename may not end with a '.' character".into(),
-            ))
+            Err(PasteFieldError::FilenameTrailingPeriod)
         } else {
             Ok(Self(s.to_string()))
         }
@@ -474,7 +600,7 @@ impl TryFrom<&String> for Filename {
     type Error = Error;
 
     fn try_from(value: &String) -> Result<Self, Self::Error> {
-        value.parse()
+        Ok(value.parse()?)
     }
 }
 
@@ -510,13 +636,11 @@ impl Description {
 }
 
 impl FromStr for Description {
-    type Err = Error;
+    type Err = PasteFieldError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.chars().count() > 256 {
-            Err(Error::Parse(
-                "Description may not be longer than 256 characters".into(),
-            ))
+            Err(PasteFieldError::DescriptionTooLong)
         } else {
             Ok(Self(s.to_string()))
         }
@@ -527,7 +651,7 @@ impl TryFrom<&String> for Description {
     type Error = Error;
 
     fn try_from(value: &String) -> Result<Self, Self::Error> {
-        value.parse()
+        Ok(value.parse()?)
     }
 }
 
@@ -555,11 +679,11 @@ impl Body {
 }
 
 impl FromStr for Body {
-    type Err = Error;
+    type Err = PasteFieldError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.trim().is_empty() {
-            Err(Error::Parse("Body may not be blank".into()))
+            Err(PasteFieldError::BodyBlank)
         } else {
             Ok(Self(s.to_string()))
         }
@@ -570,7 +694,7 @@ impl TryFrom<&String> for Body {
     type Error = Error;
 
     fn try_from(value: &String) -> Result<Self, Self::Error> {
-        value.parse()
+        Ok(value.parse()?)
     }
 }
 
@@ -586,7 +710,7 @@ impl FromSql for Body {
     }
 }
 
-#[derive(Clone, Copy, Debug, IsVariant, Serialize)]
+#[derive(Clone, Copy, Debug, IsVariant, Serialize, ToSchema)]
 pub enum Visibility {
     #[serde(rename = "public")]
     Public,
@@ -594,16 +718,27 @@ pub enum Visibility {
     Secret,
 }
 
+impl Visibility {
+    // Once a paste is public, we don't let people update it back to secret because the paste could
+    // have been indexed (or otherwise seen/recorded by someone) and we don't want to give the
+    // impression that we can somehow undo the paste's public disclosure
+    pub fn check_change_to(self, new: Visibility) -> Result<(), PasteFieldError> {
+        if self.is_public() && new.is_secret() {
+            Err(PasteFieldError::VisibilityCannotBecomeSecret)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 impl FromStr for Visibility {
-    type Err = Error;
+    type Err = PasteFieldError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "public" => Ok(Self::Public),
             "secret" => Ok(Self::Secret),
-            _ => Err(Error::Parse(
-                "Unrecognized value for visibility. Valid values are 'public' or 'secret'".into(),
-            )),
+            _ => Err(PasteFieldError::VisibilityUnrecognized),
         }
     }
 }
@@ -612,7 +747,7 @@ impl TryFrom<&String> for Visibility {
     type Error = Error;
 
     fn try_from(value: &String) -> Result<Self, Self::Error> {
-        value.parse()
+        Ok(value.parse()?)
     }
 }
 
@@ -646,6 +781,55 @@ impl FromSql for Visibility {
     }
 }
 
+// The rules that a paste's fields are validated against. Each has a stable code that the JSON API
+// reports with its message, so that clients can tell which rule was broken without parsing the
+// message.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+pub enum PasteFieldError {
+    #[error("Filename may not be blank")]
+    FilenameBlank,
+
+    #[error("Filename may not be longer than 256 characters")]
+    FilenameTooLong,
+
+    #[error("Filename may not contain the following characters: < > : \" / \\ | ? *")]
+    FilenameInvalidCharacters,
+
+    #[error("Filename may not end with a '.' character")]
+    FilenameTrailingPeriod,
+
+    #[error("Description may not be longer than 256 characters")]
+    DescriptionTooLong,
+
+    #[error("Body may not be blank")]
+    BodyBlank,
+
+    #[error("Unrecognized value for visibility. Valid values are 'public' or 'secret'")]
+    VisibilityUnrecognized,
+
+    #[error("Cannot change from public to secret visibility")]
+    VisibilityCannotBecomeSecret,
+}
+
+impl PasteFieldError {
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::FilenameBlank | Self::BodyBlank => "blank",
+            Self::FilenameTooLong | Self::DescriptionTooLong => "too_long",
+            Self::FilenameInvalidCharacters => "invalid_characters",
+            Self::FilenameTrailingPeriod => "trailing_period",
+            Self::VisibilityUnrecognized => "unrecognized_value",
+            Self::VisibilityCannotBecomeSecret => "cannot_become_secret",
+        }
+    }
+}
+
+impl From<PasteFieldError> for Error {
+    fn from(error: PasteFieldError) -> Self {
+        Self::Parse(error.to_string())
+    }
+}
+
 impl HasOrderedId for (Paste, Username) {
     fn ordered_id(&self) -> Uuid {
         self.0.id
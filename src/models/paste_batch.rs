@@ -0,0 +1,193 @@
+use crate::db::Database;
+use crate::helpers::conditional_request_helper::IfMatch;
+use crate::helpers::syntax_highlight_helper;
+use crate::models::paste::{Body, Description, Filename, Paste, PasteFieldError, Visibility};
+use crate::models::prelude::*;
+use jiff::Timestamp;
+use rusqlite::{Transaction, TransactionBehavior};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const MAX_BATCH_OPERATIONS: usize = 100;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    // Either every operation is applied or none of them are
+    #[default]
+    AllOrNothing,
+    // Operations that can be applied are, whatever happens to the others
+    PerItem,
+}
+
+#[derive(Clone, Debug)]
+pub enum PasteOperation {
+    Create(Paste),
+    Update {
+        id: Uuid,
+        if_match: IfMatch,
+        filename: Option<Filename>,
+        description: Option<Description>,
+        body: Option<Body>,
+        visibility: Option<Visibility>,
+    },
+    Delete {
+        id: Uuid,
+        if_match: IfMatch,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub enum PasteOperationOutcome {
+    Created(Paste),
+    Updated(Paste),
+    Deleted(Uuid),
+    NotFound,
+    Forbidden,
+    PreconditionFailed,
+    Invalid(PasteFieldError),
+    // The operation could have been applied, but was undone because another one in an
+    // all-or-nothing batch failed
+    RolledBack,
+}
+
+impl PasteOperationOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Created(_) | Self::Updated(_) | Self::Deleted(_))
+    }
+}
+
+// Applies operations on behalf of a user in order, in a single transaction, so later operations
+// see the effects of earlier ones. Every check an operation has to pass is made before it writes
+// anything, so a failed operation never leaves a partial change behind.
+pub async fn run(
+    db: &Database,
+    user_id: Uuid,
+    mode: BatchMode,
+    operations: Vec<PasteOperation>,
+) -> Result<Vec<PasteOperationOutcome>> {
+    tracing::info!("running a batch of {} paste operations", operations.len());
+    // Highlighting is slow, so it's done on the blocking thread pool before taking the database
+    // connection
+    let operations: Vec<_> = tokio::task::spawn_blocking(move || {
+        operations
+            .into_iter()
+            .map(|operation| {
+                let html = match &operation {
+                    PasteOperation::Create(paste) => syntax_highlight_helper::generate(
+                        paste.body.as_ref(),
+                        paste.filename.extension(),
+                    ),
+                    _ => None,
+                };
+                (operation, html)
+            })
+            .collect()
+    })
+    .await?;
+
+    let outcomes = db
+        .conn
+        .call(move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut outcomes = Vec::with_capacity(operations.len());
+            for (operation, html) in operations {
+                outcomes.push(tx_apply(&tx, user_id, operation, html.as_deref())?);
+            }
+
+            if mode == BatchMode::AllOrNothing
+                && !outcomes.iter().all(PasteOperationOutcome::is_success)
+            {
+                tx.rollback()?;
+                for outcome in &mut outcomes {
+                    if outcome.is_success() {
+                        *outcome = PasteOperationOutcome::RolledBack;
+                    }
+                }
+            } else {
+                tx.commit()?;
+            }
+            Ok(outcomes)
+        })
+        .await?;
+    Ok(outcomes)
+}
+
+fn tx_apply(
+    tx: &Transaction,
+    user_id: Uuid,
+    operation: PasteOperation,
+    html: Option<&str>,
+) -> tokio_rusqlite::Result<PasteOperationOutcome> {
+    match operation {
+        PasteOperation::Create(paste) => {
+            paste.tx_insert(tx, html)?;
+            Ok(PasteOperationOutcome::Created(paste))
+        }
+        PasteOperation::Update {
+            id,
+            if_match,
+            filename,
+            description,
+            body,
+            visibility,
+        } => {
+            let mut paste = match tx_find_owned(tx, user_id, &id, &if_match)? {
+                Ok(paste) => paste,
+                Err(outcome) => return Ok(outcome),
+            };
+            if let Some(visibility) = visibility {
+                if let Err(err) = paste.visibility.check_change_to(visibility) {
+                    return Ok(PasteOperationOutcome::Invalid(err));
+                }
+                paste.visibility = visibility;
+            }
+
+            let original_extension = paste.filename.extension().map(str::to_string);
+            let body_changed = body.as_ref().is_some_and(|body| *body != paste.body);
+            if let Some(filename) = filename {
+                paste.filename = filename;
+            }
+            if let Some(description) = description {
+                paste.description = description;
+            }
+            if let Some(body) = body {
+                paste.body = body;
+            }
+            paste.updated_at = Timestamp::now();
+            paste.tx_save(tx)?;
+
+            // The highlighting is regenerated the next time the paste is viewed
+            if body_changed || original_extension.as_deref() != paste.filename.extension() {
+                syntax_highlight_helper::tx_cache_expire(tx, &paste.id)?;
+            }
+            Ok(PasteOperationOutcome::Updated(paste))
+        }
+        PasteOperation::Delete { id, if_match } => {
+            let paste = match tx_find_owned(tx, user_id, &id, &if_match)? {
+                Ok(paste) => paste,
+                Err(outcome) => return Ok(outcome),
+            };
+            paste.tx_delete(tx)?;
+            Ok(PasteOperationOutcome::Deleted(paste.id))
+        }
+    }
+}
+
+// The paste an operation applies to, or the outcome of an operation that can't be applied to it
+fn tx_find_owned(
+    tx: &Transaction,
+    user_id: Uuid,
+    id: &Uuid,
+    if_match: &IfMatch,
+) -> tokio_rusqlite::Result<Result<Paste, PasteOperationOutcome>> {
+    Ok(match Paste::tx_find(tx, id)? {
+        None => Err(PasteOperationOutcome::NotFound),
+        Some(paste) if paste.user_id != user_id => Err(PasteOperationOutcome::Forbidden),
+        Some(paste) if !if_match.matches(&paste.etag()) => {
+            Err(PasteOperationOutcome::PreconditionFailed)
+        }
+        Some(paste) => Ok(paste),
+    })
+}
@@ -0,0 +1,518 @@
+use crate::db::Database;
+use crate::helpers::pagination_helper::Direction;
+use crate::helpers::syntax_highlight_helper;
+use crate::models::paste::{Paste, Visibility};
+use crate::models::prelude::*;
+use crate::models::user::Username;
+use jiff::civil::Date;
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use rusqlite::ToSql;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+type NamedParam = (String, Box<dyn ToSql>);
+
+// A listing of pastes, narrowed down by any combination of filters. Every listing starts out as
+// what the viewer is allowed to see, so filters can only ever remove pastes from it.
+#[derive(Clone, Debug, Default)]
+pub struct PasteQuery {
+    include_secret_and_hidden: bool,
+    user_id: Option<Uuid>,
+    owner: Option<Username>,
+    visibility: Option<Visibility>,
+    // Each entry is a set of alternatives, so that a language and an extension can both apply
+    extensions: Vec<Vec<String>>,
+    filename: Option<String>,
+    created_after: Option<Timestamp>,
+    created_before: Option<Timestamp>,
+    updated_after: Option<Timestamp>,
+    updated_before: Option<Timestamp>,
+    sort: PasteSort,
+}
+
+impl PasteQuery {
+    // Public pastes that haven't been hidden by an admin
+    pub fn public() -> Self {
+        Self::default()
+    }
+
+    // Public pastes of a single user, that haven't been hidden by an admin
+    pub fn public_for_user_id(user_id: Uuid) -> Self {
+        Self {
+            user_id: Some(user_id),
+            ..Self::default()
+        }
+    }
+
+    // Every paste of a single user, including secret and hidden ones, for the user themselves
+    pub fn all_for_user_id(user_id: Uuid) -> Self {
+        Self {
+            include_secret_and_hidden: true,
+            user_id: Some(user_id),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_owner(mut self, owner: Username) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    #[must_use]
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    // Only pastes whose filename ends in one of the extensions
+    #[must_use]
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions.push(extensions);
+        self
+    }
+
+    #[must_use]
+    pub fn with_filename_containing(mut self, filename: &str) -> Self {
+        self.filename = Some(filename.to_string());
+        self
+    }
+
+    // Either bound may be left open. `after` is inclusive and `before` is exclusive.
+    #[must_use]
+    pub fn with_created_between(
+        mut self,
+        after: Option<Timestamp>,
+        before: Option<Timestamp>,
+    ) -> Self {
+        self.created_after = after;
+        self.created_before = before;
+        self
+    }
+
+    // Either bound may be left open. `after` is inclusive and `before` is exclusive.
+    #[must_use]
+    pub fn with_updated_between(
+        mut self,
+        after: Option<Timestamp>,
+        before: Option<Timestamp>,
+    ) -> Self {
+        self.updated_after = after;
+        self.updated_before = before;
+        self
+    }
+
+    #[must_use]
+    pub fn with_sort(mut self, sort: PasteSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn sort(&self) -> PasteSort {
+        self.sort
+    }
+
+    // Applies the filters from a query string on top of this query
+    pub fn filtered(mut self, params: &PasteFilterParams) -> Result<Self> {
+        if let Some(owner) = non_empty(params.owner.as_ref()) {
+            self = self.with_owner(owner.parse()?);
+        }
+        if let Some(visibility) = non_empty(params.visibility.as_ref()) {
+            self = self.with_visibility(visibility.parse()?);
+        }
+        if let Some(language) = non_empty(params.language.as_ref()) {
+            let extensions = syntax_highlight_helper::extensions_for_language(language)
+                .ok_or_else(|| Error::Parse(format!("Unrecognized language '{language}'")))?;
+            self = self.with_extensions(extensions);
+        }
+        if let Some(extension) = non_empty(params.extension.as_ref()) {
+            self = self.with_extensions(vec![extension.trim_start_matches('.').to_string()]);
+        }
+        if let Some(filename) = non_empty(params.filename.as_ref()) {
+            self = self.with_filename_containing(filename);
+        }
+        self = self.with_created_between(
+            parse_date_bound("Created after", params.created_after.as_ref())?,
+            parse_date_bound("Created before", params.created_before.as_ref())?,
+        );
+        self = self.with_updated_between(
+            parse_date_bound("Updated after", params.updated_after.as_ref())?,
+            parse_date_bound("Updated before", params.updated_before.as_ref())?,
+        );
+        if let Some(sort) = non_empty(params.sort.as_ref()) {
+            self = self.with_sort(sort.parse()?);
+        }
+        Ok(self)
+    }
+
+    pub async fn cursor_paginated(
+        self,
+        db: &Database,
+        limit: usize,
+        direction: Direction,
+        cursor: Option<PasteCursor>,
+    ) -> Result<Vec<Paste>> {
+        let pairs = self
+            .cursor_paginated_with_username(db, limit, direction, cursor)
+            .await?;
+        Ok(pairs.into_iter().map(|(paste, _)| paste).collect())
+    }
+
+    pub async fn cursor_paginated_with_username(
+        self,
+        db: &Database,
+        limit: usize,
+        direction: Direction,
+        cursor: Option<PasteCursor>,
+    ) -> Result<Vec<(Paste, Username)>> {
+        // A cursor is only meaningful in the order it was taken from
+        if cursor.is_some_and(|cursor| cursor.sort != self.sort) {
+            return Err(Error::Parse(
+                "The cursor belongs to a listing with a different sort order".into(),
+            ));
+        }
+
+        let pairs: Vec<_> = db
+            .conn
+            .call(move |conn| {
+                let (mut conditions, mut params) = self.to_sql_conditions();
+                let column = self.sort.column();
+                let direction_sql = direction.to_raw_sql();
+                if let Some(cursor) = cursor {
+                    let operator = match direction {
+                        Direction::Ascending => ">",
+                        Direction::Descending => "<",
+                    };
+                    conditions.push(format!(
+                        "({column}, pastes.id) {operator} (:cursor_key, :cursor_id)"
+                    ));
+                    params.push((":cursor_key".into(), Box::new(cursor.key.as_millisecond())));
+                    params.push((":cursor_id".into(), Box::new(cursor.id)));
+                }
+                params.push((":limit".into(), Box::new(limit)));
+                let where_sql = if conditions.is_empty() {
+                    "1 = 1".to_string()
+                } else {
+                    conditions.join(" AND ")
+                };
+                let raw_sql = format!(
+                    r"SELECT
+                      pastes.id,
+                      pastes.user_id,
+                      pastes.filename,
+                      pastes.description,
+                      pastes.body,
+                      pastes.visibility,
+                      pastes.created_at,
+                      pastes.updated_at,
+                      pastes.hidden_at,
+                      users.username
+                    FROM pastes JOIN users ON pastes.user_id = users.id
+                    WHERE {where_sql}
+                    ORDER BY {column} {direction_sql}, pastes.id {direction_sql}
+                    LIMIT :limit;"
+                );
+                let mut stmt = conn.prepare(&raw_sql)?;
+                let params: Vec<(&str, &dyn ToSql)> = params
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_ref()))
+                    .collect();
+                let pair_iter = stmt.query_map(params.as_slice(), |row| {
+                    let paste = Paste::from_sql_row(row)?;
+                    let username: Username = row.get(9)?;
+                    Ok((paste, username))
+                })?;
+                Ok(pair_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+
+        Ok(pairs)
+    }
+
+    fn to_sql_conditions(&self) -> (Vec<String>, Vec<NamedParam>) {
+        let mut conditions = Vec::new();
+        let mut params: Vec<NamedParam> = Vec::new();
+
+        if !self.include_secret_and_hidden {
+            conditions.push("pastes.visibility = 'public'".to_string());
+            conditions.push("pastes.hidden_at IS NULL".to_string());
+        }
+        if let Some(user_id) = self.user_id {
+            conditions.push("pastes.user_id = :user_id".to_string());
+            params.push((":user_id".into(), Box::new(user_id)));
+        }
+        if let Some(owner) = &self.owner {
+            conditions.push("users.username = :owner".to_string());
+            params.push((":owner".into(), Box::new(owner.to_string())));
+        }
+        if let Some(visibility) = self.visibility {
+            conditions.push("pastes.visibility = :visibility".to_string());
+            params.push((":visibility".into(), Box::new(visibility.to_string())));
+        }
+        for (i, alternatives) in self.extensions.iter().enumerate() {
+            let mut matches = Vec::new();
+            for (j, extension) in alternatives.iter().enumerate() {
+                let name = format!(":extension_{i}_{j}");
+                matches.push(format!(r"pastes.filename LIKE {name} ESCAPE '\'"));
+                params.push((name, Box::new(format!("%.{}", escape_like(extension)))));
+            }
+            if matches.is_empty() {
+                // A language that no file extension maps to can't match any paste
+                conditions.push("0 = 1".to_string());
+            } else {
+                conditions.push(format!("({})", matches.join(" OR ")));
+            }
+        }
+        if let Some(filename) = &self.filename {
+            conditions.push(r"pastes.filename LIKE :filename ESCAPE '\'".to_string());
+            params.push((
+                ":filename".into(),
+                Box::new(format!("%{}%", escape_like(filename))),
+            ));
+        }
+        let ranges = [
+            ("pastes.created_at >=", ":created_after", self.created_after),
+            (
+                "pastes.created_at <",
+                ":created_before",
+                self.created_before,
+            ),
+            ("pastes.updated_at >=", ":updated_after", self.updated_after),
+            (
+                "pastes.updated_at <",
+                ":updated_before",
+                self.updated_before,
+            ),
+        ];
+        for (comparison, name, bound) in ranges {
+            if let Some(bound) = bound {
+                conditions.push(format!("{comparison} {name}"));
+                params.push((name.into(), Box::new(bound.as_millisecond())));
+            }
+        }
+
+        (conditions, params)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PasteSort {
+    #[default]
+    Created,
+    Updated,
+}
+
+impl PasteSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PasteSort::Created => "created",
+            PasteSort::Updated => "updated",
+        }
+    }
+
+    pub fn cursor_for(self, paste: &Paste) -> PasteCursor {
+        let key = match self {
+            PasteSort::Created => paste.created_at,
+            PasteSort::Updated => paste.updated_at,
+        };
+        PasteCursor {
+            sort: self,
+            key,
+            id: paste.id,
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            PasteSort::Created => "pastes.created_at",
+            PasteSort::Updated => "pastes.updated_at",
+        }
+    }
+}
+
+impl FromStr for PasteSort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(Self::Created),
+            "updated" => Ok(Self::Updated),
+            _ => Err(Error::Parse(
+                "Unrecognized value for sort. Valid values are 'created' or 'updated'".into(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for PasteSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Where a page of a sorted listing ends. Timestamps aren't unique, so the paste's id breaks ties.
+// It's written as `<sort>.<milliseconds>.<id>`, which is safe to use in a URL as is.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PasteCursor {
+    sort: PasteSort,
+    key: Timestamp,
+    id: Uuid,
+}
+
+impl FromStr for PasteCursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::Parse(format!("Invalid cursor '{s}'"));
+        let mut parts = s.splitn(3, '.');
+        let (Some(sort), Some(key), Some(id)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            sort: sort.parse().map_err(|_| invalid())?,
+            key: key
+                .parse()
+                .ok()
+                .and_then(|millis| Timestamp::from_millisecond(millis).ok())
+                .ok_or_else(invalid)?,
+            id: Uuid::try_parse(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl TryFrom<String> for PasteCursor {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PasteCursor> for String {
+    fn from(value: PasteCursor) -> String {
+        value.to_string()
+    }
+}
+
+// Cursors are documented as the opaque strings clients see
+impl utoipa::PartialSchema for PasteCursor {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        utoipa::openapi::ObjectBuilder::new()
+            .schema_type(utoipa::openapi::schema::Type::String)
+            .examples(["created.1704070861001.0192a3b4c5d67e8f90a1b2c3d4e5f607"])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for PasteCursor {}
+
+impl std::fmt::Display for PasteCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}",
+            self.sort,
+            self.key.as_millisecond(),
+            self.id.as_simple()
+        )
+    }
+}
+
+// Filters as they arrive in a query string. HTML forms submit fields that were left alone as empty
+// strings, so those count as absent.
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PasteFilterParams {
+    #[param(example = "example")]
+    pub owner: Option<String>,
+    #[param(example = "secret")]
+    pub visibility: Option<String>,
+    #[param(example = "Rust")]
+    pub language: Option<String>,
+    #[param(example = "rs")]
+    pub extension: Option<String>,
+    #[param(example = "notes")]
+    pub filename: Option<String>,
+    #[param(example = "2024-01-01")]
+    pub created_after: Option<String>,
+    #[param(example = "2024-01-01T00:00:00Z")]
+    pub created_before: Option<String>,
+    #[param(example = "2024-01-01")]
+    pub updated_after: Option<String>,
+    #[param(example = "2024-01-01T00:00:00Z")]
+    pub updated_before: Option<String>,
+    #[param(example = "updated")]
+    pub sort: Option<String>,
+}
+
+impl PasteFilterParams {
+    // Whether anything is being filtered out, as opposed to only sorted
+    pub fn is_active(&self) -> bool {
+        self.pairs().iter().any(|(name, _)| *name != "sort")
+    }
+
+    // The filters as a query string, ending in '&' when there are any, so that pagination params
+    // can be appended to carry the filters over to the next page
+    pub fn to_query_prefix(&self) -> String {
+        let pairs = self.pairs();
+        if pairs.is_empty() {
+            return String::new();
+        }
+        serde_urlencoded::to_string(pairs).map_or_else(|_| String::new(), |query| query + "&")
+    }
+
+    // The value of a field by its name, for filling in a form
+    pub fn field(&self, name: &str) -> &str {
+        self.pairs()
+            .into_iter()
+            .find_map(|(field, value)| (field == name).then_some(value))
+            .unwrap_or_default()
+    }
+
+    fn pairs(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("owner", &self.owner),
+            ("visibility", &self.visibility),
+            ("language", &self.language),
+            ("extension", &self.extension),
+            ("filename", &self.filename),
+            ("created_after", &self.created_after),
+            ("created_before", &self.created_before),
+            ("updated_after", &self.updated_after),
+            ("updated_before", &self.updated_before),
+            ("sort", &self.sort),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| non_empty(value.as_ref()).map(|value| (name, value)))
+        .collect()
+    }
+}
+
+fn non_empty(value: Option<&String>) -> Option<&str> {
+    value.map(|s| s.trim()).filter(|s| !s.is_empty())
+}
+
+// A bare date means midnight UTC at the start of that day, which is what a date input submits
+fn parse_date_bound(label: &str, value: Option<&String>) -> Result<Option<Timestamp>> {
+    let Some(value) = non_empty(value) else {
+        return Ok(None);
+    };
+    value
+        .parse::<Timestamp>()
+        .or_else(|_| {
+            value
+                .parse::<Date>()
+                .and_then(|date| date.to_zoned(TimeZone::UTC))
+                .map(|zoned| zoned.timestamp())
+        })
+        .map(Some)
+        .map_err(|_| {
+            Error::Parse(format!(
+                "{label} must be a date (YYYY-MM-DD) or a timestamp (RFC 3339)"
+            ))
+        })
+}
@@ -10,8 +10,34 @@ pub enum Error {
     #[error(transparent)]
     Jiff(#[from] jiff::Error),
 
+    #[error(transparent)]
+    Join(#[from] tokio::task::JoinError),
+
+    #[error(transparent)]
+    PasswordHashing(#[from] crate::helpers::password_hashing_helper::Error),
+
     #[error("{0}")]
     Parse(String),
+
+    // A conditional change was requested, but the record no longer matches the version it was
+    // conditioned on
+    #[error("precondition failed")]
+    PreconditionFailed,
+
+    // A user asked to change to a username or email that another user already has
+    #[error("username is already taken")]
+    UsernameTaken,
+
+    #[error("email is already taken")]
+    EmailTaken,
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Escapes the wildcards in a string that's about to become part of a LIKE pattern. The pattern
+// must be used with `ESCAPE '\'`.
+pub fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
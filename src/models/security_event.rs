@@ -0,0 +1,173 @@
+use crate::db::Database;
+use crate::models::prelude::*;
+use derive_more::Display;
+use jiff::Timestamp;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
+use rusqlite::{named_params, Row, Transaction};
+use std::str::FromStr;
+use uuid::Uuid;
+
+const RECENT_EVENTS_LIMIT: usize = 10;
+
+#[derive(Clone, Debug, Display)]
+#[display("{{ id: {id}, user_id: {user_id}, kind: {kind} }}")]
+pub struct SecurityEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: SecurityEventKind,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: Timestamp,
+}
+
+impl SecurityEvent {
+    pub fn new(
+        user_id: Uuid,
+        kind: SecurityEventKind,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            user_id,
+            kind,
+            user_agent,
+            ip_address,
+            created_at: Timestamp::now(),
+        }
+    }
+
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            kind: row.get(2)?,
+            user_agent: row.get(3)?,
+            ip_address: row.get(4)?,
+            created_at: Timestamp::from_millisecond(row.get(5)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(5, Type::Integer, Box::new(e))
+            })?,
+        })
+    }
+
+    pub async fn recent_for_user_id(db: &Database, user_id: Uuid) -> Result<Vec<Self>> {
+        let security_events: Vec<_> = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"SELECT id, user_id, kind, user_agent, ip_address, created_at
+                    FROM security_events
+                    WHERE user_id = :user_id ORDER BY id DESC LIMIT :limit;",
+                )?;
+                let security_event_iter = statement.query_map(
+                    named_params! {":user_id": user_id, ":limit": RECENT_EVENTS_LIMIT},
+                    Self::from_sql_row,
+                )?;
+                Ok(security_event_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(security_events)
+    }
+
+    pub async fn insert(self, db: &Database) -> Result<usize> {
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let result = self.tx_insert(&tx)?;
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub fn tx_insert(&self, tx: &Transaction) -> rusqlite::Result<usize> {
+        tracing::info!("inserting security event {self}");
+        let mut stmt = tx.prepare(
+            r"INSERT INTO security_events (id, user_id, kind, user_agent, ip_address, created_at)
+            VALUES (:id, :user_id, :kind, :user_agent, :ip_address, :created_at);",
+        )?;
+        stmt.execute(named_params! {
+            ":id": self.id,
+            ":user_id": self.user_id,
+            ":kind": self.kind,
+            ":user_agent": self.user_agent,
+            ":ip_address": self.ip_address,
+            ":created_at": self.created_at.as_millisecond(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SecurityEventKind {
+    PasswordChanged,
+    ApiKeysRevoked,
+    AccountLocked,
+    AccountDeletionScheduled,
+    AccountDeletionCancelled,
+    UsernameChanged,
+    EmailChanged,
+}
+
+impl SecurityEventKind {
+    pub fn description(&self) -> &str {
+        match self {
+            Self::PasswordChanged => "Password changed and all other sessions signed out",
+            Self::ApiKeysRevoked => "All API keys revoked",
+            Self::AccountLocked => "Sign in temporarily locked after too many failed attempts",
+            Self::AccountDeletionScheduled => "Account scheduled for deletion",
+            Self::AccountDeletionCancelled => "Scheduled account deletion cancelled",
+            Self::UsernameChanged => "Username changed",
+            Self::EmailChanged => "Email address changed",
+        }
+    }
+}
+
+impl FromStr for SecurityEventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "password_changed" => Ok(Self::PasswordChanged),
+            "api_keys_revoked" => Ok(Self::ApiKeysRevoked),
+            "account_locked" => Ok(Self::AccountLocked),
+            "account_deletion_scheduled" => Ok(Self::AccountDeletionScheduled),
+            "account_deletion_cancelled" => Ok(Self::AccountDeletionCancelled),
+            "username_changed" => Ok(Self::UsernameChanged),
+            "email_changed" => Ok(Self::EmailChanged),
+            _ => Err(Error::Parse("Unrecognized security event kind".into())),
+        }
+    }
+}
+
+impl std::fmt::Display for SecurityEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::PasswordChanged => "password_changed",
+            Self::ApiKeysRevoked => "api_keys_revoked",
+            Self::AccountLocked => "account_locked",
+            Self::AccountDeletionScheduled => "account_deletion_scheduled",
+            Self::AccountDeletionCancelled => "account_deletion_cancelled",
+            Self::UsernameChanged => "username_changed",
+            Self::EmailChanged => "email_changed",
+        };
+        f.write_str(s)
+    }
+}
+
+impl ToSql for SecurityEventKind {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        Ok(self.to_string().into())
+    }
+}
+
+impl FromSql for SecurityEventKind {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).and_then(|s| {
+            s.parse()
+                .map_err(|_| FromSqlError::Other("Unrecognized security event kind".into()))
+        })
+    }
+}
@@ -2,7 +2,7 @@ use crate::db::Database;
 use crate::models::prelude::*;
 use crate::models::user::User;
 use derive_more::{Display, From};
-use jiff::{Timestamp, ToSpan, Unit};
+use jiff::{Span, Timestamp, Unit};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
@@ -12,10 +12,6 @@ use sha2::{Digest, Sha256};
 use tokio_rusqlite::named_params;
 use uuid::Uuid;
 
-pub const SESSION_COOKIE_NAME: &str = "session_token";
-const ABSOLUTE_SESSION_TTL_SECONDS: i64 = 1_209_600; // 14 days
-const IDLE_SESSION_TTL_SECONDS: i64 = 28_800; // 8 hours
-
 #[derive(Clone, Debug, Display)]
 #[display("{{ token: {session_token}, user: {user} }}")]
 pub struct Session {
@@ -23,6 +19,11 @@ pub struct Session {
     pub user: User,
 }
 
+// A session whose user is an admin, which is what gates the admin area
+#[derive(Clone, Debug, Display)]
+#[display("{_0}")]
+pub struct AdminSession(pub Session);
+
 impl Session {
     pub async fn find_by_unhashed_token(
         db: &Database,
@@ -51,16 +52,17 @@ impl Session {
     ) -> tokio_rusqlite::Result<Option<Self>> {
         let mut stmt = tx.prepare(
             r"SELECT
-                users.id, users.username, users.email, users.password, users.created_at, users.updated_at,
-                session_tokens.id, session_tokens.token, session_tokens.user_id, session_tokens.created_at, session_tokens.last_used_at
+                users.id, users.username, users.email, users.password, users.created_at, users.updated_at, users.is_admin, users.suspended_at, users.display_name, users.bio, users.website,
+                session_tokens.id, session_tokens.token, session_tokens.user_id, session_tokens.created_at, session_tokens.last_used_at,
+                session_tokens.user_agent, session_tokens.ip_address, session_tokens.remember_me
             FROM users JOIN session_tokens ON users.id = session_tokens.user_id
-            WHERE session_tokens.token = :token;",
+            WHERE session_tokens.token = :token AND users.suspended_at IS NULL;",
         )?;
         let mut rows = stmt.query(named_params! {":token": token})?;
         match rows.next()? {
             Some(row) => {
                 let user = User::from_sql_row(row)?;
-                let session_token = SessionToken::from_sql_row(row, 6)?;
+                let session_token = SessionToken::from_sql_row(row, 11)?;
                 Ok(Some(Self {
                     session_token,
                     user,
@@ -79,10 +81,18 @@ pub struct SessionToken {
     pub user_id: Uuid,
     pub created_at: Timestamp,
     pub last_used_at: Timestamp,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub remember_me: bool,
 }
 
 impl SessionToken {
-    pub fn new(user_id: Uuid) -> (UnhashedToken, Self) {
+    pub fn new(
+        user_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+        remember_me: bool,
+    ) -> (UnhashedToken, Self) {
         let unhashed_token = UnhashedToken::generate();
         let session_token = Self {
             id: Uuid::now_v7(),
@@ -90,6 +100,9 @@ impl SessionToken {
             user_id,
             created_at: Timestamp::now(),
             last_used_at: Timestamp::now(),
+            user_agent,
+            ip_address,
+            remember_me,
         };
         (unhashed_token, session_token)
     }
@@ -105,25 +118,79 @@ impl SessionToken {
             last_used_at: Timestamp::from_millisecond(row.get(4 + offset)?).map_err(|e| {
                 rusqlite::Error::FromSqlConversionFailure(4 + offset, Type::Integer, Box::new(e))
             })?,
+            user_agent: row.get(5 + offset)?,
+            ip_address: row.get(6 + offset)?,
+            remember_me: row.get(7 + offset)?,
         })
     }
 
-    pub async fn expire_absolute(db: &Database) -> Result<usize> {
-        let expiration_ttl = ABSOLUTE_SESSION_TTL_SECONDS.seconds();
+    pub async fn all_for_user_id(db: &Database, user_id: Uuid) -> Result<Vec<Self>> {
+        let session_tokens: Vec<_> = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"SELECT id, token, user_id, created_at, last_used_at, user_agent, ip_address, remember_me
+                    FROM session_tokens
+                    WHERE user_id = :user_id ORDER BY last_used_at DESC;",
+                )?;
+                let session_token_iter = statement
+                    .query_map(named_params! {":user_id": user_id}, |t| {
+                        Self::from_sql_row(t, 0)
+                    })?;
+                Ok(session_token_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(session_tokens)
+    }
+
+    pub async fn find_scoped_by_user_id(
+        db: &Database,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<Self>> {
+        let maybe_session_token = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"SELECT id, token, user_id, created_at, last_used_at, user_agent, ip_address, remember_me
+                    FROM session_tokens
+                    WHERE id = :id AND user_id = :user_id;",
+                )?;
+                let mut rows = stmt.query(named_params! {":id": id, ":user_id": user_id})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(Self::from_sql_row(row, 0)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_session_token)
+    }
+
+    pub async fn expire_absolute(
+        db: &Database,
+        absolute_ttl: Span,
+        remember_me_ttl: Span,
+    ) -> Result<usize> {
         tracing::info!(
-            "expiring sessions older than {} days",
-            expiration_ttl.total(Unit::Day)?
+            "expiring sessions older than {} days, or {} days for remember me sessions",
+            absolute_ttl.total(Unit::Day)?,
+            remember_me_ttl.total(Unit::Day)?
         );
-        let expiration_timestamp = Timestamp::now().checked_sub(expiration_ttl)?;
+        let expiration_timestamp = Timestamp::now().checked_sub(absolute_ttl)?;
+        let remember_me_expiration_timestamp = Timestamp::now().checked_sub(remember_me_ttl)?;
         let result = db
             .conn
             .call(move |conn| {
                 let mut statement = conn.prepare(
-                    "DELETE FROM session_tokens WHERE created_at < :expiration_timestamp;",
-                )?;
-                let result = statement.execute(
-                    named_params! {":expiration_timestamp": expiration_timestamp.as_millisecond()},
+                    r"DELETE FROM session_tokens
+                    WHERE (remember_me = 0 AND created_at < :expiration_timestamp)
+                    OR (remember_me = 1 AND created_at < :remember_me_expiration_timestamp);",
                 )?;
+                let result = statement.execute(named_params! {
+                    ":expiration_timestamp": expiration_timestamp.as_millisecond(),
+                    ":remember_me_expiration_timestamp": remember_me_expiration_timestamp.as_millisecond(),
+                })?;
                 Ok(result)
             })
             .await?;
@@ -132,18 +199,19 @@ impl SessionToken {
         Ok(result)
     }
 
-    pub async fn expire_idle(db: &Database) -> Result<usize> {
-        let expiration_ttl = IDLE_SESSION_TTL_SECONDS.seconds();
+    pub async fn expire_idle(db: &Database, idle_ttl: Span) -> Result<usize> {
         tracing::info!(
             "expiring sessions idle for more than {} hours",
-            expiration_ttl.total(Unit::Hour)?
+            idle_ttl.total(Unit::Hour)?
         );
-        let expiration_timestamp = Timestamp::now().checked_sub(expiration_ttl)?;
+        let expiration_timestamp = Timestamp::now().checked_sub(idle_ttl)?;
         let result = db
             .conn
             .call(move |conn| {
+                // Remember me sessions are exempt from idle expiration, otherwise they'd be no
+                // longer lived than any other session for users who visit less than daily
                 let mut statement = conn.prepare(
-                    "DELETE FROM session_tokens WHERE last_used_at < :expiration_timestamp;",
+                    "DELETE FROM session_tokens WHERE remember_me = 0 AND last_used_at < :expiration_timestamp;",
                 )?;
                 let result = statement.execute(
                     named_params! {":expiration_timestamp": expiration_timestamp.as_millisecond()},
@@ -157,24 +225,63 @@ impl SessionToken {
     }
 
     pub async fn insert(self, db: &Database) -> Result<usize> {
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let result = self.tx_insert(&tx)?;
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub fn tx_insert(&self, tx: &Transaction) -> rusqlite::Result<usize> {
         tracing::info!("inserting session token {self}");
+        let mut statement = tx.prepare(
+            r"INSERT INTO session_tokens (id, token, user_id, created_at, last_used_at, user_agent, ip_address, remember_me)
+            VALUES (:id, :token, :user_id, :created_at, :last_used_at, :user_agent, :ip_address, :remember_me);",
+        )?;
+        statement.execute(named_params! {
+            ":id": self.id,
+            ":token": self.token,
+            ":user_id": self.user_id,
+            ":created_at": self.created_at.as_millisecond(),
+            ":last_used_at": self.last_used_at.as_millisecond(),
+            ":user_agent": self.user_agent,
+            ":ip_address": self.ip_address,
+            ":remember_me": self.remember_me,
+        })
+    }
+
+    pub async fn delete(self, db: &Database) -> Result<usize> {
+        tracing::info!("deleting session token {self}");
         let result = db
             .conn
             .call(move |conn| {
-                let mut statement = conn.prepare(
-                    "INSERT INTO session_tokens VALUES (:id, :token, :user_id, :created_at, :last_used_at);",
-                )?;
-                let result = statement.execute(named_params! {
-                    ":id": self.id,
-                    ":token": self.token,
-                    ":user_id": self.user_id,
-                    ":created_at": self.created_at.as_millisecond(),
-                    ":last_used_at": self.last_used_at.as_millisecond(),
-                })?;
+                let mut stmt = conn.prepare("DELETE FROM session_tokens WHERE id = :id;")?;
+                let result = stmt.execute(named_params! {":id": self.id})?;
                 Ok(result)
             })
             .await?;
+        Ok(result)
+    }
 
+    pub async fn delete_all_others_for_user_id(&self, db: &Database) -> Result<usize> {
+        tracing::info!("deleting all other sessions for session token {self}");
+        let (id, user_id) = (self.id, self.user_id);
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "DELETE FROM session_tokens WHERE user_id = :user_id AND id != :id;",
+                )?;
+                let result = stmt.execute(named_params! {":user_id": user_id, ":id": id})?;
+                Ok(result)
+            })
+            .await?;
         Ok(result)
     }
 
@@ -0,0 +1,343 @@
+use crate::config::{ThrottleConfig, ThrottlePolicy};
+use crate::db::Database;
+use crate::models::prelude::*;
+use derive_more::Display;
+use jiff::{SignedDuration, Timestamp};
+use rusqlite::types::{ToSql, ToSqlOutput, Type};
+use rusqlite::{named_params, Row, Transaction, TransactionBehavior};
+use uuid::Uuid;
+
+const MAX_KEY_LENGTH: usize = 256;
+// Throttles that are neither locked nor have seen an attempt within this window are forgotten
+const THROTTLE_WINDOW: SignedDuration = SignedDuration::from_mins(15);
+// The delay after the first attempt beyond a policy's free ones
+const BASE_DELAY: SignedDuration = SignedDuration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Display, PartialEq)]
+pub enum ThrottleScope {
+    // Failed sign ins against a single email address, whether or not an account exists for it
+    #[display("login_account")]
+    LoginAccount,
+    // Failed sign ins from a single IP address, across all email addresses
+    #[display("login_ip")]
+    LoginIp,
+    // Username and email availability checks from a single IP address
+    #[display("signup_validation_ip")]
+    SignupValidationIp,
+}
+
+impl ThrottleScope {
+    fn policy(self, config: &ThrottleConfig) -> ThrottlePolicy {
+        match self {
+            Self::LoginAccount => config.login_account(),
+            Self::LoginIp => config.login_ip(),
+            Self::SignupValidationIp => config.signup_validation_ip(),
+        }
+    }
+}
+
+// The delay doubles with every attempt beyond the free ones, up to the policy's max delay
+fn delay_after(policy: ThrottlePolicy, attempt_count: i64) -> SignedDuration {
+    let max_delay = SignedDuration::from_secs(policy.max_delay_seconds);
+    let Some(exponent) = attempt_count
+        .checked_sub(policy.free_attempts)
+        .and_then(|n| u32::try_from(n).ok())
+    else {
+        return SignedDuration::ZERO;
+    };
+    2_i32
+        .checked_pow(exponent)
+        .and_then(|factor| BASE_DELAY.checked_mul(factor))
+        .map_or(max_delay, |delay| delay.min(max_delay))
+}
+
+impl ToSql for ThrottleScope {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        Ok(self.to_string().into())
+    }
+}
+
+#[derive(Clone, Debug, Display)]
+#[display("{{ scope: {scope}, key: {key} }}")]
+pub struct ThrottleKey {
+    pub scope: ThrottleScope,
+    pub key: String,
+}
+
+impl ThrottleKey {
+    // Returns `None` for blank keys, which can't meaningfully be throttled.
+    pub fn new(scope: ThrottleScope, key: &str) -> Option<Self> {
+        let key: String = key
+            .trim()
+            .to_lowercase()
+            .chars()
+            .take(MAX_KEY_LENGTH)
+            .collect();
+        if key.is_empty() {
+            None
+        } else {
+            Some(Self { scope, key })
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ThrottledAttempt {
+    // An earlier attempt hasn't been waited out yet, so this one wasn't counted
+    RetryAfter(SignedDuration),
+    // The attempt was counted, and locked out these keys
+    Counted { locked_keys: Vec<ThrottleKey> },
+}
+
+#[derive(Clone, Debug, Display)]
+#[display("{{ key: {key}, attempt_count: {attempt_count} }}")]
+pub struct Throttle {
+    pub key: ThrottleKey,
+    pub attempt_count: i64,
+    pub last_attempt_at: Timestamp,
+    pub locked_until: Option<Timestamp>,
+}
+
+impl Throttle {
+    pub fn from_sql_row(row: &Row, key: ThrottleKey) -> rusqlite::Result<Self> {
+        Ok(Self {
+            key,
+            attempt_count: row.get(0)?,
+            last_attempt_at: Timestamp::from_millisecond(row.get(1)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, Type::Integer, Box::new(e))
+            })?,
+            locked_until: row
+                .get::<_, Option<i64>>(2)?
+                .map(Timestamp::from_millisecond)
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(2, Type::Integer, Box::new(e))
+                })?,
+        })
+    }
+
+    // Checks whether an attempt against each of `keys` is allowed and, if it is, counts it, all in
+    // a single transaction. Attempts are counted before the work they throttle is done rather than
+    // after it fails, so that concurrent attempts can't all get past the check before any of them
+    // is counted. Attempts that turn out to be legitimate can be handed back with `refund`.
+    pub async fn attempt(
+        db: &Database,
+        config: &ThrottleConfig,
+        keys: Vec<ThrottleKey>,
+    ) -> Result<ThrottledAttempt> {
+        let config = config.clone();
+        let now = Timestamp::now();
+        let window_start = now.checked_sub(THROTTLE_WINDOW)?;
+        let keys = keys
+            .into_iter()
+            .map(|key| {
+                let policy = key.scope.policy(&config);
+                let lockout_ends_at =
+                    now.checked_add(SignedDuration::from_secs(policy.lockout_seconds))?;
+                Ok((key, policy.lockout_threshold, lockout_ends_at))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        db.conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let mut previous_counts = Vec::with_capacity(keys.len());
+                let mut retry_after = None;
+                for (key, _, _) in &keys {
+                    let previous_count = match Self::tx_find_by_key(&tx, key.clone())? {
+                        Some(t) if t.is_stale(now, window_start) => {
+                            Self::tx_delete_by_key(&tx, key)?;
+                            0
+                        }
+                        Some(t) => {
+                            match t.wait_from(&config, now) {
+                                Ok(wait) => retry_after = retry_after.max(wait),
+                                Err(e) => return Ok(Err(e)),
+                            }
+                            t.attempt_count
+                        }
+                        None => 0,
+                    };
+                    previous_counts.push(previous_count);
+                }
+                if let Some(retry_after) = retry_after {
+                    return Ok(Ok(ThrottledAttempt::RetryAfter(retry_after)));
+                }
+
+                let mut locked_keys = Vec::new();
+                for ((key, lockout_threshold, lockout_ends_at), previous_count) in
+                    keys.into_iter().zip(previous_counts)
+                {
+                    let attempt_count = previous_count + 1;
+                    let locked_until = (lockout_threshold > 0
+                        && previous_count < lockout_threshold
+                        && attempt_count >= lockout_threshold)
+                        .then_some(lockout_ends_at);
+
+                    let mut stmt = tx.prepare(
+                        r"INSERT INTO throttles (id, scope, key, attempt_count, created_at, last_attempt_at, locked_until)
+                        VALUES (:id, :scope, :key, :attempt_count, :now, :now, :locked_until)
+                        ON CONFLICT(scope, key) DO UPDATE SET
+                            attempt_count = excluded.attempt_count,
+                            last_attempt_at = excluded.last_attempt_at,
+                            locked_until = coalesce(excluded.locked_until, locked_until);",
+                    )?;
+                    stmt.execute(named_params! {
+                        ":id": Uuid::now_v7(),
+                        ":scope": key.scope,
+                        ":key": key.key,
+                        ":attempt_count": attempt_count,
+                        ":now": now.as_millisecond(),
+                        ":locked_until": locked_until.map(Timestamp::as_millisecond),
+                    })?;
+
+                    if locked_until.is_some() {
+                        tracing::info!("locking out throttle {key} until {lockout_ends_at}");
+                        locked_keys.push(key);
+                    }
+                }
+                tx.commit()?;
+                Ok(Ok(ThrottledAttempt::Counted { locked_keys }))
+            })
+            .await?
+    }
+
+    // Takes back an attempt counted by `attempt`, along with any lockouts it caused
+    pub async fn refund(
+        db: &Database,
+        keys: Vec<ThrottleKey>,
+        locked_keys: Vec<ThrottleKey>,
+    ) -> Result<usize> {
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let mut result = 0;
+                for key in keys {
+                    let unlock = locked_keys
+                        .iter()
+                        .any(|locked| locked.scope == key.scope && locked.key == key.key);
+                    let mut stmt = tx.prepare(
+                        r"DELETE FROM throttles
+                        WHERE scope = :scope AND key = :key AND attempt_count <= 1;",
+                    )?;
+                    result += stmt.execute(named_params! {":scope": key.scope, ":key": key.key})?;
+                    let mut stmt = tx.prepare(
+                        r"UPDATE throttles SET
+                            attempt_count = attempt_count - 1,
+                            locked_until = CASE WHEN :unlock THEN NULL ELSE locked_until END
+                        WHERE scope = :scope AND key = :key;",
+                    )?;
+                    result += stmt.execute(named_params! {
+                        ":scope": key.scope,
+                        ":key": key.key,
+                        ":unlock": unlock,
+                    })?;
+                }
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    // Moves the last attempt against each of `keys` up to now, for attempts whose work finished
+    // well after they were counted
+    pub async fn restart_delays(db: &Database, keys: Vec<ThrottleKey>) -> Result<usize> {
+        let now = Timestamp::now();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut stmt = tx.prepare(
+                    r"UPDATE throttles SET last_attempt_at = max(last_attempt_at, :now)
+                    WHERE scope = :scope AND key = :key;",
+                )?;
+                let mut result = 0;
+                for key in keys {
+                    result += stmt.execute(named_params! {
+                        ":scope": key.scope,
+                        ":key": key.key,
+                        ":now": now.as_millisecond(),
+                    })?;
+                }
+                drop(stmt);
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn clear(db: &Database, key: ThrottleKey) -> Result<usize> {
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let result = Self::tx_delete_by_key(&tx, &key)?;
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    pub async fn expire(db: &Database) -> Result<usize> {
+        tracing::info!("expiring lapsed throttles and lockouts");
+        let now = Timestamp::now();
+        let window_start = now.checked_sub(THROTTLE_WINDOW)?;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"DELETE FROM throttles
+                    WHERE (locked_until IS NOT NULL AND locked_until <= :now)
+                    OR (locked_until IS NULL AND last_attempt_at < :window_start);",
+                )?;
+                let result = statement.execute(named_params! {
+                    ":now": now.as_millisecond(),
+                    ":window_start": window_start.as_millisecond(),
+                })?;
+                Ok(result)
+            })
+            .await?;
+
+        tracing::info!("done expiring throttles, expired {result} throttles");
+        Ok(result)
+    }
+
+    fn tx_find_by_key(tx: &Transaction, key: ThrottleKey) -> rusqlite::Result<Option<Self>> {
+        let mut stmt = tx.prepare(
+            r"SELECT attempt_count, last_attempt_at, locked_until
+            FROM throttles WHERE scope = :scope AND key = :key;",
+        )?;
+        let mut rows = stmt.query(named_params! {":scope": key.scope, ":key": key.key})?;
+        match rows.next()? {
+            Some(row) => Ok(Some(Self::from_sql_row(row, key)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn tx_delete_by_key(tx: &Transaction, key: &ThrottleKey) -> rusqlite::Result<usize> {
+        let mut stmt = tx.prepare("DELETE FROM throttles WHERE scope = :scope AND key = :key;")?;
+        stmt.execute(named_params! {":scope": key.scope, ":key": key.key})
+    }
+
+    // A stale throttle is one that the background task would expire, but hasn't gotten to yet
+    fn is_stale(&self, now: Timestamp, window_start: Timestamp) -> bool {
+        match self.locked_until {
+            Some(locked_until) => locked_until <= now,
+            None => self.last_attempt_at < window_start,
+        }
+    }
+
+    fn wait_from(&self, config: &ThrottleConfig, now: Timestamp) -> Result<Option<SignedDuration>> {
+        if let Some(locked_until) = self.locked_until {
+            return Ok((locked_until > now).then(|| locked_until.duration_since(now)));
+        }
+
+        let delay = delay_after(self.key.scope.policy(config), self.attempt_count);
+        let allowed_at = self.last_attempt_at.checked_add(delay)?;
+        Ok((allowed_at > now).then(|| allowed_at.duration_since(now)))
+    }
+}
@@ -1,17 +1,26 @@
 use crate::db::Database;
+use crate::helpers::pagination_helper::{Direction, HasOrderedId};
+use crate::helpers::password_hashing_helper::PasswordHashingPool;
+use crate::models::invite_code::InviteCode;
 use crate::models::prelude::*;
+use crate::models::security_event::{SecurityEvent, SecurityEventKind};
+use crate::models::session::SessionToken;
 use argon2::password_hash::{PasswordHasher, SaltString};
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordVerifier, Version};
 use derive_more::Display;
 use jiff::Timestamp;
 use rand::rngs::OsRng;
 use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
-use rusqlite::{named_params, Row};
+use rusqlite::{named_params, Row, Transaction, TransactionBehavior};
 use secrecy::{ExposeSecret, SecretString};
 use std::convert::TryFrom;
 use std::str::FromStr;
 use uuid::Uuid;
 
+// 16 and 32 zero bytes in the unpadded base64 that PHC strings use
+const DUMMY_SALT: &str = "AAAAAAAAAAAAAAAAAAAAAA";
+const DUMMY_OUTPUT: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
 #[derive(Clone, Debug, PartialEq, Display)]
 #[display("{{ id: {id}, username: {username}, email: {email} }}")]
 pub struct User {
@@ -21,16 +30,22 @@ pub struct User {
     pub password: HashedPassword,
     pub created_at: Timestamp,
     pub updated_at: Timestamp,
+    pub is_admin: bool,
+    pub suspended_at: Option<Timestamp>,
+    pub display_name: DisplayName,
+    pub bio: Bio,
+    pub website: Website,
 }
 
 impl User {
-    pub fn new(
+    pub async fn new(
         username: Username,
         email: EmailAddress,
         password: UnhashedPassword,
+        hashing_pool: &PasswordHashingPool,
     ) -> Result<Self> {
+        let hashed_password = HashedPassword::new(password, hashing_pool).await?;
         let now = Timestamp::now();
-        let hashed_password = HashedPassword::try_from(password)?;
         Ok(User {
             id: Uuid::now_v7(),
             username,
@@ -38,6 +53,11 @@ impl User {
             password: hashed_password,
             created_at: now,
             updated_at: now,
+            is_admin: false,
+            suspended_at: None,
+            display_name: DisplayName::default(),
+            bio: Bio::default(),
+            website: Website::default(),
         })
     }
 
@@ -53,14 +73,84 @@ impl User {
             updated_at: Timestamp::from_millisecond(row.get(5)?).map_err(|e| {
                 rusqlite::Error::FromSqlConversionFailure(5, Type::Integer, Box::new(e))
             })?,
+            is_admin: row.get(6)?,
+            suspended_at: row
+                .get::<_, Option<i64>>(7)?
+                .map(Timestamp::from_millisecond)
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(7, Type::Integer, Box::new(e))
+                })?,
+            display_name: row.get(8)?,
+            bio: row.get(9)?,
+            website: row.get(10)?,
         })
     }
 
-    pub fn verify_password(&self, password: &UnhashedPassword) -> Result<()> {
-        Ok(Argon2::default().verify_password(
-            password.expose_secret().as_bytes(),
-            &PasswordHash::new(self.password.expose_secret())?,
-        )?)
+    // Verification uses the parameters recorded in the stored hash, so hashes created with older
+    // parameters keep working after the configured parameters change
+    pub async fn verify_password(
+        &self,
+        password: &UnhashedPassword,
+        hashing_pool: &PasswordHashingPool,
+    ) -> Result<()> {
+        self.password.verify(password, hashing_pool).await
+    }
+
+    // Replaces a stored hash that's weaker than the configured parameters with one created using
+    // them. This requires the plaintext password, so it can only happen right after a successful
+    // sign in. The update is skipped if the password has changed in the meantime.
+    pub async fn rehash_password(
+        &self,
+        db: &Database,
+        password: UnhashedPassword,
+        hashing_pool: &PasswordHashingPool,
+    ) -> Result<usize> {
+        tracing::info!("rehashing password for user {self}");
+        let id = self.id;
+        let old_hashed_password = self.password.clone();
+        let new_hashed_password = HashedPassword::new(password, hashing_pool).await?;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    "UPDATE users SET password = :new_password WHERE id = :id AND password = :old_password;",
+                )?;
+                Ok(statement.execute(named_params! {
+                    ":new_password": new_hashed_password,
+                    ":old_password": old_hashed_password,
+                    ":id": id,
+                })?)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    // The number of accounts whose stored hashes are weaker than `params`, and so will be rehashed
+    // when their owners next sign in
+    pub async fn count_with_outdated_password_hashes(
+        db: &Database,
+        params: &Params,
+    ) -> Result<usize> {
+        let params = params.clone();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare("SELECT password FROM users;")?;
+                let password_iter =
+                    statement.query_map([], |row| row.get::<_, HashedPassword>(0))?;
+                let mut count = 0;
+                for password in password_iter {
+                    if password?.is_weaker_than(&params) {
+                        count += 1;
+                    }
+                }
+                Ok(count)
+            })
+            .await?;
+
+        Ok(result)
     }
 
     pub async fn insert(self, db: &Database) -> Result<usize> {
@@ -68,16 +158,9 @@ impl User {
         let result = db
             .conn
             .call(move |conn| {
-                let mut statement =
-                    conn.prepare("INSERT INTO users VALUES (:id, :username, :email, :password, :created_at, :updated_at);")?;
-                let result = statement.execute(named_params! {
-                    ":id": self.id,
-                    ":username": self.username,
-                    ":email": self.email,
-                    ":password": self.password.expose_secret(),
-                    ":created_at": self.created_at.as_millisecond(),
-                    ":updated_at": self.updated_at.as_millisecond(),
-                })?;
+                let tx = conn.transaction()?;
+                let result = self.tx_insert(&tx)?;
+                tx.commit()?;
                 Ok(result)
             })
             .await?;
@@ -85,24 +168,267 @@ impl User {
         Ok(result)
     }
 
+    // Inserting the user and redeeming their invite code happen in a single transaction, so that a
+    // code can never be redeemed more times than it allows, even by concurrent signups. Returns
+    // false, having inserted nothing, when the code can't be redeemed.
+    pub async fn insert_with_invite_code(self, db: &Database, invite_code: String) -> Result<bool> {
+        tracing::info!("inserting user {self} with invite code {invite_code}");
+        let now = Timestamp::now();
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                self.tx_insert(&tx)?;
+                if !InviteCode::tx_redeem(&tx, &invite_code, self.id, now)? {
+                    return Ok(false);
+                }
+                tx.commit()?;
+                Ok(true)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    pub fn tx_insert(&self, tx: &Transaction) -> rusqlite::Result<usize> {
+        Self::tx_release_previous_username(tx, &self.username)?;
+        let mut statement = tx.prepare(
+            r"INSERT INTO users (id, username, email, password, created_at, updated_at, is_admin, suspended_at)
+            VALUES (:id, :username, :email, :password, :created_at, :updated_at, :is_admin, :suspended_at);",
+        )?;
+        statement.execute(named_params! {
+            ":id": self.id,
+            ":username": self.username,
+            ":email": self.email,
+            ":password": self.password.expose_secret(),
+            ":created_at": self.created_at.as_millisecond(),
+            ":updated_at": self.updated_at.as_millisecond(),
+            ":is_admin": self.is_admin,
+            ":suspended_at": self.suspended_at.map(Timestamp::as_millisecond),
+        })
+    }
+
+    // Claiming a username that someone else used to have ends the redirects from their old URLs
+    fn tx_release_previous_username(
+        tx: &Transaction,
+        username: &Username,
+    ) -> rusqlite::Result<usize> {
+        let mut statement =
+            tx.prepare("DELETE FROM username_history WHERE username = :username;")?;
+        statement.execute(named_params! {":username": username})
+    }
+
+    // Changing a username records the old one in the username history, so that URLs using it can be
+    // redirected. Any changes are recorded as security events in the same transaction. Whether the
+    // new username and email are free is checked within the transaction too, so that concurrent
+    // changes to the same name fail with `UsernameTaken` or `EmailTaken` rather than a constraint
+    // violation.
+    pub async fn update_account(
+        &self,
+        db: &Database,
+        new_username: Username,
+        new_email: EmailAddress,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<usize> {
+        tracing::info!("updating account for user {self}");
+        let id = self.id;
+        let old_username = self.username.clone();
+        let username_changed = new_username != self.username;
+        let email_changed = new_email != self.email;
+        let mut events = Vec::new();
+        if username_changed {
+            events.push(SecurityEvent::new(
+                id,
+                SecurityEventKind::UsernameChanged,
+                user_agent.clone(),
+                ip_address.clone(),
+            ));
+        }
+        if email_changed {
+            events.push(SecurityEvent::new(
+                id,
+                SecurityEventKind::EmailChanged,
+                user_agent,
+                ip_address,
+            ));
+        }
+
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                if username_changed && Self::tx_is_username_taken(&tx, &new_username)? {
+                    return Ok(Err(Error::UsernameTaken));
+                }
+                if email_changed && Self::tx_is_email_taken(&tx, &new_email)? {
+                    return Ok(Err(Error::EmailTaken));
+                }
+
+                let result = {
+                    if username_changed {
+                        Self::tx_release_previous_username(&tx, &new_username)?;
+                        let mut history_stmt = tx.prepare(
+                            r"INSERT INTO username_history (username, user_id, created_at)
+                            VALUES (:username, :user_id, :created_at)
+                            ON CONFLICT(username) DO UPDATE SET user_id = :user_id, created_at = :created_at;",
+                        )?;
+                        history_stmt.execute(named_params! {
+                            ":username": old_username,
+                            ":user_id": id,
+                            ":created_at": Timestamp::now().as_millisecond(),
+                        })?;
+                    }
+
+                    let mut users_stmt = tx.prepare(
+                        r"UPDATE users SET username = :username, email = :email, updated_at = :updated_at
+                        WHERE id = :id;",
+                    )?;
+                    let result = users_stmt.execute(named_params! {
+                        ":username": new_username,
+                        ":email": new_email,
+                        ":updated_at": Timestamp::now().as_millisecond(),
+                        ":id": id,
+                    })?;
+
+                    for event in events {
+                        event.tx_insert(&tx)?;
+                    }
+                    result
+                };
+                tx.commit()?;
+                Ok(Ok(result))
+            })
+            .await?;
+
+        result
+    }
+
+    fn tx_is_username_taken(tx: &Transaction, username: &Username) -> rusqlite::Result<bool> {
+        let mut stmt =
+            tx.prepare("SELECT EXISTS(SELECT 1 FROM users WHERE username = :username);")?;
+        stmt.query_row(named_params! {":username": username}, |row| row.get(0))
+    }
+
+    fn tx_is_email_taken(tx: &Transaction, email: &EmailAddress) -> rusqlite::Result<bool> {
+        let mut stmt = tx.prepare("SELECT EXISTS(SELECT 1 FROM users WHERE email = :email);")?;
+        stmt.query_row(named_params! {":email": email}, |row| row.get(0))
+    }
+
+    pub async fn update_profile(
+        &self,
+        db: &Database,
+        display_name: DisplayName,
+        bio: Bio,
+        website: Website,
+    ) -> Result<usize> {
+        tracing::info!("updating profile for user {self}");
+        let id = self.id;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"UPDATE users SET display_name = :display_name, bio = :bio, website = :website, updated_at = :updated_at
+                    WHERE id = :id;",
+                )?;
+                Ok(statement.execute(named_params! {
+                    ":display_name": display_name,
+                    ":bio": bio,
+                    ":website": website,
+                    ":updated_at": Timestamp::now().as_millisecond(),
+                    ":id": id,
+                })?)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    // Changing a password terminates every existing session and replaces them with
+    // `new_session_token`, so that a leaked session can't outlive the password that it was created
+    // with. All of this happens in a single transaction, along with recording the change as a
+    // security event.
     pub async fn update_password(
         &self,
         db: &Database,
         new_password: UnhashedPassword,
+        hashing_pool: &PasswordHashingPool,
+        new_session_token: SessionToken,
+        revoke_api_keys: bool,
     ) -> Result<usize> {
         tracing::info!("updating password for user {self}");
         let id = self.id;
-        let hashed_password = HashedPassword::try_from(new_password)?;
+        let hashed_password = HashedPassword::new(new_password, hashing_pool).await?;
+        let (user_agent, ip_address) = (
+            new_session_token.user_agent.clone(),
+            new_session_token.ip_address.clone(),
+        );
+        let mut events = vec![SecurityEvent::new(
+            id,
+            SecurityEventKind::PasswordChanged,
+            user_agent.clone(),
+            ip_address.clone(),
+        )];
+        if revoke_api_keys {
+            events.push(SecurityEvent::new(
+                id,
+                SecurityEventKind::ApiKeysRevoked,
+                user_agent,
+                ip_address,
+            ));
+        }
+
         let result = db
             .conn
             .call(move |conn| {
-                let mut statement =
-                    conn.prepare("UPDATE users SET password = :password, updated_at = :updated_at WHERE id = :id;")?;
-                let result = statement.execute(named_params! {
-                    ":password": hashed_password,
-                    ":id": id,
-                    ":updated_at": Timestamp::now().as_millisecond(),
-                })?;
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let result = {
+                    let mut users_stmt = tx.prepare(
+                        "UPDATE users SET password = :password, updated_at = :updated_at WHERE id = :id;",
+                    )?;
+                    let result = users_stmt.execute(named_params! {
+                        ":password": hashed_password,
+                        ":id": id,
+                        ":updated_at": Timestamp::now().as_millisecond(),
+                    })?;
+
+                    let mut sessions_stmt =
+                        tx.prepare("DELETE FROM session_tokens WHERE user_id = :user_id;")?;
+                    sessions_stmt.execute(named_params! {":user_id": id})?;
+                    new_session_token.tx_insert(&tx)?;
+
+                    if revoke_api_keys {
+                        let mut api_keys_stmt =
+                            tx.prepare("DELETE FROM api_keys WHERE user_id = :user_id;")?;
+                        api_keys_stmt.execute(named_params! {":user_id": id})?;
+                    }
+
+                    for event in events {
+                        event.tx_insert(&tx)?;
+                    }
+                    result
+                };
+                tx.commit()?;
+                Ok(result)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    // Deleting a user cascades to their pastes (and those pastes' syntax highlight cache entries),
+    // API keys, sessions, security events, invite code redemption and any pending account deletion.
+    // Invite codes that the user minted are deleted explicitly, since their foreign key only nulls
+    // out `created_by` and would otherwise leave the codes redeemable.
+    pub async fn delete(&self, db: &Database) -> Result<usize> {
+        tracing::info!("deleting user {self}");
+        let id = self.id;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let result = Self::tx_delete(&tx, id)?;
+                tx.commit()?;
                 Ok(result)
             })
             .await?;
@@ -110,12 +436,21 @@ impl User {
         Ok(result)
     }
 
+    pub fn tx_delete(tx: &Transaction, id: Uuid) -> rusqlite::Result<usize> {
+        let mut invite_codes_stmt =
+            tx.prepare("DELETE FROM invite_codes WHERE created_by = :user_id;")?;
+        invite_codes_stmt.execute(named_params! {":user_id": id})?;
+
+        let mut users_stmt = tx.prepare("DELETE FROM users WHERE id = :id;")?;
+        users_stmt.execute(named_params! {":id": id})
+    }
+
     pub async fn find_by_email(db: &Database, email: EmailAddress) -> Result<Option<User>> {
         let maybe_user = db
             .conn
             .call(move |conn| {
                 let mut statement = conn.prepare(
-                    "SELECT id, username, email, password, created_at, updated_at FROM users WHERE email = :email;",
+                    "SELECT id, username, email, password, created_at, updated_at, is_admin, suspended_at, display_name, bio, website FROM users WHERE email = :email;",
                 )?;
                 let mut rows = statement.query(named_params! {":email": email})?;
                 match rows.next()? {
@@ -133,7 +468,7 @@ impl User {
             .conn
             .call(move |conn| {
                 let mut statement = conn.prepare(
-                    "SELECT id, username, email, password, created_at, updated_at FROM users WHERE username = :username;",
+                    "SELECT id, username, email, password, created_at, updated_at, is_admin, suspended_at, display_name, bio, website FROM users WHERE username = :username;",
                 )?;
                 let mut rows =
                     statement.query(named_params! {":username": username})?;
@@ -147,21 +482,214 @@ impl User {
         Ok(maybe_user)
     }
 
-    pub async fn delete_sessions(self, db: &Database) -> Result<usize> {
-        tracing::info!("deleting all sessions for user {self}");
+    // Finds the user who used to go by `username`, provided that nobody has claimed it since
+    pub async fn find_by_previous_username(
+        db: &Database,
+        username: Username,
+    ) -> Result<Option<User>> {
+        let maybe_user = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    r"SELECT users.id, users.username, users.email, users.password, users.created_at, users.updated_at, users.is_admin, users.suspended_at, users.display_name, users.bio, users.website
+                    FROM username_history
+                    JOIN users ON users.id = username_history.user_id
+                    WHERE username_history.username = :username;",
+                )?;
+                let mut rows = statement.query(named_params! {":username": username})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(User::from_sql_row(row)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_user)
+    }
+
+    pub async fn find(db: &Database, id: Uuid) -> Result<Option<User>> {
+        let maybe_user = db
+            .conn
+            .call(move |conn| {
+                let mut statement = conn.prepare(
+                    "SELECT id, username, email, password, created_at, updated_at, is_admin, suspended_at, display_name, bio, website FROM users WHERE id = :id;",
+                )?;
+                let mut rows = statement.query(named_params! {":id": id})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(User::from_sql_row(row)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+
+        Ok(maybe_user)
+    }
+
+    // Lists users whose username or email contains `query`, or every user when there's no query
+    pub async fn cursor_paginated_search(
+        db: &Database,
+        query: Option<String>,
+        limit: usize,
+        direction: Direction,
+        cursor: Option<Uuid>,
+    ) -> Result<Vec<User>> {
+        let users: Vec<_> = db
+            .conn
+            .call(move |conn| {
+                let direction_sql = direction.to_raw_sql();
+                let cursor_sql = match (cursor, &direction) {
+                    (None, _) => "",
+                    (Some(_), Direction::Ascending) => "AND id > :cursor",
+                    (Some(_), Direction::Descending) => "AND id < :cursor",
+                };
+                let raw_sql = format!(
+                    r"SELECT id, username, email, password, created_at, updated_at, is_admin, suspended_at, display_name, bio, website
+                    FROM users
+                    WHERE (:pattern IS NULL OR username LIKE :pattern ESCAPE '\' OR email LIKE :pattern ESCAPE '\') {cursor_sql}
+                    ORDER BY id {direction_sql}
+                    LIMIT :limit;"
+                );
+                let pattern = query.map(|q| format!("%{}%", escape_like(&q.to_lowercase())));
+                let mut stmt = conn.prepare(&raw_sql)?;
+                let user_iter = match cursor {
+                    None => stmt.query_map(
+                        named_params! {":pattern": pattern, ":limit": limit},
+                        User::from_sql_row,
+                    )?,
+                    Some(cursor) => stmt.query_map(
+                        named_params! {":pattern": pattern, ":limit": limit, ":cursor": cursor},
+                        User::from_sql_row,
+                    )?,
+                };
+                Ok(user_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(users)
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    // Suspending an account also signs it out everywhere. Its API keys are kept, but can't be used
+    // until the account is unsuspended.
+    pub async fn suspend(&self, db: &Database) -> Result<usize> {
+        tracing::info!("suspending user {self}");
+        let id = self.id;
+        let now = Timestamp::now();
         let result = db
             .conn
             .call(move |conn| {
-                let mut statement =
-                    conn.prepare("DELETE FROM session_tokens WHERE user_id = :user_id;")?;
-                let result = statement.execute(named_params! {
-                    ":user_id": self.id
-                })?;
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let result = {
+                    let mut users_stmt = tx.prepare(
+                        "UPDATE users SET suspended_at = :suspended_at WHERE id = :id AND suspended_at IS NULL;",
+                    )?;
+                    let result = users_stmt.execute(
+                        named_params! {":suspended_at": now.as_millisecond(), ":id": id},
+                    )?;
+
+                    let mut sessions_stmt =
+                        tx.prepare("DELETE FROM session_tokens WHERE user_id = :user_id;")?;
+                    sessions_stmt.execute(named_params! {":user_id": id})?;
+                    result
+                };
+                tx.commit()?;
                 Ok(result)
             })
             .await?;
+
         Ok(result)
     }
+
+    pub async fn unsuspend(&self, db: &Database) -> Result<usize> {
+        tracing::info!("unsuspending user {self}");
+        let id = self.id;
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("UPDATE users SET suspended_at = NULL WHERE id = :id;")?;
+                Ok(stmt.execute(named_params! {":id": id})?)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    // Admins are bootstrapped from the config at startup, since there's no admin around yet to
+    // grant the first admin flag. Each configured name is bound to the user holding it the first
+    // time it's seen, and from then on the role belongs to that user, whatever they're called, so
+    // that claiming a name an admin has changed away from doesn't make anyone an admin. Exactly the
+    // bound users end up admins, so taking a name off the list revokes that user's role and frees
+    // the name to be bound again. Returns how many users gained or lost the role.
+    pub async fn sync_admins_by_usernames(db: &Database, usernames: Vec<String>) -> Result<usize> {
+        tracing::info!("syncing admins with usernames {usernames:?}");
+        let now = Timestamp::now();
+        let (granted, revoked) = db
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                {
+                    let mut stmt = tx.prepare("SELECT username FROM configured_admins;")?;
+                    let bound = stmt
+                        .query_map([], |row| row.get::<_, String>(0))?
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+                    let mut stmt =
+                        tx.prepare("DELETE FROM configured_admins WHERE username = :username;")?;
+                    for username in bound {
+                        if !usernames.contains(&username) {
+                            stmt.execute(named_params! {":username": username})?;
+                        }
+                    }
+                    let mut stmt = tx.prepare(
+                        r"INSERT INTO configured_admins (username, user_id, created_at)
+                        SELECT username, id, :created_at FROM users WHERE username = :username
+                        ON CONFLICT(username) DO NOTHING;",
+                    )?;
+                    for username in &usernames {
+                        stmt.execute(named_params! {
+                            ":username": username,
+                            ":created_at": now.as_millisecond(),
+                        })?;
+                    }
+                }
+                let revoked = {
+                    let mut stmt = tx.prepare(
+                        r"UPDATE users SET is_admin = 0
+                        WHERE is_admin = 1 AND id NOT IN (
+                            SELECT user_id FROM configured_admins WHERE user_id IS NOT NULL
+                        )
+                        RETURNING username;",
+                    )?;
+                    let revoked = stmt
+                        .query_map([], |row| row.get::<_, String>(0))?
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+                    revoked
+                };
+                let granted = tx.execute(
+                    r"UPDATE users SET is_admin = 1
+                    WHERE is_admin = 0 AND id IN (
+                        SELECT user_id FROM configured_admins WHERE user_id IS NOT NULL
+                    );",
+                    [],
+                )?;
+                tx.commit()?;
+                Ok((granted, revoked))
+            })
+            .await?;
+
+        if !revoked.is_empty() {
+            tracing::info!("revoked admin from users no longer configured as admins {revoked:?}");
+        }
+        Ok(granted + revoked.len())
+    }
+}
+
+impl HasOrderedId for User {
+    fn ordered_id(&self) -> Uuid {
+        self.id
+    }
 }
 
 #[derive(Clone, Debug, Display, PartialEq)]
@@ -187,7 +715,8 @@ impl FromStr for Username {
             Err(Error::Parse(
                 "Username may only contain alphanumeric characters or single hyphens, and may not begin or end with a hyphen".into(),
             ))
-        } else if s == "api"
+        } else if s == "admin"
+            || s == "api"
             || s == "api_sessions"
             || s == "assets"
             || s == "health"
@@ -252,6 +781,13 @@ impl FromStr for EmailAddress {
     }
 }
 
+impl EmailAddress {
+    // Everything after the last '@', which parsing guarantees is present and non-empty
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map_or("", |(_, domain)| domain)
+    }
+}
+
 impl TryFrom<&String> for EmailAddress {
     type Error = Error;
 
@@ -272,6 +808,158 @@ impl FromSql for EmailAddress {
     }
 }
 
+#[derive(Clone, Debug, Default, Display, PartialEq)]
+pub struct DisplayName(String);
+
+impl FromStr for DisplayName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.chars().count() > 64 {
+            Err(Error::Parse(
+                "Display name is too long (maximum is 64 characters)".into(),
+            ))
+        } else if s.chars().any(char::is_control) {
+            Err(Error::Parse(
+                "Display name may not contain control characters".into(),
+            ))
+        } else {
+            Ok(Self(s.to_string()))
+        }
+    }
+}
+
+impl DisplayName {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<&String> for DisplayName {
+    type Error = Error;
+
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl ToSql for DisplayName {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        self.0.to_sql()
+    }
+}
+
+impl FromSql for DisplayName {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).map(Self)
+    }
+}
+
+#[derive(Clone, Debug, Default, Display, PartialEq)]
+pub struct Bio(String);
+
+impl FromStr for Bio {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.chars().count() > 256 {
+            Err(Error::Parse(
+                "Bio is too long (maximum is 256 characters)".into(),
+            ))
+        } else {
+            Ok(Self(s.to_string()))
+        }
+    }
+}
+
+impl Bio {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<&String> for Bio {
+    type Error = Error;
+
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl ToSql for Bio {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        self.0.to_sql()
+    }
+}
+
+impl FromSql for Bio {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).map(Self)
+    }
+}
+
+// A link to the user's own site, which is only ever rendered as an href, so anything other than a
+// plain http(s) URL is refused to keep out `javascript:` and friends
+#[derive(Clone, Debug, Default, Display, PartialEq)]
+pub struct Website(String);
+
+impl FromStr for Website {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let lowercase = s.to_lowercase();
+
+        if s.is_empty() {
+            Ok(Self::default())
+        } else if s.chars().count() > 256 {
+            Err(Error::Parse(
+                "Website is too long (maximum is 256 characters)".into(),
+            ))
+        } else if !(lowercase.starts_with("http://") || lowercase.starts_with("https://")) {
+            Err(Error::Parse(
+                "Website must begin with http:// or https://".into(),
+            ))
+        } else if s.chars().any(|c| c.is_whitespace() || c.is_control())
+            || s.split_once("://").is_some_and(|(_, rest)| rest.is_empty())
+        {
+            Err(Error::Parse("Website is not a valid URL".into()))
+        } else {
+            Ok(Self(s.to_string()))
+        }
+    }
+}
+
+impl Website {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<&String> for Website {
+    type Error = Error;
+
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl ToSql for Website {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        self.0.to_sql()
+    }
+}
+
+impl FromSql for Website {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).map(Self)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UnhashedPassword(SecretString);
 
@@ -310,19 +998,76 @@ impl PartialEq for UnhashedPassword {
 #[derive(Clone, Debug)]
 pub struct HashedPassword(SecretString);
 
-impl TryFrom<UnhashedPassword> for HashedPassword {
-    type Error = Error;
+impl HashedPassword {
+    pub async fn new(
+        password: UnhashedPassword,
+        hashing_pool: &PasswordHashingPool,
+    ) -> Result<Self> {
+        hashing_pool
+            .run(move |params| {
+                Ok(HashedPassword(SecretString::new(
+                    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+                        .hash_password(
+                            password.expose_secret().as_bytes(),
+                            &SaltString::generate(&mut OsRng),
+                        )?
+                        .to_string()
+                        .into(),
+                )))
+            })
+            .await?
+    }
+
+    // A well formed hash with the given parameters, whose salt and output are all zeros, so that
+    // no password will realistically verify against it. Verifying against it takes as long as
+    // verifying against a real hash created with `params` would.
+    pub fn dummy(params: &Params) -> Self {
+        HashedPassword(SecretString::new(
+            format!(
+                "$argon2id$v=19$m={},t={},p={}${DUMMY_SALT}${DUMMY_OUTPUT}",
+                params.m_cost(),
+                params.t_cost(),
+                params.p_cost(),
+            )
+            .into(),
+        ))
+    }
+
+    // The verifying parameters are read from the hash itself, so older hashes still verify after
+    // the configured parameters change
+    pub async fn verify(
+        &self,
+        password: &UnhashedPassword,
+        hashing_pool: &PasswordHashingPool,
+    ) -> Result<()> {
+        let password = password.clone();
+        let hashed_password = self.clone();
+        hashing_pool
+            .run(move |_| {
+                Ok(Argon2::default().verify_password(
+                    password.expose_secret().as_bytes(),
+                    &PasswordHash::new(hashed_password.expose_secret())?,
+                )?)
+            })
+            .await?
+    }
+
+    // Whether this hash was created with a different algorithm or version, or with any cost
+    // parameter lower than `params`. A hash that can't be parsed counts as weaker, since it can't
+    // be verified anyway.
+    pub fn is_weaker_than(&self, params: &Params) -> bool {
+        let Ok(hash) = PasswordHash::new(self.expose_secret()) else {
+            return true;
+        };
+        let Ok(hash_params) = Params::try_from(&hash) else {
+            return true;
+        };
 
-    fn try_from(value: UnhashedPassword) -> Result<Self, Self::Error> {
-        Ok(HashedPassword(SecretString::new(
-            Argon2::default()
-                .hash_password(
-                    value.expose_secret().as_bytes(),
-                    &SaltString::generate(&mut OsRng),
-                )?
-                .to_string()
-                .into(),
-        )))
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || hash_params.m_cost() < params.m_cost()
+            || hash_params.t_cost() < params.t_cost()
+            || hash_params.p_cost() < params.p_cost()
     }
 }
 
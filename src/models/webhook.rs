@@ -0,0 +1,627 @@
+use crate::db::Database;
+use crate::helpers::webhook_helper;
+use crate::models::paste::Paste;
+use crate::models::prelude::*;
+use derive_more::Display;
+use jiff::{SignedDuration, Timestamp};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
+use rusqlite::{named_params, Row, Transaction};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use std::str::FromStr;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+pub const MAX_WEBHOOKS_PER_USER: usize = 10;
+// With the first retry a minute after the first attempt and the delay doubling each time after
+// that, a delivery is given up on a little over two hours after its event
+pub const MAX_DELIVERY_ATTEMPTS: i64 = 8;
+const FIRST_RETRY_DELAY: SignedDuration = SignedDuration::from_mins(1);
+// Up to DELIVERY_CONCURRENCY deliveries are attempted at once, each giving up after 10 seconds, so
+// a run where every receiver times out takes about 20 seconds
+const DELIVERIES_PER_RUN: i64 = 20;
+const DELIVERY_CONCURRENCY: usize = 10;
+const DELIVERY_LOG_LENGTH: i64 = 50;
+const DELIVERY_RETENTION: SignedDuration = SignedDuration::from_hours(30 * 24);
+const WEBHOOK_COLUMNS: &str = "id, user_id, url, events, secret, created_at";
+
+#[derive(Clone, Debug, Display)]
+#[display("{{ id: {id}, user_id: {user_id} }}")]
+pub struct Webhook {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub url: String,
+    pub events: WebhookEvents,
+    pub secret: WebhookSecret,
+    pub created_at: Timestamp,
+}
+
+impl Webhook {
+    // Unless private addresses are allowed, URLs have to name a domain, since IP addresses can't
+    // be checked as they're resolved when delivering
+    pub fn new(
+        user_id: Uuid,
+        url: &str,
+        events: WebhookEvents,
+        allow_private_addresses: bool,
+    ) -> Result<Self> {
+        let url = url.trim();
+        if url.chars().count() > 2048 {
+            return Err(Error::Parse(
+                "URL may not be longer than 2048 characters".into(),
+            ));
+        }
+        match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => {
+                if !allow_private_addresses && webhook_helper::has_ip_address_host(&parsed) {
+                    return Err(Error::Parse(
+                        "URL must use a domain name rather than an IP address".into(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(Error::Parse(
+                    "URL must be a full http:// or https:// address".into(),
+                ))
+            }
+        }
+        if events.is_empty() {
+            return Err(Error::Parse("Choose at least one event".into()));
+        }
+
+        Ok(Self {
+            id: Uuid::now_v7(),
+            user_id,
+            url: url.to_string(),
+            events,
+            secret: WebhookSecret::generate(),
+            created_at: Timestamp::now(),
+        })
+    }
+
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            url: row.get(2)?,
+            events: row.get(3)?,
+            secret: row.get(4)?,
+            created_at: Timestamp::from_millisecond(row.get(5)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(5, Type::Integer, Box::new(e))
+            })?,
+        })
+    }
+
+    pub async fn all_for_user_id(db: &Database, user_id: Uuid) -> Result<Vec<Self>> {
+        let webhooks = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE user_id = :user_id ORDER BY id DESC;"
+                ))?;
+                let webhook_iter =
+                    stmt.query_map(named_params! {":user_id": user_id}, Self::from_sql_row)?;
+                Ok(webhook_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(webhooks)
+    }
+
+    pub async fn find_scoped_by_user_id(
+        db: &Database,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<Self>> {
+        let maybe_webhook = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id = :id AND user_id = :user_id;"
+                ))?;
+                let mut rows = stmt.query(named_params! {":id": id, ":user_id": user_id})?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(Self::from_sql_row(row)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+        Ok(maybe_webhook)
+    }
+
+    pub async fn count_for_user_id(db: &Database, user_id: Uuid) -> Result<usize> {
+        let count = db
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT count(*) FROM webhooks WHERE user_id = :user_id;")?;
+                let count =
+                    stmt.query_row(named_params! {":user_id": user_id}, |row| row.get(0))?;
+                Ok(count)
+            })
+            .await?;
+        Ok(count)
+    }
+
+    pub async fn insert(self, db: &Database) -> Result<usize> {
+        tracing::info!("inserting webhook {self}");
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"INSERT INTO webhooks (id, user_id, url, events, secret, created_at)
+                    VALUES (:id, :user_id, :url, :events, :secret, :created_at);",
+                )?;
+                let result = stmt.execute(named_params! {
+                    ":id": self.id,
+                    ":user_id": self.user_id,
+                    ":url": self.url,
+                    ":events": self.events,
+                    ":secret": self.secret,
+                    ":created_at": self.created_at.as_millisecond(),
+                })?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    // Deliveries that haven't been made yet are dropped along with the webhook
+    pub async fn delete(self, db: &Database) -> Result<usize> {
+        tracing::info!("deleting webhook {self}");
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("DELETE FROM webhooks WHERE id = :id;")?;
+                let result = stmt.execute(named_params! {":id": self.id})?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookEvent {
+    PasteCreated,
+    PasteUpdated,
+    PasteDeleted,
+}
+
+impl WebhookEvent {
+    pub const ALL: [Self; 3] = [Self::PasteCreated, Self::PasteUpdated, Self::PasteDeleted];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PasteCreated => "paste.created",
+            Self::PasteUpdated => "paste.updated",
+            Self::PasteDeleted => "paste.deleted",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::PasteCreated => "One of your pastes is created",
+            Self::PasteUpdated => "One of your pastes is edited",
+            Self::PasteDeleted => "One of your pastes is deleted",
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+            .ok_or_else(|| Error::Parse(format!("Unrecognized event '{s}'")))
+    }
+}
+
+impl std::fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ToSql for WebhookEvent {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for WebhookEvent {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).and_then(|s| {
+            s.parse()
+                .map_err(|_| FromSqlError::Other("Unrecognized value for event".into()))
+        })
+    }
+}
+
+// The events a webhook is sent, always kept in the order of `WebhookEvent::ALL` so that equal
+// sets compare and store the same way
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WebhookEvents(Vec<WebhookEvent>);
+
+impl WebhookEvents {
+    pub fn all() -> Self {
+        Self(WebhookEvent::ALL.to_vec())
+    }
+
+    pub fn contains(&self, event: &WebhookEvent) -> bool {
+        self.0.contains(event)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = WebhookEvent> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl FromIterator<WebhookEvent> for WebhookEvents {
+    fn from_iter<I: IntoIterator<Item = WebhookEvent>>(iter: I) -> Self {
+        let events: Vec<_> = iter.into_iter().collect();
+        Self(
+            WebhookEvent::ALL
+                .into_iter()
+                .filter(|event| events.contains(event))
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for WebhookEvents {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace().map(str::parse).collect()
+    }
+}
+
+impl std::fmt::Display for WebhookEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let events: Vec<_> = self.iter().map(WebhookEvent::as_str).collect();
+        f.write_str(&events.join(" "))
+    }
+}
+
+impl ToSql for WebhookEvents {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        Ok(self.to_string().into())
+    }
+}
+
+impl FromSql for WebhookEvents {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).and_then(|s| {
+            s.parse()
+                .map_err(|_| FromSqlError::Other("Unrecognized value for events".into()))
+        })
+    }
+}
+
+// The key that deliveries are signed with, which receivers use to check that a delivery really
+// came from us
+#[derive(Clone)]
+pub struct WebhookSecret(SecretString);
+
+impl WebhookSecret {
+    pub fn generate() -> Self {
+        let mut rng = ChaCha20Rng::from_entropy();
+        Self(SecretString::new(
+            format!("{:032x}{:032x}", rng.gen::<u128>(), rng.gen::<u128>()).into(),
+        ))
+    }
+}
+
+impl ExposeSecret<str> for WebhookSecret {
+    fn expose_secret(&self) -> &str {
+        self.0.expose_secret()
+    }
+}
+
+impl std::fmt::Debug for WebhookSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[REDACTED WebhookSecret]")
+    }
+}
+
+impl ToSql for WebhookSecret {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>, rusqlite::Error> {
+        Ok(self.expose_secret().into())
+    }
+}
+
+impl FromSql for WebhookSecret {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        String::column_result(value).map(|s| Self(SecretString::new(s.into())))
+    }
+}
+
+// The body of a delivery
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: &'static str,
+    occurred_at: Timestamp,
+    paste: &'a Paste,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: String,
+    pub attempts: i64,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub created_at: Timestamp,
+    pub last_attempt_at: Option<Timestamp>,
+    pub next_attempt_at: Option<Timestamp>,
+    pub delivered_at: Option<Timestamp>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryState {
+    Delivered,
+    Pending,
+    Failed,
+}
+
+impl WebhookDelivery {
+    pub fn from_sql_row(row: &Row) -> rusqlite::Result<Self> {
+        let timestamp = |idx: usize| -> rusqlite::Result<Option<Timestamp>> {
+            row.get::<_, Option<i64>>(idx)?
+                .map(Timestamp::from_millisecond)
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(idx, Type::Integer, Box::new(e))
+                })
+        };
+        Ok(Self {
+            id: row.get(0)?,
+            webhook_id: row.get(1)?,
+            event: row.get(2)?,
+            payload: row.get(3)?,
+            attempts: row.get(4)?,
+            response_status: row.get(5)?,
+            error: row.get(6)?,
+            created_at: timestamp(7)?.ok_or(rusqlite::Error::InvalidColumnType(
+                7,
+                "created_at".into(),
+                Type::Null,
+            ))?,
+            last_attempt_at: timestamp(8)?,
+            next_attempt_at: timestamp(9)?,
+            delivered_at: timestamp(10)?,
+        })
+    }
+
+    pub fn state(&self) -> DeliveryState {
+        if self.delivered_at.is_some() {
+            DeliveryState::Delivered
+        } else if self.next_attempt_at.is_some() {
+            DeliveryState::Pending
+        } else {
+            DeliveryState::Failed
+        }
+    }
+
+    // Queues a delivery of the event to each of the paste owner's webhooks that wants it. This is
+    // done in the same transaction as the change to the paste, so that an event is queued if and
+    // only if the change it describes is made.
+    pub fn tx_enqueue(
+        tx: &Transaction,
+        event: WebhookEvent,
+        paste: &Paste,
+    ) -> tokio_rusqlite::Result<usize> {
+        let mut stmt = tx.prepare("SELECT id, events FROM webhooks WHERE user_id = :user_id;")?;
+        let webhook_ids = stmt
+            .query_map(named_params! {":user_id": paste.user_id}, |row| {
+                Ok((row.get::<_, Uuid>(0)?, row.get::<_, WebhookEvents>(1)?))
+            })?
+            .filter_map(|row| match row {
+                Ok((id, events)) if events.contains(&event) => Some(Ok(id)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if webhook_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let now = Timestamp::now();
+        let payload = serde_json::to_string(&Payload {
+            event: event.as_str(),
+            occurred_at: now,
+            paste,
+        })
+        .map_err(|e| tokio_rusqlite::Error::Other(Box::new(e)))?;
+        let mut stmt = tx.prepare(
+            r"INSERT INTO webhook_deliveries (id, webhook_id, event, payload, created_at, next_attempt_at)
+            VALUES (:id, :webhook_id, :event, :payload, :created_at, :created_at);",
+        )?;
+        for webhook_id in &webhook_ids {
+            stmt.execute(named_params! {
+                ":id": Uuid::now_v7(),
+                ":webhook_id": webhook_id,
+                ":event": event,
+                ":payload": payload,
+                ":created_at": now.as_millisecond(),
+            })?;
+        }
+        tracing::info!(
+            "queued {} deliveries of {event} for paste {paste}",
+            webhook_ids.len()
+        );
+        Ok(webhook_ids.len())
+    }
+
+    pub async fn recent_for_webhook_id(db: &Database, webhook_id: Uuid) -> Result<Vec<Self>> {
+        let deliveries = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"SELECT id, webhook_id, event, payload, attempts, response_status, error, created_at, last_attempt_at, next_attempt_at, delivered_at
+                    FROM webhook_deliveries WHERE webhook_id = :webhook_id
+                    ORDER BY id DESC LIMIT :limit;",
+                )?;
+                let delivery_iter = stmt.query_map(
+                    named_params! {":webhook_id": webhook_id, ":limit": DELIVERY_LOG_LENGTH},
+                    Self::from_sql_row,
+                )?;
+                Ok(delivery_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(deliveries)
+    }
+
+    // Makes an attempt at each delivery that's due, starting with the oldest and making several
+    // at once, and returns how many attempts were made. Only one of these should run at a time,
+    // since nothing stops two runs from attempting the same delivery.
+    pub async fn deliver_due(
+        db: &Database,
+        client: &webhook_helper::WebhookClient,
+    ) -> Result<usize> {
+        let now = Timestamp::now();
+        let due = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"SELECT
+                        webhook_deliveries.id, webhook_deliveries.webhook_id, webhook_deliveries.event, webhook_deliveries.payload,
+                        webhook_deliveries.attempts, webhook_deliveries.response_status, webhook_deliveries.error,
+                        webhook_deliveries.created_at, webhook_deliveries.last_attempt_at, webhook_deliveries.next_attempt_at,
+                        webhook_deliveries.delivered_at, webhooks.url, webhooks.secret
+                    FROM webhook_deliveries JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+                    WHERE webhook_deliveries.next_attempt_at <= :now
+                    ORDER BY webhook_deliveries.next_attempt_at, webhook_deliveries.id LIMIT :limit;",
+                )?;
+                let due_iter = stmt.query_map(
+                    named_params! {":now": now.as_millisecond(), ":limit": DELIVERIES_PER_RUN},
+                    |row| {
+                        Ok((
+                            Self::from_sql_row(row)?,
+                            row.get::<_, String>(11)?,
+                            row.get::<_, WebhookSecret>(12)?,
+                        ))
+                    },
+                )?;
+                Ok(due_iter.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+
+        let attempted = due.len();
+        let mut attempts = JoinSet::new();
+        for (delivery, url, secret) in due {
+            if attempts.len() >= DELIVERY_CONCURRENCY {
+                if let Some(result) = attempts.join_next().await {
+                    result??;
+                }
+            }
+            let db = db.clone();
+            let client = client.clone();
+            attempts.spawn(async move {
+                let result = webhook_helper::send(
+                    &client,
+                    &url,
+                    secret.expose_secret(),
+                    delivery.event.as_str(),
+                    delivery.id,
+                    delivery.payload.clone(),
+                )
+                .await;
+                let (response_status, error) = match result {
+                    Ok(status) => (Some(status), None),
+                    Err(e) => {
+                        tracing::info!("webhook delivery {} got no response: {e}", delivery.id);
+                        (None, Some(e.to_string()))
+                    }
+                };
+                delivery
+                    .record_attempt(&db, response_status.map(|s| s.as_u16()), error)
+                    .await
+            });
+        }
+        while let Some(result) = attempts.join_next().await {
+            result??;
+        }
+        Ok(attempted)
+    }
+
+    // Successful attempts are those answered with a 2xx. Anything else is retried with
+    // exponential backoff, until the delivery runs out of attempts.
+    async fn record_attempt(
+        self,
+        db: &Database,
+        response_status: Option<u16>,
+        error: Option<String>,
+    ) -> Result<usize> {
+        let now = Timestamp::now();
+        let attempts = self.attempts + 1;
+        let succeeded = response_status.is_some_and(|status| (200..300).contains(&status));
+        let next_attempt_at = if succeeded || attempts >= MAX_DELIVERY_ATTEMPTS {
+            None
+        } else {
+            let exponent = u32::try_from(attempts - 1).unwrap_or(u32::MAX);
+            let delay = FIRST_RETRY_DELAY
+                .checked_mul(2_i32.saturating_pow(exponent))
+                .unwrap_or(SignedDuration::MAX);
+            Some(now.saturating_add(delay))
+        };
+        let delivered_at = succeeded.then_some(now);
+        tracing::info!(
+            "webhook delivery {} attempt {attempts} got {response_status:?}",
+            self.id
+        );
+
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"UPDATE webhook_deliveries
+                    SET attempts = :attempts, response_status = :response_status, error = :error,
+                        last_attempt_at = :now, next_attempt_at = :next_attempt_at, delivered_at = :delivered_at
+                    WHERE id = :id;",
+                )?;
+                let result = stmt.execute(named_params! {
+                    ":attempts": attempts,
+                    ":response_status": response_status,
+                    ":error": error,
+                    ":now": now.as_millisecond(),
+                    ":next_attempt_at": next_attempt_at.map(Timestamp::as_millisecond),
+                    ":delivered_at": delivered_at.map(Timestamp::as_millisecond),
+                    ":id": self.id,
+                })?;
+                Ok(result)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    // Deliveries that are finished with, whether they were delivered or given up on, are kept in
+    // the log for 30 days
+    pub async fn purge_old(db: &Database) -> Result<usize> {
+        tracing::info!("purging old webhook deliveries");
+        let cutoff = Timestamp::now().saturating_sub(DELIVERY_RETENTION);
+        let result = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "DELETE FROM webhook_deliveries WHERE next_attempt_at IS NULL AND created_at < :cutoff;",
+                )?;
+                let result = stmt.execute(named_params! {":cutoff": cutoff.as_millisecond()})?;
+                Ok(result)
+            })
+            .await?;
+
+        tracing::info!("done purging webhook deliveries, purged {result} webhook deliveries");
+        Ok(result)
+    }
+}
@@ -0,0 +1,10 @@
+use crate::models::instance_stats::InstanceStats;
+use crate::models::session::Session;
+use askama_axum::Template;
+
+#[derive(Default, Template)]
+#[template(path = "admin/index.html")]
+pub struct IndexPage {
+    pub session: Option<Session>,
+    pub stats: InstanceStats,
+}
@@ -0,0 +1,3 @@
+pub mod index;
+pub mod pastes;
+pub mod users;
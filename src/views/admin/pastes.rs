@@ -0,0 +1,22 @@
+use crate::helpers::pagination_helper::CursorPaginationResponse;
+use crate::helpers::view_helper::filters;
+use crate::models::paste::Paste;
+use crate::models::session::Session;
+use crate::models::user::Username;
+use askama_axum::Template;
+
+#[derive(Template)]
+#[template(path = "admin/pastes.html")]
+pub struct PastesPage {
+    pub session: Option<Session>,
+    pub paste_username_pairs: Vec<(Paste, Username)>,
+    pub pagination: CursorPaginationResponse,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Template)]
+#[template(path = "admin/partials/paste_row.html")]
+pub struct PasteRowPartial {
+    pub paste: Paste,
+    pub username: Username,
+}
@@ -0,0 +1,21 @@
+use crate::helpers::pagination_helper::CursorPaginationResponse;
+use crate::helpers::view_helper::filters;
+use crate::models::session::Session;
+use crate::models::user::User;
+use askama_axum::Template;
+
+#[derive(Template)]
+#[template(path = "admin/users.html")]
+pub struct UsersPage {
+    pub session: Option<Session>,
+    pub query: Option<String>,
+    pub users: Vec<User>,
+    pub pagination: CursorPaginationResponse,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Template)]
+#[template(path = "admin/partials/user_row.html")]
+pub struct UserRowPartial {
+    pub user: User,
+}
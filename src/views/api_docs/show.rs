@@ -0,0 +1,90 @@
+use crate::models::session::Session;
+use askama_axum::Template;
+use utoipa::openapi::path::{Operation, ParameterIn};
+use utoipa::openapi::{OpenApi, RefOr, Required};
+
+// A reference for the JSON API, rendered from the same OpenAPI document that the API serves
+#[derive(Template)]
+#[template(path = "api_docs/show.html")]
+pub struct ShowPage {
+    pub session: Option<Session>,
+    pub operations: Vec<ApiOperation>,
+}
+
+pub struct ApiOperation {
+    pub method: &'static str,
+    pub path: String,
+    pub summary: String,
+    pub description: String,
+    pub parameters: Vec<ApiParameter>,
+    pub responses: Vec<(String, String)>,
+}
+
+pub struct ApiParameter {
+    pub name: String,
+    pub location: &'static str,
+    pub required: bool,
+    pub description: String,
+}
+
+impl From<(Option<Session>, OpenApi)> for ShowPage {
+    fn from(value: (Option<Session>, OpenApi)) -> Self {
+        let (session, openapi) = value;
+        let mut operations = Vec::new();
+        for (path, item) in openapi.paths.paths {
+            for (method, operation) in [
+                ("GET", item.get),
+                ("POST", item.post),
+                ("PUT", item.put),
+                ("PATCH", item.patch),
+                ("DELETE", item.delete),
+            ] {
+                if let Some(operation) = operation {
+                    operations.push(ApiOperation::new(method, path.clone(), operation));
+                }
+            }
+        }
+        Self {
+            session,
+            operations,
+        }
+    }
+}
+
+impl ApiOperation {
+    fn new(method: &'static str, path: String, operation: Operation) -> Self {
+        let parameters = operation
+            .parameters
+            .unwrap_or_default()
+            .into_iter()
+            .map(|parameter| ApiParameter {
+                name: parameter.name,
+                location: match parameter.parameter_in {
+                    ParameterIn::Query => "query",
+                    ParameterIn::Path => "path",
+                    ParameterIn::Header => "header",
+                    ParameterIn::Cookie => "cookie",
+                },
+                required: parameter.required == Required::True,
+                description: parameter.description.unwrap_or_default(),
+            })
+            .collect();
+        let responses = operation
+            .responses
+            .responses
+            .into_iter()
+            .map(|(status, response)| match response {
+                RefOr::T(response) => (status, response.description),
+                RefOr::Ref(_) => (status, String::new()),
+            })
+            .collect();
+        Self {
+            method,
+            path,
+            summary: operation.summary.unwrap_or_default(),
+            description: operation.description.unwrap_or_default(),
+            parameters,
+            responses,
+        }
+    }
+}
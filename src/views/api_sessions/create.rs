@@ -1,11 +1,34 @@
-use crate::models::api_session::UnhashedKey;
+use crate::controllers::invite_codes_controller::Expiry;
+use crate::helpers::view_helper::filters;
+use crate::models::api_session::{ApiKey, Scopes, UnhashedKey};
 use askama_axum::Template;
 use secrecy::ExposeSecret;
-use uuid::Uuid;
 
 #[derive(Template)]
 #[template(path = "api_sessions/create.html")]
 pub struct CreatePage {
     pub unhashed_key: UnhashedKey,
-    pub api_key_id: Uuid,
+    pub api_key: ApiKey,
+    pub new_api_key_form: NewApiKeyFormPartial,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Template)]
+#[template(path = "api_sessions/partials/new_api_key_form.html")]
+pub struct NewApiKeyFormPartial {
+    pub name: String,
+    pub scopes: Scopes,
+    pub expiry: Expiry,
+    pub error_message: Option<String>,
+}
+
+impl Default for NewApiKeyFormPartial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            scopes: Scopes::all(),
+            expiry: Expiry::Never,
+            error_message: None,
+        }
+    }
 }
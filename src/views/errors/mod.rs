@@ -1,4 +1,6 @@
 pub mod forbidden;
 pub mod internal_server_error;
 pub mod not_found;
+pub mod service_unavailable;
+pub mod too_many_requests;
 pub mod unauthorized;
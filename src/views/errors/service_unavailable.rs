@@ -0,0 +1,8 @@
+use crate::models::session::Session;
+use askama::Template;
+
+#[derive(Debug, Template)]
+#[template(path = "errors/503.html")]
+pub struct ServiceUnavailablePage {
+    pub session: Option<Session>,
+}
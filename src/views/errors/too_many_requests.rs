@@ -0,0 +1,8 @@
+use crate::models::session::Session;
+use askama::Template;
+
+#[derive(Debug, Template)]
+#[template(path = "errors/429.html")]
+pub struct TooManyRequestsPage {
+    pub session: Option<Session>,
+}
@@ -0,0 +1,43 @@
+use crate::config::RegistrationMode;
+use crate::controllers::invite_codes_controller::Expiry;
+use crate::helpers::view_helper::filters;
+use crate::models::invite_code::{InviteCode, InviteCodeRedemption, MAX_USES_PER_CODE};
+use crate::models::session::Session;
+use askama_axum::Template;
+
+#[derive(Default, Template)]
+#[template(path = "invite_codes/index.html")]
+pub struct IndexPage {
+    pub session: Option<Session>,
+    pub registration_mode: RegistrationMode,
+    pub invite_codes: Vec<InviteCode>,
+    pub redemptions: Vec<InviteCodeRedemption>,
+    pub new_invite_code_form: NewInviteCodeFormPartial,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Template)]
+#[template(path = "invite_codes/partials/new_invite_code_form.html")]
+pub struct NewInviteCodeFormPartial {
+    pub remaining_invitations: i64,
+    pub max_uses: i64,
+    pub expiry: Expiry,
+    pub error_message: Option<String>,
+}
+
+impl NewInviteCodeFormPartial {
+    pub fn max_uses_limit(&self) -> i64 {
+        self.remaining_invitations.clamp(1, MAX_USES_PER_CODE)
+    }
+}
+
+impl Default for NewInviteCodeFormPartial {
+    fn default() -> Self {
+        Self {
+            remaining_invitations: 0,
+            max_uses: 1,
+            expiry: Expiry::default(),
+            error_message: None,
+        }
+    }
+}
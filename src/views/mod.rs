@@ -1,6 +1,10 @@
+pub mod admin;
+pub mod api_docs;
 pub mod api_sessions;
 pub mod errors;
 pub mod index;
+pub mod invite_codes;
 pub mod pastes;
 pub mod sessions;
 pub mod users;
+pub mod webhooks;
@@ -0,0 +1,24 @@
+use crate::helpers::diff_helper::DiffLine;
+use crate::models::session::Session;
+use crate::views::pastes::edit::EditFormPartial;
+use askama_axum::Template;
+use uuid::Uuid;
+
+#[derive(Debug, Template)]
+#[template(path = "pastes/conflict.html")]
+pub struct ConflictPage {
+    pub session: Option<Session>,
+    pub paste_id: Uuid,
+    pub filename: String,
+    pub changed_fields: Vec<ConflictingField>,
+    // From the current body to the body that was submitted
+    pub body_diff: Vec<DiffLine>,
+    pub edit_pastes_form: EditFormPartial,
+}
+
+#[derive(Debug)]
+pub struct ConflictingField {
+    pub label: &'static str,
+    pub current: String,
+    pub submitted: String,
+}
@@ -41,6 +41,8 @@ pub struct EditFormPartial {
     pub body_error_message: Option<String>,
     pub visibility: String,
     pub visibility_error_message: Option<String>,
+    // The ETag of the paste the form was filled from, so that saving can detect concurrent edits
+    pub version: String,
 }
 
 impl From<(Username, Paste)> for EditFormPartial {
@@ -53,6 +55,7 @@ impl From<(Username, Paste)> for EditFormPartial {
             description: paste.description.to_string(),
             body: paste.body.to_string(),
             visibility: paste.visibility.to_string(),
+            version: paste.etag().to_string(),
             ..Default::default()
         }
     }
@@ -68,6 +71,7 @@ impl From<(Username, Uuid, UpdateParams)> for EditFormPartial {
             description: params.description,
             body: params.body,
             visibility: params.visibility,
+            version: params.version.unwrap_or_default(),
             ..Default::default()
         }
     }
@@ -1,3 +1,4 @@
+pub mod conflict;
 pub mod edit;
 pub mod index;
 pub mod new;
@@ -11,4 +11,15 @@ pub struct ShowPage {
     pub paste: Paste,
     pub username: Username,
     pub syntax_highlighted_html: Option<String>,
+    pub pin_button: Option<PinButtonPartial>,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Default, Template)]
+#[template(path = "pastes/partials/pin_button.html")]
+pub struct PinButtonPartial {
+    pub username: String,
+    pub paste_id: String,
+    pub is_pinned: bool,
+    pub error_message: Option<String>,
 }
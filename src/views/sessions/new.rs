@@ -9,6 +9,7 @@ pub struct NewPage {
     pub session: Option<Session>,
     pub email: String,
     pub password: SecretString,
+    pub remember_me: bool,
     pub error_message: Option<String>,
 }
 
@@ -17,6 +18,7 @@ impl From<CreateParams> for NewPage {
         Self {
             email: params.email,
             password: params.password,
+            remember_me: params.remember_me,
             ..Default::default()
         }
     }
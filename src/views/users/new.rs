@@ -1,3 +1,4 @@
+use crate::config::RegistrationMode;
 use crate::controllers::users_controller::CreateParams;
 use crate::models::session::Session;
 use askama_axum::Template;
@@ -7,6 +8,7 @@ use secrecy::{ExposeSecret, SecretString};
 #[template(path = "users/new.html")]
 pub struct NewPage {
     pub session: Option<Session>,
+    pub registration_mode: RegistrationMode,
     pub username: String,
     pub username_error_message: Option<String>,
     pub email: String,
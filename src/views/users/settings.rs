@@ -1,8 +1,14 @@
-use crate::controllers::users_controller::ChangePasswordParams;
+use crate::controllers::users_controller::{
+    ChangePasswordParams, DeleteAccountParams, UpdateAccountParams, UpdateProfileParams,
+};
 use crate::helpers::view_helper::filters;
+use crate::models::account_deletion::AccountDeletion;
 use crate::models::api_session::ApiKey;
-use crate::models::session::Session;
+use crate::models::security_event::SecurityEvent;
+use crate::models::session::{Session, SessionToken};
+use crate::views::api_sessions::create::NewApiKeyFormPartial;
 use askama_axum::Template;
+use jiff::Timestamp;
 use secrecy::{ExposeSecret, SecretString};
 
 #[derive(Default, Template)]
@@ -10,7 +16,71 @@ use secrecy::{ExposeSecret, SecretString};
 pub struct SettingsPage {
     pub session: Option<Session>,
     pub api_keys: Vec<ApiKey>,
+    pub new_api_key_form: NewApiKeyFormPartial,
+    pub session_tokens: Vec<SessionToken>,
+    pub security_events: Vec<SecurityEvent>,
+    pub profile_form: ProfileFormPartial,
+    pub avatar_form: AvatarFormPartial,
+    pub account_form: AccountFormPartial,
     pub change_password_form: ChangePasswordFormPartial,
+    pub account_deletion: Option<AccountDeletion>,
+    pub delete_account_form: DeleteAccountFormPartial,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Default, Template)]
+#[template(path = "users/partials/profile_form.html")]
+pub struct ProfileFormPartial {
+    pub display_name: String,
+    pub display_name_error_message: Option<String>,
+    pub bio: String,
+    pub bio_error_message: Option<String>,
+    pub website: String,
+    pub website_error_message: Option<String>,
+    pub show_success_message: bool,
+}
+
+impl From<UpdateProfileParams> for ProfileFormPartial {
+    fn from(params: UpdateProfileParams) -> Self {
+        Self {
+            display_name: params.display_name,
+            bio: params.bio,
+            website: params.website,
+            ..Default::default()
+        }
+    }
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Default, Template)]
+#[template(path = "users/partials/avatar_form.html")]
+pub struct AvatarFormPartial {
+    pub username: String,
+    pub avatar_created_at: Option<Timestamp>,
+    pub error_message: Option<String>,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Default, Template)]
+#[template(path = "users/partials/account_form.html")]
+pub struct AccountFormPartial {
+    pub username: String,
+    pub username_error_message: Option<String>,
+    pub email: String,
+    pub email_error_message: Option<String>,
+    pub password: SecretString,
+    pub password_error_message: Option<String>,
+}
+
+impl From<UpdateAccountParams> for AccountFormPartial {
+    fn from(params: UpdateAccountParams) -> Self {
+        Self {
+            username: params.username,
+            email: params.email,
+            password: params.password,
+            ..Default::default()
+        }
+    }
 }
 
 // TODO: replace this partial with a block fragment once askama 0.13.0 releases
@@ -22,6 +92,7 @@ pub struct ChangePasswordFormPartial {
     pub new_password_confirm: SecretString,
     pub old_password_error_message: Option<String>,
     pub new_password_error_message: Option<String>,
+    pub revoke_api_keys: bool,
     pub show_success_message: bool,
 }
 
@@ -31,6 +102,25 @@ impl From<ChangePasswordParams> for ChangePasswordFormPartial {
             old_password: params.old_password,
             new_password: params.new_password,
             new_password_confirm: params.new_password_confirm,
+            revoke_api_keys: params.revoke_api_keys,
+            ..Default::default()
+        }
+    }
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Default, Template)]
+#[template(path = "users/partials/delete_account_form.html")]
+pub struct DeleteAccountFormPartial {
+    pub password: SecretString,
+    pub password_error_message: Option<String>,
+    pub has_grace_period: bool,
+}
+
+impl From<DeleteAccountParams> for DeleteAccountFormPartial {
+    fn from(params: DeleteAccountParams) -> Self {
+        Self {
+            password: params.password,
             ..Default::default()
         }
     }
@@ -1,15 +1,32 @@
 use crate::helpers::pagination_helper::CursorPaginationResponse;
 use crate::helpers::view_helper::filters;
 use crate::models::paste::Paste;
+use crate::models::paste_query::{PasteCursor, PasteFilterParams};
 use crate::models::session::Session;
 use crate::models::user::User;
 use askama_axum::Template;
+use jiff::Timestamp;
 
-#[derive(Template)]
+#[derive(Debug, Template)]
 #[template(path = "users/show.html")]
 pub struct ShowPage {
     pub session: Option<Session>,
     pub user: User,
+    pub avatar_created_at: Option<Timestamp>,
+    pub pinned_pastes: Vec<Paste>,
     pub paste_html_pairs: Vec<(Paste, Option<String>)>,
-    pub pagination: CursorPaginationResponse,
+    pub pagination: CursorPaginationResponse<PasteCursor>,
+    pub filters: PasteFilterParams,
+    pub filter_error: Option<String>,
+    pub show_owner_filter: bool,
+    pub show_visibility_filter: bool,
+    // Only shown to the profile's owner, who can act on several of their pastes at once
+    pub batch_actions: Option<BatchActionsPartial>,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Default, Template)]
+#[template(path = "users/partials/batch_actions.html")]
+pub struct BatchActionsPartial {
+    pub error_message: Option<String>,
 }
@@ -0,0 +1,11 @@
+use crate::models::webhook::Webhook;
+use crate::views::webhooks::index::NewWebhookFormPartial;
+use askama_axum::Template;
+use secrecy::ExposeSecret;
+
+#[derive(Template)]
+#[template(path = "webhooks/create.html")]
+pub struct CreatePage {
+    pub webhook: Webhook,
+    pub new_webhook_form: NewWebhookFormPartial,
+}
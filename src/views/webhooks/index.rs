@@ -0,0 +1,31 @@
+use crate::helpers::view_helper::filters;
+use crate::models::session::Session;
+use crate::models::webhook::{Webhook, WebhookEvents};
+use askama_axum::Template;
+
+#[derive(Default, Template)]
+#[template(path = "webhooks/index.html")]
+pub struct IndexPage {
+    pub session: Option<Session>,
+    pub webhooks: Vec<Webhook>,
+    pub new_webhook_form: NewWebhookFormPartial,
+}
+
+// TODO: replace this partial with a block fragment once askama 0.13.0 releases
+#[derive(Clone, Debug, Template)]
+#[template(path = "webhooks/partials/new_webhook_form.html")]
+pub struct NewWebhookFormPartial {
+    pub url: String,
+    pub events: WebhookEvents,
+    pub error_message: Option<String>,
+}
+
+impl Default for NewWebhookFormPartial {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: WebhookEvents::all(),
+            error_message: None,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use crate::helpers::view_helper::filters;
+use crate::models::session::Session;
+use crate::models::webhook::{DeliveryState, Webhook, WebhookDelivery};
+use askama_axum::Template;
+
+#[derive(Template)]
+#[template(path = "webhooks/show.html")]
+pub struct ShowPage {
+    pub session: Option<Session>,
+    pub webhook: Webhook,
+    pub deliveries: Vec<WebhookDelivery>,
+}
@@ -2,13 +2,23 @@ use crate::common::mocks::mock_user::MockUser;
 use crate::common::rand_helper;
 use crate::common::test_app::TestApp;
 use crate::prelude::*;
+use jiff::Timestamp;
 use uuid::Uuid;
 
+pub const ALL_SCOPES: [&str; 4] = [
+    "pastes:read",
+    "pastes:write",
+    "pastes:delete",
+    "account:read",
+];
+
 #[derive(Clone, Debug)]
 pub struct MockApiKey {
     pub id: String,
     pub name: String,
     pub unhashed_key: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<Timestamp>,
 }
 
 #[derive(Clone, Default)]
@@ -16,6 +26,8 @@ pub struct MockApiKeyBuilder {
     id: Option<String>,
     name: Option<String>,
     unhashed_key: Option<String>,
+    scopes: Option<Vec<String>>,
+    expires_at: Option<Timestamp>,
 }
 
 impl MockApiKey {
@@ -51,12 +63,29 @@ impl MockApiKeyBuilder {
         self
     }
 
+    pub fn unhashed_key(mut self, unhashed_key: impl Into<String>) -> Self {
+        let _ = self.unhashed_key.insert(unhashed_key.into());
+        self
+    }
+
+    pub fn scopes(mut self, scopes: &[&str]) -> Self {
+        let _ = self
+            .scopes
+            .insert(scopes.iter().map(ToString::to_string).collect());
+        self
+    }
+
+    pub fn expires_at(mut self, expires_at: Timestamp) -> Self {
+        let _ = self.expires_at.insert(expires_at);
+        self
+    }
+
     pub fn random_name(self) -> Result<Self> {
         Ok(self.name(rand_helper::random_string(1..=256)?))
     }
 
     pub fn random(self) -> Result<Self> {
-        Ok(self.random_name()?)
+        self.random_name()
     }
 
     pub fn build(self) -> MockApiKey {
@@ -66,11 +95,17 @@ impl MockApiKeyBuilder {
             .unhashed_key
             .clone()
             .unwrap_or(rand_helper::random_api_key());
+        let scopes = self
+            .scopes
+            .clone()
+            .unwrap_or(ALL_SCOPES.iter().map(ToString::to_string).collect());
 
         MockApiKey {
             id,
             name,
             unhashed_key,
+            scopes,
+            expires_at: self.expires_at,
         }
     }
 }
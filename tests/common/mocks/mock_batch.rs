@@ -0,0 +1,62 @@
+use crate::common::mocks::mock_paste::MockPaste;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct MockBatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    pub operations: Vec<MockOperation>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum MockOperation {
+    Create(MockPaste),
+    Update {
+        #[serde(flatten)]
+        paste: MockPaste,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        if_match: Option<String>,
+    },
+    Delete {
+        id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        if_match: Option<String>,
+    },
+}
+
+impl MockOperation {
+    pub fn update(paste: &MockPaste) -> Self {
+        Self::Update {
+            paste: paste.clone(),
+            if_match: None,
+        }
+    }
+
+    pub fn delete(paste: &MockPaste) -> Self {
+        Self::Delete {
+            id: paste.id.clone().unwrap_or_default(),
+            if_match: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MockBatchResponse {
+    pub committed: bool,
+    pub results: Vec<MockBatchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MockBatchResult {
+    pub status: u16,
+    pub id: Option<String>,
+    pub etag: Option<String>,
+    pub error: Option<serde_json::Value>,
+}
+
+impl MockBatchResponse {
+    pub fn statuses(&self) -> Vec<u16> {
+        self.results.iter().map(|result| result.status).collect()
+    }
+}
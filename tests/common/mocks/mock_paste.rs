@@ -82,11 +82,10 @@ impl MockPasteBuilder {
 
     // This does not set visibility, since that's usually not what we want
     pub fn random(self) -> Result<Self> {
-        Ok(self
-            .random_id()
+        self.random_id()
             .random_filename()?
             .random_description()?
-            .random_body()?)
+            .random_body()
     }
 
     pub fn build(self) -> MockPaste {
@@ -30,6 +30,12 @@ impl MockUser {
         Ok(self)
     }
 
+    pub async fn seed_as_admin(self, app: &TestApp) -> Result<Self> {
+        let user = self.seed(app).await?;
+        app.grant_admin(&user).await?;
+        Ok(user)
+    }
+
     pub async fn seed_with_api_key(mut self, app: &TestApp) -> Result<(Self, MockApiKey)> {
         let id = Uuid::now_v7().to_string();
         self.id = Some(id.clone());
@@ -73,7 +79,7 @@ impl MockUserBuilder {
     }
 
     pub fn random(self) -> Result<Self> {
-        Ok(self.random_username()?.random_email()?.random_password()?)
+        self.random_username()?.random_email()?.random_password()
     }
 
     pub fn build(self) -> MockUser {
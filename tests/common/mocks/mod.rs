@@ -1,4 +1,5 @@
 pub mod mock_api_key;
+pub mod mock_batch;
 pub mod mock_pagination;
 pub mod mock_paste;
 pub mod mock_user;
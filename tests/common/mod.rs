@@ -2,3 +2,4 @@ pub mod mocks;
 pub mod rand_helper;
 pub mod test_app;
 pub mod test_client;
+pub mod webhook_receiver;
@@ -8,7 +8,9 @@ use rand::seq::SliceRandom;
 use rand::SeedableRng;
 use rand::{thread_rng, Rng};
 use rand_chacha::ChaCha20Rng;
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
+use std::path::PathBuf;
 
 const COMMON_TLDS: [&str; 32] = [
     ".au", ".biz", ".br", ".ca", ".cn", ".co", ".com", ".cz", ".de", ".edu", ".fr", ".gov", ".gr",
@@ -24,7 +26,7 @@ const COMMON_FILE_EXTENSIONS: [&str; 32] = [
 
 pub fn random_string(range: RangeInclusive<usize>) -> Result<String> {
     if range.is_empty() {
-        return Err("range cannot be empty".into());
+        Err("range cannot be empty")?;
     }
 
     let mut rng = thread_rng();
@@ -34,7 +36,7 @@ pub fn random_string(range: RangeInclusive<usize>) -> Result<String> {
 
 pub fn random_alphanumeric_string(range: RangeInclusive<usize>) -> Result<String> {
     if range.is_empty() {
-        return Err("range cannot be empty".into());
+        Err("range cannot be empty")?;
     }
 
     let mut rng = thread_rng();
@@ -44,11 +46,11 @@ pub fn random_alphanumeric_string(range: RangeInclusive<usize>) -> Result<String
 
 pub fn random_email(range: RangeInclusive<usize>) -> Result<String> {
     if range.is_empty() {
-        return Err("range cannot be empty".into());
+        Err("range cannot be empty")?;
     }
 
     if *range.start() < 3 {
-        return Err("range lower bound must be 3 or greater to accommodate the space required for an email address".into());
+        Err("range lower bound must be 3 or greater to accommodate the space required for an email address")?;
     }
 
     let mut rng = thread_rng();
@@ -71,18 +73,18 @@ pub fn random_email(range: RangeInclusive<usize>) -> Result<String> {
 
 pub fn random_filename(range: RangeInclusive<usize>) -> Result<String> {
     if range.is_empty() {
-        return Err("range cannot be empty".into());
+        Err("range cannot be empty")?;
     }
 
     if *range.start() < 1 {
-        return Err("range lower bound must be 1 or greater to accommodate the space required for a filename".into());
+        Err("range lower bound must be 1 or greater to accommodate the space required for a filename")?;
     }
 
     let mut rng = thread_rng();
     let len = rng.gen_range(range);
     let extension = COMMON_FILE_EXTENSIONS
         .iter()
-        .filter(|ext| ext.len() <= len - 1)
+        .filter(|ext| ext.len() < len)
         .collect::<Vec<_>>()
         .choose(&mut rng)
         .copied()
@@ -98,6 +100,11 @@ pub fn random_api_key() -> String {
     format!("{:032x}", rng.gen::<u128>())
 }
 
+pub fn random_csrf_nonce() -> String {
+    let mut rng = ChaCha20Rng::from_entropy();
+    format!("{:032x}", rng.gen::<u128>())
+}
+
 pub fn hash_password(password: String) -> Result<String> {
     Ok(Argon2::default()
         .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))?
@@ -107,3 +114,27 @@ pub fn hash_password(password: String) -> Result<String> {
 pub fn hash_api_key(api_key: String) -> Vec<u8> {
     Sha256::digest(api_key.as_bytes()).to_vec()
 }
+
+// Writes a breached password corpus in the Pwned Passwords downloader format to a temporary file,
+// padded out with random hashes so that lookups have to actually search through it
+pub fn random_breached_password_corpus(breached_passwords: &[&str]) -> Result<PathBuf> {
+    let mut lines: Vec<String> = (0..2000)
+        .map(|_| random_string(8..=20))
+        .collect::<Result<Vec<_>>>()?
+        .iter()
+        .map(String::as_str)
+        .chain(breached_passwords.iter().copied())
+        .map(|password| {
+            let count = thread_rng().gen_range(1..10_000);
+            format!("{:X}:{count}", Sha1::digest(password.as_bytes()))
+        })
+        .collect();
+    lines.sort();
+
+    let path = std::env::temp_dir().join(format!(
+        "gluestick-breached-passwords-{}.txt",
+        random_alphanumeric_string(16..=16)?
+    ));
+    std::fs::write(&path, lines.join("\r\n") + "\r\n")?;
+    Ok(path)
+}
@@ -2,9 +2,10 @@ use crate::common::mocks::mock_api_key::MockApiKey;
 use crate::common::mocks::mock_paste::MockPaste;
 use crate::common::mocks::mock_user::MockUser;
 use crate::common::rand_helper;
+use crate::common::test_client::TEST_CSRF_SECRET;
 use crate::prelude::*;
 use core::net::SocketAddr;
-use gluestick::{db::migrations, db::Database, router};
+use gluestick::{config::Config, db::migrations, db::Database, router};
 use jiff::Timestamp;
 use std::sync::LazyLock;
 use tokio::net::TcpListener;
@@ -31,6 +32,10 @@ pub struct TestApp {
 
 impl TestApp {
     pub async fn spawn() -> Result<Self> {
+        Self::spawn_with_config(Config::default()).await
+    }
+
+    pub async fn spawn_with_config(config: Config) -> Result<Self> {
         LazyLock::force(&INIT_TRACING);
 
         let mut db = Database {
@@ -54,11 +59,21 @@ impl TestApp {
         let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
         let address = listener.local_addr().unwrap();
 
+        // Test clients sign their own CSRF tokens, so they have to know the key
+        let session = config
+            .session()
+            .clone()
+            .with_csrf_secret(TEST_CSRF_SECRET.into());
+        let config = config.with_session(session);
+
         let db_clone = db.clone();
         tokio::spawn(async move {
-            axum::serve(listener, router(db_clone))
-                .await
-                .expect("Failed to serve test server.")
+            axum::serve(
+                listener,
+                router(db_clone, config).into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .expect("Failed to serve test server.")
         });
 
         Ok(Self { address, db })
@@ -68,8 +83,13 @@ impl TestApp {
         self.db
             .conn
             .call(move |conn| {
-                let mut stmt = conn.prepare("INSERT INTO invite_codes VALUES(:invite_code);")?;
-                stmt.execute(named_params! {":invite_code": invite_code})?;
+                let mut stmt = conn.prepare(
+                    "INSERT INTO invite_codes (code, max_uses, used_count, created_at) VALUES (:invite_code, 1, 0, :now);",
+                )?;
+                stmt.execute(named_params! {
+                    ":invite_code": invite_code,
+                    ":now": Timestamp::now().as_millisecond(),
+                })?;
                 Ok(())
             })
             .await?;
@@ -95,7 +115,7 @@ impl TestApp {
             .conn
             .call(move |conn| {
                 let mut stmt =
-                    conn.prepare("INSERT INTO users VALUES(:id, :username, :email, :password, :created_at, :updated_at);")?;
+                    conn.prepare("INSERT INTO users (id, username, email, password, created_at, updated_at) VALUES(:id, :username, :email, :password, :created_at, :updated_at);")?;
                 stmt.execute(named_params! {
                     ":id": id,
                     ":username": user.username,
@@ -124,7 +144,8 @@ impl TestApp {
             .conn
             .call(move |conn| {
                 let mut stmt = conn.prepare(
-                    "INSERT INTO api_keys VALUES(:id, :name, :key, :user_id, :created_at, :last_used_at);",
+                    r"INSERT INTO api_keys (id, name, key, user_id, created_at, last_used_at, scopes, expires_at)
+                    VALUES (:id, :name, :key, :user_id, :created_at, :last_used_at, :scopes, :expires_at);",
                 )?;
                 stmt.execute(named_params! {
                     ":id": api_key_id,
@@ -133,6 +154,8 @@ impl TestApp {
                     ":user_id": user_id,
                     ":created_at": now,
                     ":last_used_at": now,
+                    ":scopes": api_key.scopes.join(" "),
+                    ":expires_at": api_key.expires_at.map(Timestamp::as_millisecond),
                 })?;
                 Ok(())
             })
@@ -158,7 +181,7 @@ impl TestApp {
             .conn
             .call(move |conn| {
                 let mut stmt = conn
-                    .prepare("INSERT INTO pastes VALUES(:id, :user_id, :filename, :description, :body, :visibility, :created_at, :updated_at);")?;
+                    .prepare("INSERT INTO pastes (id, user_id, filename, description, body, visibility, created_at, updated_at) VALUES(:id, :user_id, :filename, :description, :body, :visibility, :created_at, :updated_at);")?;
                 stmt.execute(named_params! {
                     ":id": id,
                     ":user_id": user_id,
@@ -174,4 +197,137 @@ impl TestApp {
             .await?;
         Ok(())
     }
+
+    pub async fn set_paste_timestamps(
+        &self,
+        paste: &MockPaste,
+        created_at: Timestamp,
+        updated_at: Timestamp,
+    ) -> Result<()> {
+        let id = Uuid::try_parse(paste.id.as_deref().unwrap_or_default())?;
+        self.db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "UPDATE pastes SET created_at = :created_at, updated_at = :updated_at WHERE id = :id;",
+                )?;
+                stmt.execute(named_params! {
+                    ":id": id,
+                    ":created_at": created_at.as_millisecond(),
+                    ":updated_at": updated_at.as_millisecond(),
+                })?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn grant_admin(&self, user: &MockUser) -> Result<()> {
+        let username = user.username.clone();
+        self.db
+            .conn
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("UPDATE users SET is_admin = 1 WHERE username = :username;")?;
+                stmt.execute(named_params! {":username": username})?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Shifts every throttle into the past, as if `seconds` had elapsed since the last attempt
+    pub async fn backdate_throttles(&self, seconds: i64) -> Result<()> {
+        let millis = seconds * 1000;
+        self.db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r"UPDATE throttles SET
+                        created_at = created_at - :millis,
+                        last_attempt_at = last_attempt_at - :millis,
+                        locked_until = locked_until - :millis;",
+                )?;
+                stmt.execute(named_params! {":millis": millis})?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn expire_invite_codes(&self) -> Result<()> {
+        self.db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("UPDATE invite_codes SET expires_at = :now;")?;
+                stmt.execute(named_params! {":now": Timestamp::now().as_millisecond()})?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Moves every pending account deletion's scheduled time into the past
+    pub async fn elapse_account_deletion_grace_periods(&self) -> Result<()> {
+        self.db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "UPDATE account_deletions SET requested_at = :now, scheduled_for = :now;",
+                )?;
+                stmt.execute(named_params! {":now": Timestamp::now().as_millisecond()})?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    // Moves every pending webhook delivery's next attempt into the past, as if its backoff had
+    // elapsed
+    pub async fn elapse_webhook_delivery_backoffs(&self) -> Result<()> {
+        self.db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "UPDATE webhook_deliveries SET next_attempt_at = :now WHERE next_attempt_at IS NOT NULL;",
+                )?;
+                stmt.execute(named_params! {":now": Timestamp::now().as_millisecond()})?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn count_rows(&self, table: &'static str) -> Result<i64> {
+        let count = self
+            .db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!("SELECT COUNT(*) FROM {table};"))?;
+                Ok(stmt.query_row([], |row| row.get(0))?)
+            })
+            .await?;
+        Ok(count)
+    }
+
+    // The number of rows in `table` that reference the user through `column`
+    pub async fn count_rows_for_user(
+        &self,
+        table: &'static str,
+        column: &'static str,
+        user: &MockUser,
+    ) -> Result<i64> {
+        let user_id = Uuid::try_parse(&user.id.clone().unwrap_or_default())?;
+        let count = self
+            .db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT COUNT(*) FROM {table} WHERE {column} = :user_id;"
+                ))?;
+                Ok(stmt.query_row(named_params! {":user_id": user_id}, |row| row.get(0))?)
+            })
+            .await?;
+        Ok(count)
+    }
 }
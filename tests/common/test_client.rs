@@ -1,23 +1,141 @@
 #![allow(dead_code)]
 
 use crate::common::mocks::mock_api_key::MockApiKey;
+use crate::common::mocks::mock_batch::MockBatch;
 use crate::common::mocks::mock_pagination::{MockPaginationParams, MockPaginationResponse};
 use crate::common::mocks::mock_paste::MockPaste;
 use crate::common::mocks::mock_user::MockUser;
+use crate::common::rand_helper::random_csrf_nonce;
 use crate::prelude::*;
 use core::net::SocketAddr;
+use hmac::{Hmac, Mac};
+use reqwest::cookie::{CookieStore, Jar};
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{Client, Response, Url};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, IntoUrl, Method, RequestBuilder, Response, Url};
 use serde::Deserialize;
+use sha2::Sha256;
+use std::sync::Arc;
+
+pub const USER_AGENT: &str = "gluestick-test-client";
+pub const TEST_CSRF_SECRET: &str = "test-csrf-secret-that-is-long-enough";
+const SESSION_COOKIE_NAME: &str = "session_token";
 
 pub struct TestClient {
     base_url: Url,
+    client: CsrfClient,
+}
+
+// Sends the CSRF token that a page would have been rendered with, like htmx does. The token is
+// signed over the client's CSRF and session cookies, which change as it signs in and out, so it's
+// worked out afresh for every request.
+struct CsrfClient {
     client: Client,
+    cookie_jar: Arc<Jar>,
+    base_url: Url,
+    with_csrf_token: bool,
+}
+
+impl CsrfClient {
+    fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match self.csrf_token() {
+            Some(token) => request.header("X-CSRF-Token", token),
+            None => request,
+        }
+    }
+
+    fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    fn put(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PUT, url)
+    }
+
+    fn patch(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::PATCH, url)
+    }
+
+    fn delete(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::DELETE, url)
+    }
+
+    fn csrf_token(&self) -> Option<String> {
+        if !self.with_csrf_token {
+            return None;
+        }
+        let cookies = self.cookie_jar.cookies(&self.base_url)?;
+        let cookie = |name: &str| {
+            cookies.to_str().ok()?.split("; ").find_map(|cookie| {
+                let (cookie_name, value) = cookie.split_once('=')?;
+                (cookie_name == name).then(|| value.to_string())
+            })
+        };
+        let nonce = cookie("csrf_token")?;
+        let session_token = cookie(SESSION_COOKIE_NAME).unwrap_or_default();
+        Some(sign_csrf_token(&nonce, &session_token))
+    }
+}
+
+pub fn sign_csrf_token(nonce: &str, session_token: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(TEST_CSRF_SECRET.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(format!("{nonce}.{session_token}").as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
 }
 
 impl TestClient {
     pub fn new(address: SocketAddr, api_key: Option<&MockApiKey>) -> Result<Self> {
+        Self::build(
+            address,
+            api_key,
+            reqwest::redirect::Policy::default(),
+            true,
+            None,
+        )
+    }
+
+    // Useful for inspecting the headers (e.g. Set-Cookie) on responses that redirect
+    pub fn new_without_redirects(address: SocketAddr) -> Result<Self> {
+        Self::build(address, None, reqwest::redirect::Policy::none(), true, None)
+    }
+
+    // Behaves like a cross site request would, since it's never handed a CSRF token to echo back
+    pub fn new_without_csrf_token(address: SocketAddr) -> Result<Self> {
+        Self::build(
+            address,
+            None,
+            reqwest::redirect::Policy::default(),
+            false,
+            None,
+        )
+    }
+
+    // Behaves like a reverse proxy relaying requests on behalf of the client at `forwarded_for`
+    pub fn new_forwarded_for(address: SocketAddr, forwarded_for: &str) -> Result<Self> {
+        Self::build(
+            address,
+            None,
+            reqwest::redirect::Policy::default(),
+            true,
+            Some(forwarded_for),
+        )
+    }
+
+    fn build(
+        address: SocketAddr,
+        api_key: Option<&MockApiKey>,
+        redirect_policy: reqwest::redirect::Policy,
+        with_csrf_token: bool,
+        forwarded_for: Option<&str>,
+    ) -> Result<Self> {
         let base_url = Url::parse(&format!("http://{address}/"))?;
+        let cookie_jar = Arc::new(Jar::default());
 
         let mut headers = HeaderMap::new();
         if let Some(api_key) = api_key {
@@ -26,48 +144,82 @@ impl TestClient {
                 HeaderValue::from_str(api_key.as_ref())?,
             );
         }
+        if let Some(forwarded_for) = forwarded_for {
+            headers.insert("X-Forwarded-For", HeaderValue::from_str(forwarded_for)?);
+        }
+        // The server accepts any well formed nonce, so the client can pick its own instead of having
+        // to fetch a page first
+        if with_csrf_token {
+            let nonce = random_csrf_nonce();
+            cookie_jar.add_cookie_str(&format!("csrf_token={nonce}; Path=/"), &base_url);
+        }
 
         let client = reqwest::Client::builder()
-            .cookie_store(true)
+            .cookie_provider(cookie_jar.clone())
+            .user_agent(USER_AGENT)
             .default_headers(headers)
+            .redirect(redirect_policy)
             .build()?;
 
-        Ok(Self { base_url, client })
+        Ok(Self {
+            base_url: base_url.clone(),
+            client: CsrfClient {
+                client,
+                cookie_jar,
+                base_url,
+                with_csrf_token,
+            },
+        })
+    }
+
+    pub fn admin(&self) -> AdminEndpoint<'_> {
+        AdminEndpoint(self)
     }
 
-    pub fn api_pastes(&self) -> ApiPastesEndpoint {
+    pub fn api_me(&self) -> ApiMeEndpoint<'_> {
+        ApiMeEndpoint(self)
+    }
+
+    pub fn api_pastes(&self) -> ApiPastesEndpoint<'_> {
         ApiPastesEndpoint(self)
     }
 
-    pub fn api_sessions(&self) -> ApiSessionsEndpoint {
+    pub fn api_sessions(&self) -> ApiSessionsEndpoint<'_> {
         ApiSessionsEndpoint(self)
     }
 
-    pub fn health(&self) -> HealthEndpoint {
+    pub fn api_users(&self, username: &str) -> ApiUsersEndpoint<'_> {
+        ApiUsersEndpoint {
+            client: self,
+            username: username.to_string(),
+        }
+    }
+
+    pub fn health(&self) -> HealthEndpoint<'_> {
         HealthEndpoint(self)
     }
 
-    pub fn login(&self) -> LoginEndpoint {
+    pub fn login(&self) -> LoginEndpoint<'_> {
         LoginEndpoint(self)
     }
 
-    pub fn logout(&self) -> LogoutEndpoint {
+    pub fn logout(&self) -> LogoutEndpoint<'_> {
         LogoutEndpoint(self)
     }
 
-    pub fn pastes(&self) -> PastesEndpoint {
+    pub fn pastes(&self) -> PastesEndpoint<'_> {
         PastesEndpoint(self)
     }
 
-    pub fn settings(&self) -> SettingsEndpoint {
+    pub fn settings(&self) -> SettingsEndpoint<'_> {
         SettingsEndpoint(self)
     }
 
-    pub fn signup(&self) -> SignupEndpoint {
+    pub fn signup(&self) -> SignupEndpoint<'_> {
         SignupEndpoint(self)
     }
 
-    pub fn username(&self, username: &String) -> UsernameEndpoint {
+    pub fn username(&self, username: &String) -> UsernameEndpoint<'_> {
         UsernameEndpoint {
             client: self,
             username: username.to_string(),
@@ -84,6 +236,64 @@ impl TestClient {
     }
 }
 
+pub struct AdminEndpoint<'c>(&'c TestClient);
+
+impl<'c> AdminEndpoint<'c> {
+    pub async fn get(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("admin")?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
+    pub async fn get_users(&self, query: Option<&str>) -> Result<Response> {
+        let mut url = self.0.base_url.join("admin/users")?;
+        if let Some(query) = query {
+            url = Url::parse_with_params(url.as_str(), [("q", query)])?;
+        }
+        Ok(self.0.client.get(url).send().await?)
+    }
+
+    pub async fn post_suspension(&self, user: &MockUser) -> Result<Response> {
+        let id = user.id.clone().unwrap_or_default();
+        let endpoint = self
+            .0
+            .base_url
+            .join(&format!("admin/users/{id}/suspension"))?;
+        Ok(self.0.client.post(endpoint).send().await?)
+    }
+
+    pub async fn delete_suspension(&self, user: &MockUser) -> Result<Response> {
+        let id = user.id.clone().unwrap_or_default();
+        let endpoint = self
+            .0
+            .base_url
+            .join(&format!("admin/users/{id}/suspension"))?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+
+    pub async fn get_pastes(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("admin/pastes")?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
+    pub async fn post_hidden(&self, paste: &MockPaste) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.0.base_url.join(&format!("admin/pastes/{id}/hidden"))?;
+        Ok(self.0.client.post(endpoint).send().await?)
+    }
+
+    pub async fn delete_hidden(&self, paste: &MockPaste) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.0.base_url.join(&format!("admin/pastes/{id}/hidden"))?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+
+    pub async fn delete_paste(&self, paste: &MockPaste) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.0.base_url.join(&format!("admin/pastes/{id}"))?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+}
+
 pub struct ApiPastesEndpoint<'c>(&'c TestClient);
 
 #[derive(Debug, Deserialize)]
@@ -127,6 +337,22 @@ impl<'c> ApiPastesEndpoint<'c> {
         Ok(response_data)
     }
 
+    pub async fn get_with_query_params(&self, params: &MockPaginationParams) -> Result<Response> {
+        Ok(self
+            .0
+            .client
+            .get(self.endpoint()?)
+            .query(&params.to_query_params())
+            .send()
+            .await?)
+    }
+
+    // Follows a path taken from a Link header, which is relative to the root of the app
+    pub async fn get_link(&self, link: &str) -> Result<Response> {
+        let endpoint = self.0.base_url.join(link)?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
     pub async fn post(&self, paste: &MockPaste) -> Result<Response> {
         Ok(self
             .0
@@ -151,17 +377,114 @@ impl<'c> ApiPastesEndpoint<'c> {
         Ok(self.0.client.get(endpoint).send().await?)
     }
 
+    pub async fn get_by_id_if_none_match(&self, paste: &MockPaste, etag: &str) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
+        Ok(self
+            .0
+            .client
+            .get(endpoint)
+            .header("If-None-Match", etag)
+            .send()
+            .await?)
+    }
+
+    pub async fn get_raw_by_id_if_none_match(
+        &self,
+        paste: &MockPaste,
+        etag: &str,
+    ) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self
+            .endpoint_with_trailing_slash()?
+            .join(&format!("{id}/raw"))?;
+        Ok(self
+            .0
+            .client
+            .get(endpoint)
+            .header("If-None-Match", etag)
+            .send()
+            .await?)
+    }
+
     pub async fn patch_by_id(&self, paste: &MockPaste) -> Result<Response> {
         let id = paste.id.clone().unwrap_or_default();
         let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
         Ok(self.0.client.patch(endpoint).json(&paste).send().await?)
     }
 
+    pub async fn patch_by_id_if_match(&self, paste: &MockPaste, etag: &str) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
+        Ok(self
+            .0
+            .client
+            .patch(endpoint)
+            .header("If-Match", etag)
+            .json(&paste)
+            .send()
+            .await?)
+    }
+
     pub async fn delete_by_id(&self, paste: &MockPaste) -> Result<Response> {
         let id = paste.id.clone().unwrap_or_default();
         let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
         Ok(self.0.client.delete(endpoint).send().await?)
     }
+
+    pub async fn delete_by_id_if_match(&self, paste: &MockPaste, etag: &str) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
+        Ok(self
+            .0
+            .client
+            .delete(endpoint)
+            .header("If-Match", etag)
+            .send()
+            .await?)
+    }
+
+    pub async fn post_batch(&self, batch: &MockBatch) -> Result<Response> {
+        let endpoint = self.endpoint_with_trailing_slash()?.join("batch")?;
+        Ok(self.0.client.post(endpoint).json(batch).send().await?)
+    }
+}
+
+pub struct ApiMeEndpoint<'c>(&'c TestClient);
+
+impl<'c> ApiMeEndpoint<'c> {
+    pub async fn get(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("api/v1/me")?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
+    pub async fn get_pastes(&self, params: Option<MockPaginationParams>) -> Result<Response> {
+        let endpoint = self.0.base_url.join("api/v1/me/pastes")?;
+        let mut request = self.0.client.get(endpoint);
+        if let Some(params) = params {
+            request = request.json(&params);
+        }
+        Ok(request.send().await?)
+    }
+}
+
+pub struct ApiUsersEndpoint<'c> {
+    client: &'c TestClient,
+    username: String,
+}
+
+impl<'c> ApiUsersEndpoint<'c> {
+    pub async fn get_pastes(&self, params: Option<MockPaginationParams>) -> Result<Response> {
+        let endpoint = self
+            .client
+            .base_url
+            .join(&format!("api/v1/users/{}/pastes", self.username))?;
+        let mut request = self.client.client.get(endpoint);
+        if let Some(params) = params {
+            request = request.json(&params);
+        }
+        Ok(request.send().await?)
+    }
 }
 
 pub struct ApiSessionsEndpoint<'c>(&'c TestClient);
@@ -179,8 +502,16 @@ impl<'c> ApiSessionsEndpoint<'c> {
         Ok(self.0.base_url.join(&format!("{}/", self.endpoint_str()))?)
     }
 
-    pub async fn post(&self) -> Result<Response> {
-        Ok(self.0.client.post(self.endpoint()?).send().await?)
+    pub async fn post(&self, name: &str, scopes: &[&str], expiry: &str) -> Result<Response> {
+        let mut form = vec![("name", name), ("expiry", expiry)];
+        form.extend(scopes.iter().map(|scope| (*scope, "true")));
+        Ok(self
+            .0
+            .client
+            .post(self.endpoint()?)
+            .form(&form)
+            .send()
+            .await?)
     }
 
     pub async fn delete_by_id(&self, api_key: &MockApiKey) -> Result<Response> {
@@ -221,6 +552,39 @@ impl<'c> LoginEndpoint<'c> {
             .send()
             .await?)
     }
+
+    pub async fn post_with_remember_me(&self, user: &MockUser) -> Result<Response> {
+        Ok(self
+            .0
+            .client
+            .post(self.endpoint()?)
+            .form(&[
+                ("email", user.email.as_str()),
+                ("password", user.password.as_str()),
+                ("remember_me", "true"),
+            ])
+            .send()
+            .await?)
+    }
+
+    // Submits the CSRF token the way a form does without JavaScript, rather than in a header
+    pub async fn post_with_csrf_form_field(
+        &self,
+        user: &MockUser,
+        csrf_token: &str,
+    ) -> Result<Response> {
+        Ok(self
+            .0
+            .client
+            .post(self.endpoint()?)
+            .form(&[
+                ("email", user.email.as_str()),
+                ("password", user.password.as_str()),
+                ("csrf_token", csrf_token),
+            ])
+            .send()
+            .await?)
+    }
 }
 
 pub struct LogoutEndpoint<'c>(&'c TestClient);
@@ -253,7 +617,7 @@ impl<'c> PastesEndpoint<'c> {
     pub async fn get(&self, params: Option<MockPaginationParams>) -> Result<Response> {
         let mut url = self.endpoint()?;
         if let Some(params) = params {
-            url = Url::parse_with_params(&url.to_string(), params.to_query_params())?;
+            url = Url::parse_with_params(url.as_str(), params.to_query_params())?;
         }
         Ok(self.0.client.get(url).send().await?)
     }
@@ -283,6 +647,15 @@ impl<'c> PastesEndpoint<'c> {
         let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
         Ok(self.0.client.delete(endpoint).send().await?)
     }
+
+    pub async fn post_batch(&self, action: &str, pastes: &[&MockPaste]) -> Result<Response> {
+        let endpoint = self.endpoint_with_trailing_slash()?.join("batch")?;
+        let mut form = vec![("action", action.to_string())];
+        for paste in pastes {
+            form.push(("ids", paste.id.clone().unwrap_or_default()));
+        }
+        Ok(self.0.client.post(endpoint).form(&form).send().await?)
+    }
 }
 
 pub struct SettingsEndpoint<'c>(&'c TestClient);
@@ -295,6 +668,170 @@ impl<'c> SettingsEndpoint<'c> {
     pub async fn get(&self) -> Result<Response> {
         Ok(self.0.client.get(self.endpoint()?).send().await?)
     }
+
+    pub async fn post_account(
+        &self,
+        username: &str,
+        email: &str,
+        password: &str,
+    ) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/account")?;
+        Ok(self
+            .0
+            .client
+            .post(endpoint)
+            .form(&[
+                ("username", username),
+                ("email", email),
+                ("password", password),
+            ])
+            .send()
+            .await?)
+    }
+
+    pub async fn post_profile(
+        &self,
+        display_name: &str,
+        bio: &str,
+        website: &str,
+    ) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/profile")?;
+        Ok(self
+            .0
+            .client
+            .post(endpoint)
+            .form(&[
+                ("display_name", display_name),
+                ("bio", bio),
+                ("website", website),
+            ])
+            .send()
+            .await?)
+    }
+
+    pub async fn post_avatar(&self, image: Vec<u8>) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/avatar")?;
+        let form = Form::new().part("avatar", Part::bytes(image).file_name("avatar"));
+        Ok(self.0.client.post(endpoint).multipart(form).send().await?)
+    }
+
+    // Submits the CSRF token the way a form does without JavaScript, rather than in a header
+    pub async fn post_avatar_with_csrf_form_field(
+        &self,
+        image: Vec<u8>,
+        csrf_token: &str,
+    ) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/avatar")?;
+        let form = Form::new()
+            .text("csrf_token", csrf_token.to_string())
+            .part("avatar", Part::bytes(image).file_name("avatar"));
+        Ok(self.0.client.post(endpoint).multipart(form).send().await?)
+    }
+
+    pub async fn delete_avatar(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/avatar")?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+
+    pub async fn post_change_password(
+        &self,
+        old_password: &str,
+        new_password: &str,
+        revoke_api_keys: bool,
+    ) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/change_password")?;
+        let mut form = vec![
+            ("old_password", old_password),
+            ("new_password", new_password),
+            ("new_password_confirm", new_password),
+        ];
+        if revoke_api_keys {
+            form.push(("revoke_api_keys", "true"));
+        }
+        Ok(self.0.client.post(endpoint).form(&form).send().await?)
+    }
+
+    // Submits the CSRF token the way a form does without JavaScript, rather than in a header
+    pub async fn post_change_password_with_csrf_form_field(
+        &self,
+        old_password: &str,
+        new_password: &str,
+        csrf_token: &str,
+    ) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/change_password")?;
+        let form = [
+            ("old_password", old_password),
+            ("new_password", new_password),
+            ("new_password_confirm", new_password),
+            ("csrf_token", csrf_token),
+        ];
+        Ok(self.0.client.post(endpoint).form(&form).send().await?)
+    }
+
+    pub async fn delete_session_by_id(&self, id: &str) -> Result<Response> {
+        let endpoint = self.0.base_url.join(&format!("settings/sessions/{id}"))?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+
+    pub async fn delete_other_sessions(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/sessions")?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+
+    pub async fn post_delete_account(&self, password: &str) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/delete_account")?;
+        Ok(self
+            .0
+            .client
+            .post(endpoint)
+            .form(&[("password", password)])
+            .send()
+            .await?)
+    }
+
+    pub async fn delete_account_deletion(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/delete_account")?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
+
+    pub async fn get_invites(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/invites")?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
+    pub async fn post_invite(&self, max_uses: i64, expiry: &str) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/invites")?;
+        let max_uses = max_uses.to_string();
+        Ok(self
+            .0
+            .client
+            .post(endpoint)
+            .form(&[("max_uses", max_uses.as_str()), ("expiry", expiry)])
+            .send()
+            .await?)
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/webhooks")?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
+    pub async fn post_webhook(&self, url: &str, events: &[&str]) -> Result<Response> {
+        let endpoint = self.0.base_url.join("settings/webhooks")?;
+        let mut form = vec![("url", url)];
+        form.extend(events.iter().map(|event| (*event, "true")));
+        Ok(self.0.client.post(endpoint).form(&form).send().await?)
+    }
+
+    pub async fn get_webhook_by_id(&self, id: &str) -> Result<Response> {
+        let endpoint = self.0.base_url.join(&format!("settings/webhooks/{id}"))?;
+        Ok(self.0.client.get(endpoint).send().await?)
+    }
+
+    pub async fn delete_webhook_by_id(&self, id: &str) -> Result<Response> {
+        let endpoint = self.0.base_url.join(&format!("settings/webhooks/{id}"))?;
+        Ok(self.0.client.delete(endpoint).send().await?)
+    }
 }
 
 pub struct SignupEndpoint<'c>(&'c TestClient);
@@ -322,6 +859,36 @@ impl<'c> SignupEndpoint<'c> {
             .send()
             .await?)
     }
+
+    pub async fn post_validate_email(&self, user: &MockUser) -> Result<Response> {
+        Ok(self
+            .0
+            .client
+            .post(self.0.base_url.join("signup/validate/email")?)
+            .form(&[
+                ("username", user.username.as_str()),
+                ("email", user.email.as_str()),
+                ("password", user.password.as_str()),
+                ("invite_code", ""),
+            ])
+            .send()
+            .await?)
+    }
+
+    pub async fn post_validate_password(&self, user: &MockUser) -> Result<Response> {
+        Ok(self
+            .0
+            .client
+            .post(self.0.base_url.join("signup/validate/password")?)
+            .form(&[
+                ("username", user.username.as_str()),
+                ("email", user.email.as_str()),
+                ("password", user.password.as_str()),
+                ("invite_code", ""),
+            ])
+            .send()
+            .await?)
+    }
 }
 
 pub struct UsernameEndpoint<'c> {
@@ -341,11 +908,16 @@ impl<'c> UsernameEndpoint<'c> {
     pub async fn get(&self, params: Option<MockPaginationParams>) -> Result<Response> {
         let mut url = self.endpoint()?;
         if let Some(params) = params {
-            url = Url::parse_with_params(&url.to_string(), params.to_query_params())?;
+            url = Url::parse_with_params(url.as_str(), params.to_query_params())?;
         }
         Ok(self.client.client.get(url).send().await?)
     }
 
+    pub async fn get_avatar(&self) -> Result<Response> {
+        let endpoint = self.endpoint_with_trailing_slash()?.join("avatar")?;
+        Ok(self.client.client.get(endpoint).send().await?)
+    }
+
     pub async fn get_by_paste_id(&self, paste: &MockPaste) -> Result<Response> {
         let id = paste.id.clone().unwrap_or_default();
         let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
@@ -360,6 +932,24 @@ impl<'c> UsernameEndpoint<'c> {
         Ok(self.client.client.get(endpoint).send().await?)
     }
 
+    pub async fn get_raw_by_paste_id_if_none_match(
+        &self,
+        paste: &MockPaste,
+        etag: &str,
+    ) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self
+            .endpoint_with_trailing_slash()?
+            .join(&format!("{id}/raw"))?;
+        Ok(self
+            .client
+            .client
+            .get(endpoint)
+            .header("If-None-Match", etag)
+            .send()
+            .await?)
+    }
+
     pub async fn get_download_by_paste_id(&self, paste: &MockPaste) -> Result<Response> {
         let id = paste.id.clone().unwrap_or_default();
         let endpoint = self
@@ -393,9 +983,48 @@ impl<'c> UsernameEndpoint<'c> {
             .await?)
     }
 
+    // Submits the edit form as it would be when filled in from the paste version tagged `version`
+    pub async fn put_by_paste_id_with_version(
+        &self,
+        paste: &MockPaste,
+        version: &str,
+    ) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
+        Ok(self
+            .client
+            .client
+            .put(endpoint)
+            .form(&[
+                ("filename", paste.filename.as_str()),
+                ("description", paste.description.as_str()),
+                ("body", paste.body.as_str()),
+                ("visibility", paste.visibility.as_str()),
+                ("version", version),
+            ])
+            .send()
+            .await?)
+    }
+
     pub async fn delete_by_paste_id(&self, paste: &MockPaste) -> Result<Response> {
         let id = paste.id.clone().unwrap_or_default();
         let endpoint = self.endpoint_with_trailing_slash()?.join(&id)?;
         Ok(self.client.client.delete(endpoint).send().await?)
     }
+
+    pub async fn post_pin_by_paste_id(&self, paste: &MockPaste) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self
+            .endpoint_with_trailing_slash()?
+            .join(&format!("{id}/pin"))?;
+        Ok(self.client.client.post(endpoint).send().await?)
+    }
+
+    pub async fn delete_pin_by_paste_id(&self, paste: &MockPaste) -> Result<Response> {
+        let id = paste.id.clone().unwrap_or_default();
+        let endpoint = self
+            .endpoint_with_trailing_slash()?
+            .join(&format!("{id}/pin"))?;
+        Ok(self.client.client.delete(endpoint).send().await?)
+    }
 }
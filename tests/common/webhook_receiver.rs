@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use core::net::SocketAddr;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+#[derive(Clone, Debug)]
+pub struct ReceivedDelivery {
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl ReceivedDelivery {
+    pub fn header(&self, name: &str) -> &str {
+        self.headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn json(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+}
+
+#[derive(Clone)]
+struct ReceiverState {
+    deliveries: Arc<Mutex<Vec<ReceivedDelivery>>>,
+    status: Arc<AtomicU16>,
+}
+
+// A local HTTP server to point webhooks at, which records every request it gets and answers them
+// all with the same status
+pub struct WebhookReceiver {
+    pub address: SocketAddr,
+    state: ReceiverState,
+}
+
+impl WebhookReceiver {
+    pub async fn spawn() -> Result<Self> {
+        let state = ReceiverState {
+            deliveries: Arc::new(Mutex::new(Vec::new())),
+            status: Arc::new(AtomicU16::new(200)),
+        };
+        let router = Router::new()
+            .route("/hook", post(receive))
+            .with_state(state.clone());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let address = listener.local_addr()?;
+        tokio::spawn(async move {
+            axum::serve(listener, router)
+                .await
+                .expect("Failed to serve webhook receiver.")
+        });
+
+        Ok(Self { address, state })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/hook", self.address)
+    }
+
+    pub fn respond_with(&self, status: u16) {
+        self.state.status.store(status, Ordering::SeqCst);
+    }
+
+    pub fn deliveries(&self) -> Vec<ReceivedDelivery> {
+        self.state.deliveries.lock().unwrap().clone()
+    }
+}
+
+async fn receive(
+    State(state): State<ReceiverState>,
+    headers: HeaderMap,
+    body: String,
+) -> StatusCode {
+    state
+        .deliveries
+        .lock()
+        .unwrap()
+        .push(ReceivedDelivery { headers, body });
+    StatusCode::from_u16(state.status.load(Ordering::SeqCst))
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
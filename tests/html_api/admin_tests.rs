@@ -0,0 +1,365 @@
+use crate::common::mocks::mock_paste::MockPaste;
+use crate::common::mocks::mock_user::MockUser;
+use crate::common::rand_helper::{random_alphanumeric_string, random_filename};
+use crate::common::test_app::TestApp;
+use crate::common::test_client::TestClient;
+use crate::prelude::*;
+use gluestick::db::migrations;
+use gluestick::models::user::{User, Username};
+use tokio_rusqlite::{named_params, Connection};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn admin_area_requires_an_admin() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.admin().get().await?;
+    assert_eq!(response.status(), 401);
+
+    client.login().post(&user).await?;
+    let response = client.admin().get().await?;
+    assert_eq!(response.status(), 403);
+    let response = client.admin().get_users(None).await?;
+    assert_eq!(response.status(), 403);
+    let response = client.admin().get_pastes().await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_area_shows_instance_stats() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&admin).await?;
+
+    let response = client.admin().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains("Overview"));
+    assert!(html.contains("Suspended users"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_can_search_users() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let alice = MockUser::builder()
+        .random()?
+        .username("alicesearchable")
+        .build()
+        .seed(&app)
+        .await?;
+    let bob = MockUser::builder()
+        .random()?
+        .username("bobsearchable")
+        .build()
+        .seed(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&admin).await?;
+
+    let response = client.admin().get_users(Some("alice")).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(&alice.username));
+    assert!(!html.contains(&bob.username));
+
+    let response = client.admin().get_users(None).await?;
+    let html = response.text().await?;
+    assert!(html.contains(&alice.username));
+    assert!(html.contains(&bob.username));
+    Ok(())
+}
+
+#[tokio::test]
+async fn suspending_a_user_ends_their_sessions_and_blocks_access() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let admin_client = TestClient::new(app.address, None)?;
+    admin_client.login().post(&admin).await?;
+    let user_client = TestClient::new(app.address, None)?;
+    user_client.login().post(&user).await?;
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+
+    let response = admin_client.admin().post_suspension(&user).await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains("Unsuspend"));
+
+    // Existing sessions are gone, new logins are refused and the API key stops working
+    let response = user_client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    let response = user_client.login().post(&user).await?;
+    assert!(response
+        .text()
+        .await?
+        .contains("This account has been suspended"));
+    let response = user_client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    let response = api_client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 401);
+
+    let response = admin_client.admin().delete_suspension(&user).await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains("Suspend"));
+
+    user_client.login().post(&user).await?;
+    let response = user_client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let response = api_client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn admins_cannot_be_suspended() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let other_admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&admin).await?;
+
+    let response = client.admin().post_suspension(&other_admin).await?;
+    assert_eq!(response.status(), 403);
+    let response = client.admin().post_suspension(&admin).await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn hidden_pastes_are_only_visible_to_their_owner_and_admins() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let owner = MockUser::builder().random()?.build().seed(&app).await?;
+    let (_, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let paste = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &owner)
+        .await?;
+    let admin_client = TestClient::new(app.address, None)?;
+    admin_client.login().post(&admin).await?;
+    let owner_client = TestClient::new(app.address, None)?;
+    owner_client.login().post(&owner).await?;
+    let anonymous_client = TestClient::new(app.address, None)?;
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+
+    let response = admin_client.admin().post_hidden(&paste).await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains("Unhide"));
+
+    let response = anonymous_client.pastes().get(None).await?;
+    assert!(!response.text().await?.contains(&paste.filename));
+    let response = anonymous_client
+        .username(&owner.username)
+        .get_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 404);
+    let response = anonymous_client
+        .username(&owner.username)
+        .get_raw_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 404);
+    let response = api_client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 404);
+
+    let response = owner_client
+        .username(&owner.username)
+        .get_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains("Hidden"));
+    let response = admin_client
+        .username(&owner.username)
+        .get_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    let response = admin_client.admin().delete_hidden(&paste).await?;
+    assert_eq!(response.status(), 200);
+
+    let response = anonymous_client.pastes().get(None).await?;
+    assert!(response.text().await?.contains(&paste.filename));
+    let response = api_client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_can_delete_any_paste() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let owner = MockUser::builder().random()?.build().seed(&app).await?;
+    let paste = MockPaste::builder()
+        .random()?
+        .visibility("secret")
+        .build()
+        .seed(&app, &owner)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.admin().delete_paste(&paste).await?;
+    assert_eq!(response.status(), 401);
+
+    client.login().post(&admin).await?;
+    let response = client.admin().get_pastes().await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains(&paste.filename));
+
+    let response = client.admin().delete_paste(&paste).await?;
+    assert_eq!(response.status(), 200);
+    let response = client
+        .username(&owner.username)
+        .get_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn admins_no_longer_configured_lose_the_admin_role() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let kept_admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+    let removed_admin = MockUser::builder()
+        .random()?
+        .build()
+        .seed_as_admin(&app)
+        .await?;
+
+    let changed =
+        User::sync_admins_by_usernames(&app.db, vec![kept_admin.username.clone()]).await?;
+    assert_eq!(changed, 1);
+
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&kept_admin).await?;
+    let response = client.admin().get().await?;
+    assert_eq!(response.status(), 200);
+
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&removed_admin).await?;
+    let response = client.admin().get().await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_role_stays_with_the_configured_user_after_a_rename() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let admin = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let configured = vec![admin.username.clone()];
+    assert_eq!(
+        User::sync_admins_by_usernames(&app.db, configured.clone()).await?,
+        1
+    );
+
+    let admin_client = TestClient::new(app.address, None)?;
+    admin_client.login().post(&admin).await?;
+    let new_username = format!("renamed-{}", random_alphanumeric_string(8..=8)?).to_lowercase();
+    let response = admin_client
+        .settings()
+        .post_account(&new_username, &admin.email, &admin.password)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    // The admin's old name is free for anyone to claim, but claiming it doesn't carry the role
+    let other_client = TestClient::new(app.address, None)?;
+    other_client.login().post(&other_user).await?;
+    let response = other_client
+        .settings()
+        .post_account(&admin.username, &other_user.email, &other_user.password)
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        User::sync_admins_by_usernames(&app.db, configured).await?,
+        0
+    );
+
+    let response = other_client.admin().get().await?;
+    assert_eq!(response.status(), 403);
+    let response = admin_client.admin().get().await?;
+    assert_eq!(response.status(), 200);
+
+    // Taking the name off the list revokes the role from the user it was bound to
+    assert_eq!(
+        User::sync_admins_by_usernames(&app.db, Vec::new()).await?,
+        1
+    );
+    let response = admin_client.admin().get().await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn existing_users_named_admin_are_renamed() -> Result<()> {
+    let mut conn = Connection::open_in_memory().await?;
+    migrations().to_version(&mut conn, 14).await?;
+    conn.call(|conn| {
+        let mut stmt = conn.prepare(
+            r"INSERT INTO users (id, username, email, password, created_at, updated_at)
+            VALUES (:id, 'admin', 'admin@example.com', 'not-a-real-hash', 0, 0);",
+        )?;
+        stmt.execute(named_params! {":id": Uuid::now_v7()})?;
+        Ok(())
+    })
+    .await?;
+
+    migrations().to_latest(&mut conn).await?;
+    let username: String = conn
+        .call(|conn| {
+            Ok(conn.query_row(
+                "SELECT username FROM users WHERE email = 'admin@example.com';",
+                [],
+                |row| row.get(0),
+            )?)
+        })
+        .await?;
+    assert!(username.starts_with("admin-"));
+    assert!(username.parse::<Username>().is_ok());
+    Ok(())
+}
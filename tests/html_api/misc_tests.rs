@@ -1,6 +1,11 @@
+use crate::common::mocks::mock_paste::MockPaste;
+use crate::common::mocks::mock_user::MockUser;
 use crate::common::test_app::TestApp;
-use crate::common::test_client::TestClient;
+use crate::common::test_client::{sign_csrf_token, TestClient};
 use crate::prelude::*;
+use gluestick::config::{Config, Quota, RateLimitConfig, SecurityHeadersConfig};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
 
 #[tokio::test]
 async fn fallback_responds_with_404() -> Result<()> {
@@ -22,3 +27,329 @@ async fn health_check_responds_with_200_and_zero_content() -> Result<()> {
     assert_eq!(Some(0), response.content_length());
     Ok(())
 }
+
+#[tokio::test]
+async fn responses_include_rate_limit_headers() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.get().await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-ratelimit-limit"], "300");
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "299");
+    assert!(response.headers().contains_key("x-ratelimit-reset"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_are_rate_limited_per_ip() -> Result<()> {
+    let quota = Quota {
+        burst: 3,
+        per_minute: 1,
+    };
+    let config = Config::default().with_rate_limit(RateLimitConfig::new(quota, quota));
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    for _ in 0..3 {
+        let response = client.get().await?;
+        assert_eq!(response.status(), 200);
+    }
+
+    let response = client.get().await?;
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+    let retry_after: u64 = response.headers()["retry-after"].to_str()?.parse()?;
+    assert!(retry_after > 0 && retry_after <= 60);
+    assert!(response.text().await?.contains("429 Too Many Requests"));
+
+    // Static assets aren't counted against the limit
+    let response = client.get_arbitrary("assets/css/main.css").await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_behind_a_trusted_proxy_are_rate_limited_per_client() -> Result<()> {
+    let quota = Quota {
+        burst: 3,
+        per_minute: 1,
+    };
+    let config = Config::default()
+        .with_rate_limit(RateLimitConfig::new(quota, quota))
+        .with_trusted_proxies(vec![Ipv4Addr::LOCALHOST.into()]);
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new_forwarded_for(app.address, "203.0.113.1")?;
+
+    for _ in 0..3 {
+        let response = client.get().await?;
+        assert_eq!(response.status(), 200);
+    }
+    let response = client.get().await?;
+    assert_eq!(response.status(), 429);
+
+    let other_client = TestClient::new_forwarded_for(app.address, "203.0.113.2")?;
+    let response = other_client.get().await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn ipv6_clients_are_rate_limited_per_64_prefix() -> Result<()> {
+    let quota = Quota {
+        burst: 3,
+        per_minute: 1,
+    };
+    let config = Config::default()
+        .with_rate_limit(RateLimitConfig::new(quota, quota))
+        .with_trusted_proxies(vec![Ipv4Addr::LOCALHOST.into()]);
+    let app = TestApp::spawn_with_config(config).await?;
+
+    for address in ["2001:db8::1", "2001:db8::2", "2001:db8::ffff:1"] {
+        let client = TestClient::new_forwarded_for(app.address, address)?;
+        let response = client.get().await?;
+        assert_eq!(response.status(), 200);
+    }
+    let client = TestClient::new_forwarded_for(app.address, "2001:db8::3")?;
+    let response = client.get().await?;
+    assert_eq!(response.status(), 429);
+
+    let other_client = TestClient::new_forwarded_for(app.address, "2001:db8:0:1::1")?;
+    let response = other_client.get().await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn state_changing_requests_without_a_csrf_token_are_forbidden() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new_without_csrf_token(app.address)?;
+
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 403);
+    let invite = app.seed_random_invite_code().await?;
+    let response = client
+        .signup()
+        .post(invite, &MockUser::builder().random()?.build())
+        .await?;
+    assert_eq!(response.status(), 403);
+
+    // Even once the client has been handed a token, it still has to echo it back
+    let response = client.login().get().await?;
+    assert_eq!(response.status(), 200);
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn csrf_token_is_embedded_in_pages_and_accepted_as_a_form_field() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new_without_csrf_token(app.address)?;
+
+    let response = client.login().get().await?;
+    let set_cookie = response.headers()["set-cookie"].to_str()?.to_string();
+    let (nonce, _) = set_cookie
+        .strip_prefix("csrf_token=")
+        .and_then(|rest| rest.split_once(';'))
+        .ok_or("csrf cookie not found")?;
+    assert!(set_cookie.contains("HttpOnly"));
+    let html = response.text().await?;
+    let csrf_token = embedded_csrf_token(&html)?;
+    // The page gets a signature of the cookie rather than the cookie itself
+    assert_eq!(csrf_token, sign_csrf_token(nonce, ""));
+    assert!(html.contains(&format!(r#"{{"X-CSRF-Token": "{csrf_token}"}}"#)));
+
+    let response = client
+        .login()
+        .post_with_csrf_form_field(&user, &"0".repeat(32))
+        .await?;
+    assert_eq!(response.status(), 403);
+    let response = client
+        .login()
+        .post_with_csrf_form_field(&user, &csrf_token)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn csrf_token_is_bound_to_the_session() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new_without_csrf_token(app.address)?;
+
+    let html = client.login().get().await?.text().await?;
+    let signed_out_token = embedded_csrf_token(&html)?;
+    let response = client
+        .login()
+        .post_with_csrf_form_field(&user, &signed_out_token)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    // Anyone can be handed a token for a CSRF cookie they planted, but not for the victim's session
+    let response = client
+        .settings()
+        .post_avatar_with_csrf_form_field(avatar_upload()?, &signed_out_token)
+        .await?;
+    assert_eq!(response.status(), 403);
+
+    // Multipart forms carry the token as one of their parts
+    let html = client.settings().get().await?.text().await?;
+    let csrf_token = embedded_csrf_token(&html)?;
+    let response = client
+        .settings()
+        .post_avatar_with_csrf_form_field(avatar_upload()?, &csrf_token)
+        .await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn csrf_token_is_handed_to_htmx_when_the_session_rotates() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new_without_csrf_token(app.address)?;
+    let html = client.login().get().await?.text().await?;
+    client
+        .login()
+        .post_with_csrf_form_field(&user, &embedded_csrf_token(&html)?)
+        .await?;
+    let html = client.settings().get().await?.text().await?;
+    let old_token = embedded_csrf_token(&html)?;
+
+    let response = client
+        .settings()
+        .post_change_password_with_csrf_form_field(&user.password, "a-new-password", &old_token)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let trigger: HashMap<String, String> =
+        serde_json::from_str(response.headers()["hx-trigger"].to_str()?)?;
+    let new_token = &trigger["csrfTokenChanged"];
+
+    let response = client
+        .settings()
+        .post_avatar_with_csrf_form_field(avatar_upload()?, &old_token)
+        .await?;
+    assert_eq!(response.status(), 403);
+    let response = client
+        .settings()
+        .post_avatar_with_csrf_form_field(avatar_upload()?, new_token)
+        .await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+// The value of the hidden `csrf_token` input that forms are rendered with
+fn embedded_csrf_token(html: &str) -> Result<String> {
+    let (_, rest) = html
+        .split_once(r#"name="csrf_token""#)
+        .ok_or("csrf input not found")?;
+    let (_, rest) = rest
+        .split_once(r#"value=""#)
+        .ok_or("csrf value not found")?;
+    let (csrf_token, _) = rest.split_once('"').ok_or("csrf value not found")?;
+    Ok(csrf_token.to_string())
+}
+
+fn avatar_upload() -> Result<Vec<u8>> {
+    let mut upload = Vec::new();
+    image::RgbImage::new(64, 64).write_to(
+        &mut std::io::Cursor::new(&mut upload),
+        image::ImageFormat::Png,
+    )?;
+    Ok(upload)
+}
+
+#[tokio::test]
+async fn json_api_does_not_require_a_csrf_token() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new_without_csrf_token(app.address)?;
+
+    // Rejected for the missing API key, not for the missing CSRF token
+    let paste = MockPaste::builder().random()?.build();
+    let response = client.api_pastes().post(&paste).await?;
+    assert_eq!(response.status(), 401);
+    Ok(())
+}
+
+#[tokio::test]
+async fn responses_include_security_headers() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.get().await?;
+    assert_eq!(response.status(), 200);
+    let headers = response.headers().clone();
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["referrer-policy"], "same-origin");
+    assert_eq!(
+        headers["strict-transport-security"],
+        "max-age=31536000; includeSubDomains"
+    );
+    assert!(headers.contains_key("permissions-policy"));
+
+    // Every script on the page carries the nonce that the policy allows, and nothing else does
+    let csp = headers["content-security-policy"].to_str()?;
+    assert!(csp.contains("default-src 'none'"));
+    assert!(csp.contains("frame-ancestors 'none'"));
+    let (_, rest) = csp
+        .split_once("script-src 'nonce-")
+        .ok_or("script nonce not found")?;
+    let (nonce, _) = rest.split_once('\'').ok_or("script nonce not found")?;
+    let html = response.text().await?;
+    assert_eq!(
+        html.matches("<script").count(),
+        html.matches(&format!(r#"nonce="{nonce}""#)).count()
+    );
+    assert!(!html.contains("hx-on"));
+
+    // Each response gets a fresh nonce
+    let response = client.get().await?;
+    assert_ne!(response.headers()["content-security-policy"].to_str()?, csp);
+    Ok(())
+}
+
+#[tokio::test]
+async fn security_headers_are_configurable() -> Result<()> {
+    let config = Config::default().with_security_headers(SecurityHeadersConfig::new(0, true));
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.get().await?;
+    assert_eq!(response.status(), 200);
+    let headers = response.headers();
+    assert!(!headers.contains_key("strict-transport-security"));
+    assert!(!headers.contains_key("content-security-policy"));
+    assert!(headers["content-security-policy-report-only"]
+        .to_str()?
+        .contains("script-src 'nonce-"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn syntax_highlight_stylesheet_matches_theme() -> Result<()> {
+    let stylesheet = include_str!("../../src/assets/css/syntax-highlight.css");
+    let theme_css =
+        gluestick::helpers::syntax_highlight_helper::theme_css().ok_or("theme not found")?;
+    assert_eq!(stylesheet, theme_css);
+    Ok(())
+}
+
+#[tokio::test]
+async fn api_docs_list_every_documented_endpoint() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.get_arbitrary("api/docs").await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains("<code>/pastes/{id}/raw</code>"));
+    assert!(html.contains("Update a paste"));
+    assert!(html.contains("If-Match"));
+    Ok(())
+}
@@ -1,3 +1,5 @@
+mod admin_tests;
 mod misc_tests;
 mod paste_tests;
 mod user_tests;
+mod webhook_tests;
@@ -222,6 +222,59 @@ async fn index_paginates_correctly() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn index_filters_pastes_and_keeps_the_filters_in_pagination_links() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    for filename in ["a.rs", "b.rs", "c.rs", "d.md"] {
+        MockPaste::builder()
+            .random()?
+            .filename(filename)
+            .build()
+            .seed(&app, &user)
+            .await?;
+    }
+
+    let response = client
+        .get_arbitrary("/pastes?extension=rs&sort=updated&per_page=2")
+        .await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"paste\">").count(), 2);
+    assert!(html.contains("c.rs") && html.contains("b.rs"));
+    assert!(html.contains(r#"value="rs""#));
+    let next_link = html
+        .split(r#"<a href=""#)
+        .find_map(|s| s.split_once(r#"">Older</a>"#))
+        .map(|(link, _)| link.replace("&amp;", "&"))
+        .ok_or("Failed to find the next page link")?;
+    assert!(next_link.starts_with("/pastes?extension=rs&sort=updated&next_page=updated."));
+
+    let response = client.get_arbitrary(&next_link).await?;
+    let html = response.text().await?;
+    assert!(html.contains("a.rs"));
+    assert!(!html.contains("d.md"));
+
+    let response = client
+        .get_arbitrary("/pastes?filename=nothing-like-this")
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(response
+        .text()
+        .await?
+        .contains("No pastes match these filters."));
+
+    let response = client.get_arbitrary("/pastes?created_after=soon").await?;
+    assert_eq!(response.status(), 422);
+    assert!(response
+        .text()
+        .await?
+        .contains("Created after must be a date (YYYY-MM-DD) or a timestamp (RFC 3339)"));
+    Ok(())
+}
+
 #[tokio::test]
 async fn create_show_update_destroy_happy_path() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -475,6 +528,40 @@ async fn show_and_edit_do_not_include_no_index_header_when_paste_is_public() ->
     Ok(())
 }
 
+#[tokio::test]
+async fn raw_and_download_are_sandboxed() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste = MockPaste::builder()
+        .random()?
+        .body("<script>alert(1)</script>")
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    for response in [
+        client
+            .username(&user.username)
+            .get_raw_by_paste_id(&paste)
+            .await?,
+        client
+            .username(&user.username)
+            .get_download_by_paste_id(&paste)
+            .await?,
+    ] {
+        assert_eq!(response.status(), 200);
+        let headers = response.headers();
+        assert_eq!(headers["content-type"], "text/plain; charset=utf-8");
+        assert_eq!(
+            headers["content-security-policy"],
+            "sandbox; default-src 'none'"
+        );
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn show_responds_with_404_when_paste_exists_but_username_is_wrong() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -592,6 +679,104 @@ async fn update_does_not_persist_paste_when_invalid_fields() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn update_shows_a_conflict_when_the_paste_changed_while_editing() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    client.login().post(&user).await?;
+    let paste = MockPaste::builder()
+        .random()?
+        .body("first line\nsecond line\n")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    let stale_version = response.headers()["etag"].to_str()?.to_string();
+
+    let response = client
+        .username(&user.username)
+        .get_edit_by_paste_id(&paste)
+        .await?;
+    let html = response.text().await?;
+    assert!(html.contains(&stale_version.replace('"', "&quot;")));
+
+    // Someone else saves a change while the edit form is open
+    let mut their_edit = paste.clone();
+    their_edit.body = "first line\ntheir second line\n".into();
+    let response = client.api_pastes().patch_by_id(&their_edit).await?;
+    assert_eq!(response.status(), 200);
+
+    let mut my_edit = paste.clone();
+    my_edit.body = "first line\nmy second line\n".into();
+    let response = client
+        .username(&user.username)
+        .put_by_paste_id_with_version(&my_edit, &stale_version)
+        .await?;
+    assert_eq!(response.status(), 409);
+    let html = response.text().await?;
+    assert!(html.contains("Edit conflict"));
+    assert!(html.contains(r#"<span class="removed">- their second line</span>"#));
+    assert!(html.contains(r#"<span class="added">+ my second line</span>"#));
+
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    let current_version = response.headers()["etag"].to_str()?.to_string();
+    let persisted_paste: MockPaste = response.json().await?;
+    assert_eq!(persisted_paste, their_edit);
+    assert!(html.contains(&current_version.replace('"', "&quot;")));
+
+    // Saving again from the conflict page overwrites the current version
+    let response = client
+        .username(&user.username)
+        .put_by_paste_id_with_version(&my_edit, &current_version)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    let persisted_paste: MockPaste = response.json().await?;
+    assert_eq!(persisted_paste, my_edit);
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_raw_responds_with_304_when_the_etag_is_current() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste = MockPaste::builder()
+        .random()?
+        .visibility("public")
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client
+        .username(&user.username)
+        .get_raw_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let etag = response.headers()["etag"].to_str()?.to_string();
+
+    let response = client
+        .username(&user.username)
+        .get_raw_by_paste_id_if_none_match(&paste, &etag)
+        .await?;
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert!(response.text().await?.is_empty());
+
+    let response = client
+        .username(&user.username)
+        .get_raw_by_paste_id_if_none_match(&paste, "\"stale\"")
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await?, paste.body);
+    Ok(())
+}
+
 #[tokio::test]
 async fn cannot_update_other_users_pastes() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -767,3 +952,186 @@ async fn cannot_destroy_other_users_pastes() -> Result<()> {
     assert_eq!(response.status(), 200);
     Ok(())
 }
+
+#[tokio::test]
+async fn pinned_pastes_are_shown_at_the_top_of_the_profile() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let paste = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client
+        .username(&user.username)
+        .get_by_paste_id(&paste)
+        .await?;
+    assert!(response.text().await?.contains("Pin to profile"));
+
+    let response = client
+        .username(&user.username)
+        .post_pin_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains("Unpin"));
+
+    let anonymous_client = TestClient::new(app.address, None)?;
+    let response = anonymous_client.username(&user.username).get(None).await?;
+    let html = response.text().await?;
+    assert!(html.contains("Pinned"));
+    assert!(html.contains(&paste.filename));
+
+    let other_client = TestClient::new(app.address, None)?;
+    other_client.login().post(&other_user).await?;
+    let response = other_client
+        .username(&user.username)
+        .delete_pin_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 403);
+
+    let response = client
+        .username(&user.username)
+        .delete_pin_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains("Pin to profile"));
+    let response = anonymous_client.username(&user.username).get(None).await?;
+    assert!(!response.text().await?.contains("Pinned"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn pinning_is_limited_to_public_pastes_up_to_a_maximum() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let secret_paste = MockPaste::builder()
+        .random()?
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let response = client
+        .username(&user.username)
+        .post_pin_by_paste_id(&secret_paste)
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response
+        .text()
+        .await?
+        .contains("Only public pastes can be pinned"));
+
+    for _ in 0..6 {
+        let paste = MockPaste::builder()
+            .random()?
+            .build()
+            .seed(&app, &user)
+            .await?;
+        let response = client
+            .username(&user.username)
+            .post_pin_by_paste_id(&paste)
+            .await?;
+        assert_eq!(response.status(), 200);
+    }
+
+    let paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let response = client
+        .username(&user.username)
+        .post_pin_by_paste_id(&paste)
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response
+        .text()
+        .await?
+        .contains("You can pin at most 6 pastes"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn pastes_can_be_deleted_or_made_public_together_from_the_profile() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    client.login().post(&user).await?;
+    let mut secret_pastes = Vec::new();
+    for _ in 0..2 {
+        secret_pastes.push(
+            MockPaste::builder()
+                .random()?
+                .visibility("secret")
+                .build()
+                .seed(&app, &user)
+                .await?,
+        );
+    }
+    let unselected_paste = MockPaste::builder()
+        .random()?
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let other_users_paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &other_user)
+        .await?;
+
+    let response = client.username(&user.username).get(None).await?;
+    let html = response.text().await?;
+    assert!(html.contains("Delete selected"));
+    assert!(html.contains(r#"name="ids""#));
+    let anonymous_client = TestClient::new(app.address, None)?;
+    let response = anonymous_client.username(&user.username).get(None).await?;
+    assert!(!response.text().await?.contains("Delete selected"));
+
+    let response = client
+        .pastes()
+        .post_batch("make_public", &[&secret_pastes[0], &secret_pastes[1]])
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["hx-refresh"], "true");
+    for paste in &secret_pastes {
+        let response = client.api_pastes().get_by_id(paste).await?;
+        let persisted_paste: MockPaste = response.json().await?;
+        assert_eq!(persisted_paste.visibility, "public");
+    }
+    let response = client.api_pastes().get_by_id(&unselected_paste).await?;
+    let persisted_paste: MockPaste = response.json().await?;
+    assert_eq!(persisted_paste.visibility, "secret");
+
+    let response = client
+        .pastes()
+        .post_batch("delete", &[&secret_pastes[0], &other_users_paste])
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(!response.headers().contains_key("hx-refresh"));
+    assert!(response
+        .text()
+        .await?
+        .contains("1 of 2 selected pastes could not be deleted."));
+    let response = client.api_pastes().get_by_id(&secret_pastes[0]).await?;
+    assert_eq!(response.status(), 404);
+    let response = client.api_pastes().get_by_id(&other_users_paste).await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client.pastes().post_batch("delete", &[]).await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("Select at least one paste"));
+    Ok(())
+}
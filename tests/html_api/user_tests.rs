@@ -1,11 +1,24 @@
-use crate::common::mocks::mock_api_key::MockApiKey;
+use crate::common::mocks::mock_api_key::{MockApiKey, ALL_SCOPES};
 use crate::common::mocks::mock_pagination::MockPaginationParams;
 use crate::common::mocks::mock_paste::MockPaste;
 use crate::common::mocks::mock_user::MockUser;
-use crate::common::rand_helper::{random_alphanumeric_string, random_filename, random_string};
+use crate::common::rand_helper::{
+    random_alphanumeric_string, random_breached_password_corpus, random_filename, random_string,
+};
 use crate::common::test_app::TestApp;
-use crate::common::test_client::TestClient;
+use crate::common::test_client::{TestClient, USER_AGENT};
 use crate::prelude::*;
+use argon2::Params;
+use gluestick::config::{
+    Config, PasswordHashingConfig, RegistrationMode, ThrottleConfig, ThrottlePolicy,
+};
+use gluestick::models::account_deletion::AccountDeletion;
+use gluestick::models::throttle::Throttle;
+use gluestick::models::user::{EmailAddress, User};
+use secrecy::ExposeSecret;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use tokio::task::JoinSet;
 use uuid::Uuid;
 
 #[tokio::test]
@@ -71,6 +84,93 @@ async fn invite_codes_are_consumed_on_signup() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn signup_page_asks_for_an_invite_code_by_default() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let html = client.signup().get().await?.text().await?;
+    assert!(html.contains("name=\"invite_code\""));
+    Ok(())
+}
+
+#[tokio::test]
+async fn open_registration_does_not_require_an_invite_code() -> Result<()> {
+    let config = Config::default().with_registration_mode(RegistrationMode::Open);
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let html = client.signup().get().await?.text().await?;
+    assert!(!html.contains("name=\"invite_code\""));
+
+    let user = MockUser::builder().random()?.build();
+    let response = client.signup().post(String::new(), &user).await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn closed_registration_rejects_all_signups() -> Result<()> {
+    let config = Config::default().with_registration_mode(RegistrationMode::Closed);
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+    let invite = app.seed_random_invite_code().await?;
+
+    let html = client.signup().get().await?.text().await?;
+    assert!(html.contains("Sign ups are currently closed"));
+    assert!(!html.contains("action=\"/signup\""));
+
+    let user = MockUser::builder().random()?.build();
+    let response = client.signup().post(invite, &user).await?;
+    assert_eq!(response.status(), 403);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    Ok(())
+}
+
+#[tokio::test]
+async fn domain_restricted_registration_only_accepts_allowed_email_domains() -> Result<()> {
+    let config =
+        Config::default().with_registration_mode(RegistrationMode::AllowedEmailDomains(vec![
+            "example.com".into(),
+        ]));
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let html = client.signup().get().await?.text().await?;
+    assert!(html.contains("example.com"));
+    assert!(!html.contains("name=\"invite_code\""));
+
+    let outsider = MockUser::builder()
+        .random()?
+        .email(format!(
+            "{}@example.org",
+            random_alphanumeric_string(8..=8)?
+        ))
+        .build();
+    let response = client.signup().post_validate_email(&outsider).await?;
+    assert_eq!(response.status(), 422);
+    let response = client.signup().post(String::new(), &outsider).await?;
+    assert_eq!(response.status(), 422);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+
+    let insider = MockUser::builder()
+        .random()?
+        .email(format!(
+            "{}@Example.com",
+            random_alphanumeric_string(8..=8)?
+        ))
+        .build();
+    let response = client.signup().post(String::new(), &insider).await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
 #[tokio::test]
 async fn signup_requires_all_fields() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -83,7 +183,7 @@ async fn signup_requires_all_fields() -> Result<()> {
 
     for bad_user in bad_users {
         let invite = app.seed_random_invite_code().await?;
-        let response = client.signup().post(invite, &bad_user).await?;
+        let response = client.signup().post(invite, bad_user).await?;
         assert_eq!(response.status(), 422);
 
         // Since settings is session gated, we can use it to check for a session
@@ -113,7 +213,7 @@ async fn signup_requires_valid_username() -> Result<()> {
 
     for bad_user in bad_users {
         let invite = app.seed_random_invite_code().await?;
-        let response = client.signup().post(invite, &bad_user).await?;
+        let response = client.signup().post(invite, bad_user).await?;
         assert_eq!(response.status(), 422);
 
         // Since settings is session gated, we can use it to check for a session
@@ -140,7 +240,7 @@ async fn signup_requires_valid_email_address() -> Result<()> {
 
     for bad_user in bad_users {
         let invite = app.seed_random_invite_code().await?;
-        let response = client.signup().post(invite, &bad_user).await?;
+        let response = client.signup().post(invite, bad_user).await?;
         assert_eq!(response.status(), 422);
 
         // Since settings is session gated, we can use it to check for a session
@@ -166,7 +266,7 @@ async fn signup_requires_password_between_8_and_256_chars() -> Result<()> {
 
     for bad_user in bad_users {
         let invite = app.seed_random_invite_code().await?;
-        let response = client.signup().post(invite, &bad_user).await?;
+        let response = client.signup().post(invite, bad_user).await?;
         assert_eq!(response.status(), 422);
 
         // Since settings is session gated, we can use it to check for a session
@@ -177,6 +277,42 @@ async fn signup_requires_password_between_8_and_256_chars() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn signup_rejects_breached_passwords() -> Result<()> {
+    let breached_passwords: Vec<String> = (0..5)
+        .map(|_| random_alphanumeric_string(8..=20))
+        .collect::<Result<_>>()?;
+    let corpus_path = random_breached_password_corpus(
+        &breached_passwords
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>(),
+    )?;
+    let config = Config::default().with_breached_passwords_path(Some(corpus_path));
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    for password in breached_passwords {
+        let bad_user = MockUser::builder().random()?.password(password).build();
+        let response = client.signup().post_validate_password(&bad_user).await?;
+        assert_eq!(response.status(), 422);
+        assert!(response.text().await?.contains("data breach"));
+
+        let invite = app.seed_random_invite_code().await?;
+        let response = client.signup().post(invite, &bad_user).await?;
+        assert_eq!(response.status(), 422);
+        assert!(response.text().await?.contains("data breach"));
+    }
+
+    let user = MockUser::builder().random()?.build();
+    let response = client.signup().post_validate_password(&user).await?;
+    assert_eq!(response.status(), 200);
+    let invite = app.seed_random_invite_code().await?;
+    let response = client.signup().post(invite, &user).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
 #[tokio::test]
 async fn cant_signup_twice_with_the_same_username() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -271,7 +407,7 @@ async fn login_requires_email_and_password() -> Result<()> {
     let bad_users = &[no_email, no_password, no_nothing];
 
     for bad_user in bad_users {
-        let response = client.login().post(&bad_user).await?;
+        let response = client.login().post(bad_user).await?;
         assert_eq!(response.status(), 401);
 
         let response = client.settings().get().await?;
@@ -313,445 +449,2093 @@ async fn cant_login_with_your_password_but_someone_elses_email() -> Result<()> {
 }
 
 #[tokio::test]
-async fn show_happpy_path() -> Result<()> {
+async fn login_is_throttled_after_repeated_failures() -> Result<()> {
     let app = TestApp::spawn().await?;
-    let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let paste1 = MockPaste::builder()
-        .random()?
-        .build()
-        .seed(&app, &user)
-        .await?;
-    let paste2 = MockPaste::builder()
-        .random()?
-        .build()
-        .seed(&app, &user)
-        .await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let mut wrong_password = user.clone();
+    wrong_password.password = "not-the-password".into();
 
-    let response = client.username(&user.username).get(None).await?;
+    for _ in 0..5 {
+        let response = client.login().post(&wrong_password).await?;
+        assert_eq!(response.status(), 401);
+    }
+
+    let response = client.login().post(&wrong_password).await?;
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+
+    // Even the correct password is refused until the backoff has elapsed
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 429);
+
+    app.backdate_throttles(60).await?;
+    let response = client.login().post(&user).await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await.unwrap();
-    assert!(html.contains(&paste1.filename));
-    assert!(html.contains(&paste2.filename));
     Ok(())
 }
 
 #[tokio::test]
-async fn show_does_not_include_secret_pastes() -> Result<()> {
+async fn concurrent_failed_logins_cannot_skip_the_backoff() -> Result<()> {
     let app = TestApp::spawn().await?;
+    let client = Arc::new(TestClient::new(app.address, None)?);
     let user = MockUser::builder().random()?.build().seed(&app).await?;
-    let client = TestClient::new(app.address, None)?;
-    let paste1 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .build()
-        .seed(&app, &user)
-        .await?;
-    let paste2 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .visibility("secret")
-        .build()
-        .seed(&app, &user)
-        .await?;
+    let mut wrong_password = user.clone();
+    wrong_password.password = "not-the-password".into();
+    let wrong_password = Arc::new(wrong_password);
+
+    // Every attempt is counted before its password is checked, so only the 5 free attempts get
+    // through, however many are in flight at once
+    let mut requests = JoinSet::new();
+    for _ in 0..12 {
+        let client = client.clone();
+        let wrong_password = wrong_password.clone();
+        requests.spawn(async move {
+            client
+                .login()
+                .post(&wrong_password)
+                .await
+                .map(|r| r.status())
+        });
+    }
+    let mut refused = 0;
+    while let Some(status) = requests.join_next().await {
+        if status?? == 429 {
+            refused += 1;
+        }
+    }
+    assert_eq!(refused, 7);
 
-    let response = client.username(&user.username).get(None).await?;
+    // A successful sign in hands back its attempt, so it doesn't count against the IP
+    app.backdate_throttles(60).await?;
+    let response = client.login().post(&user).await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await.unwrap();
-    assert!(html.contains(&paste1.filename));
-    assert!(!html.contains(&paste2.filename));
     Ok(())
 }
 
 #[tokio::test]
-async fn show_does_not_include_other_users_pastes() -> Result<()> {
-    let app = TestApp::spawn().await?;
+async fn login_upgrades_password_hashes_with_outdated_parameters() -> Result<()> {
+    let password_hashing = PasswordHashingConfig::new(Params::DEFAULT_M_COST, 3, 1)?;
+    let params = password_hashing.params().clone();
+    let config = Config::default().with_password_hashing(password_hashing);
+    let app = TestApp::spawn_with_config(config).await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
-    let user2 = MockUser::builder().random()?.build().seed(&app).await?;
+    let email: EmailAddress = user.email.parse()?;
+
+    let stored_user = User::find_by_email(&app.db, email.clone())
+        .await?
+        .ok_or("user not found")?;
+    assert!(stored_user.password.is_weaker_than(&params));
+    assert_eq!(
+        User::count_with_outdated_password_hashes(&app.db, &params).await?,
+        1
+    );
+
     let client = TestClient::new(app.address, None)?;
-    let paste1 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .build()
-        .seed(&app, &user)
-        .await?;
-    let paste2 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .build()
-        .seed(&app, &user2)
-        .await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
 
-    let response = client.username(&user.username).get(None).await?;
+    let stored_user = User::find_by_email(&app.db, email)
+        .await?
+        .ok_or("user not found")?;
+    assert!(!stored_user.password.is_weaker_than(&params));
+    assert!(stored_user.password.expose_secret().contains("t=3"));
+    assert_eq!(
+        User::count_with_outdated_password_hashes(&app.db, &params).await?,
+        0
+    );
+
+    // The upgraded hash still verifies
+    client.logout().delete().await?;
+    let response = client.login().post(&user).await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await.unwrap();
-    assert!(html.contains(&paste1.filename));
-    assert!(!html.contains(&paste2.filename));
     Ok(())
 }
 
 #[tokio::test]
-async fn show_includes_your_own_secret_pastes_when_logged_in() -> Result<()> {
-    let app = TestApp::spawn().await?;
-    let user = MockUser::builder().random()?.build().seed(&app).await?;
-    let client = TestClient::new(app.address, None)?;
-    let paste1 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .build()
-        .seed(&app, &user)
-        .await?;
-    let paste2 = MockPaste::builder()
+async fn admin_overview_reports_accounts_on_outdated_password_hashes() -> Result<()> {
+    let password_hashing = PasswordHashingConfig::new(Params::DEFAULT_M_COST, 3, 1)?;
+    let config = Config::default().with_password_hashing(password_hashing);
+    let app = TestApp::spawn_with_config(config).await?;
+    let admin = MockUser::builder()
         .random()?
-        .filename(random_filename(64..=64)?)
-        .visibility("secret")
         .build()
-        .seed(&app, &user)
+        .seed_as_admin(&app)
         .await?;
-    client.login().post(&user).await?;
+    MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&admin).await?;
 
-    let response = client.username(&user.username).get(None).await?;
-    assert_eq!(response.status(), 200);
-    let html = response.text().await.unwrap();
-    assert!(html.contains(&paste1.filename));
-    assert!(html.contains(&paste2.filename));
+    // Signing in upgraded the admin's own hash, which leaves the other account
+    let response = client.admin().get().await?;
+    let html = response.text().await?;
+    let (_, stat) = html
+        .split_once("Accounts on outdated password hashing</dt>")
+        .ok_or("stat not found")?;
+    assert!(stat.trim_start().starts_with("<dd>1</dd>"));
     Ok(())
 }
 
 #[tokio::test]
-async fn show_does_not_include_other_users_secret_pastes_when_logged_in() -> Result<()> {
-    let app = TestApp::spawn().await?;
+async fn login_is_turned_away_when_password_hashing_is_saturated() -> Result<()> {
+    let password_hashing = PasswordHashingConfig::default()
+        .with_max_concurrent(1)
+        .with_max_queued(0);
+    // Concurrent sign ins are counted against the throttles while they're in flight, so the
+    // throttles are loosened to leave only the hashing pool turning them away
+    let policy = ThrottlePolicy {
+        free_attempts: 100,
+        lockout_threshold: 0,
+        max_delay_seconds: 30,
+        lockout_seconds: 0,
+    };
+    let throttle = ThrottleConfig::new(
+        policy,
+        policy,
+        ThrottleConfig::default().signup_validation_ip(),
+    );
+    let config = Config::default()
+        .with_password_hashing(password_hashing)
+        .with_throttle(throttle);
+    let app = TestApp::spawn_with_config(config).await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
-    let user2 = MockUser::builder().random()?.build().seed(&app).await?;
-    let client = TestClient::new(app.address, None)?;
-    let paste1 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .build()
-        .seed(&app, &user2)
-        .await?;
-    let paste2 = MockPaste::builder()
-        .random()?
-        .filename(random_filename(64..=64)?)
-        .visibility("secret")
-        .build()
-        .seed(&app, &user2)
-        .await?;
-    client.login().post(&user).await?;
 
-    let response = client.username(&user2.username).get(None).await?;
+    let mut logins = JoinSet::new();
+    for _ in 0..16 {
+        let client = TestClient::new(app.address, None)?;
+        let user = user.clone();
+        logins.spawn(async move { client.login().post(&user).await.map(|r| r.status()) });
+    }
+    let mut statuses = Vec::new();
+    while let Some(status) = logins.join_next().await {
+        statuses.push(status??);
+    }
+
+    assert!(statuses.iter().any(|status| *status == 200));
+    assert!(statuses.iter().any(|status| *status == 503));
+    assert!(statuses
+        .iter()
+        .all(|status| *status == 200 || *status == 503));
+
+    // Turned away sign ins don't count as failed attempts
+    let client = TestClient::new(app.address, None)?;
+    let response = client.login().post(&user).await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await.unwrap();
-    assert!(html.contains(&paste1.filename));
-    assert!(!html.contains(&paste2.filename));
     Ok(())
 }
 
 #[tokio::test]
-async fn show_has_per_page_default() -> Result<()> {
+async fn login_throttle_does_not_reveal_whether_an_email_exists() -> Result<()> {
     let app = TestApp::spawn().await?;
-    let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    for _ in 0..11 {
-        MockPaste::builder()
-            .random()?
-            .build()
-            .seed(&app, &user)
-            .await?;
+    let nonexistent_user = MockUser::builder().random()?.build();
+
+    for _ in 0..5 {
+        let response = client.login().post(&nonexistent_user).await?;
+        assert_eq!(response.status(), 401);
+        assert!(response
+            .text()
+            .await?
+            .contains("Incorrect email or password"));
     }
 
-    let response = client.username(&user.username).get(None).await?;
-    assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert_eq!(html.matches("<li class=\"paste\">").count(), 10);
+    let response = client.login().post(&nonexistent_user).await?;
+    assert_eq!(response.status(), 429);
     Ok(())
 }
 
 #[tokio::test]
-async fn show_uses_per_page_when_provided() -> Result<()> {
+async fn successful_login_resets_the_account_throttle() -> Result<()> {
     let app = TestApp::spawn().await?;
-    let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let per_page = 3;
-    for _ in 0..per_page + 1 {
-        MockPaste::builder()
-            .random()?
-            .build()
-            .seed(&app, &user)
-            .await?;
-    }
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let mut wrong_password = user.clone();
+    wrong_password.password = "not-the-password".into();
 
-    let params = MockPaginationParams::builder().per_page(per_page).build();
-    let response = client.username(&user.username).get(Some(params)).await?;
+    for _ in 0..4 {
+        client.login().post(&wrong_password).await?;
+    }
+    let response = client.login().post(&user).await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert_eq!(html.matches("<li class=\"paste\">").count(), per_page);
+
+    for _ in 0..5 {
+        let response = client.login().post(&wrong_password).await?;
+        assert_eq!(response.status(), 401);
+    }
     Ok(())
 }
 
 #[tokio::test]
-async fn show_falls_back_to_default_if_per_page_more_than_100() -> Result<()> {
+async fn login_is_throttled_per_ip_across_accounts() -> Result<()> {
     let app = TestApp::spawn().await?;
-    let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let per_page = 101;
-    for _ in 0..11 {
-        MockPaste::builder()
-            .random()?
-            .build()
-            .seed(&app, &user)
-            .await?;
+
+    for _ in 0..20 {
+        let user = MockUser::builder().random()?.build();
+        let response = client.login().post(&user).await?;
+        assert_eq!(response.status(), 401);
     }
 
-    let params = MockPaginationParams::builder().per_page(per_page).build();
-    let response = client.username(&user.username).get(Some(params)).await?;
-    assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert_eq!(html.matches("<li class=\"paste\">").count(), 10);
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 429);
     Ok(())
 }
 
 #[tokio::test]
-async fn show_paginates_correctly() -> Result<()> {
-    fn extract_next_cursor(html: &str) -> Result<&str> {
-        html.split("next_page=")
-            .nth(1)
-            .and_then(|s| s.split('"').next())
-            .ok_or_else(|| "Failed to find next cursor".into())
+async fn login_ip_throttle_uses_the_client_address_forwarded_by_a_trusted_proxy() -> Result<()> {
+    let config = Config::default().with_trusted_proxies(vec![Ipv4Addr::LOCALHOST.into()]);
+    let app = TestApp::spawn_with_config(config).await?;
+    // The proxy's own address is on the right, after whatever the client claimed to be
+    let client = TestClient::new_forwarded_for(app.address, "10.0.0.1, 203.0.113.1, 127.0.0.1")?;
+
+    for _ in 0..20 {
+        let user = MockUser::builder().random()?.build();
+        let response = client.login().post(&user).await?;
+        assert_eq!(response.status(), 401);
     }
 
-    fn extract_prev_cursor(html: &str) -> Result<&str> {
-        html.split("prev_page=")
-            .nth(1)
-            .and_then(|s| s.split('"').next())
-            .ok_or_else(|| "Failed to find prev cursor".into())
-    }
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 429);
+
+    // Other clients behind the same proxy are unaffected
+    let other_client = TestClient::new_forwarded_for(app.address, "10.0.0.1, 203.0.113.2")?;
+    let response = other_client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
 
+#[tokio::test]
+async fn login_ip_throttle_trusts_a_proxy_on_loopback_by_default() -> Result<()> {
     let app = TestApp::spawn().await?;
+    let client = TestClient::new_forwarded_for(app.address, "203.0.113.1")?;
+
+    for _ in 0..20 {
+        let user = MockUser::builder().random()?.build();
+        let response = client.login().post(&user).await?;
+        assert_eq!(response.status(), 401);
+    }
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 429);
+
+    // One client's failures behind the proxy don't lock out everyone else behind it
+    let other_client = TestClient::new_forwarded_for(app.address, "203.0.113.2")?;
+    let response = other_client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_ip_throttle_ignores_forwarded_for_from_untrusted_peers() -> Result<()> {
+    let app =
+        TestApp::spawn_with_config(Config::default().with_trusted_proxies(Vec::new())).await?;
+    let client = TestClient::new_forwarded_for(app.address, "203.0.113.1")?;
+
+    for _ in 0..20 {
+        let user = MockUser::builder().random()?.build();
+        let response = client.login().post(&user).await?;
+        assert_eq!(response.status(), 401);
+    }
+
+    let other_client = TestClient::new_forwarded_for(app.address, "203.0.113.2")?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let response = other_client.login().post(&user).await?;
+    assert_eq!(response.status(), 429);
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_throttle_policies_are_configurable() -> Result<()> {
+    let policy = ThrottlePolicy {
+        free_attempts: 2,
+        lockout_threshold: 0,
+        max_delay_seconds: 30,
+        lockout_seconds: 0,
+    };
+    let throttle = ThrottleConfig::new(
+        policy,
+        policy,
+        ThrottleConfig::default().signup_validation_ip(),
+    );
+    let app = TestApp::spawn_with_config(Config::default().with_throttle(throttle)).await?;
     let client = TestClient::new(app.address, None)?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let mut wrong_password = user.clone();
+    wrong_password.password = "not-the-password".into();
 
-    let mut pastes = Vec::new();
-    for _ in 0..8 {
-        let paste = MockPaste::builder()
-            .random()?
-            .id(Uuid::now_v7())
-            .build()
-            .seed(&app, &user)
-            .await?;
-        pastes.push(paste);
+    for _ in 0..2 {
+        let response = client.login().post(&wrong_password).await?;
+        assert_eq!(response.status(), 401);
     }
+    let response = client.login().post(&wrong_password).await?;
+    assert_eq!(response.status(), 429);
 
-    // First page
-    let params = MockPaginationParams::builder().per_page(3).build();
-    let response = client.username(&user.username).get(Some(params)).await?;
-    let html = response.text().await?;
-    for paste in &pastes[5..8] {
-        assert!(html.contains(&paste.filename));
+    // A lockout threshold of 0 never locks the account out, however many attempts are made
+    for _ in 0..20 {
+        app.backdate_throttles(60).await?;
+        let response = client.login().post(&wrong_password).await?;
+        assert_eq!(response.status(), 401);
     }
-    assert!(html.contains("<span>Newer</span>"));
-    assert!(html.contains("Older</a>"));
+    app.backdate_throttles(60).await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
 
-    // Second page (forward)
-    let next_cursor = extract_next_cursor(&html)?;
-    let params = MockPaginationParams::builder()
-        .per_page(3)
-        .next_page(next_cursor)
-        .build();
-    let response = client.username(&user.username).get(Some(params)).await?;
-    let html = response.text().await?;
-    for paste in &pastes[2..5] {
-        assert!(html.contains(&paste.filename));
+#[tokio::test]
+async fn account_is_locked_after_too_many_failures_and_owner_is_notified() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let mut wrong_password = user.clone();
+    wrong_password.password = "not-the-password".into();
+
+    for _ in 0..10 {
+        let response = client.login().post(&wrong_password).await?;
+        assert_eq!(response.status(), 401);
+        // Wait out the backoff, so that every attempt counts
+        app.backdate_throttles(60).await?;
     }
-    assert!(html.contains("Newer</a>"));
-    assert!(html.contains("Older</a>"));
 
-    // Third page (forward)
-    let next_cursor = extract_next_cursor(&html)?;
-    let params = MockPaginationParams::builder()
-        .per_page(3)
-        .next_page(next_cursor)
-        .build();
-    let response = client.username(&user.username).get(Some(params)).await?;
-    let html = response.text().await?;
-    for paste in &pastes[0..2] {
-        assert!(html.contains(&paste.filename));
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 429);
+
+    // Lockouts last for 15 minutes
+    app.backdate_throttles(14 * 60).await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client.settings().get().await?;
+    assert!(response
+        .text()
+        .await?
+        .contains("Sign in temporarily locked after too many failed attempts"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn lapsed_lockouts_are_expired() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+    let user = MockUser::builder().random()?.build();
+
+    for _ in 0..10 {
+        client.login().post(&user).await?;
+        app.backdate_throttles(60).await?;
     }
-    assert!(html.contains("Newer</a>"));
-    assert!(html.contains("<span>Older</span>"));
+    assert_eq!(Throttle::expire(&app.db).await?, 0);
 
-    // Second page (backward)
-    let prev_cursor = extract_prev_cursor(&html)?;
-    let params = MockPaginationParams::builder()
-        .per_page(3)
-        .prev_page(prev_cursor)
-        .build();
-    let response = client.username(&user.username).get(Some(params)).await?;
-    let html = response.text().await?;
-    for paste in &pastes[2..5] {
-        assert!(html.contains(&paste.filename));
+    app.backdate_throttles(15 * 60).await?;
+    // Both the account lockout and the (unlocked, but now stale) IP throttle are expired
+    assert_eq!(Throttle::expire(&app.db).await?, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn signup_validation_is_throttled() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    for _ in 0..60 {
+        let user = MockUser::builder().random()?.build();
+        let response = client.signup().post_validate_email(&user).await?;
+        assert_eq!(response.status(), 200);
     }
-    assert!(html.contains("Newer</a>"));
-    assert!(html.contains("Older</a>"));
 
-    // First page (backward)
-    let prev_cursor = extract_prev_cursor(&html)?;
-    let params = MockPaginationParams::builder()
-        .per_page(3)
-        .prev_page(prev_cursor)
-        .build();
-    let response = client.username(&user.username).get(Some(params)).await?;
-    let html = response.text().await?;
-    for paste in &pastes[5..8] {
-        assert!(html.contains(&paste.filename));
+    let user = MockUser::builder().random()?.build();
+    let response = client.signup().post_validate_email(&user).await?;
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+    Ok(())
+}
+
+fn session_cookie(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|value| value.starts_with("session_token="))
+        .expect("response should set a session cookie")
+        .to_string()
+}
+
+#[tokio::test]
+async fn login_sets_a_hardened_browser_session_cookie_by_default() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new_without_redirects(app.address)?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 303);
+
+    let cookie = session_cookie(&response);
+    assert!(cookie.contains("HttpOnly"));
+    assert!(cookie.contains("Secure"));
+    assert!(cookie.contains("SameSite=Lax"));
+    assert!(cookie.contains("Path=/"));
+    assert!(!cookie.contains("Max-Age"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_with_remember_me_sets_a_persistent_cookie() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new_without_redirects(app.address)?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+
+    let response = client.login().post_with_remember_me(&user).await?;
+    assert_eq!(response.status(), 303);
+
+    let cookie = session_cookie(&response);
+    assert!(cookie.contains("Max-Age=2592000"));
+    assert!(cookie.contains("SameSite=Lax"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn signup_sets_a_hardened_session_cookie() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new_without_redirects(app.address)?;
+    let invite = app.seed_random_invite_code().await?;
+    let user = MockUser::builder().random()?.build();
+
+    let response = client.signup().post(invite, &user).await?;
+    assert_eq!(response.status(), 303);
+
+    let cookie = session_cookie(&response);
+    assert!(cookie.contains("HttpOnly"));
+    assert!(cookie.contains("Secure"));
+    assert!(cookie.contains("SameSite=Lax"));
+    assert!(cookie.contains("Path=/"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn logout_clears_the_session_cookie() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new_without_redirects(app.address)?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    client.login().post(&user).await?;
+
+    let response = client.logout().delete().await?;
+    assert_eq!(response.status(), 200);
+
+    let cookie = session_cookie(&response);
+    assert!(cookie.contains("Max-Age=0"));
+    assert!(cookie.contains("Path=/"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_happpy_path() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste1 = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let paste2 = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client.username(&user.username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&paste1.filename));
+    assert!(html.contains(&paste2.filename));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_does_not_include_secret_pastes() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste1 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let paste2 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client.username(&user.username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&paste1.filename));
+    assert!(!html.contains(&paste2.filename));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_does_not_include_other_users_pastes() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let user2 = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste1 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let paste2 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &user2)
+        .await?;
+
+    let response = client.username(&user.username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&paste1.filename));
+    assert!(!html.contains(&paste2.filename));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_includes_your_own_secret_pastes_when_logged_in() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste1 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let paste2 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    client.login().post(&user).await?;
+
+    let response = client.username(&user.username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&paste1.filename));
+    assert!(html.contains(&paste2.filename));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_does_not_include_other_users_secret_pastes_when_logged_in() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let user2 = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let paste1 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .build()
+        .seed(&app, &user2)
+        .await?;
+    let paste2 = MockPaste::builder()
+        .random()?
+        .filename(random_filename(64..=64)?)
+        .visibility("secret")
+        .build()
+        .seed(&app, &user2)
+        .await?;
+    client.login().post(&user).await?;
+
+    let response = client.username(&user2.username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&paste1.filename));
+    assert!(!html.contains(&paste2.filename));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_has_per_page_default() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    for _ in 0..11 {
+        MockPaste::builder()
+            .random()?
+            .build()
+            .seed(&app, &user)
+            .await?;
+    }
+
+    let response = client.username(&user.username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"paste\">").count(), 10);
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_uses_per_page_when_provided() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let per_page = 3;
+    for _ in 0..per_page + 1 {
+        MockPaste::builder()
+            .random()?
+            .build()
+            .seed(&app, &user)
+            .await?;
+    }
+
+    let params = MockPaginationParams::builder().per_page(per_page).build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"paste\">").count(), per_page);
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_falls_back_to_default_if_per_page_more_than_100() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let per_page = 101;
+    for _ in 0..11 {
+        MockPaste::builder()
+            .random()?
+            .build()
+            .seed(&app, &user)
+            .await?;
+    }
+
+    let params = MockPaginationParams::builder().per_page(per_page).build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"paste\">").count(), 10);
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_paginates_correctly() -> Result<()> {
+    fn extract_next_cursor(html: &str) -> Result<&str> {
+        html.split("next_page=")
+            .nth(1)
+            .and_then(|s| s.split('"').next())
+            .ok_or_else(|| "Failed to find next cursor".into())
+    }
+
+    fn extract_prev_cursor(html: &str) -> Result<&str> {
+        html.split("prev_page=")
+            .nth(1)
+            .and_then(|s| s.split('"').next())
+            .ok_or_else(|| "Failed to find prev cursor".into())
+    }
+
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let mut pastes = Vec::new();
+    for _ in 0..8 {
+        let paste = MockPaste::builder()
+            .random()?
+            .id(Uuid::now_v7())
+            .build()
+            .seed(&app, &user)
+            .await?;
+        pastes.push(paste);
+    }
+
+    // First page
+    let params = MockPaginationParams::builder().per_page(3).build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    let html = response.text().await?;
+    for paste in &pastes[5..8] {
+        assert!(html.contains(&paste.filename));
+    }
+    assert!(html.contains("<span>Newer</span>"));
+    assert!(html.contains("Older</a>"));
+
+    // Second page (forward)
+    let next_cursor = extract_next_cursor(&html)?;
+    let params = MockPaginationParams::builder()
+        .per_page(3)
+        .next_page(next_cursor)
+        .build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    let html = response.text().await?;
+    for paste in &pastes[2..5] {
+        assert!(html.contains(&paste.filename));
+    }
+    assert!(html.contains("Newer</a>"));
+    assert!(html.contains("Older</a>"));
+
+    // Third page (forward)
+    let next_cursor = extract_next_cursor(&html)?;
+    let params = MockPaginationParams::builder()
+        .per_page(3)
+        .next_page(next_cursor)
+        .build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    let html = response.text().await?;
+    for paste in &pastes[0..2] {
+        assert!(html.contains(&paste.filename));
+    }
+    assert!(html.contains("Newer</a>"));
+    assert!(html.contains("<span>Older</span>"));
+
+    // Second page (backward)
+    let prev_cursor = extract_prev_cursor(&html)?;
+    let params = MockPaginationParams::builder()
+        .per_page(3)
+        .prev_page(prev_cursor)
+        .build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    let html = response.text().await?;
+    for paste in &pastes[2..5] {
+        assert!(html.contains(&paste.filename));
+    }
+    assert!(html.contains("Newer</a>"));
+    assert!(html.contains("Older</a>"));
+
+    // First page (backward)
+    let prev_cursor = extract_prev_cursor(&html)?;
+    let params = MockPaginationParams::builder()
+        .per_page(3)
+        .prev_page(prev_cursor)
+        .build();
+    let response = client.username(&user.username).get(Some(params)).await?;
+    let html = response.text().await?;
+    for paste in &pastes[5..8] {
+        assert!(html.contains(&paste.filename));
+    }
+    assert!(html.contains("<span>Newer</span>"));
+    assert!(html.contains("Older</a>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_lets_owners_filter_their_pastes_by_visibility() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let public_paste = MockPaste::builder()
+        .random()?
+        .filename("public.txt")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    MockPaste::builder()
+        .random()?
+        .filename("secret.txt")
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    client.login().post(&user).await?;
+    client
+        .username(&user.username)
+        .post_pin_by_paste_id(&public_paste)
+        .await?;
+    let response = client.username(&user.username).get(None).await?;
+    assert!(response.text().await?.contains(r#"class="pinned""#));
+
+    let response = client
+        .get_arbitrary(&format!("/{}?visibility=secret", user.username))
+        .await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(r#"name="visibility""#));
+    assert!(html.contains("secret.txt"));
+    // Pinned pastes are left out while filtering, since they might not match
+    assert!(!html.contains("public.txt"));
+
+    // Other people only ever see public pastes, whatever they filter by
+    let other_client = TestClient::new(app.address, None)?;
+    let response = other_client
+        .get_arbitrary(&format!("/{}?visibility=secret", user.username))
+        .await?;
+    let html = response.text().await?;
+    assert!(!html.contains(r#"name="visibility""#));
+    assert!(!html.contains("secret.txt"));
+    assert!(html.contains("No pastes match these filters."));
+    Ok(())
+}
+
+#[tokio::test]
+async fn settings_inaccessible_when_logged_out() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn settings_lists_users_api_keys() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let api_key1 = MockApiKey::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let api_key2 = MockApiKey::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(&api_key1.name));
+    assert!(html.contains(&api_key2.name));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn settings_does_not_list_other_users_api_keys() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let api_key1 = MockApiKey::builder()
+        .random()?
+        .build()
+        .seed(&app, &other_user)
+        .await?;
+    let api_key2 = MockApiKey::builder()
+        .random()?
+        .build()
+        .seed(&app, &other_user)
+        .await?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(!html.contains(&api_key1.name));
+    assert!(!html.contains(&api_key2.name));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_generate_new_api_keys() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(!html.contains("<li class=\"key\">"));
+
+    let response = client.api_sessions().post("", &ALL_SCOPES, "never").await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains("<li class=\"key\">"));
+    assert!(html.contains("API Key ending in"));
+    assert!(html.contains("Never expires"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn generated_api_keys_are_named_scoped_and_expiring() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let name = random_alphanumeric_string(1..=30)?;
+
+    let response = client
+        .api_sessions()
+        .post(&name, &["pastes:read"], "one_day")
+        .await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(&name));
+    let (_, rest) = html.split_once("<code id=").ok_or("key not found")?;
+    let (_, rest) = rest.split_once('>').ok_or("key not found")?;
+    let (unhashed_key, _) = rest.split_once('<').ok_or("key not found")?;
+    let api_key = MockApiKey::builder()
+        .random()?
+        .unhashed_key(unhashed_key)
+        .build();
+
+    let response = client.settings().get().await?;
+    let html = response.text().await?;
+    let (_, keys) = html
+        .split_once(r#"<ul class="keys">"#)
+        .ok_or("keys not found")?;
+    assert!(keys.contains(&name));
+    assert!(keys.contains("<code>pastes:read</code>"));
+    assert!(!keys.contains("<code>pastes:write</code>"));
+    assert!(keys.contains("Expires"));
+
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let response = api_client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 200);
+    let paste = MockPaste::builder().random()?.build();
+    let response = api_client.api_pastes().post(&paste).await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_generate_api_keys_without_scopes_or_with_long_names() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client
+        .api_sessions()
+        .post("No scopes", &[], "never")
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("Choose at least one scope"));
+
+    let response = client
+        .api_sessions()
+        .post(&"a".repeat(257), &ALL_SCOPES, "never")
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response
+        .text()
+        .await?
+        .contains("Name may not be longer than 256 characters"));
+
+    assert_eq!(
+        app.count_rows_for_user("api_keys", "user_id", &user)
+            .await?,
+        0
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_delete_api_keys() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let api_key = MockApiKey::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(&api_key.name));
+
+    let response = client.api_sessions().delete_by_id(&api_key).await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(!html.contains(&api_key.name));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_delete_other_users_api_keys() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_client = TestClient::new(app.address, None)?;
+    let api_key = MockApiKey::builder()
+        .random()?
+        .build()
+        .seed(&app, &other_user)
+        .await?;
+    client.login().post(&user).await?;
+    other_client.login().post(&other_user).await?;
+
+    let response = other_client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(&api_key.name));
+
+    let response = client.api_sessions().delete_by_id(&api_key).await?;
+    assert_eq!(response.status(), 404);
+
+    let response = other_client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(html.contains(&api_key.name));
+
+    Ok(())
+}
+
+fn revocable_session_ids(html: &str) -> Vec<String> {
+    html.match_indices("hx-delete=\"/settings/sessions/")
+        .map(|(i, m)| html[i + m.len()..i + m.len() + 36].to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn logout_only_ends_the_current_session() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    other_client.login().post(&user).await?;
+
+    let response = client.logout().delete().await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    let response = other_client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn settings_lists_active_sessions() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"session\">").count(), 1);
+    assert!(html.contains(USER_AGENT));
+    assert!(html.contains("127.0.0.1"));
+    assert!(html.contains("This session"));
+    assert!(revocable_session_ids(&html).is_empty());
+
+    other_client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"session\">").count(), 2);
+    assert_eq!(revocable_session_ids(&html).len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_revoke_another_session() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    other_client.login().post(&user).await?;
+
+    let html = client.settings().get().await?.text().await?;
+    let other_session_id = revocable_session_ids(&html).pop().unwrap();
+
+    let response = client
+        .settings()
+        .delete_session_by_id(&other_session_id)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    let response = other_client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_sign_out_all_other_sessions() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_clients = [
+        TestClient::new(app.address, None)?,
+        TestClient::new(app.address, None)?,
+    ];
+    client.login().post(&user).await?;
+    for other_client in &other_clients {
+        other_client.login().post(&user).await?;
+    }
+
+    let response = client.settings().delete_other_sessions().await?;
+    assert_eq!(response.status(), 200);
+
+    for other_client in &other_clients {
+        let response = other_client.settings().get().await?;
+        assert_eq!(response.status(), 401);
+    }
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"session\">").count(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_revoke_other_users_sessions() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_clients = [
+        TestClient::new(app.address, None)?,
+        TestClient::new(app.address, None)?,
+    ];
+    client.login().post(&user).await?;
+    for other_client in &other_clients {
+        other_client.login().post(&other_user).await?;
+    }
+
+    let html = other_clients[0].settings().get().await?.text().await?;
+    let other_users_session_id = revocable_session_ids(&html).pop().unwrap();
+
+    let response = client
+        .settings()
+        .delete_session_by_id(&other_users_session_id)
+        .await?;
+    assert_eq!(response.status(), 404);
+
+    let response = other_clients[1].settings().get().await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_happy_path() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let mut user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let new_password = random_alphanumeric_string(8..=20)?;
+    let response = client
+        .settings()
+        .post_change_password(&user.password, &new_password, false)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+
+    client.logout().delete().await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 401);
+    user.password = new_password;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_rejects_breached_passwords() -> Result<()> {
+    let breached_password = random_alphanumeric_string(8..=20)?;
+    let corpus_path = random_breached_password_corpus(&[&breached_password])?;
+    let config = Config::default().with_breached_passwords_path(Some(corpus_path));
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client
+        .settings()
+        .post_change_password(&user.password, &breached_password, false)
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("data breach"));
+
+    // The old password still works
+    client.logout().delete().await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_requires_correct_old_password() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client
+        .settings()
+        .post_change_password("not-the-password", "a-new-password", false)
+        .await?;
+    assert_eq!(response.status(), 401);
+
+    client.logout().delete().await?;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_signs_out_all_other_sessions() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let other_client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    other_client.login().post(&user).await?;
+
+    let response = client
+        .settings()
+        .post_change_password(&user.password, "a-new-password", false)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    let response = other_client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert_eq!(html.matches("<li class=\"session\">").count(), 1);
+    assert!(html.contains("Password changed"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_keeps_api_keys_by_default() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    client.login().post(&user).await?;
+
+    client
+        .settings()
+        .post_change_password(&user.password, "a-new-password", false)
+        .await?;
+
+    let response = api_client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_can_revoke_all_api_keys() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    client.login().post(&user).await?;
+
+    client
+        .settings()
+        .post_change_password(&user.password, "a-new-password", true)
+        .await?;
+
+    let response = api_client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 401);
+    let html = client.settings().get().await?.text().await?;
+    assert!(!html.contains(&api_key.name));
+    assert!(html.contains("All API keys revoked"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_password_preserves_remember_me() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new_without_redirects(app.address)?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    client.login().post_with_remember_me(&user).await?;
+
+    let new_password = random_alphanumeric_string(8..=20)?;
+    let response = client
+        .settings()
+        .post_change_password(&user.password, &new_password, false)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    let cookie = session_cookie(&response);
+    assert!(cookie.contains("Max-Age=2592000"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invites_inaccessible_when_logged_out() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.settings().get_invites().await?;
+    assert_eq!(response.status(), 401);
+    let response = client.settings().post_invite(1, "one_week").await?;
+    assert_eq!(response.status(), 401);
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_create_invite_codes_that_others_can_sign_up_with() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().post_invite(2, "one_week").await?;
+    assert_eq!(response.status(), 200);
+    let html = client.settings().get_invites().await?.text().await?;
+    let codes = invite_codes(&html);
+    assert_eq!(codes.len(), 1);
+    assert!(html.contains("Used 0 of 2 times"));
+    assert!(html.contains("You have 3 invitations remaining"));
+
+    for _ in 0..2 {
+        let invitee = MockUser::builder().random()?.build();
+        let invitee_client = TestClient::new(app.address, None)?;
+        let response = invitee_client
+            .signup()
+            .post(codes[0].clone(), &invitee)
+            .await?;
+        assert_eq!(response.status(), 200);
+    }
+
+    let invitee = MockUser::builder().random()?.build();
+    let invitee_client = TestClient::new(app.address, None)?;
+    let response = invitee_client
+        .signup()
+        .post(codes[0].clone(), &invitee)
+        .await?;
+    assert_eq!(response.status(), 422);
+
+    let html = client.settings().get_invites().await?.text().await?;
+    assert!(html.contains("Used 2 of 2 times"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invites_page_lists_who_signed_up_with_each_code() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    client.settings().post_invite(1, "never").await?;
+    let html = client.settings().get_invites().await?.text().await?;
+    let codes = invite_codes(&html);
+    assert!(html.contains("Never expires"));
+
+    let invitee = MockUser::builder().random()?.build();
+    let invitee_client = TestClient::new(app.address, None)?;
+    invitee_client
+        .signup()
+        .post(codes[0].clone(), &invitee)
+        .await?;
+
+    let html = client.settings().get_invites().await?.text().await?;
+    assert!(html.contains(&format!("href=\"/{}\"", invitee.username)));
+
+    // Invitees can't see the codes of the person who invited them
+    let html = invitee_client
+        .settings()
+        .get_invites()
+        .await?
+        .text()
+        .await?;
+    assert!(invite_codes(&html).is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn invite_codes_are_limited_by_the_invite_allowance() -> Result<()> {
+    let config = Config::default().with_invite_allowance(3);
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().post_invite(4, "one_week").await?;
+    assert_eq!(response.status(), 422);
+
+    let response = client.settings().post_invite(2, "one_week").await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().post_invite(2, "one_week").await?;
+    assert_eq!(response.status(), 422);
+    let response = client.settings().post_invite(1, "one_week").await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().post_invite(1, "one_week").await?;
+    assert_eq!(response.status(), 422);
+
+    let html = client.settings().get_invites().await?.text().await?;
+    assert_eq!(invite_codes(&html).len(), 2);
+    assert!(html.contains("You have 0 invitations remaining"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_invite_codes_cannot_exceed_the_invite_allowance() -> Result<()> {
+    let config = Config::default().with_invite_allowance(3);
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = Arc::new(TestClient::new(app.address, None)?);
+    client.login().post(&user).await?;
+
+    let mut requests = JoinSet::new();
+    for _ in 0..8 {
+        let client = client.clone();
+        requests.spawn(async move {
+            client
+                .settings()
+                .post_invite(1, "one_week")
+                .await
+                .map(|r| r.status())
+        });
+    }
+    let mut created = 0;
+    while let Some(status) = requests.join_next().await {
+        if status?? == 200 {
+            created += 1;
+        }
+    }
+    assert_eq!(created, 3);
+
+    let html = client.settings().get_invites().await?.text().await?;
+    assert_eq!(invite_codes(&html).len(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn invites_page_describes_the_registration_mode() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let html = client.settings().get_invites().await?.text().await?;
+    assert!(html.contains("Gluestick is invite only"));
+
+    let config = Config::default().with_registration_mode(RegistrationMode::Open);
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let html = client.settings().get_invites().await?.text().await?;
+    assert!(!html.contains("Gluestick is invite only"));
+    assert!(html.contains("Sign ups are currently open to everyone"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invite_codes_must_allow_between_1_and_10_uses() -> Result<()> {
+    let config = Config::default().with_invite_allowance(100);
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    for max_uses in [0, -1, 11] {
+        let response = client.settings().post_invite(max_uses, "one_week").await?;
+        assert_eq!(response.status(), 422);
     }
-    assert!(html.contains("<span>Newer</span>"));
-    assert!(html.contains("Older</a>"));
     Ok(())
 }
 
 #[tokio::test]
-async fn settings_inaccessible_when_logged_out() -> Result<()> {
-    let app = TestApp::spawn().await?;
+async fn expired_invite_codes_are_rejected_and_return_unused_invitations() -> Result<()> {
+    let config = Config::default().with_invite_allowance(3);
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
 
-    let response = client.settings().get().await?;
-    assert_eq!(response.status(), 401);
+    client.settings().post_invite(3, "one_day").await?;
+    let html = client.settings().get_invites().await?.text().await?;
+    let codes = invite_codes(&html);
+
+    let invitee = MockUser::builder().random()?.build();
+    let invitee_client = TestClient::new(app.address, None)?;
+    let response = invitee_client
+        .signup()
+        .post(codes[0].clone(), &invitee)
+        .await?;
+    assert_eq!(response.status(), 200);
+
+    app.expire_invite_codes().await?;
 
+    let invitee = MockUser::builder().random()?.build();
+    let invitee_client = TestClient::new(app.address, None)?;
+    let response = invitee_client
+        .signup()
+        .post(codes[0].clone(), &invitee)
+        .await?;
+    assert_eq!(response.status(), 422);
+
+    // Only the one used invitation remains spent
+    let html = client.settings().get_invites().await?.text().await?;
+    assert!(html.contains("You have 2 invitations remaining"));
     Ok(())
 }
 
+fn invite_codes(html: &str) -> Vec<String> {
+    html.split("<div class=\"code\">")
+        .skip(1)
+        .filter_map(|s| s.split("</div>").next())
+        .map(String::from)
+        .collect()
+}
+
 #[tokio::test]
-async fn settings_lists_users_api_keys() -> Result<()> {
+async fn delete_account_requires_correct_password() -> Result<()> {
     let app = TestApp::spawn().await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let api_key1 = MockApiKey::builder()
+    client.login().post(&user).await?;
+
+    let response = client
+        .settings()
+        .post_delete_account("not-the-password")
+        .await?;
+    assert_eq!(response.status(), 401);
+    assert!(response.text().await?.contains("Incorrect password"));
+
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(app.count_rows_for_user("users", "id", &user).await?, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_account_removes_the_user_and_all_of_their_data() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let public_paste = MockPaste::builder()
         .random()?
+        .filename("main.rs")
         .build()
         .seed(&app, &user)
         .await?;
-    let api_key2 = MockApiKey::builder()
+    MockPaste::builder()
         .random()?
+        .visibility("secret")
         .build()
         .seed(&app, &user)
         .await?;
+    let client = TestClient::new(app.address, None)?;
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
     client.login().post(&user).await?;
+    client.settings().post_invite(2, "one_week").await?;
+    // Changing the password records a security event
+    client
+        .settings()
+        .post_change_password(&user.password, &user.password, false)
+        .await?;
+    // Viewing the paste populates its syntax highlight cache entry
+    client
+        .username(&user.username)
+        .get_by_paste_id(&public_paste)
+        .await?;
+    assert_eq!(app.count_rows("syntax_highlight_cache").await?, 1);
 
-    let response = client.settings().get().await?;
+    let response = client
+        .settings()
+        .post_delete_account(&user.password)
+        .await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert!(html.contains(&api_key1.name));
-    assert!(html.contains(&api_key2.name));
+    assert_eq!(response.headers()["hx-redirect"], "/");
+
+    for (table, column) in [
+        ("users", "id"),
+        ("pastes", "user_id"),
+        ("api_keys", "user_id"),
+        ("session_tokens", "user_id"),
+        ("security_events", "user_id"),
+        ("invite_codes", "created_by"),
+    ] {
+        assert_eq!(app.count_rows_for_user(table, column, &user).await?, 0);
+    }
+    assert_eq!(app.count_rows("syntax_highlight_cache").await?, 0);
 
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 401);
+    let response = api_client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 401);
+    let response = client
+        .username(&user.username)
+        .get_by_paste_id(&public_paste)
+        .await?;
+    assert_eq!(response.status(), 404);
     Ok(())
 }
 
 #[tokio::test]
-async fn settings_does_not_list_other_users_api_keys() -> Result<()> {
-    let app = TestApp::spawn().await?;
+async fn delete_account_with_grace_period_can_be_cancelled() -> Result<()> {
+    let config = Config::default().with_account_deletion_grace_period_seconds(86_400);
+    let app = TestApp::spawn_with_config(config).await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
-    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let api_key1 = MockApiKey::builder()
-        .random()?
-        .build()
-        .seed(&app, &other_user)
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    assert!(response.text().await?.contains("after a grace period"));
+
+    let response = client
+        .settings()
+        .post_delete_account(&user.password)
         .await?;
-    let api_key2 = MockApiKey::builder()
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["hx-redirect"], "/settings");
+
+    let response = client.settings().get().await?;
+    let html = response.text().await?;
+    assert!(html.contains("Your account is scheduled to be deleted"));
+    assert!(html.contains("Account scheduled for deletion"));
+
+    // The grace period hasn't passed, so nothing is deleted yet
+    AccountDeletion::purge_due(&app.db).await?;
+    assert_eq!(app.count_rows_for_user("users", "id", &user).await?, 1);
+
+    let response = client.settings().delete_account_deletion().await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().get().await?;
+    let html = response.text().await?;
+    assert!(!html.contains("Your account is scheduled to be deleted"));
+    assert!(html.contains("Scheduled account deletion cancelled"));
+
+    app.elapse_account_deletion_grace_periods().await?;
+    AccountDeletion::purge_due(&app.db).await?;
+    assert_eq!(app.count_rows_for_user("users", "id", &user).await?, 1);
+
+    let response = client.settings().delete_account_deletion().await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_account_with_grace_period_deletes_once_it_passes() -> Result<()> {
+    let config = Config::default().with_account_deletion_grace_period_seconds(86_400);
+    let app = TestApp::spawn_with_config(config).await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    MockPaste::builder()
         .random()?
         .build()
-        .seed(&app, &other_user)
+        .seed(&app, &user)
         .await?;
+    let client = TestClient::new(app.address, None)?;
     client.login().post(&user).await?;
 
+    client
+        .settings()
+        .post_delete_account(&user.password)
+        .await?;
+    app.elapse_account_deletion_grace_periods().await?;
+    assert_eq!(AccountDeletion::purge_due(&app.db).await?, 1);
+
+    assert_eq!(app.count_rows_for_user("users", "id", &user).await?, 0);
+    assert_eq!(
+        app.count_rows_for_user("pastes", "user_id", &user).await?,
+        0
+    );
+    assert_eq!(app.count_rows("account_deletions").await?, 0);
     let response = client.settings().get().await?;
+    assert_eq!(response.status(), 401);
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_account_changes_username_and_email() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let mut user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let new_username = format!("renamed-{}", random_alphanumeric_string(8..=8)?).to_lowercase();
+    let new_email = format!("{}@example.com", random_alphanumeric_string(8..=8)?).to_lowercase();
+
+    let response = client
+        .settings()
+        .post_account(&new_username, &new_email, &user.password)
+        .await?;
     assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["hx-redirect"], "/settings");
+
+    let response = client.settings().get().await?;
     let html = response.text().await?;
-    assert!(!html.contains(&api_key1.name));
-    assert!(!html.contains(&api_key2.name));
+    assert!(html.contains(&format!("value=\"{new_username}\"")));
+    assert!(html.contains(&format!("value=\"{new_email}\"")));
+    assert!(html.contains("Username changed"));
+    assert!(html.contains("Email address changed"));
 
+    client.logout().delete().await?;
+    user.email = new_email;
+    let response = client.login().post(&user).await?;
+    assert_eq!(response.status(), 200);
+    let response = client.settings().get().await?;
+    assert_eq!(response.status(), 200);
     Ok(())
 }
 
 #[tokio::test]
-async fn can_generate_new_api_keys() -> Result<()> {
+async fn update_account_requires_correct_password() -> Result<()> {
     let app = TestApp::spawn().await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
     client.login().post(&user).await?;
 
-    let response = client.settings().get().await?;
-    assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert!(!html.contains("<li class=\"key\">"));
+    let response = client
+        .settings()
+        .post_account("a-new-username", &user.email, "not-the-password")
+        .await?;
+    assert_eq!(response.status(), 401);
+    assert!(response.text().await?.contains("Incorrect password"));
 
-    let response = client.api_sessions().post().await?;
+    let response = client.username(&user.username).get(None).await?;
     assert_eq!(response.status(), 200);
+    Ok(())
+}
 
-    let response = client.settings().get().await?;
-    assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert!(html.contains("<li class=\"key\">"));
+#[tokio::test]
+async fn concurrent_renames_to_the_same_username_are_rejected_as_taken() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user1 = MockUser::builder().random()?.build().seed(&app).await?;
+    let user2 = MockUser::builder().random()?.build().seed(&app).await?;
+    let client1 = TestClient::new(app.address, None)?;
+    let client2 = TestClient::new(app.address, None)?;
+    client1.login().post(&user1).await?;
+    client2.login().post(&user2).await?;
+    let new_username = random_alphanumeric_string(16..=16)?.to_lowercase();
+
+    let (settings1, settings2) = (client1.settings(), client2.settings());
+    let (response1, response2) = tokio::join!(
+        settings1.post_account(&new_username, &user1.email, &user1.password),
+        settings2.post_account(&new_username, &user2.email, &user2.password),
+    );
+    let mut statuses = [response1?.status(), response2?.status()];
+    statuses.sort();
+    assert_eq!(statuses, [200, 422]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_account_rejects_invalid_and_taken_usernames_and_emails() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    for (username, email, error_message) in [
+        (
+            "not valid!",
+            user.email.as_str(),
+            "Username may only contain",
+        ),
+        ("settings", user.email.as_str(), "Username is unavailable"),
+        (user.username.as_str(), "not-an-email", "Email is missing"),
+        (
+            other_user.username.as_str(),
+            user.email.as_str(),
+            "Username is already taken",
+        ),
+        (
+            user.username.as_str(),
+            other_user.email.as_str(),
+            "Email is already taken",
+        ),
+    ] {
+        let response = client
+            .settings()
+            .post_account(username, email, &user.password)
+            .await?;
+        assert_eq!(response.status(), 422);
+        assert!(response.text().await?.contains(error_message));
+    }
 
+    // Resubmitting the current username and email is not a conflict
+    let response = client
+        .settings()
+        .post_account(&user.username, &user.email, &user.password)
+        .await?;
+    assert_eq!(response.status(), 200);
     Ok(())
 }
 
 #[tokio::test]
-async fn can_delete_api_keys() -> Result<()> {
+async fn validate_email_accepts_the_signed_in_users_own_email() -> Result<()> {
     let app = TestApp::spawn().await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let api_key = MockApiKey::builder()
+
+    let response = client.signup().post_validate_email(&user).await?;
+    assert_eq!(response.status(), 422);
+
+    client.login().post(&user).await?;
+    let response = client.signup().post_validate_email(&user).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn old_username_urls_permanently_redirect_to_the_new_username() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let paste = MockPaste::builder()
         .random()?
         .build()
         .seed(&app, &user)
         .await?;
+    let client = TestClient::new(app.address, None)?;
     client.login().post(&user).await?;
+    let new_username = format!("renamed-{}", random_alphanumeric_string(8..=8)?).to_lowercase();
+    client
+        .settings()
+        .post_account(&new_username, &user.email, &user.password)
+        .await?;
 
-    let response = client.settings().get().await?;
-    assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert!(html.contains(&api_key.name));
+    let id = paste.id.clone().unwrap_or_default();
+    let anonymous_client = TestClient::new_without_redirects(app.address)?;
+    for (old_path, new_path) in [
+        (
+            format!("{}?limit=1", user.username),
+            format!("/{new_username}?limit=1"),
+        ),
+        (
+            format!("{}/{id}", user.username),
+            format!("/{new_username}/{id}"),
+        ),
+        (
+            format!("{}/{id}/raw", user.username),
+            format!("/{new_username}/{id}/raw"),
+        ),
+        (
+            format!("{}/{id}/download", user.username),
+            format!("/{new_username}/{id}/download"),
+        ),
+    ] {
+        let response = anonymous_client.get_arbitrary(&old_path).await?;
+        assert_eq!(response.status(), 308);
+        assert_eq!(response.headers()["location"], new_path.as_str());
+    }
 
-    let response = client.api_sessions().delete_by_id(&api_key).await?;
+    let response = anonymous_client
+        .username(&new_username)
+        .get_by_paste_id(&paste)
+        .await?;
     assert_eq!(response.status(), 200);
+    Ok(())
+}
 
-    let response = client.settings().get().await?;
+#[tokio::test]
+async fn old_usernames_stop_redirecting_once_claimed_by_someone_else() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let old_username = user.username.clone();
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let new_username = format!("renamed-{}", random_alphanumeric_string(8..=8)?).to_lowercase();
+    client
+        .settings()
+        .post_account(&new_username, &user.email, &user.password)
+        .await?;
+
+    let other_client = TestClient::new(app.address, None)?;
+    other_client.login().post(&other_user).await?;
+    let response = other_client
+        .settings()
+        .post_account(&old_username, &other_user.email, &other_user.password)
+        .await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert!(!html.contains(&api_key.name));
 
+    let anonymous_client = TestClient::new_without_redirects(app.address)?;
+    let response = anonymous_client.username(&old_username).get(None).await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await?.contains(&old_username));
+    let response = anonymous_client
+        .username(&other_user.username)
+        .get(None)
+        .await?;
+    assert_eq!(response.status(), 308);
+    assert_eq!(response.headers()["location"], format!("/{old_username}"));
     Ok(())
 }
 
 #[tokio::test]
-async fn cannot_delete_other_users_api_keys() -> Result<()> {
+async fn profile_fields_are_shown_on_the_profile_page() -> Result<()> {
     let app = TestApp::spawn().await?;
     let user = MockUser::builder().random()?.build().seed(&app).await?;
     let client = TestClient::new(app.address, None)?;
-    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
-    let other_client = TestClient::new(app.address, None)?;
-    let api_key = MockApiKey::builder()
-        .random()?
-        .build()
-        .seed(&app, &other_user)
-        .await?;
     client.login().post(&user).await?;
-    other_client.login().post(&other_user).await?;
 
-    let response = other_client.settings().get().await?;
+    let response = client
+        .settings()
+        .post_profile(
+            "Ada Lovelace",
+            "Writes notes on engines",
+            "https://example.com/ada",
+        )
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert!(response
+        .text()
+        .await?
+        .contains("Your profile has been updated"));
+
+    let anonymous_client = TestClient::new(app.address, None)?;
+    let response = anonymous_client.username(&user.username).get(None).await?;
     assert_eq!(response.status(), 200);
     let html = response.text().await?;
-    assert!(html.contains(&api_key.name));
+    assert!(html.contains("Ada Lovelace"));
+    assert!(html.contains("Writes notes on engines"));
+    assert!(html.contains(r#"href="https://example.com/ada""#));
+    assert!(html.contains(r#"rel="nofollow ugc noopener""#));
+    Ok(())
+}
 
-    let response = client.api_sessions().delete_by_id(&api_key).await?;
+#[tokio::test]
+async fn profile_fields_are_validated() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client
+        .settings()
+        .post_profile(&random_string(65..=65)?, "", "")
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("Display name is too long"));
+
+    let response = client
+        .settings()
+        .post_profile("", &random_string(257..=257)?, "")
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("Bio is too long"));
+
+    let response = client
+        .settings()
+        .post_profile("", "", "javascript:alert(1)")
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response
+        .text()
+        .await?
+        .contains("Website must begin with http:// or https://"));
+
+    let response = client.username(&user.username).get(None).await?;
+    assert!(!response.text().await?.contains("javascript:alert(1)"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn uploaded_avatars_are_cropped_resized_and_served() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client.username(&user.username).get_avatar().await?;
     assert_eq!(response.status(), 404);
 
-    let response = other_client.settings().get().await?;
+    let mut upload = Vec::new();
+    image::RgbImage::new(800, 400).write_to(
+        &mut std::io::Cursor::new(&mut upload),
+        image::ImageFormat::Jpeg,
+    )?;
+    let response = client.settings().post_avatar(upload).await?;
     assert_eq!(response.status(), 200);
-    let html = response.text().await?;
-    assert!(html.contains(&api_key.name));
+    assert!(response.text().await?.contains("Remove Avatar"));
+
+    let response = client.username(&user.username).get(None).await?;
+    assert!(response
+        .text()
+        .await?
+        .contains(&format!("/{}/avatar?v=", user.username)));
+
+    let anonymous_client = TestClient::new(app.address, None)?;
+    let response = anonymous_client
+        .username(&user.username)
+        .get_avatar()
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let avatar = image::load_from_memory(&response.bytes().await?)?;
+    assert_eq!((avatar.width(), avatar.height()), (256, 256));
+
+    let response = client.settings().delete_avatar().await?;
+    assert_eq!(response.status(), 200);
+    let response = anonymous_client
+        .username(&user.username)
+        .get_avatar()
+        .await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn avatar_uploads_must_be_images() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client
+        .settings()
+        .post_avatar(b"definitely not an image".to_vec())
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response
+        .text()
+        .await?
+        .contains("Avatar must be a PNG, JPEG, GIF or WebP image"));
+
+    let response = client
+        .settings()
+        .post_avatar(vec![0; 1024 * 1024 + 1])
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("Avatar is too large"));
 
+    let response = client.username(&user.username).get_avatar().await?;
+    assert_eq!(response.status(), 404);
     Ok(())
 }
@@ -0,0 +1,491 @@
+use crate::common::mocks::mock_batch::{MockBatch, MockBatchResponse, MockOperation};
+use crate::common::mocks::mock_paste::MockPaste;
+use crate::common::mocks::mock_user::MockUser;
+use crate::common::test_app::TestApp;
+use crate::common::test_client::TestClient;
+use crate::common::webhook_receiver::{ReceivedDelivery, WebhookReceiver};
+use crate::prelude::*;
+use gluestick::config::Config;
+use gluestick::helpers::webhook_helper::WebhookClient;
+use gluestick::models::webhook::{WebhookDelivery, MAX_DELIVERY_ATTEMPTS};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+const ALL_EVENTS: [&str; 3] = ["paste.created", "paste.updated", "paste.deleted"];
+
+// Adds a webhook through the settings page, returning its id and secret
+async fn add_webhook(client: &TestClient, url: &str, events: &[&str]) -> Result<(String, String)> {
+    let response = client.settings().post_webhook(url, events).await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    let (_, rest) = html.split_once("<code id=\"").ok_or("secret not found")?;
+    let (id, rest) = rest.split_once("\">").ok_or("secret not found")?;
+    let (secret, _) = rest.split_once('<').ok_or("secret not found")?;
+    Ok((id.to_string(), secret.to_string()))
+}
+
+// Receivers listen on 127.0.0.1, which webhooks can only point at when private addresses are
+// allowed
+async fn spawn_app() -> Result<TestApp> {
+    TestApp::spawn_with_config(Config::default().with_webhooks_allow_private_addresses(true)).await
+}
+
+async fn deliver_due(app: &TestApp) -> Result<usize> {
+    Ok(WebhookDelivery::deliver_due(&app.db, &WebhookClient::new(true)?).await?)
+}
+
+fn assert_signed(delivery: &ReceivedDelivery, secret: &str) -> Result<()> {
+    let timestamp = delivery.header("x-gluestick-timestamp");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(format!("{timestamp}.{}", delivery.body).as_bytes());
+    let expected = format!("sha256={:x}", mac.finalize().into_bytes());
+    assert_eq!(delivery.header("x-gluestick-signature"), expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_add_webhooks_and_their_secrets_are_only_shown_once() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    let response = client.settings().get().await?;
+    assert!(response.text().await?.contains("/settings/webhooks"));
+
+    let url = "https://example.com/hooks/gluestick";
+    let (id, secret) = add_webhook(&client, url, &["paste.created"]).await?;
+    assert_eq!(secret.len(), 64);
+
+    let response = client.settings().get_webhooks().await?;
+    assert_eq!(response.status(), 200);
+    let html = response.text().await?;
+    assert!(!html.contains(&secret));
+    let (_, webhooks) = html
+        .split_once(r#"<ul class="webhooks-list">"#)
+        .ok_or("webhooks not found")?;
+    assert!(webhooks.contains(url));
+    assert!(webhooks.contains(&id));
+    assert!(webhooks.contains("<code>paste.created</code>"));
+    assert!(!webhooks.contains("<code>paste.deleted</code>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_add_webhooks_with_invalid_urls_or_without_events() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    for url in [
+        "",
+        "not a url",
+        "ftp://example.com/hook",
+        "javascript:alert(1)",
+    ] {
+        let response = client.settings().post_webhook(url, &ALL_EVENTS).await?;
+        assert_eq!(response.status(), 422);
+        assert!(response
+            .text()
+            .await?
+            .contains("URL must be a full http:// or https:// address"));
+    }
+
+    let response = client
+        .settings()
+        .post_webhook("https://example.com/hook", &[])
+        .await?;
+    assert_eq!(response.status(), 422);
+    assert!(response.text().await?.contains("Choose at least one event"));
+
+    assert_eq!(
+        app.count_rows_for_user("webhooks", "user_id", &user)
+            .await?,
+        0
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_add_webhooks_for_ip_addresses_unless_private_addresses_are_allowed() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+
+    for url in [
+        "http://127.0.0.1:8080/hook",
+        "http://[::1]/hook",
+        "https://93.184.215.14/hook",
+    ] {
+        let response = client.settings().post_webhook(url, &ALL_EVENTS).await?;
+        assert_eq!(response.status(), 422);
+        assert!(response
+            .text()
+            .await?
+            .contains("URL must use a domain name rather than an IP address"));
+    }
+
+    let app = spawn_app().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    add_webhook(&client, "http://127.0.0.1:8080/hook", &ALL_EVENTS).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn deliveries_to_private_addresses_are_refused_unless_allowed() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    // A domain is accepted when the webhook is added, but is checked again on every delivery, so
+    // it can't be pointed somewhere private later on
+    let url = format!("http://localhost:{}/hook", receiver.address.port());
+    let (id, _) = add_webhook(&client, &url, &ALL_EVENTS).await?;
+
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder().random()?.build();
+    api_client.api_pastes().post(&paste).await?;
+    let client_without_private_addresses = WebhookClient::new(false)?;
+    assert_eq!(
+        WebhookDelivery::deliver_due(&app.db, &client_without_private_addresses).await?,
+        1
+    );
+    assert!(receiver.deliveries().is_empty());
+    let html = client
+        .settings()
+        .get_webhook_by_id(&id)
+        .await?
+        .text()
+        .await?;
+    assert!(html.contains("class=\"error\""));
+    assert!(html.contains("Retrying"));
+
+    app.elapse_webhook_delivery_backoffs().await?;
+    assert_eq!(deliver_due(&app).await?, 1);
+    assert_eq!(receiver.deliveries().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn paste_events_are_delivered_signed() -> Result<()> {
+    let app = spawn_app().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let (_, secret) = add_webhook(&client, &receiver.url(), &ALL_EVENTS).await?;
+
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let mut paste = MockPaste::builder().random()?.build();
+    let response = api_client.api_pastes().post(&paste).await?;
+    assert_eq!(response.status(), 200);
+    paste.id = response.json().await?;
+    paste.description = "Updated".into();
+    let response = api_client.api_pastes().patch_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+    let response = api_client.api_pastes().delete_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+
+    assert_eq!(deliver_due(&app).await?, 3);
+    assert_eq!(deliver_due(&app).await?, 0);
+
+    let deliveries = receiver.deliveries();
+    assert_eq!(deliveries.len(), 3);
+    let paste_id = paste.id.clone().unwrap_or_default();
+    for (delivery, event) in deliveries.iter().zip(ALL_EVENTS) {
+        assert_signed(delivery, &secret)?;
+        assert_eq!(delivery.header("content-type"), "application/json");
+        assert_eq!(delivery.header("x-gluestick-event"), event);
+        assert!(!delivery.header("x-gluestick-delivery").is_empty());
+        let payload = delivery.json()?;
+        assert_eq!(payload["event"], event);
+        assert_eq!(payload["paste"]["id"], paste_id.as_str());
+        assert_eq!(payload["paste"]["filename"], paste.filename.as_str());
+    }
+    assert_eq!(deliveries[1].json()?["paste"]["description"], "Updated");
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhooks_only_get_the_events_they_want_for_their_owners_pastes() -> Result<()> {
+    let app = spawn_app().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let (_, other_api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    add_webhook(&client, &receiver.url(), &["paste.deleted"]).await?;
+
+    for api_key in [&api_key, &other_api_key] {
+        let api_client = TestClient::new(app.address, Some(api_key))?;
+        let mut paste = MockPaste::builder().random()?.build();
+        let response = api_client.api_pastes().post(&paste).await?;
+        paste.id = response.json().await?;
+        let response = api_client.api_pastes().patch_by_id(&paste).await?;
+        assert_eq!(response.status(), 200);
+        let response = api_client.api_pastes().delete_by_id(&paste).await?;
+        assert_eq!(response.status(), 200);
+    }
+
+    assert_eq!(deliver_due(&app).await?, 1);
+    let deliveries = receiver.deliveries();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].header("x-gluestick-event"), "paste.deleted");
+    Ok(())
+}
+
+#[tokio::test]
+async fn rolled_back_batches_send_no_events() -> Result<()> {
+    let app = spawn_app().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    add_webhook(&client, &receiver.url(), &ALL_EVENTS).await?;
+    let missing = MockPaste::builder().random()?.random_id().build();
+
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let batch = MockBatch {
+        mode: None,
+        operations: vec![
+            MockOperation::Create(MockPaste::builder().random()?.build()),
+            MockOperation::delete(&missing),
+        ],
+    };
+    let response = api_client.api_pastes().post_batch(&batch).await?;
+    assert_eq!(response.status(), 200);
+    let body: MockBatchResponse = response.json().await?;
+    assert!(!body.committed);
+
+    assert_eq!(app.count_rows("webhook_deliveries").await?, 0);
+    assert_eq!(deliver_due(&app).await?, 0);
+    assert!(receiver.deliveries().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff_and_logged() -> Result<()> {
+    let app = spawn_app().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let (id, secret) = add_webhook(&client, &receiver.url(), &ALL_EVENTS).await?;
+
+    let response = client.settings().get_webhook_by_id(&id).await?;
+    assert_eq!(response.status(), 200);
+    assert!(response
+        .text()
+        .await?
+        .contains("Nothing has been sent to this webhook yet"));
+
+    receiver.respond_with(503);
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder().random()?.build();
+    api_client.api_pastes().post(&paste).await?;
+    assert_eq!(deliver_due(&app).await?, 1);
+    // The retry isn't due until the backoff has passed
+    assert_eq!(deliver_due(&app).await?, 0);
+
+    let html = client
+        .settings()
+        .get_webhook_by_id(&id)
+        .await?
+        .text()
+        .await?;
+    assert!(html.contains("<code>paste.created</code>"));
+    assert!(html.contains("503"));
+    assert!(html.contains("Retrying"));
+
+    receiver.respond_with(204);
+    app.elapse_webhook_delivery_backoffs().await?;
+    assert_eq!(deliver_due(&app).await?, 1);
+
+    let deliveries = receiver.deliveries();
+    assert_eq!(deliveries.len(), 2);
+    assert_eq!(
+        deliveries[0].header("x-gluestick-delivery"),
+        deliveries[1].header("x-gluestick-delivery")
+    );
+    assert_signed(&deliveries[1], &secret)?;
+
+    let html = client
+        .settings()
+        .get_webhook_by_id(&id)
+        .await?
+        .text()
+        .await?;
+    assert!(html.contains("204"));
+    assert!(html.contains("Delivered"));
+    assert!(!html.contains("Retrying"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn deliveries_are_given_up_on_after_too_many_attempts() -> Result<()> {
+    let app = spawn_app().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let (id, _) = add_webhook(&client, &receiver.url(), &ALL_EVENTS).await?;
+
+    receiver.respond_with(500);
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder().random()?.build();
+    api_client.api_pastes().post(&paste).await?;
+    for _ in 0..MAX_DELIVERY_ATTEMPTS {
+        assert_eq!(deliver_due(&app).await?, 1);
+        app.elapse_webhook_delivery_backoffs().await?;
+    }
+    assert_eq!(deliver_due(&app).await?, 0);
+    assert_eq!(
+        receiver.deliveries().len(),
+        usize::try_from(MAX_DELIVERY_ATTEMPTS)?
+    );
+
+    let html = client
+        .settings()
+        .get_webhook_by_id(&id)
+        .await?
+        .text()
+        .await?;
+    assert!(html.contains("500"));
+    assert!(html.contains("Failed"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn unreachable_receivers_are_logged_as_errors() -> Result<()> {
+    let app = spawn_app().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    // Nothing listens on the discard port
+    let (id, _) = add_webhook(&client, "http://127.0.0.1:9/hook", &ALL_EVENTS).await?;
+
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder().random()?.build();
+    api_client.api_pastes().post(&paste).await?;
+    assert_eq!(deliver_due(&app).await?, 1);
+
+    let html = client
+        .settings()
+        .get_webhook_by_id(&id)
+        .await?
+        .text()
+        .await?;
+    assert!(html.contains("class=\"error\""));
+    assert!(html.contains("Retrying"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_delete_webhooks() -> Result<()> {
+    let app = spawn_app().await?;
+    let receiver = WebhookReceiver::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let (id, _) = add_webhook(&client, &receiver.url(), &ALL_EVENTS).await?;
+    let api_client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder().random()?.build();
+    api_client.api_pastes().post(&paste).await?;
+
+    let response = client.settings().delete_webhook_by_id(&id).await?;
+    assert_eq!(response.status(), 200);
+
+    let html = client.settings().get_webhooks().await?.text().await?;
+    assert!(!html.contains(&id));
+    let response = client.settings().get_webhook_by_id(&id).await?;
+    assert_eq!(response.status(), 404);
+    // Deliveries that were still to be made are dropped with the webhook
+    assert_eq!(app.count_rows("webhook_deliveries").await?, 0);
+    assert_eq!(deliver_due(&app).await?, 0);
+    assert!(receiver.deliveries().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn other_users_cannot_see_or_delete_webhooks() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    client.login().post(&user).await?;
+    let url = "https://example.com/hooks/private";
+    let (id, _) = add_webhook(&client, url, &ALL_EVENTS).await?;
+
+    let other_client = TestClient::new(app.address, None)?;
+    other_client.login().post(&other_user).await?;
+    let html = other_client.settings().get_webhooks().await?.text().await?;
+    assert!(!html.contains(url));
+    let response = other_client.settings().get_webhook_by_id(&id).await?;
+    assert_eq!(response.status(), 404);
+    let response = other_client.settings().delete_webhook_by_id(&id).await?;
+    assert_eq!(response.status(), 404);
+
+    assert_eq!(
+        app.count_rows_for_user("webhooks", "user_id", &user)
+            .await?,
+        1
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_pages_require_a_session() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.settings().get_webhooks().await?;
+    assert_eq!(response.status(), 401);
+    let response = client
+        .settings()
+        .post_webhook("https://example.com/hook", &ALL_EVENTS)
+        .await?;
+    assert_eq!(response.status(), 401);
+    assert_eq!(app.count_rows("webhooks").await?, 0);
+    Ok(())
+}
@@ -1,7 +1,16 @@
+use crate::common::mocks::mock_api_key::MockApiKey;
+use crate::common::mocks::mock_batch::{MockBatch, MockOperation};
+use crate::common::mocks::mock_paste::MockPaste;
 use crate::common::mocks::mock_user::MockUser;
 use crate::common::test_app::TestApp;
 use crate::common::test_client::TestClient;
 use crate::prelude::*;
+use gluestick::config::{Config, Quota, RateLimitConfig};
+use gluestick::models::api_session::ApiKey;
+use jiff::{Timestamp, ToSpan};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::HashMap;
 
 #[tokio::test]
 async fn fallback_responds_with_404() -> Result<()> {
@@ -18,3 +27,306 @@ async fn fallback_responds_with_404() -> Result<()> {
     assert_eq!(response.status(), 404);
     Ok(())
 }
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    status: u16,
+    error: String,
+    message: String,
+}
+
+#[tokio::test]
+async fn requests_are_rate_limited_per_api_key() -> Result<()> {
+    let config = Config::default().with_rate_limit(RateLimitConfig::new(
+        Quota {
+            burst: 100,
+            per_minute: 100,
+        },
+        Quota {
+            burst: 2,
+            per_minute: 1,
+        },
+    ));
+    let app = TestApp::spawn_with_config(config).await?;
+    let (_, api_key1) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let (_, api_key2) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client1 = TestClient::new(app.address, Some(&api_key1))?;
+    let client2 = TestClient::new(app.address, Some(&api_key2))?;
+
+    for _ in 0..2 {
+        let response = client1.api_pastes().get(None).await?;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-ratelimit-limit"], "2");
+    }
+
+    let response = client1.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let body: ErrorBody = response.json().await?;
+    assert_eq!(body.status, 429);
+    assert_eq!(body.error, "Too Many Requests");
+
+    // Each API key has its own bucket, even from the same IP
+    let response = client2.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn batches_count_each_operation_against_the_rate_limit() -> Result<()> {
+    let config = Config::default().with_rate_limit(RateLimitConfig::new(
+        Quota {
+            burst: 100,
+            per_minute: 100,
+        },
+        Quota {
+            burst: 5,
+            per_minute: 1,
+        },
+    ));
+    let app = TestApp::spawn_with_config(config).await?;
+    let (_, api_key1) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let (_, api_key2) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client1 = TestClient::new(app.address, Some(&api_key1))?;
+    let client2 = TestClient::new(app.address, Some(&api_key2))?;
+    let batch = |len: usize| -> Result<MockBatch> {
+        let operations = (0..len)
+            .map(|_| {
+                Ok(MockOperation::Create(
+                    MockPaste::builder().random()?.build(),
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(MockBatch {
+            mode: None,
+            operations,
+        })
+    };
+
+    let response = client1.api_pastes().post_batch(&batch(3)?).await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "2");
+
+    let response = client1.api_pastes().post_batch(&batch(3)?).await?;
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+    let response = client1.api_me().get_pastes(None).await?;
+    let index: serde_json::Value = response.json().await?;
+    assert_eq!(index["pastes"].as_array().map(Vec::len), Some(3));
+
+    // A batch is never charged more than a full bucket, so that it can always be sent eventually
+    let response = client2.api_pastes().post_batch(&batch(10)?).await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_without_an_api_key_are_rate_limited_per_ip() -> Result<()> {
+    let config = Config::default().with_rate_limit(RateLimitConfig::new(
+        Quota {
+            burst: 2,
+            per_minute: 1,
+        },
+        Quota {
+            burst: 100,
+            per_minute: 100,
+        },
+    ));
+    let app = TestApp::spawn_with_config(config).await?;
+    let client = TestClient::new(app.address, None)?;
+
+    for _ in 0..2 {
+        let response = client.api_pastes().get(None).await?;
+        assert_ne!(response.status(), 429);
+    }
+
+    let response = client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["x-ratelimit-limit"], "2");
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_with_made_up_api_keys_are_rate_limited_per_ip() -> Result<()> {
+    let config = Config::default().with_rate_limit(RateLimitConfig::new(
+        Quota {
+            burst: 2,
+            per_minute: 1,
+        },
+        Quota {
+            burst: 100,
+            per_minute: 100,
+        },
+    ));
+    let app = TestApp::spawn_with_config(config).await?;
+
+    for _ in 0..2 {
+        let made_up_key = MockApiKey::builder().random()?.build();
+        let client = TestClient::new(app.address, Some(&made_up_key))?;
+        let response = client.api_pastes().get(None).await?;
+        assert_eq!(response.status(), 401);
+    }
+
+    let made_up_key = MockApiKey::builder().random()?.build();
+    let client = TestClient::new(app.address, Some(&made_up_key))?;
+    let response = client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["x-ratelimit-limit"], "2");
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_need_the_scope_for_the_endpoint() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let read_only_key = MockApiKey::builder()
+        .random()?
+        .scopes(&["pastes:read"])
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let write_only_key = MockApiKey::builder()
+        .random()?
+        .scopes(&["pastes:write"])
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let read_only_client = TestClient::new(app.address, Some(&read_only_key))?;
+    let write_only_client = TestClient::new(app.address, Some(&write_only_key))?;
+
+    let mut paste = MockPaste::builder().random()?.build();
+    let response = read_only_client.api_pastes().post(&paste).await?;
+    assert_eq!(response.status(), 403);
+    let body: ErrorBody = response.json().await?;
+    assert_eq!(
+        body.message,
+        "This API key is missing the 'pastes:write' scope."
+    );
+
+    let response = write_only_client.api_pastes().post(&paste).await?;
+    assert_eq!(response.status(), 200);
+    paste.id = response.json().await?;
+
+    let response = write_only_client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 403);
+    let response = read_only_client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+
+    for response in [
+        read_only_client.api_pastes().delete_by_id(&paste).await?,
+        write_only_client.api_pastes().delete_by_id(&paste).await?,
+    ] {
+        assert_eq!(response.status(), 403);
+        let body: ErrorBody = response.json().await?;
+        assert_eq!(
+            body.message,
+            "This API key is missing the 'pastes:delete' scope."
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_api_keys_are_rejected_and_purged() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let expired_key = MockApiKey::builder()
+        .random()?
+        .expires_at(Timestamp::now().checked_sub(1.second())?)
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let live_key = MockApiKey::builder()
+        .random()?
+        .expires_at(Timestamp::now().checked_add(1.hour())?)
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let client = TestClient::new(app.address, Some(&expired_key))?;
+    let response = client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 401);
+    let client = TestClient::new(app.address, Some(&live_key))?;
+    let response = client.api_pastes().get(None).await?;
+    assert_eq!(response.status(), 200);
+
+    assert_eq!(ApiKey::purge_expired(&app.db).await?, 1);
+    assert_eq!(
+        app.count_rows_for_user("api_keys", "user_id", &user)
+            .await?,
+        1
+    );
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenApiDocument {
+    openapi: String,
+    paths: HashMap<String, HashMap<String, IgnoredAny>>,
+}
+
+// The (method, path) of every route in the JSON API router, in the OpenAPI document's notation
+fn json_api_routes() -> Vec<(String, String)> {
+    gluestick::json_api_routes()
+        .into_iter()
+        .map(|route| {
+            // Axum writes path parameters as `:id`, OpenAPI as `{id}`
+            let path = route
+                .path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(name) => format!("{{{name}}}"),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            (route.method.as_str().to_lowercase(), path)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn openapi_document_describes_every_route() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let client = TestClient::new(app.address, None)?;
+
+    let response = client.get_arbitrary("api/v1/openapi.json").await?;
+    assert_eq!(response.status(), 200);
+    let document: OpenApiDocument = response.json().await?;
+    assert!(document.openapi.starts_with("3.1"));
+
+    let routes = json_api_routes();
+    assert!(routes.len() >= 10);
+    let missing: Vec<_> = routes
+        .into_iter()
+        .filter(|(method, path)| {
+            !document
+                .paths
+                .get(path)
+                .is_some_and(|operations| operations.contains_key(method))
+        })
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "routes missing from the OpenAPI document: {missing:?}"
+    );
+    Ok(())
+}
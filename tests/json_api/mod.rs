@@ -1,2 +1,3 @@
 mod misc_tests;
 mod paste_tests;
+mod user_tests;
@@ -1,3 +1,5 @@
+use crate::common::mocks::mock_api_key::MockApiKey;
+use crate::common::mocks::mock_batch::{MockBatch, MockBatchResponse, MockOperation};
 use crate::common::mocks::mock_pagination::{MockPaginationParams, MockPaginationResponse};
 use crate::common::mocks::mock_paste::MockPaste;
 use crate::common::mocks::mock_user::MockUser;
@@ -6,9 +8,21 @@ use crate::common::test_app::TestApp;
 use crate::common::test_client::TestClient;
 use crate::prelude::*;
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
+#[derive(Debug, Deserialize)]
+struct FieldError {
+    code: String,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidationProblem {
+    status: u16,
+    errors: HashMap<String, Vec<FieldError>>,
+}
+
 #[derive(Debug, Deserialize)]
 struct IndexResponse {
     pastes: Vec<MockPaste>,
@@ -185,7 +199,7 @@ async fn index_paginates_correctly() -> Result<()> {
     let params = MockPaginationParams::builder().per_page(3).build();
     let response = client.api_pastes().get(Some(params)).await?;
     let response_data: IndexResponse = response.json().await?;
-    let expected: Vec<MockPaste> = pastes[5..8].into_iter().cloned().rev().collect();
+    let expected: Vec<MockPaste> = pastes[5..8].iter().cloned().rev().collect();
     assert!(response_data.pagination.prev_page.is_none());
     assert!(response_data.pagination.next_page.is_some());
     assert_eq!(expected, response_data.pastes);
@@ -198,7 +212,7 @@ async fn index_paginates_correctly() -> Result<()> {
         .build();
     let response = client.api_pastes().get(Some(params)).await?;
     let response_data: IndexResponse = response.json().await?;
-    let expected: Vec<MockPaste> = pastes[2..5].into_iter().cloned().rev().collect();
+    let expected: Vec<MockPaste> = pastes[2..5].iter().cloned().rev().collect();
     assert!(response_data.pagination.prev_page.is_some());
     assert!(response_data.pagination.next_page.is_some());
     assert_eq!(expected, response_data.pastes);
@@ -211,7 +225,7 @@ async fn index_paginates_correctly() -> Result<()> {
         .build();
     let response = client.api_pastes().get(Some(params)).await?;
     let response_data: IndexResponse = response.json().await?;
-    let expected: Vec<MockPaste> = pastes[0..2].into_iter().cloned().rev().collect();
+    let expected: Vec<MockPaste> = pastes[0..2].iter().cloned().rev().collect();
     assert!(response_data.pagination.prev_page.is_some());
     assert!(response_data.pagination.next_page.is_none());
     assert_eq!(expected, response_data.pastes);
@@ -224,7 +238,7 @@ async fn index_paginates_correctly() -> Result<()> {
         .build();
     let response = client.api_pastes().get(Some(params)).await?;
     let response_data: IndexResponse = response.json().await?;
-    let expected: Vec<MockPaste> = pastes[2..5].into_iter().cloned().rev().collect();
+    let expected: Vec<MockPaste> = pastes[2..5].iter().cloned().rev().collect();
     assert!(response_data.pagination.prev_page.is_some());
     assert!(response_data.pagination.next_page.is_some());
     assert_eq!(expected, response_data.pastes);
@@ -237,13 +251,307 @@ async fn index_paginates_correctly() -> Result<()> {
         .build();
     let response = client.api_pastes().get(Some(params)).await?;
     let response_data: IndexResponse = response.json().await?;
-    let expected: Vec<MockPaste> = pastes[5..8].into_iter().cloned().rev().collect();
+    let expected: Vec<MockPaste> = pastes[5..8].iter().cloned().rev().collect();
     assert!(response_data.pagination.prev_page.is_none());
     assert!(response_data.pagination.next_page.is_some());
     assert_eq!(expected, response_data.pastes);
     Ok(())
 }
 
+// Pulls the URL for a relation out of a Link header, e.g. `</pastes?next_page=1>; rel="next"`
+fn link_for(response: &reqwest::Response, relation: &str) -> Option<String> {
+    let header = response.headers().get("link")?.to_str().ok()?;
+    header.split(", ").find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        (params.trim() == format!(r#"rel="{relation}""#))
+            .then(|| url.trim_matches(['<', '>']).to_string())
+    })
+}
+
+#[tokio::test]
+async fn index_paginates_with_query_parameters_and_link_headers() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+
+    let mut pastes = Vec::new();
+    for i in 0..5 {
+        let paste = MockPaste::builder()
+            .id(Uuid::now_v7())
+            .filename(i.to_string())
+            .description(i.to_string())
+            .body(i.to_string())
+            .build()
+            .seed(&app, &user)
+            .await?;
+        pastes.push(paste);
+    }
+
+    // First page
+    let params = MockPaginationParams::builder().per_page(2).build();
+    let response = client.api_pastes().get_with_query_params(&params).await?;
+    assert_eq!(response.status(), 200);
+    assert!(link_for(&response, "prev").is_none());
+    let next_link = link_for(&response, "next").ok_or("next link not found")?;
+    let response_data: IndexResponse = response.json().await?;
+    let next_cursor = response_data
+        .pagination
+        .next_page
+        .ok_or("next page not found")?;
+    assert_eq!(
+        next_link,
+        format!("/api/v1/pastes?per_page=2&next_page={next_cursor}")
+    );
+    let expected: Vec<MockPaste> = pastes[3..5].iter().cloned().rev().collect();
+    assert_eq!(expected, response_data.pastes);
+
+    // Second page, by following the link
+    let response = client.api_pastes().get_link(&next_link).await?;
+    assert!(link_for(&response, "prev").is_some());
+    let next_link = link_for(&response, "next").ok_or("next link not found")?;
+    let response_data: IndexResponse = response.json().await?;
+    let expected: Vec<MockPaste> = pastes[1..3].iter().cloned().rev().collect();
+    assert_eq!(expected, response_data.pastes);
+
+    // Last page
+    let response = client.api_pastes().get_link(&next_link).await?;
+    assert!(link_for(&response, "next").is_none());
+    let prev_link = link_for(&response, "prev").ok_or("prev link not found")?;
+    let response_data: IndexResponse = response.json().await?;
+    assert_eq!(vec![pastes[0].clone()], response_data.pastes);
+
+    // Back to the second page
+    let response = client.api_pastes().get_link(&prev_link).await?;
+    let response_data: IndexResponse = response.json().await?;
+    let expected: Vec<MockPaste> = pastes[1..3].iter().cloned().rev().collect();
+    assert_eq!(expected, response_data.pastes);
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_responds_with_422_when_query_parameters_are_invalid() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (_, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+
+    let response = client
+        .api_pastes()
+        .get_link("/api/v1/pastes?next_page=not-a-cursor")
+        .await?;
+    assert_eq!(response.status(), 422);
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_filters_pastes() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (alice, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let bob = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+
+    let main_rs = MockPaste::builder()
+        .random()?
+        .filename("main.rs")
+        .build()
+        .seed(&app, &alice)
+        .await?;
+    let lib_rs = MockPaste::builder()
+        .random()?
+        .filename("lib.rs")
+        .build()
+        .seed(&app, &bob)
+        .await?;
+    let notes_md = MockPaste::builder()
+        .random()?
+        .filename("main_notes.md")
+        .build()
+        .seed(&app, &alice)
+        .await?;
+    let secret_rs = MockPaste::builder()
+        .random()?
+        .filename("secret.rs")
+        .visibility("secret")
+        .build()
+        .seed(&app, &alice)
+        .await?;
+    let day = |date: &str| -> Result<jiff::Timestamp> { Ok(format!("{date}T12:00:00Z").parse()?) };
+    app.set_paste_timestamps(&main_rs, day("2024-01-10")?, day("2024-03-01")?)
+        .await?;
+    app.set_paste_timestamps(&lib_rs, day("2024-02-10")?, day("2024-02-10")?)
+        .await?;
+    app.set_paste_timestamps(&notes_md, day("2024-03-10")?, day("2024-03-10")?)
+        .await?;
+
+    let filenames = |query: &str| {
+        let client = &client;
+        let link = format!("/api/v1/pastes?{query}");
+        async move {
+            let response = client.api_pastes().get_link(&link).await?;
+            assert_eq!(response.status(), 200, "{link}");
+            let response_data: IndexResponse = response.json().await?;
+            Ok::<_, Error>(
+                response_data
+                    .pastes
+                    .into_iter()
+                    .map(|paste| paste.filename)
+                    .collect::<Vec<_>>(),
+            )
+        }
+    };
+
+    assert_eq!(filenames("extension=rs").await?, ["lib.rs", "main.rs"]);
+    assert_eq!(filenames("extension=.RS").await?, ["lib.rs", "main.rs"]);
+    assert_eq!(filenames("language=rust").await?, ["lib.rs", "main.rs"]);
+    assert_eq!(filenames("language=Markdown").await?, ["main_notes.md"]);
+    // Wildcards in the substring are matched literally
+    assert_eq!(filenames("filename=n_").await?, ["main_notes.md"]);
+    assert_eq!(
+        filenames("filename=MAIN").await?,
+        ["main_notes.md", "main.rs"]
+    );
+    assert_eq!(
+        filenames(&format!("owner={}&extension=rs", alice.username)).await?,
+        ["main.rs"]
+    );
+    // Filters never reveal pastes that the listing wouldn't otherwise include
+    assert!(filenames("visibility=secret").await?.is_empty());
+    assert_eq!(
+        filenames("created_after=2024-02-01&created_before=2024-03-10").await?,
+        ["lib.rs"]
+    );
+    assert_eq!(
+        filenames("updated_after=2024-02-20T00:00:00Z&updated_before=2024-03-05").await?,
+        ["main.rs"]
+    );
+    assert_eq!(
+        filenames("created_after=&filename=&sort=").await?.len(),
+        3,
+        "empty filters are ignored"
+    );
+    assert!(!filenames("").await?.contains(&secret_rs.filename));
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_sorts_by_update_time_with_cursors_that_keep_the_filters() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+
+    // Created in order, but updated in the opposite order
+    let now = jiff::Timestamp::now();
+    let mut pastes = Vec::new();
+    for i in 0..4 {
+        let paste = MockPaste::builder()
+            .random()?
+            .filename(format!("{i}.txt"))
+            .build()
+            .seed(&app, &user)
+            .await?;
+        app.set_paste_timestamps(
+            &paste,
+            now - jiff::SignedDuration::from_hours(10 - i),
+            now - jiff::SignedDuration::from_hours(i),
+        )
+        .await?;
+        pastes.push(paste);
+    }
+    MockPaste::builder()
+        .random()?
+        .filename("other.md")
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client
+        .api_pastes()
+        .get_link("/api/v1/pastes?extension=txt&sort=updated&per_page=3")
+        .await?;
+    assert_eq!(response.status(), 200);
+    let next_link = link_for(&response, "next").ok_or("next link not found")?;
+    let response_data: IndexResponse = response.json().await?;
+    let filenames: Vec<_> = response_data
+        .pastes
+        .iter()
+        .map(|paste| paste.filename.as_str())
+        .collect();
+    assert_eq!(filenames, ["0.txt", "1.txt", "2.txt"]);
+    let next_cursor = response_data
+        .pagination
+        .next_page
+        .ok_or("next page not found")?;
+    assert!(next_cursor.starts_with("updated."));
+    assert_eq!(
+        next_link,
+        format!("/api/v1/pastes?extension=txt&sort=updated&per_page=3&next_page={next_cursor}")
+    );
+
+    let response = client.api_pastes().get_link(&next_link).await?;
+    assert!(link_for(&response, "next").is_none());
+    let prev_link = link_for(&response, "prev").ok_or("prev link not found")?;
+    let response_data: IndexResponse = response.json().await?;
+    assert_eq!(vec![pastes[3].clone()], response_data.pastes);
+
+    let response = client.api_pastes().get_link(&prev_link).await?;
+    let response_data: IndexResponse = response.json().await?;
+    let expected: Vec<MockPaste> = pastes[0..3].to_vec();
+    assert_eq!(expected, response_data.pastes);
+
+    // A cursor only makes sense in the order it came from
+    let response = client
+        .api_pastes()
+        .get_link(&format!(
+            "/api/v1/pastes?sort=created&next_page={next_cursor}"
+        ))
+        .await?;
+    assert_eq!(response.status(), 422);
+    Ok(())
+}
+
+#[tokio::test]
+async fn index_responds_with_422_when_filters_are_invalid() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (_, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+
+    for (query, message) in [
+        ("sort=filename", "Unrecognized value for sort"),
+        ("visibility=everyone", "Unrecognized value for visibility"),
+        ("language=klingon", "Unrecognized language 'klingon'"),
+        ("created_after=yesterday", "Created after must be a date"),
+        ("updated_before=2024-13-01", "Updated before must be a date"),
+    ] {
+        let response = client
+            .api_pastes()
+            .get_link(&format!("/api/v1/pastes?{query}"))
+            .await?;
+        assert_eq!(response.status(), 422, "{query}");
+        let body = response.text().await?;
+        assert!(body.contains(message), "{query}: {body}");
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn create_show_update_destroy_happy_path() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -289,6 +597,88 @@ async fn create_show_update_destroy_happy_path() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn show_raw_is_sandboxed() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (_user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let mut paste = MockPaste::builder()
+        .random()?
+        .body("<script>alert(1)</script>")
+        .build();
+    let response = client.api_pastes().post(&paste).await?;
+    paste.id = response.json().await?;
+
+    let response = client.api_pastes().get_raw_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+    let headers = response.headers();
+    assert_eq!(headers["content-type"], "text/plain; charset=utf-8");
+    assert_eq!(
+        headers["content-security-policy"],
+        "sandbox; default-src 'none'"
+    );
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(response.text().await?, paste.body);
+    Ok(())
+}
+
+#[tokio::test]
+async fn show_responds_with_304_when_the_etag_is_current() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let mut paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+    let etag = response.headers()["etag"].to_str()?.to_string();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+    let response = client
+        .api_pastes()
+        .get_by_id_if_none_match(&paste, &etag)
+        .await?;
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+    assert!(response.text().await?.is_empty());
+
+    let response = client
+        .api_pastes()
+        .get_raw_by_id_if_none_match(&paste, &format!("W/{etag}"))
+        .await?;
+    assert_eq!(response.status(), 304);
+
+    paste.body = random_string(1..=100)?;
+    let response = client.api_pastes().patch_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .api_pastes()
+        .get_by_id_if_none_match(&paste, &etag)
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_ne!(response.headers()["etag"], etag.as_str());
+    let response = client
+        .api_pastes()
+        .get_raw_by_id_if_none_match(&paste, &etag)
+        .await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await?, paste.body);
+    Ok(())
+}
+
 #[tokio::test]
 async fn create_requires_an_api_key() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -349,6 +739,51 @@ async fn create_responds_with_400_when_invalid_fields() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn create_reports_every_invalid_field_with_a_code() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (_user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let bad_paste = MockPaste::builder()
+        .filename("illegal/characters.md")
+        .description(random_alphanumeric_string(257..=257)?)
+        .body(" ")
+        .visibility("unlisted")
+        .build();
+
+    let response = client.api_pastes().post(&bad_paste).await?;
+    assert_eq!(response.status(), 422);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/problem+json"
+    );
+    let problem: ValidationProblem = response.json().await?;
+    assert_eq!(problem.status, 422);
+    let codes: HashMap<_, _> = problem
+        .errors
+        .iter()
+        .map(|(field, errors)| (field.as_str(), errors[0].code.as_str()))
+        .collect();
+    assert_eq!(
+        codes,
+        HashMap::from([
+            ("filename", "invalid_characters"),
+            ("description", "too_long"),
+            ("body", "blank"),
+            ("visibility", "unrecognized_value"),
+        ])
+    );
+    assert_eq!(
+        problem.errors["description"][0].message,
+        "Description may not be longer than 256 characters"
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn show_requires_an_api_key() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -548,6 +983,8 @@ async fn cannot_update_public_paste_to_secret() -> Result<()> {
     paste.visibility = "secret".into();
     let response = client.api_pastes().patch_by_id(&paste).await?;
     assert_eq!(response.status(), 422);
+    let problem: ValidationProblem = response.json().await?;
+    assert_eq!(problem.errors["visibility"][0].code, "cannot_become_secret");
     Ok(())
 }
 
@@ -573,6 +1010,93 @@ async fn can_update_secret_paste_to_public() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn update_responds_with_412_when_if_match_is_stale() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    let original_etag = response.headers()["etag"].to_str()?.to_string();
+
+    let mut first_edit = paste.clone();
+    first_edit.body = random_string(1..=100)?;
+    let response = client
+        .api_pastes()
+        .patch_by_id_if_match(&first_edit, &original_etag)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let new_etag = response.headers()["etag"].to_str()?.to_string();
+    assert_ne!(new_etag, original_etag);
+
+    let mut second_edit = paste.clone();
+    second_edit.body = random_string(1..=100)?;
+    let response = client
+        .api_pastes()
+        .patch_by_id_if_match(&second_edit, &original_etag)
+        .await?;
+    assert_eq!(response.status(), 412);
+
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.headers()["etag"], new_etag.as_str());
+    let persisted_paste: MockPaste = response.json().await?;
+    assert_eq!(persisted_paste, first_edit);
+
+    let response = client
+        .api_pastes()
+        .patch_by_id_if_match(&second_edit, "*")
+        .await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn destroy_responds_with_412_when_if_match_is_stale() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let mut paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    let original_etag = response.headers()["etag"].to_str()?.to_string();
+
+    paste.body = random_string(1..=100)?;
+    let response = client.api_pastes().patch_by_id(&paste).await?;
+    let new_etag = response.headers()["etag"].to_str()?.to_string();
+
+    let response = client
+        .api_pastes()
+        .delete_by_id_if_match(&paste, &original_etag)
+        .await?;
+    assert_eq!(response.status(), 412);
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .api_pastes()
+        .delete_by_id_if_match(&paste, &new_etag)
+        .await?;
+    assert_eq!(response.status(), 200);
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}
+
 #[tokio::test]
 async fn destroy_requires_an_api_key() -> Result<()> {
     let app = TestApp::spawn().await?;
@@ -657,3 +1181,211 @@ async fn cannot_destroy_other_users_pastes() -> Result<()> {
     assert_eq!(response.status(), 200);
     Ok(())
 }
+
+#[tokio::test]
+async fn batch_applies_every_operation_or_none_of_them() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let missing_paste = MockPaste::builder().random()?.build();
+    let mut edited_paste = paste.clone();
+    edited_paste.body = random_string(1..=100)?;
+    let new_paste = MockPaste::builder().random()?.build();
+    let batch = |mode: &str| MockBatch {
+        mode: Some(mode.into()),
+        operations: vec![
+            MockOperation::Create(new_paste.clone()),
+            MockOperation::update(&edited_paste),
+            MockOperation::delete(&missing_paste),
+        ],
+    };
+
+    let response = client
+        .api_pastes()
+        .post_batch(&batch("all_or_nothing"))
+        .await?;
+    assert_eq!(response.status(), 200);
+    let body: MockBatchResponse = response.json().await?;
+    assert!(!body.committed);
+    assert_eq!(body.statuses(), vec![424, 424, 404]);
+    let response = client.api_me().get_pastes(None).await?;
+    let index: IndexResponse = response.json().await?;
+    assert_eq!(index.pastes, vec![paste.clone()]);
+
+    let response = client.api_pastes().post_batch(&batch("per_item")).await?;
+    assert_eq!(response.status(), 200);
+    let body: MockBatchResponse = response.json().await?;
+    assert!(body.committed);
+    assert_eq!(body.statuses(), vec![201, 200, 404]);
+
+    let mut created_paste = new_paste.clone();
+    created_paste.id = body.results[0].id.clone();
+    let response = client.api_pastes().get_by_id(&created_paste).await?;
+    assert_eq!(
+        response.headers()["etag"],
+        body.results[0].etag.as_deref().unwrap_or_default()
+    );
+    let persisted_paste: MockPaste = response.json().await?;
+    assert_eq!(persisted_paste, created_paste);
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(
+        response.headers()["etag"],
+        body.results[1].etag.as_deref().unwrap_or_default()
+    );
+    let persisted_paste: MockPaste = response.json().await?;
+    assert_eq!(persisted_paste, edited_paste);
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_reports_why_each_operation_failed() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let paste = MockPaste::builder()
+        .random()?
+        .visibility("public")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let other_users_paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &other_user)
+        .await?;
+    let mut invalid_paste = MockPaste::builder().random()?.build();
+    invalid_paste.body = String::new();
+    let mut secret_paste = paste.clone();
+    secret_paste.visibility = "secret".into();
+
+    let response = client
+        .api_pastes()
+        .post_batch(&MockBatch {
+            mode: Some("per_item".into()),
+            operations: vec![
+                MockOperation::Create(invalid_paste),
+                MockOperation::delete(&other_users_paste),
+                MockOperation::update(&secret_paste),
+                MockOperation::Update {
+                    paste: paste.clone(),
+                    if_match: Some("\"stale\"".into()),
+                },
+                // Sees the paste as it was left by the operations before it
+                MockOperation::Delete {
+                    id: paste.id.clone().unwrap_or_default(),
+                    if_match: Some("*".into()),
+                },
+                MockOperation::delete(&paste),
+            ],
+        })
+        .await?;
+    assert_eq!(response.status(), 200);
+    let body: MockBatchResponse = response.json().await?;
+    assert!(body.committed);
+    assert_eq!(body.statuses(), vec![422, 403, 422, 412, 200, 404]);
+    // Invalid fields are problem details, anything else the plain error body
+    let errors: Vec<_> = body
+        .results
+        .iter()
+        .map(|result| result.error.clone().unwrap_or_default())
+        .collect();
+    assert_eq!(errors[0]["errors"]["body"][0]["code"], "blank");
+    assert_eq!(errors[1]["error"], "Forbidden");
+    assert_eq!(errors[3]["error"], "Precondition Failed");
+    assert!(errors[3]["message"].is_string());
+
+    let response = client.api_pastes().get_by_id(&paste).await?;
+    assert_eq!(response.status(), 404);
+    let response = client.api_pastes().get_by_id(&other_users_paste).await?;
+    assert_eq!(response.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_needs_the_scope_for_every_operation() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let write_only_key = MockApiKey::builder()
+        .random()?
+        .scopes(&["pastes:write"])
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let client = TestClient::new(app.address, Some(&write_only_key))?;
+    let paste = MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client
+        .api_pastes()
+        .post_batch(&MockBatch {
+            mode: None,
+            operations: vec![
+                MockOperation::Create(MockPaste::builder().random()?.build()),
+                MockOperation::delete(&paste),
+            ],
+        })
+        .await?;
+    assert_eq!(response.status(), 403);
+
+    let response = client
+        .api_pastes()
+        .post_batch(&MockBatch {
+            mode: None,
+            operations: vec![MockOperation::Create(
+                MockPaste::builder().random()?.build(),
+            )],
+        })
+        .await?;
+    assert_eq!(response.status(), 200);
+    let body: MockBatchResponse = response.json().await?;
+    assert_eq!(body.statuses(), vec![201]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_responds_with_422_when_there_are_too_many_operations() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (_user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let operations = (0..101)
+        .map(|_| {
+            Ok(MockOperation::Create(
+                MockPaste::builder().random()?.build(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let response = client
+        .api_pastes()
+        .post_batch(&MockBatch {
+            mode: None,
+            operations,
+        })
+        .await?;
+    assert_eq!(response.status(), 422);
+    let response = client.api_me().get_pastes(None).await?;
+    let index: IndexResponse = response.json().await?;
+    assert!(index.pastes.is_empty());
+    Ok(())
+}
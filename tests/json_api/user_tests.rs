@@ -0,0 +1,184 @@
+use crate::common::mocks::mock_api_key::MockApiKey;
+use crate::common::mocks::mock_pagination::{MockPaginationParams, MockPaginationResponse};
+use crate::common::mocks::mock_paste::MockPaste;
+use crate::common::mocks::mock_user::MockUser;
+use crate::common::test_app::TestApp;
+use crate::common::test_client::TestClient;
+use crate::prelude::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Deserialize)]
+struct MeResponse {
+    id: String,
+    username: String,
+    email: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexResponse {
+    pastes: Vec<MockPaste>,
+    pagination: MockPaginationResponse,
+}
+
+#[tokio::test]
+async fn me_happy_path() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+
+    let response = client.api_me().get().await?;
+    assert_eq!(response.status(), 200);
+    let me: MeResponse = response.json().await?;
+    assert_eq!(Some(me.id), user.id);
+    assert_eq!(me.username, user.username);
+    assert_eq!(me.email, user.email.to_lowercase());
+    Ok(())
+}
+
+#[tokio::test]
+async fn me_requires_an_api_key_with_account_read_scope() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, None)?;
+    let response = client.api_me().get().await?;
+    assert_eq!(response.status(), 401);
+
+    let api_key = MockApiKey::builder()
+        .random()?
+        .scopes(&["pastes:read"])
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let response = client.api_me().get().await?;
+    assert_eq!(response.status(), 403);
+    Ok(())
+}
+
+#[tokio::test]
+async fn my_pastes_include_secret_pastes() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let other_user = MockUser::builder().random()?.build().seed(&app).await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let public_paste = MockPaste::builder()
+        .random()?
+        .visibility("public")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    let secret_paste = MockPaste::builder()
+        .random()?
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    MockPaste::builder()
+        .random()?
+        .build()
+        .seed(&app, &other_user)
+        .await?;
+
+    let response = client.api_me().get_pastes(None).await?;
+    assert_eq!(response.status(), 200);
+    let response_data: IndexResponse = response.json().await?;
+    let response_pastes: HashSet<MockPaste> = response_data.pastes.into_iter().collect();
+    assert_eq!(response_pastes, HashSet::from([public_paste, secret_paste]));
+    assert!(response_data.pagination.next_page.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn my_pastes_are_paginated() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    for _ in 0..3 {
+        MockPaste::builder()
+            .random()?
+            .visibility("secret")
+            .build()
+            .seed(&app, &user)
+            .await?;
+    }
+
+    let params = MockPaginationParams::builder().per_page(2).build();
+    let response = client.api_me().get_pastes(Some(params)).await?;
+    let first_page: IndexResponse = response.json().await?;
+    assert_eq!(first_page.pastes.len(), 2);
+    let next_page = first_page
+        .pagination
+        .next_page
+        .ok_or("next page not found")?;
+
+    let params = MockPaginationParams::builder()
+        .per_page(2)
+        .next_page(next_page)
+        .build();
+    let response = client.api_me().get_pastes(Some(params)).await?;
+    let second_page: IndexResponse = response.json().await?;
+    assert_eq!(second_page.pastes.len(), 1);
+    assert!(second_page.pagination.next_page.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_pastes_only_include_public_pastes() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (user, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let public_paste = MockPaste::builder()
+        .random()?
+        .visibility("public")
+        .build()
+        .seed(&app, &user)
+        .await?;
+    MockPaste::builder()
+        .random()?
+        .visibility("secret")
+        .build()
+        .seed(&app, &user)
+        .await?;
+
+    let response = client.api_users(&user.username).get_pastes(None).await?;
+    assert_eq!(response.status(), 200);
+    let response_data: IndexResponse = response.json().await?;
+    assert_eq!(response_data.pastes, vec![public_paste]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_pastes_responds_with_404_when_user_doesnt_exist() -> Result<()> {
+    let app = TestApp::spawn().await?;
+    let (_, api_key) = MockUser::builder()
+        .random()?
+        .build()
+        .seed_with_api_key(&app)
+        .await?;
+    let client = TestClient::new(app.address, Some(&api_key))?;
+    let unpersisted_user = MockUser::builder().random()?.build();
+
+    let response = client
+        .api_users(&unpersisted_user.username)
+        .get_pastes(None)
+        .await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}